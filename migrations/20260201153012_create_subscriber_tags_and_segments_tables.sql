@@ -0,0 +1,19 @@
+-- Add migration script here
+CREATE TABLE subscriber_tags (
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id),
+    tag TEXT NOT NULL,
+    tagged_at timestamptz NOT NULL,
+    PRIMARY KEY (subscriber_id, tag)
+);
+
+CREATE TABLE segments (
+    segment_id uuid NOT NULL,
+    name TEXT NOT NULL UNIQUE,
+    definition TEXT NOT NULL,
+    created_at timestamptz NOT NULL,
+    PRIMARY KEY (segment_id)
+);
+
+ALTER TABLE newsletter_issues ADD COLUMN segment_id uuid NULL
+    REFERENCES segments (segment_id);
@@ -0,0 +1,18 @@
+-- Add migration script here
+CREATE TABLE custom_fields (
+    field_name TEXT NOT NULL,
+    label TEXT NOT NULL,
+    field_type TEXT NOT NULL,
+    enum_options TEXT[] NOT NULL,
+    created_at timestamptz NOT NULL,
+    PRIMARY KEY (field_name)
+);
+
+CREATE TABLE subscriber_field_values (
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id),
+    field_name TEXT NOT NULL
+        REFERENCES custom_fields (field_name),
+    value TEXT NOT NULL,
+    PRIMARY KEY (subscriber_id, field_name)
+);
@@ -0,0 +1,13 @@
+-- Add migration script here
+-- historical rows get the time of the migration, which is the best we know
+ALTER TABLE subscription_tokens ADD COLUMN created_at timestamptz NOT NULL DEFAULT now();
+ALTER TABLE subscriptions ADD COLUMN confirmed_at timestamptz NULL;
+
+CREATE TABLE issue_delivery_log (
+    newsletter_issue_id uuid NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id),
+    subscriber_email TEXT NOT NULL,
+    outcome TEXT NOT NULL,
+    attempted_at timestamptz NOT NULL
+);
+CREATE INDEX issue_delivery_log_subscriber_email_idx ON issue_delivery_log (subscriber_email);
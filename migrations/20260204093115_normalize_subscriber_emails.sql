@@ -0,0 +1,79 @@
+-- Add migration script here
+-- addresses that only differ by case or surrounding whitespace belong to the same
+-- subscriber. keep the most engaged row of each group as the survivor
+CREATE TEMPORARY TABLE subscriber_merges AS
+SELECT
+    s.id,
+    s.email,
+    first_value(s.id) OVER (
+        PARTITION BY lower(btrim(s.email))
+        ORDER BY
+            s.status = 'confirmed' DESC,
+            s.status = 'pending_confirmation' DESC,
+            s.subscribed_at,
+            s.id
+    ) AS survivor_id
+FROM subscriptions s;
+DELETE FROM subscriber_merges WHERE id = survivor_id;
+
+INSERT INTO subscriber_tags (subscriber_id, tag, tagged_at)
+SELECT m.survivor_id, t.tag, t.tagged_at
+FROM subscriber_tags t
+JOIN subscriber_merges m ON m.id = t.subscriber_id
+ON CONFLICT DO NOTHING;
+
+-- the survivor's own values win over those of its duplicates
+INSERT INTO subscriber_field_values (subscriber_id, field_name, value)
+SELECT m.survivor_id, f.field_name, f.value
+FROM subscriber_field_values f
+JOIN subscriber_merges m ON m.id = f.subscriber_id
+ON CONFLICT DO NOTHING;
+
+UPDATE subscription_tokens t
+SET subscriber_id = m.survivor_id
+FROM subscriber_merges m
+WHERE t.subscriber_id = m.id;
+
+UPDATE issue_delivery_log l
+SET subscriber_email = s.email
+FROM subscriber_merges m
+JOIN subscriptions s ON s.id = m.survivor_id
+WHERE l.subscriber_email = m.email;
+
+-- the same issue queued for two duplicates is delivered once
+INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_email)
+SELECT q.newsletter_issue_id, s.email
+FROM issue_delivery_queue q
+JOIN subscriber_merges m ON q.subscriber_email = m.email
+JOIN subscriptions s ON s.id = m.survivor_id
+ON CONFLICT DO NOTHING;
+DELETE FROM issue_delivery_queue q USING subscriber_merges m WHERE q.subscriber_email = m.email;
+
+DELETE FROM subscriber_tags t USING subscriber_merges m WHERE t.subscriber_id = m.id;
+DELETE FROM subscriber_field_values f USING subscriber_merges m WHERE f.subscriber_id = m.id;
+DELETE FROM subscriptions s USING subscriber_merges m WHERE s.id = m.id;
+
+-- rewrite the survivors, and the copies of their address, in the canonical form
+-- `SubscriberEmail::parse` produces. unicode normalization and punycode are left to the
+-- app, `normalize()` isn't available on every server encoding
+CREATE TEMPORARY TABLE canonical_emails AS
+SELECT email, left(trimmed, -length(domain)) || lower(domain) AS canonical
+FROM (
+    SELECT email, trimmed, substring(trimmed FROM '[^@]*$') AS domain
+    FROM (SELECT email, btrim(email) AS trimmed FROM subscriptions) n
+) d;
+DELETE FROM canonical_emails WHERE email = canonical;
+
+UPDATE issue_delivery_log l SET subscriber_email = c.canonical
+FROM canonical_emails c WHERE l.subscriber_email = c.email;
+UPDATE issue_delivery_queue q SET subscriber_email = c.canonical
+FROM canonical_emails c WHERE q.subscriber_email = c.email;
+UPDATE subscriptions s SET email = c.canonical
+FROM canonical_emails c WHERE s.email = c.email;
+
+DROP TABLE subscriber_merges;
+DROP TABLE canonical_emails;
+
+CREATE UNIQUE INDEX subscriptions_email_identity ON subscriptions (lower(email));
+-- data access requests and erasure look the log up by identity as well
+CREATE INDEX issue_delivery_log_subscriber_identity ON issue_delivery_log (lower(subscriber_email));
@@ -0,0 +1,9 @@
+-- Add migration script here
+CREATE TABLE email_suppressions (
+    -- sha256 of the lowercased address
+    email_hash TEXT NOT NULL PRIMARY KEY,
+    -- NULL once the subscriber has been erased
+    email TEXT NULL,
+    reason TEXT NOT NULL,
+    created_at timestamptz NOT NULL
+);
@@ -0,0 +1,14 @@
+-- Add migration script here
+CREATE TABLE email_events (
+    id uuid NOT NULL PRIMARY KEY,
+    provider TEXT NOT NULL,
+    -- the provider's own id, so redelivered webhooks are only recorded once
+    provider_event_id TEXT NOT NULL,
+    event_type TEXT NOT NULL,
+    email TEXT NOT NULL,
+    description TEXT NULL,
+    occurred_at timestamptz NOT NULL,
+    received_at timestamptz NOT NULL,
+    UNIQUE (provider, provider_event_id)
+);
+CREATE INDEX email_events_email_idx ON email_events (lower(email));
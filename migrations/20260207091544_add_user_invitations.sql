@@ -0,0 +1,17 @@
+-- Add migration script here
+-- the seeded admin has no email, invited users get the one they were invited with
+ALTER TABLE users ADD COLUMN email TEXT NULL;
+ALTER TABLE users ADD COLUMN is_active BOOLEAN NOT NULL DEFAULT true;
+ALTER TABLE users ADD COLUMN created_at timestamptz NOT NULL DEFAULT now();
+CREATE UNIQUE INDEX users_email_identity ON users (lower(email));
+
+CREATE TABLE user_invitations (
+    id uuid NOT NULL PRIMARY KEY,
+    -- sha256 of the token in the invitation link, the token itself is never stored
+    token_hash TEXT NOT NULL UNIQUE,
+    email TEXT NOT NULL,
+    invited_by uuid NULL REFERENCES users (user_id) ON DELETE SET NULL,
+    created_at timestamptz NOT NULL,
+    expires_at timestamptz NOT NULL,
+    accepted_at timestamptz NULL
+);
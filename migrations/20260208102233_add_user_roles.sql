@@ -0,0 +1,9 @@
+-- Add migration script here
+-- everyone who could log in so far could do everything, so they stay owners
+ALTER TABLE users ADD COLUMN role TEXT NOT NULL DEFAULT 'owner'
+    CHECK (role IN ('owner', 'editor', 'viewer'));
+ALTER TABLE users ALTER COLUMN role DROP DEFAULT;
+
+ALTER TABLE user_invitations ADD COLUMN role TEXT NOT NULL DEFAULT 'editor'
+    CHECK (role IN ('owner', 'editor', 'viewer'));
+ALTER TABLE user_invitations ALTER COLUMN role DROP DEFAULT;
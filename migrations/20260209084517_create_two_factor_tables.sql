@@ -0,0 +1,18 @@
+-- Add migration script here
+CREATE TABLE user_totp (
+    user_id uuid NOT NULL PRIMARY KEY REFERENCES users (user_id) ON DELETE CASCADE,
+    -- base32, as shown to the user during enrollment
+    secret TEXT NOT NULL,
+    -- NULL until the user proves their authenticator app works
+    confirmed_at timestamptz NULL,
+    -- the last time step a code was accepted for, so a code can't be replayed
+    last_used_step BIGINT NULL
+);
+
+CREATE TABLE user_recovery_codes (
+    user_id uuid NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+    -- sha256 of the normalized code, the code itself is only shown once
+    code_hash TEXT NOT NULL,
+    used_at timestamptz NULL,
+    PRIMARY KEY (user_id, code_hash)
+);
@@ -0,0 +1,12 @@
+-- Add migration script here
+-- sessions remember the generation they were created in, bumping it logs the user out everywhere
+ALTER TABLE users ADD COLUMN session_generation INTEGER NOT NULL DEFAULT 0;
+
+CREATE TABLE password_reset_tokens (
+    -- sha256 of the token in the reset link, the token itself is never stored
+    token_hash TEXT NOT NULL PRIMARY KEY,
+    user_id uuid NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+    created_at timestamptz NOT NULL,
+    expires_at timestamptz NOT NULL,
+    used_at timestamptz NULL
+);
@@ -0,0 +1,12 @@
+-- Add migration script here
+-- a record of every time a username or ip was locked out after too many failed logins
+CREATE TABLE login_lockouts (
+    id uuid NOT NULL PRIMARY KEY,
+    -- exactly one of these is set
+    username TEXT NULL,
+    ip TEXT NULL,
+    failures BIGINT NOT NULL,
+    created_at timestamptz NOT NULL,
+    locked_until timestamptz NOT NULL
+);
+CREATE INDEX login_lockouts_created_at ON login_lockouts (created_at);
@@ -0,0 +1,14 @@
+-- Add migration script here
+-- one row per logged in session, deleting it logs that session out
+CREATE TABLE user_sessions(
+    session_id uuid NOT NULL,
+    user_id uuid NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+    created_at timestamptz NOT NULL,
+    last_seen_at timestamptz NOT NULL,
+    ip TEXT NOT NULL,
+    user_agent TEXT NOT NULL,
+    PRIMARY KEY (session_id)
+);
+CREATE INDEX user_sessions_user_id_idx ON user_sessions (user_id);
+-- sessions are now revoked by deleting their rows
+ALTER TABLE users DROP COLUMN session_generation;
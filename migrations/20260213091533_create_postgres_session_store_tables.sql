@@ -0,0 +1,17 @@
+-- Add migration script here
+-- used instead of redis when `session_store` is postgres
+CREATE TABLE session_states(
+    session_key TEXT NOT NULL,
+    -- the session's key-value pairs, as json
+    state TEXT NOT NULL,
+    expires_at timestamptz NOT NULL,
+    PRIMARY KEY (session_key)
+);
+CREATE INDEX session_states_expires_at_idx ON session_states (expires_at);
+CREATE TABLE rate_limit_counters(
+    key TEXT NOT NULL,
+    value BIGINT NOT NULL,
+    expires_at timestamptz NOT NULL,
+    PRIMARY KEY (key)
+);
+CREATE INDEX rate_limit_counters_expires_at_idx ON rate_limit_counters (expires_at);
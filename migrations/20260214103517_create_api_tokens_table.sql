@@ -0,0 +1,14 @@
+-- Add migration script here
+-- personal tokens for scripts and CI, only a hash of the token itself is kept
+CREATE TABLE api_tokens(
+    id uuid NOT NULL,
+    user_id uuid NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    -- the permissions the token may use, on top of those of its owner's role
+    scopes TEXT[] NOT NULL,
+    created_at timestamptz NOT NULL,
+    last_used_at timestamptz,
+    PRIMARY KEY (id)
+);
+CREATE INDEX api_tokens_user_id_idx ON api_tokens (user_id);
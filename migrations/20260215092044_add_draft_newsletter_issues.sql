@@ -0,0 +1,7 @@
+-- Add migration script here
+-- issues created through the api are drafts until they are sent
+ALTER TABLE newsletter_issues ALTER COLUMN published_at DROP NOT NULL;
+ALTER TABLE newsletter_issues ADD COLUMN created_at timestamptz NULL;
+UPDATE newsletter_issues SET created_at = published_at::timestamptz;
+ALTER TABLE newsletter_issues ALTER COLUMN created_at SET NOT NULL;
+ALTER TABLE newsletter_issues ALTER COLUMN created_at SET DEFAULT now();
@@ -0,0 +1,13 @@
+-- Add migration script here
+-- one-off emails (confirmations and the like) waiting for the background worker
+CREATE TABLE email_queue (
+    email_id uuid PRIMARY KEY,
+    recipient TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    html_body TEXT NOT NULL,
+    text_body TEXT NOT NULL,
+    n_retries SMALLINT NOT NULL DEFAULT 0,
+    execute_after timestamptz NOT NULL DEFAULT now(),
+    enqueued_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE INDEX email_queue_execute_after ON email_queue (execute_after);
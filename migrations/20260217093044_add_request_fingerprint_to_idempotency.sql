@@ -0,0 +1,4 @@
+-- Add migration script here
+-- what an api request was first sent with, so its key can't be reused for a different one.
+-- form submissions leave it empty
+ALTER TABLE idempotency ADD COLUMN request_fingerprint TEXT NULL;
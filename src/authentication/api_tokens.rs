@@ -0,0 +1,130 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::tokens::{generate_token, token_hash};
+use crate::domain::{Permission, Role};
+
+pub struct ApiToken {
+    pub id: Uuid,
+    pub name: String,
+    pub scopes: Vec<Permission>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+// who a request made with a token acts as, and what the token lets it do
+pub struct ApiTokenOwner {
+    pub user_id: Uuid,
+    pub role: Role,
+    pub scopes: Vec<Permission>,
+}
+
+fn parse_scopes(scopes: &[String]) -> Result<Vec<Permission>, anyhow::Error> {
+    scopes
+        .iter()
+        .map(|s| Permission::parse(s).map_err(anyhow::Error::msg))
+        .collect()
+}
+
+// the token itself is returned once, to be shown to the user, and never stored
+#[tracing::instrument(name = "Create an api token", skip(pool))]
+pub async fn create_api_token(
+    pool: &PgPool,
+    user_id: Uuid,
+    name: &str,
+    scopes: &[Permission],
+) -> Result<String, anyhow::Error> {
+    let token = generate_token();
+    let scopes: Vec<String> = scopes.iter().map(|s| s.as_str().to_string()).collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO api_tokens (id, user_id, name, token_hash, scopes, created_at)
+        VALUES ($1, $2, $3, $4, $5, now())
+        "#,
+        Uuid::new_v4(),
+        user_id,
+        name,
+        token_hash(&token),
+        &scopes
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store the api token")?;
+    Ok(token)
+}
+
+#[tracing::instrument(name = "List a user's api tokens", skip(pool))]
+pub async fn list_api_tokens(pool: &PgPool, user_id: Uuid) -> Result<Vec<ApiToken>, anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT id, name, scopes, created_at, last_used_at
+        FROM api_tokens
+        WHERE user_id = $1
+        ORDER BY created_at DESC
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to list the api tokens")?;
+    rows.into_iter()
+        .map(|r| {
+            Ok(ApiToken {
+                id: r.id,
+                name: r.name,
+                scopes: parse_scopes(&r.scopes)?,
+                created_at: r.created_at,
+                last_used_at: r.last_used_at,
+            })
+        })
+        .collect()
+}
+
+// false if the token doesn't exist or belongs to someone else
+#[tracing::instrument(name = "Revoke an api token", skip(pool))]
+pub async fn revoke_api_token(
+    pool: &PgPool,
+    user_id: Uuid,
+    token_id: Uuid,
+) -> Result<bool, anyhow::Error> {
+    let deleted = sqlx::query!(
+        r#"DELETE FROM api_tokens WHERE id = $1 AND user_id = $2"#,
+        token_id,
+        user_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to revoke the api token")?
+    .rows_affected();
+    Ok(deleted > 0)
+}
+
+// None for unknown tokens and for tokens of deactivated users
+#[tracing::instrument(name = "Check an api token", skip(pool, token))]
+pub async fn check_api_token(
+    pool: &PgPool,
+    token: &str,
+) -> Result<Option<ApiTokenOwner>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        UPDATE api_tokens t SET last_used_at = now()
+        FROM users u
+        WHERE t.token_hash = $1 AND u.user_id = t.user_id AND u.is_active
+        RETURNING t.user_id, u.role, t.scopes
+        "#,
+        token_hash(token)
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to check the api token")?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    Ok(Some(ApiTokenOwner {
+        user_id: row.user_id,
+        role: Role::parse(&row.role).map_err(anyhow::Error::msg)?,
+        scopes: parse_scopes(&row.scopes)?,
+    }))
+}
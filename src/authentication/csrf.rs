@@ -0,0 +1,231 @@
+use actix_web::{
+    FromRequest, HttpMessage, HttpRequest, HttpResponse,
+    body::{EitherBody, MessageBody},
+    dev::{Payload, ServiceRequest, ServiceResponse},
+    http::Method,
+    middleware::Next,
+    mime,
+    web::{Bytes, BytesMut},
+};
+use futures_util::{StreamExt, stream};
+use std::future::{Ready, ready};
+use subtle::ConstantTimeEq;
+
+use super::middleware::ApiTokenScopes;
+use super::tokens::generate_token;
+use crate::session_state::TypedSession;
+use crate::utils::e500;
+
+const CSRF_FIELD: &str = "csrf_token";
+// for clients that aren't submitting an html form
+const CSRF_HEADER: &str = "X-CSRF-Token";
+// the token is the first field of every form, so a body is only read this far looking for it
+const MAX_SCANNED_BYTES: usize = 64 * 1024;
+
+// the session's token, created the first time a form is rendered for the session
+pub struct CsrfToken(String);
+
+impl CsrfToken {
+    // goes first in every form, see `MAX_SCANNED_BYTES`
+    pub fn form_field(&self) -> String {
+        format!(
+            r#"<input type="hidden" name="{}" value="{}">"#,
+            CSRF_FIELD, self.0
+        )
+    }
+}
+
+impl FromRequest for CsrfToken {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<CsrfToken, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let token = TypedSession::from_request(req, payload)
+            .into_inner()
+            .and_then(|session| {
+                if let Some(token) = session.get_csrf_token().map_err(e500)? {
+                    return Ok(token);
+                }
+                let token = generate_token();
+                session.insert_csrf_token(&token).map_err(e500)?;
+                Ok(token)
+            });
+        ready(token.map(CsrfToken))
+    }
+}
+
+// every POST has to carry the token of the session it is made with. requests made with an
+// api token don't have a session, and browsers don't attach api tokens on their own
+pub async fn verify_csrf_token(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    if req.method() != Method::POST || req.extensions().contains::<ApiTokenScopes>() {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_left_body);
+    }
+    let expected = {
+        let (http_request, payload) = req.parts_mut();
+        TypedSession::from_request(http_request, payload).await
+    }?
+    .get_csrf_token()
+    .map_err(e500)?;
+    let submitted = match req.headers().get(CSRF_HEADER) {
+        Some(value) => value.to_str().ok().map(str::to_string),
+        None => submitted_in_body(&mut req).await?,
+    };
+    let valid = match (expected, submitted) {
+        (Some(expected), Some(submitted)) => expected.as_bytes().ct_eq(submitted.as_bytes()).into(),
+        _ => false,
+    };
+    if valid {
+        next.call(req)
+            .await
+            .map(ServiceResponse::map_into_left_body)
+    } else {
+        let response = HttpResponse::Forbidden()
+            .body("This form has expired, please reload the page and try again.");
+        Ok(req.into_response(response).map_into_right_body())
+    }
+}
+
+// reads just enough of the body to find the token, then hands what it read back to the
+// handler, ahead of the rest of the body
+async fn submitted_in_body(req: &mut ServiceRequest) -> Result<Option<String>, actix_web::Error> {
+    let Ok(Some(mime_type)) = req.mime_type() else {
+        return Ok(None);
+    };
+    let format = if mime_type == mime::APPLICATION_WWW_FORM_URLENCODED {
+        BodyFormat::Form
+    } else if mime_type.essence_str() == mime::MULTIPART_FORM_DATA.essence_str() {
+        match mime_type.get_param(mime::BOUNDARY) {
+            Some(boundary) => BodyFormat::Multipart(format!("--{}", boundary.as_str())),
+            None => return Ok(None),
+        }
+    } else {
+        return Ok(None);
+    };
+
+    let mut payload = req.take_payload();
+    let mut read = BytesMut::new();
+    let mut complete = false;
+    let token = loop {
+        let token = format.find_token(&read, complete);
+        if token.is_some() || complete || read.len() >= MAX_SCANNED_BYTES {
+            break token;
+        }
+        match payload.next().await {
+            Some(chunk) => read.extend_from_slice(&chunk?),
+            None => complete = true,
+        }
+    };
+    let read = stream::once(ready(Ok::<Bytes, _>(read.freeze())));
+    req.set_payload(Payload::Stream {
+        payload: Box::pin(read.chain(payload)),
+    });
+    Ok(token)
+}
+
+enum BodyFormat {
+    Form,
+    // holds the delimiter, the boundary with its leading dashes
+    Multipart(String),
+}
+
+impl BodyFormat {
+    // only looks at fields that have been read in full
+    fn find_token(&self, read: &[u8], complete: bool) -> Option<String> {
+        match self {
+            BodyFormat::Form => {
+                let end = if complete {
+                    read.len()
+                } else {
+                    read.iter().rposition(|b| *b == b'&')?
+                };
+                serde_urlencoded::from_bytes::<Vec<(String, String)>>(&read[..end])
+                    .ok()?
+                    .into_iter()
+                    .find_map(|(name, value)| (name == CSRF_FIELD).then_some(value))
+            }
+            BodyFormat::Multipart(delimiter) => {
+                let read = std::str::from_utf8(read)
+                    .or_else(|e| std::str::from_utf8(&read[..e.valid_up_to()]))
+                    .ok()?;
+                let mut parts = read.split(delimiter.as_str()).skip(1).peekable();
+                while let Some(part) = parts.next() {
+                    // the last part may still be cut short
+                    parts.peek()?;
+                    let (headers, value) = part.split_once("\r\n\r\n")?;
+                    let field = format!("name=\"{}\"", CSRF_FIELD);
+                    let is_token = headers.lines().any(|line| {
+                        line.to_ascii_lowercase()
+                            .starts_with("content-disposition: form-data;")
+                            && line.contains(&field)
+                    });
+                    if is_token {
+                        return Some(value.trim_end_matches("\r\n").to_string());
+                    }
+                }
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BodyFormat;
+    use claims::{assert_none, assert_some_eq};
+
+    const MULTIPART: &str = "--XyZ\r\n\
+        Content-Disposition: form-data; name=\"csrf_token\"\r\n\r\n\
+        abc123\r\n\
+        --XyZ\r\n\
+        Content-Disposition: form-data; name=\"file\"; filename=\"a.csv\"\r\n\
+        Content-Type: text/csv\r\n\r\n\
+        email,name\r\n\
+        --XyZ--\r\n";
+
+    fn multipart() -> BodyFormat {
+        BodyFormat::Multipart("--XyZ".into())
+    }
+
+    #[test]
+    fn the_token_is_found_anywhere_in_a_complete_form() {
+        let body = b"name=le%20guin&csrf_token=abc123&email=ursula%40example.com";
+        assert_some_eq!(BodyFormat::Form.find_token(body, true), "abc123");
+    }
+
+    #[test]
+    fn a_form_field_that_may_still_be_cut_short_is_ignored() {
+        assert_none!(BodyFormat::Form.find_token(b"csrf_token=abc", false));
+        assert_some_eq!(
+            BodyFormat::Form.find_token(b"csrf_token=abc123&name=le", false),
+            "abc123"
+        );
+    }
+
+    #[test]
+    fn a_form_without_a_token_has_none() {
+        assert_none!(BodyFormat::Form.find_token(b"name=le%20guin", true));
+    }
+
+    #[test]
+    fn the_token_is_found_in_a_multipart_body() {
+        assert_some_eq!(multipart().find_token(MULTIPART.as_bytes(), true), "abc123");
+    }
+
+    #[test]
+    fn the_token_is_found_before_the_upload_has_been_read() {
+        let read = &MULTIPART.as_bytes()[..MULTIPART.find("Content-Type").unwrap()];
+        assert_some_eq!(multipart().find_token(read, false), "abc123");
+    }
+
+    #[test]
+    fn a_multipart_token_that_may_still_be_cut_short_is_ignored() {
+        let read = &MULTIPART.as_bytes()[..MULTIPART.find("123").unwrap()];
+        assert_none!(multipart().find_token(read, false));
+    }
+}
@@ -0,0 +1,198 @@
+use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
+use secrecy::SecretString;
+use sqlx::{Executor, PgPool};
+use uuid::Uuid;
+
+use super::tokens::{generate_token, token_hash};
+use super::users::create_user;
+use crate::configuration::PasswordHashingSettings;
+use crate::domain::{Role, Username};
+
+// how long an invitation link stays valid
+pub const INVITATION_LIFETIME_HOURS: i64 = 72;
+
+pub struct Invitation {
+    pub id: Uuid,
+    pub email: String,
+    pub role: Role,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+pub enum AcceptInvitationOutcome {
+    Accepted,
+    // unknown, expired, revoked or already used
+    InvalidToken,
+    UsernameTaken,
+}
+
+// returns the token for the invitation link. any earlier invitation
+// still pending for the same address stops working
+#[tracing::instrument(name = "Create an invitation", skip(pool))]
+pub async fn create_invitation(
+    pool: &PgPool,
+    email: &str,
+    role: Role,
+    invited_by: Uuid,
+) -> Result<String, anyhow::Error> {
+    let token = generate_token();
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    transaction
+        .execute(sqlx::query!(
+            r#"
+            DELETE FROM user_invitations
+            WHERE lower(email) = lower($1) AND accepted_at IS NULL
+            "#,
+            email
+        ))
+        .await
+        .context("Failed to remove earlier invitations")?;
+    transaction
+        .execute(sqlx::query!(
+            r#"
+            INSERT INTO user_invitations (id, token_hash, email, role, invited_by, created_at, expires_at)
+            VALUES ($1, $2, $3, $4, $5, now(), $6)
+            "#,
+            Uuid::new_v4(),
+            token_hash(&token),
+            email,
+            role.as_str(),
+            invited_by,
+            Utc::now() + Duration::hours(INVITATION_LIFETIME_HOURS)
+        ))
+        .await
+        .context("Failed to store the invitation")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to store an invitation.")?;
+    Ok(token)
+}
+
+#[tracing::instrument(name = "List pending invitations", skip(pool))]
+pub async fn list_pending_invitations(pool: &PgPool) -> Result<Vec<Invitation>, anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT id, email, role, created_at, expires_at
+        FROM user_invitations
+        WHERE accepted_at IS NULL AND expires_at > now()
+        ORDER BY created_at
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve pending invitations")?;
+    rows.into_iter()
+        .map(|r| {
+            Ok(Invitation {
+                id: r.id,
+                email: r.email,
+                role: Role::parse(&r.role).map_err(anyhow::Error::msg)?,
+                created_at: r.created_at,
+                expires_at: r.expires_at,
+            })
+        })
+        .collect()
+}
+
+#[tracing::instrument(name = "Find a pending invitation", skip(pool, token))]
+pub async fn find_pending_invitation(
+    pool: &PgPool,
+    token: &str,
+) -> Result<Option<Invitation>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT id, email, role, created_at, expires_at
+        FROM user_invitations
+        WHERE token_hash = $1 AND accepted_at IS NULL AND expires_at > now()
+        "#,
+        token_hash(token)
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the invitation")?;
+    row.map(|r| {
+        Ok(Invitation {
+            id: r.id,
+            email: r.email,
+            role: Role::parse(&r.role).map_err(anyhow::Error::msg)?,
+            created_at: r.created_at,
+            expires_at: r.expires_at,
+        })
+    })
+    .transpose()
+}
+
+// returns whether a pending invitation was revoked
+#[tracing::instrument(name = "Revoke an invitation", skip(pool))]
+pub async fn revoke_invitation(pool: &PgPool, id: Uuid) -> Result<bool, anyhow::Error> {
+    let deleted = sqlx::query!(
+        r#"DELETE FROM user_invitations WHERE id = $1 AND accepted_at IS NULL"#,
+        id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to revoke the invitation")?
+    .rows_affected();
+    Ok(deleted > 0)
+}
+
+// creates the invited user, with the address and role they were invited with
+#[tracing::instrument(name = "Accept an invitation", skip(pool, token, password, hashing))]
+pub async fn accept_invitation(
+    pool: &PgPool,
+    token: &str,
+    username: &Username,
+    password: SecretString,
+    hashing: &PasswordHashingSettings,
+) -> Result<AcceptInvitationOutcome, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    // lock the invitation, so the same link can't create two users
+    let invitation = sqlx::query!(
+        r#"
+        SELECT id, email, role FROM user_invitations
+        WHERE token_hash = $1 AND accepted_at IS NULL AND expires_at > now()
+        FOR UPDATE
+        "#,
+        token_hash(token)
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to retrieve the invitation")?;
+    let Some(invitation) = invitation else {
+        return Ok(AcceptInvitationOutcome::InvalidToken);
+    };
+    let role = Role::parse(&invitation.role).map_err(anyhow::Error::msg)?;
+    if create_user(
+        &mut transaction,
+        username,
+        &invitation.email,
+        role,
+        password,
+        hashing,
+    )
+    .await?
+    .is_none()
+    {
+        return Ok(AcceptInvitationOutcome::UsernameTaken);
+    }
+    transaction
+        .execute(sqlx::query!(
+            r#"UPDATE user_invitations SET accepted_at = now() WHERE id = $1"#,
+            invitation.id
+        ))
+        .await
+        .context("Failed to mark the invitation as accepted")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to accept an invitation.")?;
+    Ok(AcceptInvitationOutcome::Accepted)
+}
@@ -1,15 +1,23 @@
 use actix_web::{
-    FromRequest, HttpMessage,
-    body::MessageBody,
+    FromRequest, HttpMessage, HttpResponse, Route,
+    body::{BoxBody, EitherBody, MessageBody},
     dev::{ServiceRequest, ServiceResponse},
     error::InternalError,
-    middleware::Next,
+    http::{Method, StatusCode, header, header::HeaderValue},
+    middleware::{Next, from_fn},
+    web,
 };
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
 use std::ops::Deref;
 use uuid::Uuid;
 
+use super::api_tokens::check_api_token;
+use super::sessions::{SessionStatus, check_session};
+use crate::configuration::SessionTimeoutSettings;
+use crate::domain::{Permission, Role};
 use crate::session_state::TypedSession;
-use crate::utils::{e500, see_other};
+use crate::utils::{e500, json_error, see_other};
 
 #[derive(Copy, Clone, Debug)]
 pub struct UserId(Uuid);
@@ -28,25 +36,205 @@ impl Deref for UserId {
     }
 }
 
-// anonymous users should be redirected to "/login" if they try to access a path they shouldn't
+// the scopes of the api token a request was made with, requests made from a logged in
+// browser don't have any
+pub(super) struct ApiTokenScopes(Vec<Permission>);
+
+fn bearer_token(req: &ServiceRequest) -> Option<&str> {
+    req.headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
+
+fn unauthorized(code: &str, message: &str) -> HttpResponse {
+    let mut response = json_error(StatusCode::UNAUTHORIZED, code, message);
+    response
+        .headers_mut()
+        .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+    response
+}
+
+// the same json errors the api sends, scripts are the only ones to use tokens
+fn forbidden(req: &ServiceRequest, message: &str) -> HttpResponse {
+    if req.extensions().contains::<ApiTokenScopes>() {
+        json_error(StatusCode::FORBIDDEN, "forbidden", message)
+    } else {
+        HttpResponse::Forbidden().body(message.to_string())
+    }
+}
+
+// scripts send a token instead of logging in, it is either good or the request is refused,
+// there is no login page to send them to
+async fn authenticate_api_token<B: MessageBody + 'static>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, actix_web::Error> {
+    let pool = req
+        .app_data::<web::Data<PgPool>>()
+        .ok_or_else(|| e500("The database pool is not configured"))?;
+    let token = bearer_token(&req).unwrap_or_default();
+    match check_api_token(pool, token).await.map_err(e500)? {
+        Some(owner) => {
+            req.extensions_mut().insert(UserId(owner.user_id));
+            req.extensions_mut().insert(owner.role);
+            req.extensions_mut().insert(ApiTokenScopes(owner.scopes));
+            next.call(req)
+                .await
+                .map(ServiceResponse::map_into_left_body)
+        }
+        None => {
+            let response = unauthorized("invalid_token", "The API token is not valid.");
+            Ok(req.into_response(response).map_into_right_body())
+        }
+    }
+}
+
+// for /api, which only takes api tokens
+pub async fn require_api_token(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    if bearer_token(&req).is_none() {
+        let response = unauthorized("missing_token", "An API token is required.");
+        return Ok(req.into_response(response).map_into_right_body());
+    }
+    authenticate_api_token(req, next).await
+}
+
+// anonymous users should be redirected to "/login" if they try to access a path they shouldn't,
+// and so should users who have been deactivated, removed or had their session revoked or
+// timed out since they logged in.
+// users who have only entered their password are sent back to the second step.
+// requests with an api token are let in as the token's owner
 pub async fn reject_anonymous_users(
     mut req: ServiceRequest,
-    next: Next<impl MessageBody>,
+    next: Next<impl MessageBody + 'static>,
 ) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    if bearer_token(&req).is_some() {
+        return authenticate_api_token(req, next).await;
+    }
     let session = {
         let (http_request, payload) = req.parts_mut();
         TypedSession::from_request(http_request, payload).await
     }?;
 
-    match session.get_user_id().map_err(e500)? {
-        Some(user_id) => {
-            req.extensions_mut().insert(UserId(user_id));
-            next.call(req).await
+    let second_factor_pending = session.get_pending_user_id().map_err(e500)?.is_some();
+    let ids = session
+        .get_user_id()
+        .and_then(|user_id| Ok(user_id.zip(session.get_session_id()?)))
+        .map_err(e500)?;
+    let user = match ids {
+        Some((user_id, session_id)) => {
+            let pool = req
+                .app_data::<web::Data<PgPool>>()
+                .ok_or_else(|| e500("The database pool is not configured"))?;
+            let timeouts = req
+                .app_data::<web::Data<SessionTimeoutSettings>>()
+                .ok_or_else(|| e500("The session timeouts are not configured"))?;
+            match check_session(pool, user_id, session_id, timeouts)
+                .await
+                .map_err(e500)?
+            {
+                SessionStatus::Active(role) => Some((user_id, role)),
+                SessionStatus::Expired => {
+                    session.log_out();
+                    FlashMessage::info("Your session has expired, please log in again.").send();
+                    None
+                }
+                SessionStatus::Ended => {
+                    session.log_out();
+                    None
+                }
+            }
         }
-        None => {
-            let response = see_other("/login");
-            let e = anyhow::anyhow!("The user has not logged in");
-            Err(InternalError::from_response(e, response).into())
+        None => None,
+    };
+
+    let location = match user {
+        Some((user_id, role)) => {
+            req.extensions_mut().insert(UserId(user_id));
+            req.extensions_mut().insert(role);
+            return next
+                .call(req)
+                .await
+                .map(ServiceResponse::map_into_left_body);
         }
+        // the password was right but the second factor is still missing
+        None if second_factor_pending => "/login/two-factor",
+        None => "/login",
+    };
+    // a response rather than an error, so the session and flash middlewares still get
+    // to write their cookies
+    Ok(req.into_response(see_other(location)).map_into_right_body())
+}
+
+// wraps a route under the /admin scope, which has already put the user's role in the request
+pub fn require_permission(permission: Permission, route: Route) -> Route {
+    route.wrap(from_fn(move |req: ServiceRequest, next: Next<BoxBody>| {
+        check_permission(permission, req, next)
+    }))
+}
+
+async fn check_permission(
+    permission: Permission,
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let role = req
+        .extensions()
+        .get::<Role>()
+        .copied()
+        .ok_or_else(|| e500("The user's role is not known"))?;
+    if !role.can(permission) {
+        let response = forbidden(&req, "You are not allowed to do that.");
+        let e = anyhow::anyhow!("A {} may not {:?}", role.as_str(), permission);
+        return Err(InternalError::from_response(e, response).into());
+    }
+    let out_of_scope = req
+        .extensions()
+        .get::<ApiTokenScopes>()
+        .is_some_and(|scopes| !scopes.0.contains(&permission));
+    if out_of_scope {
+        let response = forbidden(&req, "This API token may not do that.");
+        let e = anyhow::anyhow!("The api token is not scoped to {:?}", permission);
+        return Err(InternalError::from_response(e, response).into());
+    }
+    next.call(req).await
+}
+
+// api tokens only get to look at admin pages with the read scope,
+// anything else under /admin is checked per route by `require_permission`
+pub async fn require_read_scope(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let reading = matches!(*req.method(), Method::GET | Method::HEAD);
+    let out_of_scope = req
+        .extensions()
+        .get::<ApiTokenScopes>()
+        .is_some_and(|scopes| !scopes.0.contains(&Permission::Read));
+    if reading && out_of_scope {
+        let response = forbidden(&req, "This API token may not do that.");
+        let e = anyhow::anyhow!("The api token is not scoped to {:?}", Permission::Read);
+        return Err(InternalError::from_response(e, response).into());
     }
+    next.call(req).await
+}
+
+// for the user's own account settings, which a leaked token shouldn't be able to change,
+// nor use to mint more tokens
+pub fn reject_api_tokens(route: Route) -> Route {
+    route.wrap(from_fn(
+        |req: ServiceRequest, next: Next<BoxBody>| async move {
+            if req.extensions().contains::<ApiTokenScopes>() {
+                let response = forbidden(&req, "This can only be done from a logged in browser.");
+                let e = anyhow::anyhow!("An api token was used to change account settings");
+                return Err(InternalError::from_response(e, response).into());
+            }
+            next.call(req).await
+        },
+    ))
 }
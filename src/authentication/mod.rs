@@ -1,6 +1,43 @@
+mod api_tokens;
+mod csrf;
+mod invitations;
 mod middleware;
 mod password;
+mod password_reset;
+mod sessions;
+mod tokens;
+mod two_factor;
+mod users;
 
+pub use api_tokens::{
+    ApiToken, ApiTokenOwner, check_api_token, create_api_token, list_api_tokens, revoke_api_token,
+};
+pub use csrf::{CsrfToken, verify_csrf_token};
+pub use invitations::{
+    AcceptInvitationOutcome, INVITATION_LIFETIME_HOURS, Invitation, accept_invitation,
+    create_invitation, find_pending_invitation, list_pending_invitations, revoke_invitation,
+};
 pub use middleware::UserId;
-pub use middleware::reject_anonymous_users;
-pub use password::{AuthError, Credentials, change_password, validate_credentials};
+pub use middleware::{
+    reject_anonymous_users, reject_api_tokens, require_api_token, require_permission,
+    require_read_scope,
+};
+pub use password::{
+    AuthError, Credentials, DummyPasswordHash, change_password, validate_credentials,
+    validate_new_password,
+};
+pub use password_reset::{
+    PASSWORD_RESET_LIFETIME_MINUTES, PasswordReset, create_password_reset,
+    password_reset_is_pending, reset_password,
+};
+pub use sessions::{
+    ActiveSession, SessionStatus, check_session, delete_expired_user_sessions, list_sessions,
+    revoke_all_sessions, revoke_session, start_session,
+};
+pub use two_factor::{
+    TwoFactorState, confirm_two_factor_enrollment, disable_two_factor, regenerate_recovery_codes,
+    start_two_factor_enrollment, two_factor_enabled, two_factor_state, verify_second_factor,
+};
+pub use users::{
+    User, delete_user, email_in_use, get_username, list_users, set_user_active, set_user_role,
+};
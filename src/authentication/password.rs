@@ -6,6 +6,7 @@ use argon2::{
 use secrecy::{ExposeSecret, SecretString};
 use sqlx::PgPool;
 
+use crate::configuration::PasswordHashingSettings;
 use crate::telemetry::spawn_blocking_with_tracing;
 
 #[derive(thiserror::Error, Debug)]
@@ -21,6 +22,17 @@ pub struct Credentials {
     pub password: SecretString,
 }
 
+// verified against when the username doesn't exist, so rejecting it takes as long as
+// rejecting a wrong password. hashed with the configured cost, once at startup
+pub struct DummyPasswordHash(SecretString);
+
+impl DummyPasswordHash {
+    pub fn new(hashing: &PasswordHashingSettings) -> Result<Self, anyhow::Error> {
+        let password = SecretString::new(Box::from("not-anyone's-password"));
+        compute_password_hash(password, hashing).map(Self)
+    }
+}
+
 #[tracing::instrument(name = "Get stored credentials", skip(username, pool))]
 async fn get_stored_credentials(
     username: &str,
@@ -30,7 +42,7 @@ async fn get_stored_credentials(
         r#"
         SELECT user_id, password_hash
         FROM users
-        WHERE username = $1
+        WHERE username = $1 AND is_active
         "#,
         username,
     )
@@ -41,18 +53,39 @@ async fn get_stored_credentials(
     Ok(row)
 }
 
-#[tracing::instrument(name = "Validate credentials", skip(credentials, pool))]
+// the rules every new password has to follow, as a message for the user when it doesn't
+pub fn validate_new_password(
+    new_password: &SecretString,
+    new_password_check: &SecretString,
+) -> Result<(), String> {
+    if new_password.expose_secret().len() < 13 {
+        return Err("Your new password is too short - it must be at least 13 characters.".into());
+    }
+    if new_password.expose_secret().len() > 128 {
+        return Err(
+            "Your new password is too long - it must be shorter than 129 characters.".into(),
+        );
+    }
+    if new_password.expose_secret() != new_password_check.expose_secret() {
+        return Err(
+            "You entered two different new passwords - the field values must match.".into(),
+        );
+    }
+    Ok(())
+}
+
+#[tracing::instrument(
+    name = "Validate credentials",
+    skip(credentials, pool, hashing, dummy_hash)
+)]
 pub async fn validate_credentials(
     credentials: Credentials,
     pool: &PgPool,
+    hashing: &PasswordHashingSettings,
+    dummy_hash: &DummyPasswordHash,
 ) -> Result<uuid::Uuid, AuthError> {
     let mut user_id = None;
-    let mut expected_password_hash = SecretString::new(Box::from(
-        "$argon2id$v=19$m=15000,t=2,p=1$\
-        gZiV/M1gPc22ElAH/Jh1Hw$\
-        CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno"
-            .to_string(),
-    ));
+    let mut expected_password_hash = dummy_hash.0.clone();
 
     if let Some((stored_user_id, stored_password_hash)) =
         get_stored_credentials(&credentials.username, pool).await?
@@ -61,8 +94,10 @@ pub async fn validate_credentials(
         expected_password_hash = stored_password_hash;
     }
 
-    spawn_blocking_with_tracing(move || {
-        verify_password_hash(expected_password_hash, credentials.password)
+    let password = credentials.password.clone();
+    let settings = hashing.clone();
+    let needs_rehash = spawn_blocking_with_tracing(move || {
+        verify_password_hash(expected_password_hash, credentials.password, &settings)
     })
     .await
     .context("Failed to spawn blocking task.")??;
@@ -70,40 +105,65 @@ pub async fn validate_credentials(
     // only set to Some if we found stored credentials
     // so even if the default password ends up matching (somehow)
     // we never authenticate a non-existing user.
-    user_id
+    let user_id = user_id
         .ok_or_else(|| anyhow::anyhow!("Unknown username."))
-        .map_err(AuthError::InvalidCredentials)
+        .map_err(AuthError::InvalidCredentials)?;
+
+    // we only ever see the plain password here, so this is where old hashes get upgraded.
+    // failing to do so shouldn't keep the user out, we'll try again next time
+    if needs_rehash && let Err(e) = change_password(user_id, password, pool, hashing).await {
+        tracing::warn!(error.cause_chain = ?e, "Failed to upgrade a password hash");
+    }
+    Ok(user_id)
 }
 
+// `true` when the password matched a hash that wasn't computed with the configured parameters
 #[tracing::instrument(
     name = "Verify password hash",
-    skip(expected_password_hash, password_candidate)
+    skip(expected_password_hash, password_candidate, hashing)
 )]
 fn verify_password_hash(
     expected_password_hash: SecretString,
     password_candidate: SecretString,
-) -> Result<(), AuthError> {
+    hashing: &PasswordHashingSettings,
+) -> Result<bool, AuthError> {
     let expected_password_hash = PasswordHash::new(expected_password_hash.expose_secret())
         .context("Failed to parse hash in PHC string format.")?;
 
+    // the algorithm, version and parameters are all read from the hash itself
     Argon2::default()
         .verify_password(
             password_candidate.expose_secret().as_bytes(),
             &expected_password_hash,
         )
         .context("Invalid password.")
-        .map_err(AuthError::InvalidCredentials)
+        .map_err(AuthError::InvalidCredentials)?;
+    Ok(needs_rehash(&expected_password_hash, hashing))
+}
+
+fn needs_rehash(password_hash: &PasswordHash, hashing: &PasswordHashingSettings) -> bool {
+    let Ok(params) = Params::try_from(password_hash) else {
+        return true;
+    };
+    password_hash.algorithm != Algorithm::Argon2id.ident()
+        || password_hash.version != Some(Version::V0x13.into())
+        || params.m_cost() != hashing.memory_kib
+        || params.t_cost() != hashing.iterations
+        || params.p_cost() != hashing.parallelism
 }
 
-#[tracing::instrument(name = "Change password", skip(password, pool))]
+#[tracing::instrument(name = "Change password", skip(password, pool, hashing))]
 pub async fn change_password(
     user_id: uuid::Uuid,
     password: SecretString,
     pool: &PgPool,
+    hashing: &PasswordHashingSettings,
 ) -> Result<(), anyhow::Error> {
-    let password_hash = spawn_blocking_with_tracing(move || compute_password_hash(password))
-        .await?
-        .context("Failed to hash password.")?;
+    let hashing = hashing.clone();
+    let password_hash =
+        spawn_blocking_with_tracing(move || compute_password_hash(password, &hashing))
+            .await?
+            .context("Failed to hash password.")?;
     sqlx::query!(
         r#"
         UPDATE users
@@ -119,14 +179,40 @@ pub async fn change_password(
     Ok(())
 }
 
-fn compute_password_hash(password: SecretString) -> Result<SecretString, anyhow::Error> {
+pub(super) fn compute_password_hash(
+    password: SecretString,
+    hashing: &PasswordHashingSettings,
+) -> Result<SecretString, anyhow::Error> {
     let salt = SaltString::generate(&mut OsRng);
-    let password_hash = Argon2::new(
-        Algorithm::Argon2id,
-        Version::V0x13,
-        Params::new(15000, 2, 1, None).unwrap(),
+    let params = Params::new(
+        hashing.memory_kib,
+        hashing.iterations,
+        hashing.parallelism,
+        None,
     )
-    .hash_password(password.expose_secret().as_bytes(), &salt)?
-    .to_string();
+    .context("Invalid Argon2 parameters.")?;
+    let password_hash = Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password(password.expose_secret().as_bytes(), &salt)?
+        .to_string();
     Ok(SecretString::new(Box::from(password_hash)))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{DummyPasswordHash, needs_rehash};
+    use crate::configuration::PasswordHashingSettings;
+    use argon2::PasswordHash;
+    use secrecy::ExposeSecret;
+
+    #[test]
+    fn the_dummy_hash_costs_as_much_as_a_stored_one() {
+        let hashing = PasswordHashingSettings {
+            memory_kib: 8192,
+            iterations: 3,
+            parallelism: 1,
+        };
+        let dummy = DummyPasswordHash::new(&hashing).unwrap();
+        let hash = PasswordHash::new(dummy.0.expose_secret()).unwrap();
+        assert!(!needs_rehash(&hash, &hashing));
+    }
+}
@@ -0,0 +1,155 @@
+use anyhow::Context;
+use chrono::{Duration, Utc};
+use secrecy::{ExposeSecret, SecretString};
+use sqlx::{Executor, PgPool};
+
+use super::password::compute_password_hash;
+use super::tokens::{generate_token, token_hash};
+use crate::configuration::PasswordHashingSettings;
+use crate::telemetry::spawn_blocking_with_tracing;
+
+// how long a reset link stays valid
+pub const PASSWORD_RESET_LIFETIME_MINUTES: i64 = 60;
+
+pub struct PasswordReset {
+    pub email: String,
+    pub token: String,
+}
+
+// `None` unless the username belongs to an active user with an email address.
+// any earlier reset link for the user stops working
+#[tracing::instrument(name = "Create a password reset", skip(pool))]
+pub async fn create_password_reset(
+    pool: &PgPool,
+    username: &str,
+) -> Result<Option<PasswordReset>, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let user = sqlx::query!(
+        r#"
+        SELECT user_id, email AS "email!"
+        FROM users
+        WHERE username = $1 AND is_active AND email IS NOT NULL
+        "#,
+        username
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to retrieve the user")?;
+    let Some(user) = user else {
+        return Ok(None);
+    };
+    transaction
+        .execute(sqlx::query!(
+            r#"DELETE FROM password_reset_tokens WHERE user_id = $1 AND used_at IS NULL"#,
+            user.user_id
+        ))
+        .await
+        .context("Failed to remove earlier reset links")?;
+    let token = generate_token();
+    transaction
+        .execute(sqlx::query!(
+            r#"
+            INSERT INTO password_reset_tokens (token_hash, user_id, created_at, expires_at)
+            VALUES ($1, $2, now(), $3)
+            "#,
+            token_hash(&token),
+            user.user_id,
+            Utc::now() + Duration::minutes(PASSWORD_RESET_LIFETIME_MINUTES)
+        ))
+        .await
+        .context("Failed to store the reset link")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to store a password reset.")?;
+    Ok(Some(PasswordReset {
+        email: user.email,
+        token,
+    }))
+}
+
+#[tracing::instrument(name = "Check a password reset link", skip(pool, token))]
+pub async fn password_reset_is_pending(pool: &PgPool, token: &str) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM password_reset_tokens
+            WHERE token_hash = $1 AND used_at IS NULL AND expires_at > now()
+        ) AS "pending!"
+        "#,
+        token_hash(token)
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to retrieve the reset link")?;
+    Ok(row.pending)
+}
+
+// returns false if the link is unknown, expired or already used.
+// every session the user had is logged out
+#[tracing::instrument(name = "Reset a password", skip(pool, token, password, hashing))]
+pub async fn reset_password(
+    pool: &PgPool,
+    token: &str,
+    password: SecretString,
+    hashing: &PasswordHashingSettings,
+) -> Result<bool, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    // lock the token, so the same link can't be used twice
+    let reset = sqlx::query!(
+        r#"
+        SELECT token_hash, user_id FROM password_reset_tokens
+        WHERE token_hash = $1 AND used_at IS NULL AND expires_at > now()
+        FOR UPDATE
+        "#,
+        token_hash(token)
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to retrieve the reset link")?;
+    let Some(reset) = reset else {
+        return Ok(false);
+    };
+    let hashing = hashing.clone();
+    let password_hash =
+        spawn_blocking_with_tracing(move || compute_password_hash(password, &hashing))
+            .await?
+            .context("Failed to hash password.")?;
+    transaction
+        .execute(sqlx::query!(
+            r#"
+            UPDATE users
+            SET password_hash = $2
+            WHERE user_id = $1
+            "#,
+            reset.user_id,
+            password_hash.expose_secret()
+        ))
+        .await
+        .context("Failed to change the user's password in the database.")?;
+    transaction
+        .execute(sqlx::query!(
+            r#"DELETE FROM user_sessions WHERE user_id = $1"#,
+            reset.user_id
+        ))
+        .await
+        .context("Failed to revoke the user's sessions")?;
+    transaction
+        .execute(sqlx::query!(
+            r#"UPDATE password_reset_tokens SET used_at = now() WHERE token_hash = $1"#,
+            reset.token_hash
+        ))
+        .await
+        .context("Failed to mark the reset link as used")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to reset a password.")?;
+    Ok(true)
+}
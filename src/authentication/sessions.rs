@@ -0,0 +1,181 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::configuration::SessionTimeoutSettings;
+use crate::domain::Role;
+
+pub enum SessionStatus {
+    Active(Role),
+    // idle for too long, or past its absolute lifetime
+    Expired,
+    // revoked, or the user has been deactivated or deleted
+    Ended,
+}
+
+pub struct ActiveSession {
+    pub session_id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub ip: String,
+    pub user_agent: String,
+}
+
+// called once the user has fully logged in, the id goes into their session cookie
+#[tracing::instrument(name = "Start a session", skip(pool, user_agent))]
+pub async fn start_session(
+    pool: &PgPool,
+    user_id: Uuid,
+    ip: &str,
+    user_agent: &str,
+) -> Result<Uuid, anyhow::Error> {
+    let session_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO user_sessions (session_id, user_id, created_at, last_seen_at, ip, user_agent)
+        VALUES ($1, $2, now(), now(), $3, $4)
+        "#,
+        session_id,
+        user_id,
+        ip,
+        user_agent
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store the session")?;
+    Ok(session_id)
+}
+
+// runs on every request, recording the activity of sessions that are still good.
+// expired sessions are deleted, so they disappear from the sessions page as well
+#[tracing::instrument(name = "Check a session", skip(pool, timeouts))]
+pub async fn check_session(
+    pool: &PgPool,
+    user_id: Uuid,
+    session_id: Uuid,
+    timeouts: &SessionTimeoutSettings,
+) -> Result<SessionStatus, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT
+            u.role,
+            (s.last_seen_at <= now() - make_interval(secs => $3)
+                OR s.created_at <= now() - make_interval(secs => $4)) AS "expired!"
+        FROM user_sessions s
+        JOIN users u ON u.user_id = s.user_id
+        WHERE s.session_id = $1 AND s.user_id = $2 AND u.is_active
+        "#,
+        session_id,
+        user_id,
+        timeouts.idle_seconds as f64,
+        timeouts.absolute_seconds as f64
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the session")?;
+    let Some(row) = row else {
+        return Ok(SessionStatus::Ended);
+    };
+    if row.expired {
+        revoke_session(pool, user_id, session_id).await?;
+        return Ok(SessionStatus::Expired);
+    }
+    sqlx::query!(
+        r#"UPDATE user_sessions SET last_seen_at = now() WHERE session_id = $1"#,
+        session_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to record the session's activity")?;
+    let role = Role::parse(&row.role).map_err(anyhow::Error::msg)?;
+    Ok(SessionStatus::Active(role))
+}
+
+// leaves out sessions that have expired without anyone noticing yet
+#[tracing::instrument(name = "List a user's sessions", skip(pool, timeouts))]
+pub async fn list_sessions(
+    pool: &PgPool,
+    user_id: Uuid,
+    timeouts: &SessionTimeoutSettings,
+) -> Result<Vec<ActiveSession>, anyhow::Error> {
+    sqlx::query_as!(
+        ActiveSession,
+        r#"
+        SELECT session_id, created_at, last_seen_at, ip, user_agent
+        FROM user_sessions
+        WHERE user_id = $1
+            AND last_seen_at > now() - make_interval(secs => $2)
+            AND created_at > now() - make_interval(secs => $3)
+        ORDER BY last_seen_at DESC
+        "#,
+        user_id,
+        timeouts.idle_seconds as f64,
+        timeouts.absolute_seconds as f64
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to list the sessions")
+}
+
+// false if the session doesn't exist or belongs to someone else
+#[tracing::instrument(name = "Revoke a session", skip(pool))]
+pub async fn revoke_session(
+    pool: &PgPool,
+    user_id: Uuid,
+    session_id: Uuid,
+) -> Result<bool, anyhow::Error> {
+    let deleted = sqlx::query!(
+        r#"DELETE FROM user_sessions WHERE session_id = $1 AND user_id = $2"#,
+        session_id,
+        user_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to revoke the session")?
+    .rows_affected();
+    Ok(deleted > 0)
+}
+
+// logs the user out everywhere, except for the session passed as `keep`
+#[tracing::instrument(name = "Revoke a user's sessions", skip(pool))]
+pub async fn revoke_all_sessions(
+    pool: &PgPool,
+    user_id: Uuid,
+    keep: Option<Uuid>,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        DELETE FROM user_sessions
+        WHERE user_id = $1 AND session_id IS DISTINCT FROM $2
+        "#,
+        user_id,
+        keep
+    )
+    .execute(pool)
+    .await
+    .context("Failed to revoke the user's sessions")?;
+    Ok(())
+}
+
+// for the cleanup worker, sessions that expire unnoticed are never checked again
+#[tracing::instrument(name = "Delete expired user sessions", skip(pool, timeouts))]
+pub async fn delete_expired_user_sessions(
+    pool: &PgPool,
+    timeouts: &SessionTimeoutSettings,
+) -> Result<u64, anyhow::Error> {
+    let deleted = sqlx::query!(
+        r#"
+        DELETE FROM user_sessions
+        WHERE last_seen_at <= now() - make_interval(secs => $1)
+            OR created_at <= now() - make_interval(secs => $2)
+        "#,
+        timeouts.idle_seconds as f64,
+        timeouts.absolute_seconds as f64
+    )
+    .execute(pool)
+    .await
+    .context("Failed to delete expired user sessions")?
+    .rows_affected();
+    Ok(deleted)
+}
@@ -0,0 +1,16 @@
+use rand::{Rng, distr::Alphanumeric, rng};
+use sha2::{Digest, Sha256};
+
+// for links sent by email: invitations and password resets
+pub(super) fn generate_token() -> String {
+    let mut rng = rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(32)
+        .collect()
+}
+
+// only the hash is stored, so a database leak doesn't hand out working links
+pub(super) fn token_hash(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
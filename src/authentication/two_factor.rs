@@ -0,0 +1,346 @@
+use anyhow::Context;
+use rand::{Rng, distr::Alphanumeric, rng};
+use sha2::{Digest, Sha256};
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use std::time::{SystemTime, UNIX_EPOCH};
+use subtle::ConstantTimeEq;
+use totp_rs::{Algorithm, Secret, TOTP};
+use uuid::Uuid;
+
+// shown next to the account name in authenticator apps
+const TOTP_ISSUER: &str = "zero2prod";
+const TOTP_STEP_SECONDS: u64 = 30;
+const RECOVERY_CODE_COUNT: usize = 10;
+
+pub enum TwoFactorState {
+    Disabled,
+    // a secret has been generated but no code has been entered for it yet
+    Pending { secret: String, otpauth_url: String },
+    Enabled { recovery_codes_left: i64 },
+}
+
+fn build_totp(secret: &str, username: &str) -> Result<TOTP, anyhow::Error> {
+    let secret = Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .map_err(|e| anyhow::anyhow!("Invalid TOTP secret: {:?}", e))?;
+    // the url's label can't contain a colon
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        1,
+        TOTP_STEP_SECONDS,
+        secret,
+        Some(TOTP_ISSUER.to_string()),
+        username.replace(':', "_"),
+    )
+    .context("Failed to build a TOTP generator")
+}
+
+// the time step the code belongs to, allowing one step of clock drift either way
+fn matching_step(totp: &TOTP, code: &str) -> Option<i64> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System time is before the unix epoch")
+        .as_secs();
+    [now - TOTP_STEP_SECONDS, now, now + TOTP_STEP_SECONDS]
+        .into_iter()
+        .find(|time| bool::from(totp.generate(*time).as_bytes().ct_eq(code.as_bytes())))
+        .map(|time| (time / TOTP_STEP_SECONDS) as i64)
+}
+
+// recovery codes are entered by hand, so case, spaces and dashes don't matter
+fn recovery_code_hash(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    hex::encode(Sha256::digest(normalized.as_bytes()))
+}
+
+fn generate_recovery_code() -> String {
+    let mut rng = rng();
+    let code: String = std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(|c| char::from(c).to_ascii_lowercase())
+        .take(10)
+        .collect();
+    format!("{}-{}", &code[..5], &code[5..])
+}
+
+#[tracing::instrument(name = "Get two-factor state", skip(pool))]
+pub async fn two_factor_state(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<TwoFactorState, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT t.secret, t.confirmed_at, u.username,
+            (SELECT count(*) FROM user_recovery_codes r
+             WHERE r.user_id = t.user_id AND r.used_at IS NULL) AS "recovery_codes_left!"
+        FROM user_totp t
+        JOIN users u ON u.user_id = t.user_id
+        WHERE t.user_id = $1
+        "#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the two-factor settings")?;
+    let state = match row {
+        None => TwoFactorState::Disabled,
+        Some(r) if r.confirmed_at.is_some() => TwoFactorState::Enabled {
+            recovery_codes_left: r.recovery_codes_left,
+        },
+        Some(r) => {
+            let otpauth_url = build_totp(&r.secret, &r.username)?.get_url();
+            TwoFactorState::Pending {
+                secret: r.secret,
+                otpauth_url,
+            }
+        }
+    };
+    Ok(state)
+}
+
+#[tracing::instrument(name = "Check whether two-factor is enabled", skip(pool))]
+pub async fn two_factor_enabled(pool: &PgPool, user_id: Uuid) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM user_totp WHERE user_id = $1 AND confirmed_at IS NOT NULL
+        ) AS "enabled!"
+        "#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to retrieve the two-factor settings")?;
+    Ok(row.enabled)
+}
+
+// generates a fresh secret, unless two-factor is already enabled
+#[tracing::instrument(name = "Start two-factor enrollment", skip(pool))]
+pub async fn start_two_factor_enrollment(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    let secret = Secret::generate_secret().to_encoded().to_string();
+    sqlx::query!(
+        r#"
+        INSERT INTO user_totp (user_id, secret)
+        VALUES ($1, $2)
+        ON CONFLICT (user_id) DO UPDATE SET secret = EXCLUDED.secret
+        WHERE user_totp.confirmed_at IS NULL
+        "#,
+        user_id,
+        secret
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store the TOTP secret")?;
+    Ok(())
+}
+
+async fn replace_recovery_codes(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+) -> Result<Vec<String>, anyhow::Error> {
+    transaction
+        .execute(sqlx::query!(
+            r#"DELETE FROM user_recovery_codes WHERE user_id = $1"#,
+            user_id
+        ))
+        .await
+        .context("Failed to remove the old recovery codes")?;
+    let codes: Vec<String> = std::iter::repeat_with(generate_recovery_code)
+        .take(RECOVERY_CODE_COUNT)
+        .collect();
+    let hashes: Vec<String> = codes.iter().map(|c| recovery_code_hash(c)).collect();
+    transaction
+        .execute(sqlx::query!(
+            r#"
+            INSERT INTO user_recovery_codes (user_id, code_hash)
+            SELECT $1, * FROM UNNEST($2::text[])
+            "#,
+            user_id,
+            &hashes
+        ))
+        .await
+        .context("Failed to store the recovery codes")?;
+    Ok(codes)
+}
+
+// `None` if the code doesn't match the pending secret, otherwise
+// two-factor is now enabled and these are the recovery codes
+#[tracing::instrument(name = "Confirm two-factor enrollment", skip(pool, code))]
+pub async fn confirm_two_factor_enrollment(
+    pool: &PgPool,
+    user_id: Uuid,
+    code: &str,
+) -> Result<Option<Vec<String>>, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let row = sqlx::query!(
+        r#"
+        SELECT t.secret, u.username
+        FROM user_totp t
+        JOIN users u ON u.user_id = t.user_id
+        WHERE t.user_id = $1 AND t.confirmed_at IS NULL
+        FOR UPDATE
+        "#,
+        user_id
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to retrieve the pending TOTP secret")?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    let Some(step) = matching_step(&build_totp(&row.secret, &row.username)?, code.trim()) else {
+        return Ok(None);
+    };
+    transaction
+        .execute(sqlx::query!(
+            r#"
+            UPDATE user_totp SET confirmed_at = now(), last_used_step = $2
+            WHERE user_id = $1
+            "#,
+            user_id,
+            step
+        ))
+        .await
+        .context("Failed to enable two-factor authentication")?;
+    let codes = replace_recovery_codes(&mut transaction, user_id).await?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to enable two-factor authentication.")?;
+    Ok(Some(codes))
+}
+
+// accepts a code from the authenticator app or an unused recovery code,
+// either of which can only be used once
+#[tracing::instrument(name = "Verify a second factor", skip(pool, code))]
+pub async fn verify_second_factor(
+    pool: &PgPool,
+    user_id: Uuid,
+    code: &str,
+) -> Result<bool, anyhow::Error> {
+    let code = code.trim();
+    let row = sqlx::query!(
+        r#"
+        SELECT t.secret, u.username
+        FROM user_totp t
+        JOIN users u ON u.user_id = t.user_id
+        WHERE t.user_id = $1 AND t.confirmed_at IS NOT NULL
+        "#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the TOTP secret")?;
+    let Some(row) = row else {
+        return Ok(false);
+    };
+
+    if let Some(step) = matching_step(&build_totp(&row.secret, &row.username)?, code) {
+        let updated = sqlx::query!(
+            r#"
+            UPDATE user_totp SET last_used_step = $2
+            WHERE user_id = $1 AND (last_used_step IS NULL OR last_used_step < $2)
+            "#,
+            user_id,
+            step
+        )
+        .execute(pool)
+        .await
+        .context("Failed to record the use of a TOTP code")?
+        .rows_affected();
+        return Ok(updated > 0);
+    }
+
+    let used = sqlx::query!(
+        r#"
+        UPDATE user_recovery_codes SET used_at = now()
+        WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL
+        "#,
+        user_id,
+        recovery_code_hash(code)
+    )
+    .execute(pool)
+    .await
+    .context("Failed to record the use of a recovery code")?
+    .rows_affected();
+    Ok(used > 0)
+}
+
+// the previous codes stop working
+#[tracing::instrument(name = "Regenerate recovery codes", skip(pool))]
+pub async fn regenerate_recovery_codes(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Vec<String>, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let codes = replace_recovery_codes(&mut transaction, user_id).await?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to replace recovery codes.")?;
+    Ok(codes)
+}
+
+#[tracing::instrument(name = "Disable two-factor authentication", skip(pool))]
+pub async fn disable_two_factor(pool: &PgPool, user_id: Uuid) -> Result<(), anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    transaction
+        .execute(sqlx::query!(
+            r#"DELETE FROM user_recovery_codes WHERE user_id = $1"#,
+            user_id
+        ))
+        .await
+        .context("Failed to remove the recovery codes")?;
+    transaction
+        .execute(sqlx::query!(
+            r#"DELETE FROM user_totp WHERE user_id = $1"#,
+            user_id
+        ))
+        .await
+        .context("Failed to remove the TOTP secret")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to disable two-factor authentication.")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate_recovery_code, recovery_code_hash};
+
+    #[test]
+    fn recovery_codes_are_two_groups_of_five() {
+        let code = generate_recovery_code();
+        assert_eq!(code.len(), 11);
+        assert_eq!(code.chars().nth(5), Some('-'));
+    }
+
+    #[test]
+    fn recovery_codes_ignore_case_spaces_and_dashes() {
+        assert_eq!(
+            recovery_code_hash("abcde-12345"),
+            recovery_code_hash(" ABCDE 12345 ")
+        );
+        assert_ne!(
+            recovery_code_hash("abcde-12345"),
+            recovery_code_hash("abcde-12346")
+        );
+    }
+}
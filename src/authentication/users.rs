@@ -0,0 +1,177 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use secrecy::{ExposeSecret, SecretString};
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use super::password::compute_password_hash;
+use crate::configuration::PasswordHashingSettings;
+use crate::domain::{Role, Username};
+use crate::telemetry::spawn_blocking_with_tracing;
+
+pub struct User {
+    pub user_id: Uuid,
+    pub username: String,
+    // only users created through an invitation have one
+    pub email: Option<String>,
+    pub is_active: bool,
+    pub role: Role,
+    pub created_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(name = "List users", skip(pool))]
+pub async fn list_users(pool: &PgPool) -> Result<Vec<User>, anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT user_id, username, email, is_active, role, created_at
+        FROM users
+        ORDER BY created_at, username
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve users")?;
+    rows.into_iter()
+        .map(|r| {
+            Ok(User {
+                user_id: r.user_id,
+                username: r.username,
+                email: r.email,
+                is_active: r.is_active,
+                role: Role::parse(&r.role).map_err(anyhow::Error::msg)?,
+                created_at: r.created_at,
+            })
+        })
+        .collect()
+}
+
+#[tracing::instrument(name = "Get username", skip(pool))]
+pub async fn get_username(user_id: Uuid, pool: &PgPool) -> Result<String, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT username
+        FROM users
+        WHERE user_id = $1
+        "#,
+        user_id,
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to perform a query to retrieve a username.")?;
+    Ok(row.username)
+}
+
+#[tracing::instrument(name = "Check whether an email belongs to a user", skip(pool))]
+pub async fn email_in_use(pool: &PgPool, email: &str) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"SELECT EXISTS (SELECT 1 FROM users WHERE lower(email) = lower($1)) AS "in_use!""#,
+        email
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to look up the email address")?;
+    Ok(row.in_use)
+}
+
+// returns whether the user exists
+#[tracing::instrument(name = "Set whether a user is active", skip(pool))]
+pub async fn set_user_active(
+    pool: &PgPool,
+    user_id: Uuid,
+    is_active: bool,
+) -> Result<bool, anyhow::Error> {
+    let updated = sqlx::query!(
+        r#"UPDATE users SET is_active = $2 WHERE user_id = $1"#,
+        user_id,
+        is_active
+    )
+    .execute(pool)
+    .await
+    .context("Failed to update the user")?
+    .rows_affected();
+    Ok(updated > 0)
+}
+
+// returns whether the user exists
+#[tracing::instrument(name = "Change the role of a user", skip(pool))]
+pub async fn set_user_role(
+    pool: &PgPool,
+    user_id: Uuid,
+    role: Role,
+) -> Result<bool, anyhow::Error> {
+    let updated = sqlx::query!(
+        r#"UPDATE users SET role = $2 WHERE user_id = $1"#,
+        user_id,
+        role.as_str()
+    )
+    .execute(pool)
+    .await
+    .context("Failed to update the user's role")?
+    .rows_affected();
+    Ok(updated > 0)
+}
+
+// returns whether the user existed
+#[tracing::instrument(name = "Delete a user", skip(pool))]
+pub async fn delete_user(pool: &PgPool, user_id: Uuid) -> Result<bool, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    // saved responses are only replayed to the user who made the request
+    transaction
+        .execute(sqlx::query!(
+            r#"DELETE FROM idempotency WHERE user_id = $1"#,
+            user_id
+        ))
+        .await
+        .context("Failed to remove the user's saved responses")?;
+    let deleted = transaction
+        .execute(sqlx::query!(
+            r#"DELETE FROM users WHERE user_id = $1"#,
+            user_id
+        ))
+        .await
+        .context("Failed to delete the user")?
+        .rows_affected();
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to delete a user.")?;
+    Ok(deleted > 0)
+}
+
+// `None` when the username or email already belongs to someone
+#[tracing::instrument(name = "Create a user", skip(transaction, password, hashing))]
+pub async fn create_user(
+    transaction: &mut Transaction<'_, Postgres>,
+    username: &Username,
+    email: &str,
+    role: Role,
+    password: SecretString,
+    hashing: &PasswordHashingSettings,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let hashing = hashing.clone();
+    let password_hash =
+        spawn_blocking_with_tracing(move || compute_password_hash(password, &hashing))
+            .await?
+            .context("Failed to hash password.")?;
+    let user_id = Uuid::new_v4();
+    let inserted = transaction
+        .execute(sqlx::query!(
+            r#"
+            INSERT INTO users (user_id, username, password_hash, email, role, is_active, created_at)
+            VALUES ($1, $2, $3, $4, $5, true, now())
+            ON CONFLICT DO NOTHING
+            "#,
+            user_id,
+            username.as_ref(),
+            password_hash.expose_secret(),
+            email,
+            role.as_str()
+        ))
+        .await
+        .context("Failed to store the new user")?
+        .rows_affected();
+    Ok((inserted > 0).then_some(user_id))
+}
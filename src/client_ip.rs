@@ -0,0 +1,76 @@
+use actix_web::{HttpRequest, web::Data};
+
+// how many reverse proxies sit in front of the app, each appending to `X-Forwarded-For`
+#[derive(Clone, Copy)]
+pub struct TrustedProxies(pub usize);
+
+// the address the per-ip limits are keyed on.
+// whatever the client sent in `X-Forwarded-For` is left of the entries our proxies
+// appended, so only the right-most entry they didn't write themselves is trusted
+pub fn client_ip(request: &HttpRequest) -> String {
+    let hops = request
+        .app_data::<Data<TrustedProxies>>()
+        .map_or(0, |proxies| proxies.0);
+    let peer_ip = request
+        .peer_addr()
+        .map(|addr| addr.ip().to_string())
+        .unwrap_or_else(|| "unknown".into());
+    if hops == 0 {
+        return peer_ip;
+    }
+    let forwarded_for: Vec<&str> = request
+        .headers()
+        .get_all("X-Forwarded-For")
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .collect();
+    // fewer entries than proxies means the request didn't come through all of them
+    forwarded_for
+        .len()
+        .checked_sub(hops)
+        .and_then(|i| forwarded_for.get(i))
+        .map_or(peer_ip, |ip| ip.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TrustedProxies, client_ip};
+    use actix_web::{test::TestRequest, web::Data};
+
+    fn request(hops: usize, forwarded_for: Option<&str>) -> TestRequest {
+        let request = TestRequest::default()
+            .peer_addr("10.0.0.1:4000".parse().unwrap())
+            .app_data(Data::new(TrustedProxies(hops)));
+        match forwarded_for {
+            Some(value) => request.insert_header(("X-Forwarded-For", value)),
+            None => request,
+        }
+    }
+
+    #[test]
+    fn without_proxies_the_peer_address_is_used() {
+        let request = request(0, Some("1.2.3.4")).to_http_request();
+        assert_eq!(client_ip(&request), "10.0.0.1");
+    }
+
+    #[test]
+    fn behind_one_proxy_the_right_most_entry_is_used() {
+        let request = request(1, Some("6.6.6.6, 1.2.3.4")).to_http_request();
+        assert_eq!(client_ip(&request), "1.2.3.4");
+    }
+
+    #[test]
+    fn behind_two_proxies_the_entry_before_the_last_proxy_is_used() {
+        let request = request(2, Some("6.6.6.6, 1.2.3.4, 10.0.0.2")).to_http_request();
+        assert_eq!(client_ip(&request), "1.2.3.4");
+    }
+
+    #[test]
+    fn too_few_entries_fall_back_to_the_peer_address() {
+        let short_chain = request(2, Some("1.2.3.4")).to_http_request();
+        assert_eq!(client_ip(&short_chain), "10.0.0.1");
+        let no_header = request(1, None).to_http_request();
+        assert_eq!(client_ip(&no_header), "10.0.0.1");
+    }
+}
@@ -40,7 +40,36 @@ pub struct Settings {
     pub database: DatabaseSettings,
     pub application: ApplicationSettings,
     pub email_client: EmailClientSettings,
-    pub redis_uri: SecretString,
+    // only needed when `session_store` is redis
+    pub redis_uri: Option<SecretString>,
+    pub session_store: SessionStoreKind,
+    pub session_timeouts: SessionTimeoutSettings,
+    pub postmark_webhook: PostmarkWebhookSettings,
+    pub signup_protection: SignupProtectionSettings,
+    pub login_protection: LoginProtectionSettings,
+    pub link_request_protection: LinkRequestProtectionSettings,
+    pub password_hashing: PasswordHashingSettings,
+    pub cors: CorsSettings,
+}
+
+// where sessions live, along with the signup and login counters kept next to them.
+// postgres spares small deployments from running redis
+#[derive(serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionStoreKind {
+    Redis,
+    Postgres,
+}
+
+// how long a logged in session keeps working
+#[derive(serde::Deserialize, Clone)]
+pub struct SessionTimeoutSettings {
+    // without a single request
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub idle_seconds: u64,
+    // since logging in, however busy the session has been
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub absolute_seconds: u64,
 }
 
 #[derive(serde::Deserialize, Clone)]
@@ -50,6 +79,9 @@ pub struct ApplicationSettings {
     pub host: String,
     pub base_url: String,
     pub hmac_secret: SecretString,
+    // 0 when clients connect directly, otherwise the number of proxies in front
+    #[serde(default, deserialize_with = "deserialize_number_from_string")]
+    pub trusted_proxy_hops: usize,
 }
 
 #[derive(serde::Deserialize, Clone)]
@@ -109,6 +141,80 @@ impl EmailClientSettings {
     }
 }
 
+// the credentials Postmark is configured to send, as basic auth, with every webhook call
+#[derive(serde::Deserialize, Clone)]
+pub struct PostmarkWebhookSettings {
+    pub username: String,
+    pub password: SecretString,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct SignupProtectionSettings {
+    // prefix for the rate limit counters in redis
+    pub key_prefix: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub window_seconds: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_signups_per_ip: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_signups_per_email: u64,
+    // e.g. disposable email providers, subdomains are blocked too
+    pub blocked_domains: Vec<String>,
+}
+
+// the Argon2id cost every password gets hashed with,
+// stored hashes with different parameters are upgraded on the next login
+#[derive(serde::Deserialize, Clone)]
+pub struct PasswordHashingSettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub memory_kib: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub iterations: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub parallelism: u32,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct LoginProtectionSettings {
+    // prefix for the failure counters and locks in redis
+    pub key_prefix: String,
+    // how long a failed login is remembered
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub window_seconds: u64,
+    // failures a username gets before each further one locks it for a while
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub free_failures: u64,
+    // the first of those locks, each one after it is twice as long
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub base_delay_seconds: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_failures_per_username: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_failures_per_ip: u64,
+    // how long a username or ip is locked out once it reaches its maximum
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub lockout_seconds: u64,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct LinkRequestProtectionSettings {
+    // prefix for the request counters in redis
+    pub key_prefix: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub window_seconds: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_requests_per_ip: u64,
+    // past this the requests are still answered, but no more emails go out
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_requests_per_recipient: u64,
+}
+
+// origins whose pages may post to `/subscriptions` from the browser, e.g. the embedded form
+#[derive(serde::Deserialize, Clone)]
+pub struct CorsSettings {
+    pub allowed_origins: Vec<String>,
+}
+
 pub fn get_configuration() -> Result<Settings, config::ConfigError> {
     let base_path = std::env::current_dir().expect("Failed to determine the current directory");
     let configuration_directory = base_path.join("configuration");
@@ -0,0 +1,165 @@
+use anyhow::Context;
+use redis::{AsyncCommands, aio::ConnectionManager};
+use secrecy::{ExposeSecret, SecretString};
+use sqlx::PgPool;
+
+use crate::configuration::SessionStoreKind;
+
+// expiring counters for the signup and login limits, kept wherever the sessions are
+#[derive(Clone)]
+pub enum CounterStore {
+    Redis(ConnectionManager),
+    Postgres(PgPool),
+}
+
+impl CounterStore {
+    pub async fn build(
+        kind: SessionStoreKind,
+        redis_uri: Option<&SecretString>,
+        pool: &PgPool,
+    ) -> Result<Self, anyhow::Error> {
+        match kind {
+            SessionStoreKind::Redis => {
+                let redis_uri =
+                    redis_uri.context("`redis_uri` is required to keep counters in redis")?;
+                let client = redis::Client::open(redis_uri.expose_secret())
+                    .context("Failed to parse the redis uri")?;
+                let connection = ConnectionManager::new(client)
+                    .await
+                    .context("Failed to connect to redis")?;
+                Ok(Self::Redis(connection))
+            }
+            SessionStoreKind::Postgres => Ok(Self::Postgres(pool.clone())),
+        }
+    }
+
+    // adds one to `key` and returns the new count.
+    // the count expires `window_seconds` after the first increment
+    pub async fn increment(&self, key: &str, window_seconds: u64) -> Result<u64, anyhow::Error> {
+        match self {
+            Self::Redis(connection) => {
+                let mut connection = connection.clone();
+                // the key gets its expiry when it's created, so no counter outlives its window
+                let (count,): (u64,) = redis::pipe()
+                    .cmd("SET")
+                    .arg(key)
+                    .arg(0)
+                    .arg("NX")
+                    .arg("EX")
+                    .arg(window_seconds)
+                    .ignore()
+                    .incr(key, 1)
+                    .query_async(&mut connection)
+                    .await
+                    .context("Failed to increment a counter")?;
+                Ok(count)
+            }
+            Self::Postgres(pool) => {
+                let row = sqlx::query!(
+                    r#"
+                    INSERT INTO rate_limit_counters (key, value, expires_at)
+                    VALUES ($1, 1, now() + make_interval(secs => $2))
+                    ON CONFLICT (key) DO UPDATE SET
+                        value = CASE WHEN rate_limit_counters.expires_at > now()
+                            THEN rate_limit_counters.value + 1 ELSE 1 END,
+                        expires_at = CASE WHEN rate_limit_counters.expires_at > now()
+                            THEN rate_limit_counters.expires_at ELSE excluded.expires_at END
+                    RETURNING value
+                    "#,
+                    key,
+                    window_seconds as f64
+                )
+                .fetch_one(pool)
+                .await
+                .context("Failed to increment a counter")?;
+                Ok(row.value as u64)
+            }
+        }
+    }
+
+    // marks `key` as present for the next `seconds`
+    pub async fn set(&self, key: &str, seconds: u64) -> Result<(), anyhow::Error> {
+        match self {
+            Self::Redis(connection) => {
+                let mut connection = connection.clone();
+                let _: () = connection
+                    .set_ex(key, 1, seconds)
+                    .await
+                    .context("Failed to set a counter")?;
+            }
+            Self::Postgres(pool) => {
+                sqlx::query!(
+                    r#"
+                    INSERT INTO rate_limit_counters (key, value, expires_at)
+                    VALUES ($1, 1, now() + make_interval(secs => $2))
+                    ON CONFLICT (key) DO UPDATE SET value = 1, expires_at = excluded.expires_at
+                    "#,
+                    key,
+                    seconds as f64
+                )
+                .execute(pool)
+                .await
+                .context("Failed to set a counter")?;
+            }
+        }
+        Ok(())
+    }
+
+    // whether any of `keys` is present and hasn't expired
+    pub async fn exists(&self, keys: &[String]) -> Result<bool, anyhow::Error> {
+        match self {
+            Self::Redis(connection) => {
+                let mut connection = connection.clone();
+                let found: u64 = connection
+                    .exists(keys)
+                    .await
+                    .context("Failed to look up counters")?;
+                Ok(found > 0)
+            }
+            Self::Postgres(pool) => {
+                let row = sqlx::query!(
+                    r#"
+                    SELECT EXISTS (
+                        SELECT 1 FROM rate_limit_counters
+                        WHERE key = ANY($1) AND expires_at > now()
+                    ) AS "found!"
+                    "#,
+                    keys
+                )
+                .fetch_one(pool)
+                .await
+                .context("Failed to look up counters")?;
+                Ok(row.found)
+            }
+        }
+    }
+
+    pub async fn delete(&self, key: &str) -> Result<(), anyhow::Error> {
+        match self {
+            Self::Redis(connection) => {
+                let mut connection = connection.clone();
+                let _: () = connection
+                    .del(key)
+                    .await
+                    .context("Failed to delete a counter")?;
+            }
+            Self::Postgres(pool) => {
+                sqlx::query!(r#"DELETE FROM rate_limit_counters WHERE key = $1"#, key)
+                    .execute(pool)
+                    .await
+                    .context("Failed to delete a counter")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// redis expires its keys by itself, postgres leaves the rows behind
+pub async fn delete_expired_counters(pool: &PgPool) -> Result<u64, anyhow::Error> {
+    let deleted = sqlx::query!(r#"DELETE FROM rate_limit_counters WHERE expires_at <= now()"#)
+        .execute(pool)
+        .await
+        .context("Failed to delete expired counters")?
+        .rows_affected();
+    Ok(deleted)
+}
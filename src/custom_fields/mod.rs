@@ -0,0 +1,6 @@
+mod persistence;
+
+pub use persistence::{
+    delete_field_value, get_field_values, insert_field_definition, list_field_definitions,
+    store_field_values,
+};
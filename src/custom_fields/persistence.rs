@@ -0,0 +1,122 @@
+use anyhow::Context;
+use sqlx::{Executor, PgPool, Postgres};
+use uuid::Uuid;
+
+use crate::domain::{CustomFieldDefinition, CustomFieldType, CustomFieldValue};
+
+#[tracing::instrument(name = "List custom field definitions", skip(pool))]
+pub async fn list_field_definitions(
+    pool: &PgPool,
+) -> Result<Vec<CustomFieldDefinition>, anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT field_name, label, field_type, enum_options
+        FROM custom_fields
+        ORDER BY created_at
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve custom field definitions.")?;
+
+    rows.into_iter()
+        .map(|r| {
+            let field_type = CustomFieldType::parse(&r.field_type, &r.enum_options)
+                .map_err(anyhow::Error::msg)?;
+            Ok(CustomFieldDefinition {
+                name: r.field_name,
+                label: r.label,
+                field_type,
+            })
+        })
+        .collect()
+}
+
+#[tracing::instrument(name = "Save a custom field definition", skip(pool, definition), fields(field_name = %definition.name))]
+pub async fn insert_field_definition(
+    pool: &PgPool,
+    definition: &CustomFieldDefinition,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO custom_fields (field_name, label, field_type, enum_options, created_at)
+        VALUES ($1, $2, $3, $4, now())
+        "#,
+        definition.name,
+        definition.label,
+        definition.field_type.as_str(),
+        definition.field_type.options(),
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// upsert the given values, leaving the subscriber's other fields untouched
+#[tracing::instrument(name = "Store custom field values", skip(executor, values))]
+pub async fn store_field_values<'c, E>(
+    executor: E,
+    subscriber_id: Uuid,
+    values: &[CustomFieldValue],
+) -> Result<(), sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    if values.is_empty() {
+        return Ok(());
+    }
+    let names: Vec<String> = values.iter().map(|v| v.name.clone()).collect();
+    let values: Vec<String> = values.iter().map(|v| v.value.clone()).collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriber_field_values (subscriber_id, field_name, value)
+        SELECT $1, field_name, value
+        FROM UNNEST($2::text[], $3::text[]) AS v(field_name, value)
+        ON CONFLICT (subscriber_id, field_name) DO UPDATE SET value = EXCLUDED.value
+        "#,
+        subscriber_id,
+        &names,
+        &values,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Delete a custom field value", skip(pool))]
+pub async fn delete_field_value(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    field_name: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        DELETE FROM subscriber_field_values
+        WHERE subscriber_id = $1 AND field_name = $2
+        "#,
+        subscriber_id,
+        field_name,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Get custom field values", skip(pool))]
+pub async fn get_field_values(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Vec<CustomFieldValue>, sqlx::Error> {
+    sqlx::query_as!(
+        CustomFieldValue,
+        r#"
+        SELECT field_name as name, value
+        FROM subscriber_field_values
+        WHERE subscriber_id = $1
+        ORDER BY field_name
+        "#,
+        subscriber_id,
+    )
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,209 @@
+#[derive(Debug, Clone, PartialEq)]
+pub enum CustomFieldType {
+    Text,
+    Number,
+    Boolean,
+    Enum(Vec<String>),
+}
+
+impl CustomFieldType {
+    pub fn parse(kind: &str, options: &[String]) -> Result<CustomFieldType, String> {
+        match kind.to_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "number" => Ok(Self::Number),
+            "boolean" => Ok(Self::Boolean),
+            "enum" => {
+                let options: Vec<String> = options
+                    .iter()
+                    .map(|o| o.trim().to_string())
+                    .filter(|o| !o.is_empty())
+                    .collect();
+                if options.is_empty() {
+                    Err("An enum field needs at least one option.".into())
+                } else {
+                    Ok(Self::Enum(options))
+                }
+            }
+            other => Err(format!(
+                "{} is not a supported field type. \
+                Use one of `text`, `number`, `boolean` or `enum`.",
+                other
+            )),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Number => "number",
+            Self::Boolean => "boolean",
+            Self::Enum(_) => "enum",
+        }
+    }
+
+    pub fn options(&self) -> &[String] {
+        match self {
+            Self::Enum(options) => options,
+            _ => &[],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CustomFieldDefinition {
+    pub name: String,
+    pub label: String,
+    pub field_type: CustomFieldType,
+}
+
+// a validated value, stored in its canonical text form
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomFieldValue {
+    pub name: String,
+    pub value: String,
+}
+
+impl CustomFieldDefinition {
+    // names double as form field names and template variables,
+    // so keep them to identifiers that can't clash with the built-in ones
+    const RESERVED_NAMES: [&'static str; 7] = [
+        "email",
+        "name",
+        "tags",
+        "idempotency_key",
+        "subscription_token",
+        "status",
+        "website",
+    ];
+
+    pub fn parse(
+        name: String,
+        label: String,
+        field_type: CustomFieldType,
+    ) -> Result<CustomFieldDefinition, String> {
+        let name = name.trim().to_lowercase();
+        let is_valid_identifier = name.len() <= 64
+            && name.starts_with(|c: char| c.is_ascii_lowercase())
+            && name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+        if !is_valid_identifier {
+            return Err(format!(
+                "{} is not a valid field name - use lowercase letters, digits and underscores.",
+                name
+            ));
+        }
+        if Self::RESERVED_NAMES.contains(&name.as_str()) {
+            return Err(format!("{} is a reserved field name.", name));
+        }
+        let label = label.trim().to_string();
+        if label.is_empty() {
+            return Err("The field label cannot be empty.".into());
+        }
+        Ok(Self {
+            name,
+            label,
+            field_type,
+        })
+    }
+
+    pub fn parse_value(&self, raw: &str) -> Result<CustomFieldValue, String> {
+        let raw = raw.trim();
+        let invalid = || format!("{} is not a valid value for {}.", raw, self.label);
+        let value = match &self.field_type {
+            CustomFieldType::Text => {
+                if raw.chars().count() > 256 {
+                    return Err(format!(
+                        "{} must be at most 256 characters long.",
+                        self.label
+                    ));
+                }
+                raw.to_string()
+            }
+            CustomFieldType::Number => {
+                let n: f64 = raw.parse().map_err(|_| invalid())?;
+                if !n.is_finite() {
+                    return Err(invalid());
+                }
+                n.to_string()
+            }
+            CustomFieldType::Boolean => match raw.to_lowercase().as_str() {
+                "true" | "yes" | "on" | "1" => "true".to_string(),
+                "false" | "no" | "off" | "0" => "false".to_string(),
+                _ => return Err(invalid()),
+            },
+            CustomFieldType::Enum(options) => options
+                .iter()
+                .find(|o| o.eq_ignore_ascii_case(raw))
+                .ok_or_else(invalid)?
+                .clone(),
+        };
+        Ok(CustomFieldValue {
+            name: self.name.clone(),
+            value,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CustomFieldDefinition, CustomFieldType};
+    use claims::{assert_err, assert_ok};
+
+    fn field(field_type: CustomFieldType) -> CustomFieldDefinition {
+        CustomFieldDefinition::parse("field".into(), "Field".into(), field_type).unwrap()
+    }
+
+    #[test]
+    fn field_names_must_be_identifiers() {
+        for name in ["", "1st", "has space", "dash-ed", "email", "Name"] {
+            assert_err!(
+                CustomFieldDefinition::parse(name.into(), "Label".into(), CustomFieldType::Text),
+                "{}",
+                name
+            );
+        }
+        assert_ok!(CustomFieldDefinition::parse(
+            "Company_2".into(),
+            "Company".into(),
+            CustomFieldType::Text
+        ));
+    }
+
+    #[test]
+    fn numbers_are_stored_in_canonical_form() {
+        let number = field(CustomFieldType::Number);
+        assert_eq!(number.parse_value(" 3.50 ").unwrap().value, "3.5");
+        assert_eq!(number.parse_value("42").unwrap().value, "42");
+        assert_err!(number.parse_value("forty-two"));
+        assert_err!(number.parse_value("NaN"));
+    }
+
+    #[test]
+    fn booleans_accept_common_spellings() {
+        let boolean = field(CustomFieldType::Boolean);
+        assert_eq!(boolean.parse_value("Yes").unwrap().value, "true");
+        assert_eq!(boolean.parse_value("0").unwrap().value, "false");
+        assert_err!(boolean.parse_value("maybe"));
+    }
+
+    #[test]
+    fn enums_only_accept_their_options() {
+        let options = vec!["Engineer".to_string(), "Designer".to_string()];
+        let role = field(CustomFieldType::parse("enum", &options).unwrap());
+        assert_eq!(role.parse_value("engineer").unwrap().value, "Engineer");
+        assert_err!(role.parse_value("Manager"));
+    }
+
+    #[test]
+    fn an_enum_without_options_is_rejected() {
+        assert_err!(CustomFieldType::parse("enum", &[" ".to_string()]));
+    }
+
+    #[test]
+    fn text_longer_than_256_characters_is_rejected() {
+        let text = field(CustomFieldType::Text);
+        assert_err!(text.parse_value(&"a".repeat(257)));
+        assert_ok!(text.parse_value(&"a".repeat(256)));
+    }
+}
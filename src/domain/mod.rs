@@ -1,7 +1,19 @@
+mod custom_field;
 mod new_subscriber;
+mod role;
+mod segment_filter;
 mod subscriber_email;
 mod subscriber_name;
+mod subscriber_tag;
+mod suppression_reason;
+mod username;
 
+pub use custom_field::{CustomFieldDefinition, CustomFieldType, CustomFieldValue};
 pub use new_subscriber::NewSubscriber;
+pub use role::{Permission, Role};
+pub use segment_filter::SegmentFilter;
 pub use subscriber_email::SubscriberEmail;
 pub use subscriber_name::SubscriberName;
+pub use subscriber_tag::SubscriberTag;
+pub use suppression_reason::SuppressionReason;
+pub use username::Username;
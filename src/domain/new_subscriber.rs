@@ -1,7 +1,11 @@
+use crate::domain::CustomFieldValue;
 use crate::domain::SubscriberEmail;
 use crate::domain::SubscriberName;
+use crate::domain::SubscriberTag;
 
 pub struct NewSubscriber {
     pub email: SubscriberEmail,
     pub name: SubscriberName,
+    pub tags: Vec<SubscriberTag>,
+    pub custom_fields: Vec<CustomFieldValue>,
 }
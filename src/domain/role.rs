@@ -0,0 +1,110 @@
+// what a user is allowed to do under /admin
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Role {
+    Owner,
+    Editor,
+    Viewer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Permission {
+    // look at the admin pages, which every role can. only api tokens can go without it
+    Read,
+    // change subscribers, tags, segments, custom fields and suppressions
+    Edit,
+    Publish,
+    // invite, deactivate and remove users, and change their roles
+    ManageUsers,
+}
+
+impl Permission {
+    pub const ALL: [Permission; 4] = [Self::Read, Self::Edit, Self::Publish, Self::ManageUsers];
+
+    // also how api token scopes are named
+    pub fn parse(s: &str) -> Result<Permission, String> {
+        match s {
+            "read" => Ok(Self::Read),
+            "edit" => Ok(Self::Edit),
+            "publish" => Ok(Self::Publish),
+            "manage_users" => Ok(Self::ManageUsers),
+            other => Err(format!("{} is not a valid permission.", other)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Edit => "edit",
+            Self::Publish => "publish",
+            Self::ManageUsers => "manage_users",
+        }
+    }
+}
+
+impl Role {
+    pub const ALL: [Role; 3] = [Self::Owner, Self::Editor, Self::Viewer];
+
+    pub fn parse(s: &str) -> Result<Role, String> {
+        match s {
+            "owner" => Ok(Self::Owner),
+            "editor" => Ok(Self::Editor),
+            "viewer" => Ok(Self::Viewer),
+            other => Err(format!("{} is not a valid role.", other)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Owner => "owner",
+            Self::Editor => "editor",
+            Self::Viewer => "viewer",
+        }
+    }
+
+    // every role can look around, viewers can't change anything
+    pub fn can(&self, permission: Permission) -> bool {
+        match self {
+            Self::Owner => true,
+            Self::Editor => permission != Permission::ManageUsers,
+            Self::Viewer => permission == Permission::Read,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Permission, Role};
+    use claims::assert_err;
+
+    #[test]
+    fn roles_round_trip_through_their_string_form() {
+        for role in Role::ALL {
+            assert_eq!(Role::parse(role.as_str()).unwrap(), role);
+        }
+        assert_err!(Role::parse("admin"));
+    }
+
+    #[test]
+    fn permissions_round_trip_through_their_string_form() {
+        for permission in Permission::ALL {
+            assert_eq!(Permission::parse(permission.as_str()).unwrap(), permission);
+        }
+        assert_err!(Permission::parse("delete"));
+    }
+
+    #[test]
+    fn only_owners_can_manage_users() {
+        assert!(Role::Owner.can(Permission::ManageUsers));
+        assert!(!Role::Editor.can(Permission::ManageUsers));
+        assert!(!Role::Viewer.can(Permission::ManageUsers));
+    }
+
+    #[test]
+    fn viewers_cannot_publish_or_edit() {
+        assert!(Role::Editor.can(Permission::Publish));
+        assert!(Role::Editor.can(Permission::Edit));
+        assert!(!Role::Viewer.can(Permission::Publish));
+        assert!(!Role::Viewer.can(Permission::Edit));
+        assert!(Role::Viewer.can(Permission::Read));
+    }
+}
@@ -0,0 +1,333 @@
+use chrono::NaiveDate;
+
+use crate::domain::{CustomFieldDefinition, SubscriberTag};
+
+// a boolean combination of subscriber predicates, written as e.g.
+// `tag:vip AND (status:confirmed OR NOT subscribed_before:2026-01-01)`
+#[derive(Debug, Clone, PartialEq)]
+pub enum SegmentFilter {
+    Tag(SubscriberTag),
+    Status(String),
+    // subscribed strictly before the start of the given day (UTC)
+    SubscribedBefore(NaiveDate),
+    // subscribed on or after the start of the given day (UTC)
+    SubscribedAfter(NaiveDate),
+    // custom field comparisons, e.g. `field:role=engineer` or `field:seats>10`
+    FieldEquals(String, String),
+    FieldGreaterThan(String, f64),
+    FieldLessThan(String, f64),
+    Not(Box<SegmentFilter>),
+    And(Vec<SegmentFilter>),
+    Or(Vec<SegmentFilter>),
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    LeftParen,
+    RightParen,
+    And,
+    Or,
+    Not,
+    Predicate(String),
+}
+
+impl SegmentFilter {
+    pub fn parse(s: &str) -> Result<SegmentFilter, String> {
+        let tokens = tokenize(s);
+        if tokens.is_empty() {
+            return Err("The segment definition cannot be empty.".into());
+        }
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+        };
+        let filter = parser.expression()?;
+        if let Some(token) = parser.tokens.get(parser.position) {
+            return Err(format!("Unexpected {:?} in segment definition.", token));
+        }
+        Ok(filter)
+    }
+
+    // field values are stored in their canonical form, so compare against that,
+    // e.g. `field:opt_in=yes` has to match a stored `true`.
+    // conditions on fields that aren't defined (anymore) are kept as written and match nothing
+    pub fn canonicalize(
+        self,
+        definitions: &[CustomFieldDefinition],
+    ) -> Result<SegmentFilter, String> {
+        let canonicalize_all = |filters: Vec<SegmentFilter>| {
+            filters
+                .into_iter()
+                .map(|f| f.canonicalize(definitions))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(match self {
+            SegmentFilter::FieldEquals(name, value) => {
+                match definitions.iter().find(|d| d.name == name) {
+                    Some(definition) => {
+                        let value = definition.parse_value(&value)?.value;
+                        SegmentFilter::FieldEquals(name, value)
+                    }
+                    None => SegmentFilter::FieldEquals(name, value),
+                }
+            }
+            SegmentFilter::Not(inner) => {
+                SegmentFilter::Not(Box::new(inner.canonicalize(definitions)?))
+            }
+            SegmentFilter::And(filters) => SegmentFilter::And(canonicalize_all(filters)?),
+            SegmentFilter::Or(filters) => SegmentFilter::Or(canonicalize_all(filters)?),
+            other => other,
+        })
+    }
+}
+
+fn tokenize(s: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let spaced = s.replace('(', " ( ").replace(')', " ) ");
+    for word in spaced.split_whitespace() {
+        let token = match word.to_uppercase().as_str() {
+            "(" => Token::LeftParen,
+            ")" => Token::RightParen,
+            "AND" => Token::And,
+            "OR" => Token::Or,
+            "NOT" => Token::Not,
+            _ => Token::Predicate(word.to_string()),
+        };
+        tokens.push(token);
+    }
+    tokens
+}
+
+// recursive descent over:
+//   expression := term ("OR" term)*
+//   term       := factor ("AND" factor)*
+//   factor     := "NOT" factor | "(" expression ")" | predicate
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.position);
+        self.position += 1;
+        token
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn expression(&mut self) -> Result<SegmentFilter, String> {
+        let mut terms = vec![self.term()?];
+        while self.peek() == Some(&Token::Or) {
+            self.position += 1;
+            terms.push(self.term()?);
+        }
+        Ok(if terms.len() == 1 {
+            terms.pop().unwrap()
+        } else {
+            SegmentFilter::Or(terms)
+        })
+    }
+
+    fn term(&mut self) -> Result<SegmentFilter, String> {
+        let mut factors = vec![self.factor()?];
+        while self.peek() == Some(&Token::And) {
+            self.position += 1;
+            factors.push(self.factor()?);
+        }
+        Ok(if factors.len() == 1 {
+            factors.pop().unwrap()
+        } else {
+            SegmentFilter::And(factors)
+        })
+    }
+
+    fn factor(&mut self) -> Result<SegmentFilter, String> {
+        match self.next() {
+            Some(Token::Not) => Ok(SegmentFilter::Not(Box::new(self.factor()?))),
+            Some(Token::LeftParen) => {
+                let inner = self.expression()?;
+                match self.next() {
+                    Some(Token::RightParen) => Ok(inner),
+                    _ => Err("Missing closing parenthesis in segment definition.".into()),
+                }
+            }
+            Some(Token::Predicate(p)) => {
+                let p = p.clone();
+                parse_predicate(&p)
+            }
+            Some(token) => Err(format!("Unexpected {:?} in segment definition.", token)),
+            None => Err("The segment definition ended unexpectedly.".into()),
+        }
+    }
+}
+
+fn parse_predicate(s: &str) -> Result<SegmentFilter, String> {
+    let (key, value) = s
+        .split_once(':')
+        .ok_or_else(|| format!("{} is not a valid segment condition.", s))?;
+    let parse_date = |v: &str| {
+        NaiveDate::parse_from_str(v, "%Y-%m-%d")
+            .map_err(|_| format!("{} is not a valid date - use YYYY-MM-DD.", v))
+    };
+    match key.to_lowercase().as_str() {
+        "tag" => Ok(SegmentFilter::Tag(SubscriberTag::parse(value.to_string())?)),
+        "status" => {
+            let status = value.to_lowercase();
+            if status.is_empty() || !status.chars().all(|c| c.is_ascii_lowercase() || c == '_') {
+                return Err(format!("{} is not a valid subscriber status.", value));
+            }
+            Ok(SegmentFilter::Status(status))
+        }
+        "subscribed_before" => Ok(SegmentFilter::SubscribedBefore(parse_date(value)?)),
+        "subscribed_after" => Ok(SegmentFilter::SubscribedAfter(parse_date(value)?)),
+        "field" => parse_field_predicate(value),
+        _ => Err(format!("{} is not a supported segment condition.", key)),
+    }
+}
+
+fn parse_field_predicate(s: &str) -> Result<SegmentFilter, String> {
+    let invalid = || {
+        format!(
+            "{} is not a valid field condition - use `field:NAME=VALUE`, \
+            `field:NAME>NUMBER` or `field:NAME<NUMBER`.",
+            s
+        )
+    };
+    let position = s.find(['=', '>', '<']).ok_or_else(invalid)?;
+    let (name, rest) = s.split_at(position);
+    let (operator, value) = rest.split_at(1);
+    let name = name.to_lowercase();
+    if name.is_empty() || value.is_empty() {
+        return Err(invalid());
+    }
+    let number = || value.parse::<f64>().map_err(|_| invalid());
+    match operator {
+        "=" => Ok(SegmentFilter::FieldEquals(name, value.to_string())),
+        ">" => Ok(SegmentFilter::FieldGreaterThan(name, number()?)),
+        _ => Ok(SegmentFilter::FieldLessThan(name, number()?)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SegmentFilter;
+    use crate::domain::{CustomFieldDefinition, CustomFieldType, SubscriberTag};
+    use chrono::NaiveDate;
+    use claims::assert_err;
+
+    fn tag(s: &str) -> SegmentFilter {
+        SegmentFilter::Tag(SubscriberTag::parse(s.to_string()).unwrap())
+    }
+
+    #[test]
+    fn a_single_predicate_is_parsed() {
+        assert_eq!(SegmentFilter::parse("tag:vip").unwrap(), tag("vip"));
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let filter = SegmentFilter::parse("tag:a OR tag:b and tag:c").unwrap();
+        assert_eq!(
+            filter,
+            SegmentFilter::Or(vec![tag("a"), SegmentFilter::And(vec![tag("b"), tag("c")])])
+        );
+    }
+
+    #[test]
+    fn parentheses_and_not_are_supported() {
+        let filter =
+            SegmentFilter::parse("NOT (tag:a OR status:confirmed) AND subscribed_after:2026-01-01")
+                .unwrap();
+        assert_eq!(
+            filter,
+            SegmentFilter::And(vec![
+                SegmentFilter::Not(Box::new(SegmentFilter::Or(vec![
+                    tag("a"),
+                    SegmentFilter::Status("confirmed".into())
+                ]))),
+                SegmentFilter::SubscribedAfter(NaiveDate::from_ymd_opt(2026, 1, 1).unwrap()),
+            ])
+        );
+    }
+
+    #[test]
+    fn field_comparisons_are_parsed() {
+        assert_eq!(
+            SegmentFilter::parse("field:Role=Engineer").unwrap(),
+            SegmentFilter::FieldEquals("role".into(), "Engineer".into())
+        );
+        assert_eq!(
+            SegmentFilter::parse("field:seats>10").unwrap(),
+            SegmentFilter::FieldGreaterThan("seats".into(), 10.0)
+        );
+        assert_eq!(
+            SegmentFilter::parse("field:seats<2.5").unwrap(),
+            SegmentFilter::FieldLessThan("seats".into(), 2.5)
+        );
+    }
+
+    #[test]
+    fn field_values_are_canonicalized_against_their_definition() {
+        let options = vec!["Engineer".to_string()];
+        let definitions = [
+            CustomFieldDefinition::parse(
+                "opt_in".into(),
+                "Opt in".into(),
+                CustomFieldType::Boolean,
+            )
+            .unwrap(),
+            CustomFieldDefinition::parse(
+                "role".into(),
+                "Role".into(),
+                CustomFieldType::parse("enum", &options).unwrap(),
+            )
+            .unwrap(),
+        ];
+        let filter =
+            SegmentFilter::parse("field:opt_in=yes AND NOT field:role=engineer OR field:other=x")
+                .unwrap()
+                .canonicalize(&definitions)
+                .unwrap();
+        assert_eq!(
+            filter,
+            SegmentFilter::Or(vec![
+                SegmentFilter::And(vec![
+                    SegmentFilter::FieldEquals("opt_in".into(), "true".into()),
+                    SegmentFilter::Not(Box::new(SegmentFilter::FieldEquals(
+                        "role".into(),
+                        "Engineer".into()
+                    ))),
+                ]),
+                SegmentFilter::FieldEquals("other".into(), "x".into()),
+            ])
+        );
+        assert_err!(
+            SegmentFilter::parse("field:opt_in=maybe")
+                .unwrap()
+                .canonicalize(&definitions)
+        );
+    }
+
+    #[test]
+    fn malformed_definitions_are_rejected() {
+        for definition in [
+            "",
+            "tag:",
+            "vip",
+            "color:red",
+            "tag:a AND",
+            "(tag:a",
+            "tag:a)",
+            "subscribed_before:yesterday",
+            "status:'; DROP TABLE subscriptions",
+            "field:role",
+            "field:=x",
+            "field:seats>many",
+        ] {
+            assert_err!(SegmentFilter::parse(definition), "{}", definition);
+        }
+    }
+}
@@ -1,16 +1,29 @@
+use unicode_normalization::UnicodeNormalization;
 use validator::ValidateEmail;
 
 #[derive(Debug)]
 pub struct SubscriberEmail(String);
 
 impl SubscriberEmail {
+    // addresses are stored in a canonical form: trimmed, NFC-normalized and with the
+    // domain lowercased (and punycode-encoded for IDNs). the local part keeps its case,
+    // uniqueness in `subscriptions` is enforced case-insensitively instead
     pub fn parse(s: String) -> Result<SubscriberEmail, String> {
-        if s.validate_email() {
-            Ok(Self(s))
+        let invalid = || format!("{} is not a valid subscriber email.", s);
+        let normalized: String = s.trim().nfc().collect();
+        let (local_part, domain) = normalized.rsplit_once('@').ok_or_else(invalid)?;
+        let domain = idna::domain_to_ascii(domain).map_err(|_| invalid())?;
+        let email = format!("{}@{}", local_part, domain);
+        if email.validate_email() {
+            Ok(Self(email))
         } else {
-            Err(format!("{} is not a valid subscriber email.", s))
+            Err(invalid())
         }
     }
+
+    pub fn domain(&self) -> &str {
+        self.0.rsplit_once('@').map(|(_, d)| d).unwrap_or_default()
+    }
 }
 
 impl AsRef<str> for SubscriberEmail {
@@ -29,7 +42,7 @@ impl std::fmt::Display for SubscriberEmail {
 #[cfg(test)]
 mod tests {
     use super::SubscriberEmail;
-    use claims::assert_err;
+    use claims::{assert_err, assert_ok};
     use fake::Fake;
     use fake::faker::internet::en::SafeEmail;
     use rand::SeedableRng;
@@ -74,4 +87,30 @@ mod tests {
         let email = "@domain.com".to_string();
         assert_err!(SubscriberEmail::parse(email));
     }
+
+    #[test]
+    fn the_domain_is_lowercased_and_the_local_part_kept() {
+        let email = SubscriberEmail::parse("  Ursula.LeGuin@Example.COM ".to_string()).unwrap();
+        assert_eq!(email.as_ref(), "Ursula.LeGuin@example.com");
+    }
+
+    #[test]
+    fn internationalized_domains_are_stored_as_punycode() {
+        let email = SubscriberEmail::parse("ursula@BÜCHER.example".to_string()).unwrap();
+        assert_eq!(email.as_ref(), "ursula@xn--bcher-kva.example");
+    }
+
+    #[test]
+    fn equivalent_unicode_spellings_are_normalized() {
+        // "ü" as a single code point and as "u" followed by a combining diaeresis
+        let composed = SubscriberEmail::parse("ursula@b\u{fc}cher.example".to_string()).unwrap();
+        let decomposed = SubscriberEmail::parse("ursula@bu\u{308}cher.example".to_string());
+        assert_ok!(&decomposed);
+        assert_eq!(composed.as_ref(), decomposed.unwrap().as_ref());
+    }
+
+    #[test]
+    fn email_with_an_invalid_domain_is_rejected() {
+        assert_err!(SubscriberEmail::parse("ursula@exa mple.com".to_string()));
+    }
 }
@@ -0,0 +1,81 @@
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubscriberTag(String);
+
+impl SubscriberTag {
+    pub fn parse(s: String) -> Result<SubscriberTag, String> {
+        // tags are case-insensitive, so store them in a single canonical form
+        let tag = s.trim().to_lowercase();
+        let is_empty = tag.is_empty();
+        let is_too_long = tag.chars().count() > 64;
+        let contains_forbidden_characters = tag
+            .chars()
+            .any(|c| !(c.is_alphanumeric() || c == '-' || c == '_'));
+
+        if is_empty || is_too_long || contains_forbidden_characters {
+            Err(format!("{} is not a valid subscriber tag.", s))
+        } else {
+            Ok(Self(tag))
+        }
+    }
+
+    // parse a comma-separated list of tags, e.g. from a hidden form field
+    pub fn parse_list(s: &str) -> Result<Vec<SubscriberTag>, String> {
+        let mut tags: Vec<SubscriberTag> = Vec::new();
+        for raw in s.split(',').filter(|t| !t.trim().is_empty()) {
+            let tag = Self::parse(raw.to_string())?;
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+        Ok(tags)
+    }
+}
+
+impl AsRef<str> for SubscriberTag {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for SubscriberTag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SubscriberTag;
+    use claims::{assert_err, assert_ok};
+
+    #[test]
+    fn tags_are_lowercased_and_trimmed() {
+        let tag = SubscriberTag::parse("  Early-Adopter ".to_string()).unwrap();
+        assert_eq!(tag.as_ref(), "early-adopter");
+    }
+
+    #[test]
+    fn empty_string_is_rejected() {
+        assert_err!(SubscriberTag::parse("".to_string()));
+    }
+
+    #[test]
+    fn a_tag_longer_than_64_characters_is_rejected() {
+        assert_err!(SubscriberTag::parse("a".repeat(65)));
+        assert_ok!(SubscriberTag::parse("a".repeat(64)));
+    }
+
+    #[test]
+    fn tags_containing_spaces_or_punctuation_are_rejected() {
+        for tag in ["two words", "semi;colon", "<script>", "a,b"] {
+            assert_err!(SubscriberTag::parse(tag.to_string()));
+        }
+    }
+
+    #[test]
+    fn a_comma_separated_list_is_parsed_and_deduplicated() {
+        let tags = SubscriberTag::parse_list("vip, beta,,VIP").unwrap();
+        let tags: Vec<&str> = tags.iter().map(|t| t.as_ref()).collect();
+        assert_eq!(tags, vec!["vip", "beta"]);
+    }
+}
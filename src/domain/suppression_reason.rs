@@ -0,0 +1,48 @@
+// why an address must never be emailed again
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SuppressionReason {
+    HardBounce,
+    SpamComplaint,
+    Manual,
+    Erased,
+}
+
+impl SuppressionReason {
+    pub fn parse(s: &str) -> Result<SuppressionReason, String> {
+        match s {
+            "hard_bounce" => Ok(Self::HardBounce),
+            "spam_complaint" => Ok(Self::SpamComplaint),
+            "manual" => Ok(Self::Manual),
+            "erased" => Ok(Self::Erased),
+            other => Err(format!("{} is not a valid suppression reason.", other)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::HardBounce => "hard_bounce",
+            Self::SpamComplaint => "spam_complaint",
+            Self::Manual => "manual",
+            Self::Erased => "erased",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SuppressionReason;
+    use claims::assert_err;
+
+    #[test]
+    fn reasons_round_trip_through_their_string_form() {
+        for reason in [
+            SuppressionReason::HardBounce,
+            SuppressionReason::SpamComplaint,
+            SuppressionReason::Manual,
+            SuppressionReason::Erased,
+        ] {
+            assert_eq!(SuppressionReason::parse(reason.as_str()).unwrap(), reason);
+        }
+        assert_err!(SuppressionReason::parse("annoyed"));
+    }
+}
@@ -0,0 +1,56 @@
+#[derive(Debug)]
+pub struct Username(String);
+
+impl Username {
+    pub fn parse(s: String) -> Result<Username, String> {
+        let username = s.trim();
+        let length = username.chars().count();
+        let has_valid_characters = username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.');
+        if !(3..=64).contains(&length) || !has_valid_characters {
+            Err(format!(
+                "{} is not a valid username - use 3 to 64 letters, digits, dots, dashes \
+                or underscores.",
+                s
+            ))
+        } else {
+            Ok(Self(username.to_string()))
+        }
+    }
+}
+
+impl AsRef<str> for Username {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Username;
+    use claims::{assert_err, assert_ok};
+
+    #[test]
+    fn usernames_are_trimmed() {
+        assert_eq!(
+            Username::parse(" ursula ".into()).unwrap().as_ref(),
+            "ursula"
+        );
+    }
+
+    #[test]
+    fn usernames_must_be_3_to_64_characters_long() {
+        assert_err!(Username::parse("ab".into()));
+        assert_ok!(Username::parse("abc".into()));
+        assert_ok!(Username::parse("a".repeat(64)));
+        assert_err!(Username::parse("a".repeat(65)));
+    }
+
+    #[test]
+    fn usernames_with_spaces_or_symbols_are_rejected() {
+        for username in ["two words", "<script>", "semi;colon", "émile"] {
+            assert_err!(Username::parse(username.into()), "{}", username);
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use tracing::{Span, field::display};
+use uuid::Uuid;
+
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::issue_delivery_worker::ExecutionOutcome;
+use crate::suppression::is_suppressed;
+
+// a failed send is retried a few times, backing off, before the email is dropped
+const MAX_RETRIES: i16 = 3;
+
+pub struct QueuedEmail {
+    pub recipient: String,
+    pub subject: String,
+    pub html_body: String,
+    pub text_body: String,
+}
+
+// hand emails over to the background worker, so requests don't wait on the email api
+#[tracing::instrument(name = "Queue emails", skip_all, fields(n_emails = emails.len()))]
+pub async fn enqueue_emails<'c, E>(executor: E, emails: &[QueuedEmail]) -> Result<(), sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    if emails.is_empty() {
+        return Ok(());
+    }
+    let ids: Vec<Uuid> = emails.iter().map(|_| Uuid::new_v4()).collect();
+    let recipients: Vec<String> = emails.iter().map(|e| e.recipient.clone()).collect();
+    let subjects: Vec<String> = emails.iter().map(|e| e.subject.clone()).collect();
+    let html_bodies: Vec<String> = emails.iter().map(|e| e.html_body.clone()).collect();
+    let text_bodies: Vec<String> = emails.iter().map(|e| e.text_body.clone()).collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO email_queue (email_id, recipient, subject, html_body, text_body)
+        SELECT * FROM UNNEST($1::uuid[], $2::text[], $3::text[], $4::text[], $5::text[])
+        "#,
+        &ids,
+        &recipients,
+        &subjects,
+        &html_bodies,
+        &text_bodies,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+// send the next email that is due, if any
+#[tracing::instrument(skip_all, fields(email_id = tracing::field::Empty), err)]
+pub async fn try_send_queued_email(
+    pool: &PgPool,
+    email_client: &EmailClient,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let email = sqlx::query!(
+        r#"
+        SELECT email_id, recipient, subject, html_body, text_body, n_retries
+        FROM email_queue
+        WHERE execute_after <= now()
+        ORDER BY execute_after
+        FOR UPDATE
+        SKIP LOCKED
+        LIMIT 1
+        "#,
+    )
+    .fetch_optional(&mut *transaction)
+    .await?;
+    let Some(email) = email else {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    };
+    Span::current().record("email_id", display(email.email_id));
+
+    match SubscriberEmail::parse(email.recipient) {
+        // the address may have bounced since the email was queued
+        Ok(recipient) if is_suppressed(pool, recipient.as_ref()).await? => {
+            tracing::info!("Not sending a queued email to a suppressed address");
+        }
+        Ok(recipient) => {
+            if let Err(e) = email_client
+                .send_email(
+                    &recipient,
+                    &email.subject,
+                    &email.html_body,
+                    &email.text_body,
+                )
+                .await
+            {
+                if email.n_retries < MAX_RETRIES {
+                    tracing::warn!(
+                        error.cause_chain = ?e,
+                        error.message = %e,
+                        "Failed to send a queued email, retrying later",
+                    );
+                    return retry_later(transaction, email.email_id).await;
+                }
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "Failed to send a queued email, giving up",
+                );
+            }
+        }
+        Err(e) => {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Skipping a queued email with an invalid recipient",
+            );
+        }
+    }
+    transaction
+        .execute(sqlx::query!(
+            r#"DELETE FROM email_queue WHERE email_id = $1"#,
+            email.email_id
+        ))
+        .await?;
+    transaction.commit().await?;
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+// waits 1, 2 and then 4 minutes
+async fn retry_later(
+    mut transaction: Transaction<'static, Postgres>,
+    email_id: Uuid,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    transaction
+        .execute(sqlx::query!(
+            r#"
+            UPDATE email_queue
+            SET
+                n_retries = n_retries + 1,
+                execute_after = now() + make_interval(mins => 1 << n_retries)
+            WHERE email_id = $1
+            "#,
+            email_id
+        ))
+        .await?;
+    transaction.commit().await?;
+    Ok(ExecutionOutcome::TaskCompleted)
+}
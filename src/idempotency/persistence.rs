@@ -14,6 +14,8 @@ struct HeaderPairRecord {
 pub enum NextAction {
     StartProcessing(Transaction<'static, Postgres>),
     ReturnSavedResponse(HttpResponse),
+    // the key was first used with a different request
+    RejectReusedKey,
 }
 
 pub async fn save_response(
@@ -101,10 +103,12 @@ pub async fn get_saved_response(
     }
 }
 
+// `request_fingerprint` identifies what the key is used for, a retry has to match it
 pub async fn try_processing(
     pool: &PgPool,
     idempotency_key: &IdempotencyKey,
     user_id: Uuid,
+    request_fingerprint: Option<&str>,
 ) -> Result<NextAction, anyhow::Error> {
     let mut transaction = pool.begin().await?;
     let query = sqlx::query!(
@@ -112,18 +116,34 @@ pub async fn try_processing(
         INSERT INTO idempotency (
             user_id,
             idempotency_key,
+            request_fingerprint,
             created_at
         )
-        VALUES ($1, $2, now())
+        VALUES ($1, $2, $3, now())
         ON CONFLICT DO NOTHING
         "#,
         user_id,
-        idempotency_key.as_ref()
+        idempotency_key.as_ref(),
+        request_fingerprint
     );
     let n_inserted_rows = transaction.execute(query).await?.rows_affected();
     if n_inserted_rows > 0 {
         Ok(NextAction::StartProcessing(transaction))
     } else {
+        let saved = sqlx::query!(
+            r#"
+            SELECT request_fingerprint
+            FROM idempotency
+            WHERE user_id = $1 AND idempotency_key = $2
+            "#,
+            user_id,
+            idempotency_key.as_ref()
+        )
+        .fetch_one(pool)
+        .await?;
+        if saved.request_fingerprint.as_deref() != request_fingerprint {
+            return Ok(NextAction::RejectReusedKey);
+        }
         let saved_response = get_saved_response(pool, idempotency_key, user_id)
             .await?
             .ok_or_else(|| anyhow::anyhow!("We expected a saved response, we didn't find it"))?;
@@ -1,10 +1,14 @@
 use sqlx::{Executor, PgPool, Postgres, Transaction};
+use std::collections::HashMap;
 use std::time::Duration;
 use tracing::{Span, field::display};
 use uuid::Uuid;
 
 use crate::domain::SubscriberEmail;
 use crate::email_client::EmailClient;
+use crate::email_queue::try_send_queued_email;
+use crate::suppression::is_suppressed;
+use crate::templating::{Escape, render_template};
 use crate::{configuration::Settings, startup::get_connection_pool};
 
 struct NewsletterIssue {
@@ -18,6 +22,26 @@ pub enum ExecutionOutcome {
     EmptyQueue,
 }
 
+// what happened to a single delivery attempt, as recorded in `issue_delivery_log`
+#[derive(Clone, Copy, Debug)]
+enum DeliveryOutcome {
+    Sent,
+    Failed,
+    InvalidEmail,
+    Suppressed,
+}
+
+impl DeliveryOutcome {
+    fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryOutcome::Sent => "sent",
+            DeliveryOutcome::Failed => "failed",
+            DeliveryOutcome::InvalidEmail => "invalid_email",
+            DeliveryOutcome::Suppressed => "suppressed",
+        }
+    }
+}
+
 // i'm not sold on needing this type but nonetheless
 type PgTransaction = Transaction<'static, Postgres>;
 
@@ -28,17 +52,20 @@ pub async fn run_worker_until_stopped(configuration: Settings) -> Result<(), any
     worker_loop(connection_pool, email_client).await
 }
 
-// loop through the task queue, popping tasks as they complete
+// loop through the task queues, popping tasks as they complete.
+// issue deliveries and one-off emails take turns so neither starves the other
 async fn worker_loop(pool: PgPool, email_client: EmailClient) -> Result<(), anyhow::Error> {
     loop {
-        match try_execute_task(&pool, &email_client).await {
-            Ok(ExecutionOutcome::EmptyQueue) => {
-                tokio::time::sleep(Duration::from_secs(10)).await;
-            }
-            Err(_) => {
+        let issue = try_execute_task(&pool, &email_client).await;
+        let email = try_send_queued_email(&pool, &email_client).await;
+        match (issue, email) {
+            (Err(_), _) | (_, Err(_)) => {
                 tokio::time::sleep(Duration::from_secs(1)).await;
             }
-            Ok(ExecutionOutcome::TaskCompleted) => {}
+            (Ok(ExecutionOutcome::EmptyQueue), Ok(ExecutionOutcome::EmptyQueue)) => {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+            }
+            _ => {}
         }
     }
 }
@@ -65,20 +92,27 @@ pub async fn try_execute_task(
     }
 
     // start the transaction
-    let (transaction, issue_id, email) = task.unwrap();
+    let (mut transaction, issue_id, email) = task.unwrap();
     Span::current()
         .record("newsletter_issue_id", display(issue_id))
         .record("subscriber_email", display(&email));
     // check if the email is good and send it if so
-    match SubscriberEmail::parse(email.clone()) {
+    let outcome = match SubscriberEmail::parse(email.clone()) {
+        // the address may have been suppressed since the issue was queued
+        Ok(email) if is_suppressed(pool, email.as_ref()).await? => {
+            tracing::info!("Skipping a suppressed subscriber");
+            DeliveryOutcome::Suppressed
+        }
         Ok(email) => {
             let issue = get_issue(pool, issue_id).await?;
+            // personalize the issue for this subscriber
+            let variables = get_template_variables(pool, email.as_ref()).await?;
             if let Err(e) = email_client
                 .send_email(
                     &email,
-                    &issue.title,
-                    &issue.html_content,
-                    &issue.text_content,
+                    &render_template(&issue.title, &variables, Escape::None),
+                    &render_template(&issue.html_content, &variables, Escape::Html),
+                    &render_template(&issue.text_content, &variables, Escape::None),
                 )
                 .await
             {
@@ -89,6 +123,9 @@ pub async fn try_execute_task(
                     "Failed to deliver issue to a confirmed subscriber. \
                     Skipping.",
                 );
+                DeliveryOutcome::Failed
+            } else {
+                DeliveryOutcome::Sent
             }
         }
         // error due to incorrect subscriber contact details
@@ -99,9 +136,11 @@ pub async fn try_execute_task(
                 "Skipping a confirmed subscriber. \
                 Their stored contact details are invalid",
             );
+            DeliveryOutcome::InvalidEmail
         }
-    }
-    // kick the task out of the queue once it's completed
+    };
+    // kick the task out of the queue once it's completed, keeping a record of the attempt
+    record_delivery(&mut transaction, issue_id, &email, outcome).await?;
     delete_task(transaction, issue_id, &email).await?;
     Ok(ExecutionOutcome::TaskCompleted)
 }
@@ -151,6 +190,62 @@ async fn get_issue(pool: &PgPool, issue_id: Uuid) -> Result<NewsletterIssue, any
     Ok(issue)
 }
 
+// the values available to `{{ ... }}` placeholders in an issue: the subscriber's
+// email, name and custom fields
+#[tracing::instrument(skip_all)]
+async fn get_template_variables(
+    pool: &PgPool,
+    email: &str,
+) -> Result<HashMap<String, String>, anyhow::Error> {
+    let mut variables = HashMap::from([("email".to_string(), email.to_string())]);
+    let subscriber = sqlx::query!(
+        r#"SELECT id, name FROM subscriptions WHERE email = $1"#,
+        email
+    )
+    .fetch_optional(pool)
+    .await?;
+    if let Some(subscriber) = subscriber {
+        variables.insert("name".into(), subscriber.name);
+        let fields = sqlx::query!(
+            r#"
+            SELECT field_name, value
+            FROM subscriber_field_values
+            WHERE subscriber_id = $1
+            "#,
+            subscriber.id
+        )
+        .fetch_all(pool)
+        .await?;
+        variables.extend(fields.into_iter().map(|f| (f.field_name, f.value)));
+    }
+    Ok(variables)
+}
+
+#[tracing::instrument(skip_all)]
+async fn record_delivery(
+    transaction: &mut PgTransaction,
+    issue_id: Uuid,
+    email: &str,
+    outcome: DeliveryOutcome,
+) -> Result<(), anyhow::Error> {
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_log (
+            newsletter_issue_id,
+            subscriber_email,
+            outcome,
+            attempted_at
+        )
+        VALUES ($1, $2, $3, now())
+        "#,
+        issue_id,
+        email,
+        outcome.as_str()
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
 // remove a task from the queue once it's complete
 #[tracing::instrument(skip_all)]
 async fn delete_task(
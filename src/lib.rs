@@ -1,12 +1,25 @@
 pub mod authentication;
+pub mod client_ip;
 pub mod configuration;
+pub mod counter_store;
+pub mod custom_fields;
 pub mod domain;
 pub mod email_client;
+pub mod email_queue;
 pub mod idempotency;
 pub mod issue_delivery_worker;
+pub mod link_request_protection;
+pub mod login_protection;
+pub mod newsletter_issues;
 // tests don't interact with routes directly, doesn't need to be pub
 mod routes;
+pub mod segmentation;
 pub mod session_state;
+pub mod session_store;
+pub mod signup_protection;
 pub mod startup;
+pub mod subscriber_data;
+pub mod suppression;
 pub mod telemetry;
+pub mod templating;
 pub mod utils;
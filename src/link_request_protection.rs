@@ -0,0 +1,43 @@
+use crate::configuration::LinkRequestProtectionSettings;
+use crate::counter_store::CounterStore;
+use crate::signup_protection::RateLimiter;
+use crate::suppression::email_hash;
+
+// the forms that email someone a link, e.g. their data or a password reset.
+// each form counts its own requests, `form` keeps their counters apart
+pub struct LinkRequestProtection {
+    rate_limiter: RateLimiter,
+    max_requests_per_ip: u64,
+    max_requests_per_recipient: u64,
+}
+
+impl LinkRequestProtection {
+    pub fn new(settings: LinkRequestProtectionSettings, counters: CounterStore) -> Self {
+        let rate_limiter = RateLimiter::new(counters, settings.key_prefix, settings.window_seconds);
+        Self {
+            rate_limiter,
+            max_requests_per_ip: settings.max_requests_per_ip,
+            max_requests_per_recipient: settings.max_requests_per_recipient,
+        }
+    }
+
+    pub async fn allow_ip(&self, form: &str, ip: &str) -> Result<bool, anyhow::Error> {
+        self.rate_limiter
+            .hit(&format!("{}:ip:{}", form, ip), self.max_requests_per_ip)
+            .await
+    }
+
+    // an email address or username, hashed so redis never sees it
+    pub async fn allow_recipient(
+        &self,
+        form: &str,
+        recipient: &str,
+    ) -> Result<bool, anyhow::Error> {
+        self.rate_limiter
+            .hit(
+                &format!("{}:recipient:{}", form, email_hash(recipient)),
+                self.max_requests_per_recipient,
+            )
+            .await
+    }
+}
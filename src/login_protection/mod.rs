@@ -0,0 +1,186 @@
+mod persistence;
+
+pub use persistence::{LockoutRecord, list_recent_lockouts, record_lockout};
+
+use anyhow::Context;
+use sqlx::PgPool;
+
+use crate::configuration::LoginProtectionSettings;
+use crate::counter_store::CounterStore;
+
+// what got locked out, and for how long
+#[derive(Debug)]
+pub struct Lockout {
+    pub subject: LockoutSubject,
+    pub failures: u64,
+    pub seconds: u64,
+}
+
+#[derive(Debug)]
+pub enum LockoutSubject {
+    Username(String),
+    Ip(String),
+}
+
+// failure counters and locks for `POST /login`, in the store that also holds the sessions.
+// a locked username or ip is turned away before its password is even hashed
+pub struct LoginProtection {
+    counters: CounterStore,
+    settings: LoginProtectionSettings,
+}
+
+impl LoginProtection {
+    pub fn new(settings: LoginProtectionSettings, counters: CounterStore) -> Self {
+        Self { counters, settings }
+    }
+
+    fn key(&self, kind: &str, id: &str) -> String {
+        format!("{}:{}:{}", self.settings.key_prefix, kind, id)
+    }
+
+    #[tracing::instrument(name = "Check for a login lock", skip(self))]
+    pub async fn is_locked(&self, username: &str, ip: &str) -> Result<bool, anyhow::Error> {
+        self.counters
+            .exists(&[self.key("lock:user", username), self.key("lock:ip", ip)])
+            .await
+            .context("Failed to look up login locks")
+    }
+
+    // counts a failed login, locking the username and/or ip if it's one too many.
+    // returns the lockouts it started, progressive delays aren't worth reporting
+    #[tracing::instrument(name = "Record a failed login", skip(self))]
+    pub async fn record_failure(
+        &self,
+        username: &str,
+        ip: &str,
+    ) -> Result<Vec<Lockout>, anyhow::Error> {
+        let mut lockouts = Vec::new();
+
+        let failures = self.count(&self.key("fail:user", username)).await?;
+        if let Some(seconds) = username_lock_seconds(&self.settings, failures) {
+            self.lock(&self.key("lock:user", username), seconds).await?;
+            if failures >= self.settings.max_failures_per_username {
+                lockouts.push(Lockout {
+                    subject: LockoutSubject::Username(username.to_string()),
+                    failures,
+                    seconds,
+                });
+            }
+        }
+
+        let failures = self.count(&self.key("fail:ip", ip)).await?;
+        if failures >= self.settings.max_failures_per_ip {
+            let seconds = self.settings.lockout_seconds;
+            self.lock(&self.key("lock:ip", ip), seconds).await?;
+            lockouts.push(Lockout {
+                subject: LockoutSubject::Ip(ip.to_string()),
+                failures,
+                seconds,
+            });
+        }
+        Ok(lockouts)
+    }
+
+    // the username's failures are forgiven, the ip's aren't:
+    // one good account shouldn't cover for guessing at others
+    #[tracing::instrument(name = "Record a successful login", skip(self))]
+    pub async fn record_success(&self, username: &str) -> Result<(), anyhow::Error> {
+        self.counters
+            .delete(&self.key("fail:user", username))
+            .await
+            .context("Failed to reset the failed login counter")
+    }
+
+    // the window starts with the first failure
+    async fn count(&self, key: &str) -> Result<u64, anyhow::Error> {
+        self.counters
+            .increment(key, self.settings.window_seconds)
+            .await
+            .context("Failed to count a failed login")
+    }
+
+    async fn lock(&self, key: &str, seconds: u64) -> Result<(), anyhow::Error> {
+        self.counters
+            .set(key, seconds)
+            .await
+            .context("Failed to lock logins")
+    }
+}
+
+// counts a failed login against the username and ip, and keeps a record
+// of any lockout that follows so other admins can see it
+pub async fn record_failed_login(
+    protection: &LoginProtection,
+    pool: &PgPool,
+    username: &str,
+    ip: &str,
+) -> Result<(), anyhow::Error> {
+    for lockout in protection.record_failure(username, ip).await? {
+        tracing::warn!(?lockout, "Locking out after too many failed logins");
+        record_lockout(pool, &lockout).await?;
+    }
+    Ok(())
+}
+
+// how long a username is locked after its `failures`th failure in the window, if at all
+fn username_lock_seconds(settings: &LoginProtectionSettings, failures: u64) -> Option<u64> {
+    if failures >= settings.max_failures_per_username {
+        return Some(settings.lockout_seconds);
+    }
+    let delayed = failures.checked_sub(settings.free_failures + 1)?;
+    let seconds = settings
+        .base_delay_seconds
+        .checked_shl(delayed as u32)
+        .unwrap_or(u64::MAX)
+        .min(settings.lockout_seconds);
+    (seconds > 0).then_some(seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::username_lock_seconds;
+    use crate::configuration::LoginProtectionSettings;
+
+    fn settings() -> LoginProtectionSettings {
+        LoginProtectionSettings {
+            key_prefix: "login".into(),
+            window_seconds: 3600,
+            free_failures: 3,
+            base_delay_seconds: 1,
+            max_failures_per_username: 10,
+            max_failures_per_ip: 50,
+            lockout_seconds: 900,
+        }
+    }
+
+    #[test]
+    fn the_first_failures_are_free() {
+        for failures in 1..=3 {
+            assert_eq!(username_lock_seconds(&settings(), failures), None);
+        }
+    }
+
+    #[test]
+    fn each_further_failure_doubles_the_delay() {
+        assert_eq!(username_lock_seconds(&settings(), 4), Some(1));
+        assert_eq!(username_lock_seconds(&settings(), 5), Some(2));
+        assert_eq!(username_lock_seconds(&settings(), 6), Some(4));
+        assert_eq!(username_lock_seconds(&settings(), 9), Some(32));
+    }
+
+    #[test]
+    fn the_maximum_locks_the_username_out() {
+        assert_eq!(username_lock_seconds(&settings(), 10), Some(900));
+        assert_eq!(username_lock_seconds(&settings(), 11), Some(900));
+    }
+
+    #[test]
+    fn delays_never_exceed_the_lockout() {
+        let settings = LoginProtectionSettings {
+            max_failures_per_username: 200,
+            ..settings()
+        };
+        assert_eq!(username_lock_seconds(&settings, 20), Some(900));
+        assert_eq!(username_lock_seconds(&settings, 150), Some(900));
+    }
+}
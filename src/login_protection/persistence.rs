@@ -0,0 +1,60 @@
+use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::{Lockout, LockoutSubject};
+
+pub struct LockoutRecord {
+    pub username: Option<String>,
+    pub ip: Option<String>,
+    pub failures: i64,
+    pub created_at: DateTime<Utc>,
+    pub locked_until: DateTime<Utc>,
+}
+
+#[tracing::instrument(name = "Record a login lockout", skip(pool))]
+pub async fn record_lockout(pool: &PgPool, lockout: &Lockout) -> Result<(), anyhow::Error> {
+    let (username, ip) = match &lockout.subject {
+        LockoutSubject::Username(username) => (Some(username.as_str()), None),
+        LockoutSubject::Ip(ip) => (None, Some(ip.as_str())),
+    };
+    let now = Utc::now();
+    sqlx::query!(
+        r#"
+        INSERT INTO login_lockouts (id, username, ip, failures, created_at, locked_until)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        Uuid::new_v4(),
+        username,
+        ip,
+        lockout.failures as i64,
+        now,
+        now + Duration::seconds(lockout.seconds as i64)
+    )
+    .execute(pool)
+    .await
+    .context("Failed to record a login lockout")?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "List recent login lockouts", skip(pool))]
+pub async fn list_recent_lockouts(
+    pool: &PgPool,
+    limit: i64,
+) -> Result<Vec<LockoutRecord>, anyhow::Error> {
+    let lockouts = sqlx::query_as!(
+        LockoutRecord,
+        r#"
+        SELECT username, ip, failures, created_at, locked_until
+        FROM login_lockouts
+        ORDER BY created_at DESC
+        LIMIT $1
+        "#,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve login lockouts")?;
+    Ok(lockouts)
+}
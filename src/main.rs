@@ -4,6 +4,7 @@ use tokio::task::JoinError;
 use zero2prod::{
     configuration::get_configuration,
     issue_delivery_worker::run_worker_until_stopped,
+    session_store::run_cleanup_worker_until_stopped,
     startup::Application,
     telemetry::{get_subscriber, init_subscriber},
 };
@@ -19,12 +20,14 @@ async fn main() -> anyhow::Result<()> {
     let configuration = get_configuration().expect("Failed to read configuration.");
     let application = Application::build(configuration.clone()).await?;
     let application_task = tokio::spawn(application.run_until_stopped());
-    let worker_task = tokio::spawn(run_worker_until_stopped(configuration));
+    let worker_task = tokio::spawn(run_worker_until_stopped(configuration.clone()));
+    let cleanup_task = tokio::spawn(run_cleanup_worker_until_stopped(configuration));
 
     // waits for multiple asynchronous functions and returns when either completes
     tokio::select! {
         o = application_task => report_exit("API", o),
         o = worker_task => report_exit("Background worker", o),
+        o = cleanup_task => report_exit("Session cleanup worker", o),
     };
 
     Ok(())
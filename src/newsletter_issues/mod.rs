@@ -0,0 +1,6 @@
+mod persistence;
+
+pub use persistence::{
+    DeliveryStats, NewsletterIssue, delivery_stats, get_newsletter_issue, insert_newsletter_issue,
+    list_newsletter_issues, publish_newsletter_issue,
+};
@@ -0,0 +1,190 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, PgPool, Postgres, QueryBuilder, Transaction};
+use uuid::Uuid;
+
+use crate::domain::SegmentFilter;
+use crate::segmentation::push_segment_filter;
+
+pub struct NewsletterIssue {
+    pub newsletter_issue_id: Uuid,
+    pub title: String,
+    pub text_content: String,
+    pub html_content: String,
+    // none means "every confirmed subscriber"
+    pub segment_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    // none until the issue has been sent
+    pub published_at: Option<DateTime<Utc>>,
+}
+
+// how far the delivery of an issue has got, as recorded in `issue_delivery_log`
+#[derive(Default)]
+pub struct DeliveryStats {
+    pub queued: i64,
+    pub sent: i64,
+    pub failed: i64,
+    pub invalid_email: i64,
+    pub suppressed: i64,
+}
+
+// stored as a draft, see `publish_newsletter_issue`
+#[tracing::instrument(skip_all)]
+pub async fn insert_newsletter_issue<'c, E>(
+    executor: E,
+    title: &str,
+    text_content: &str,
+    html_content: &str,
+    segment_id: Option<Uuid>,
+) -> Result<Uuid, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let newsletter_issue_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues (
+            newsletter_issue_id,
+            title,
+            text_content,
+            html_content,
+            segment_id,
+            created_at
+        )
+        VALUES ($1, $2, $3, $4, $5, now())
+        "#,
+        newsletter_issue_id,
+        title,
+        text_content,
+        html_content,
+        segment_id
+    )
+    .execute(executor)
+    .await?;
+    Ok(newsletter_issue_id)
+}
+
+// queues a delivery for every confirmed subscriber in the segment.
+// false if the issue has already been sent
+#[tracing::instrument(skip(transaction, segment))]
+pub async fn publish_newsletter_issue(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+    segment: Option<&SegmentFilter>,
+) -> Result<bool, sqlx::Error> {
+    let published = transaction
+        .execute(sqlx::query!(
+            r#"
+            UPDATE newsletter_issues SET published_at = now()::text
+            WHERE newsletter_issue_id = $1 AND published_at IS NULL
+            "#,
+            newsletter_issue_id
+        ))
+        .await?
+        .rows_affected();
+    if published == 0 {
+        return Ok(false);
+    }
+    // the segment is compiled to SQL at runtime, so this can't be checked by `query!`
+    let mut builder = QueryBuilder::<Postgres>::new(
+        "INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_email) SELECT ",
+    );
+    builder.push_bind(newsletter_issue_id);
+    builder.push(", s.email FROM subscriptions s WHERE s.status = 'confirmed'");
+    if let Some(segment) = segment {
+        builder.push(" AND ");
+        push_segment_filter(&mut builder, segment);
+    }
+    transaction.execute(builder.build()).await?;
+    Ok(true)
+}
+
+// newest first
+#[tracing::instrument(name = "List newsletter issues", skip(pool))]
+pub async fn list_newsletter_issues(pool: &PgPool) -> Result<Vec<NewsletterIssue>, sqlx::Error> {
+    sqlx::query_as!(
+        NewsletterIssue,
+        r#"
+        SELECT
+            newsletter_issue_id,
+            title,
+            text_content,
+            html_content,
+            segment_id,
+            created_at,
+            published_at::timestamptz AS "published_at?"
+        FROM newsletter_issues
+        ORDER BY created_at DESC, newsletter_issue_id
+        "#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+#[tracing::instrument(name = "Get a newsletter issue", skip(executor))]
+pub async fn get_newsletter_issue<'c, E>(
+    executor: E,
+    newsletter_issue_id: Uuid,
+) -> Result<Option<NewsletterIssue>, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as!(
+        NewsletterIssue,
+        r#"
+        SELECT
+            newsletter_issue_id,
+            title,
+            text_content,
+            html_content,
+            segment_id,
+            created_at,
+            published_at::timestamptz AS "published_at?"
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(executor)
+    .await
+}
+
+#[tracing::instrument(name = "Count the deliveries of an issue", skip(pool))]
+pub async fn delivery_stats(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<DeliveryStats, sqlx::Error> {
+    let queued = sqlx::query!(
+        r#"
+        SELECT count(*) AS "count!" FROM issue_delivery_queue
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_one(pool)
+    .await?
+    .count;
+    let outcomes = sqlx::query!(
+        r#"
+        SELECT outcome, count(*) AS "count!" FROM issue_delivery_log
+        WHERE newsletter_issue_id = $1
+        GROUP BY outcome
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_all(pool)
+    .await?;
+    let mut stats = DeliveryStats {
+        queued,
+        ..Default::default()
+    };
+    for row in outcomes {
+        match row.outcome.as_str() {
+            "sent" => stats.sent = row.count,
+            "failed" => stats.failed = row.count,
+            "invalid_email" => stats.invalid_email = row.count,
+            "suppressed" => stats.suppressed = row.count,
+            other => tracing::warn!("Ignoring an unknown delivery outcome: {}", other),
+        }
+    }
+    Ok(stats)
+}
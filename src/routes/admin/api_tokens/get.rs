@@ -0,0 +1,98 @@
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::authentication::{CsrfToken, UserId, list_api_tokens};
+use crate::domain::{Permission, Role};
+use crate::utils::e500;
+
+pub async fn api_tokens_page(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    role: web::ReqData<Role>,
+    csrf: CsrfToken,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_field = csrf.form_field();
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap()
+    }
+
+    let mut tokens_html = String::new();
+    for token in list_api_tokens(&pool, *user_id.into_inner())
+        .await
+        .map_err(e500)?
+    {
+        let scopes: Vec<&str> = token.scopes.iter().map(Permission::as_str).collect();
+        writeln!(
+            tokens_html,
+            r#"<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>
+                <form action="/admin/api-tokens/{}/revoke" method="post">
+                    {csrf_field}
+                    <button type="submit">Revoke</button>
+                </form>
+            </td></tr>"#,
+            encode_minimal(&token.name),
+            if scopes.is_empty() {
+                "read only".to_string()
+            } else {
+                scopes.join(", ")
+            },
+            token.created_at.format("%Y-%m-%d %H:%M"),
+            token
+                .last_used_at
+                .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_else(|| "never".into()),
+            token.id,
+        )
+        .unwrap();
+    }
+
+    // a token can't do more than its owner
+    let mut scopes_html = String::new();
+    for permission in Permission::ALL.into_iter().filter(|p| role.can(*p)) {
+        writeln!(
+            scopes_html,
+            r#"<label><input type="checkbox" name="scope" value="{0}"> {0}</label>"#,
+            permission.as_str()
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>API tokens</title>
+            </head>
+            <body>
+                {msg_html}
+                <table>
+                    <tr><th>Name</th><th>Scopes</th><th>Created at</th><th>Last used</th><th></th></tr>
+                    {tokens_html}
+                </table>
+                <p>Tokens are sent as <code>Authorization: Bearer &lt;token&gt;</code>.
+                They need the read scope to look at admin pages, and change only what their scopes allow.</p>
+                <form action="/admin/api-tokens" method="post">
+                    {csrf_field}
+                    <label>Name
+                        <input
+                            type="text"
+                            placeholder="What the token is for"
+                            name="name"
+                        >
+                    </label>
+                    {scopes_html}
+                    <button type="submit">Create token</button>
+                </form>
+                <p><a href="/admin/dashboard">&lt;- Back</a></p>
+            </body>
+            </html>"#
+        )))
+}
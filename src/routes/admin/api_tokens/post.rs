@@ -0,0 +1,83 @@
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::FlashMessage;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use unicode_segmentation::UnicodeSegmentation;
+use uuid::Uuid;
+
+use crate::authentication::{self, UserId};
+use crate::domain::{Permission, Role};
+use crate::utils::{e400, e500, see_other};
+
+// the new token is only ever shown here
+fn new_token_page(name: &str, token: &str) -> HttpResponse {
+    let name = encode_minimal(name);
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>New API token</title>
+            </head>
+            <body>
+                <p>Your new token for {name} is:</p>
+                <p><code>{token}</code></p>
+                <p>Copy it now, it won't be shown again.</p>
+                <p><a href="/admin/api-tokens">Done</a></p>
+            </body>
+            </html>"#
+        ))
+}
+
+// the scopes are checkboxes, so they come in as a repeated field
+#[tracing::instrument(name = "Create an api token", skip(form, pool, role), fields(user_id=%*user_id))]
+pub async fn create_api_token(
+    form: web::Form<Vec<(String, String)>>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    role: web::ReqData<Role>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut name = String::new();
+    let mut scopes = Vec::new();
+    for (field, value) in form.0 {
+        match field.as_str() {
+            "name" => name = value.trim().to_string(),
+            "scope" => {
+                let scope = Permission::parse(&value).map_err(e400)?;
+                if !scopes.contains(&scope) {
+                    scopes.push(scope);
+                }
+            }
+            _ => {}
+        }
+    }
+    if name.is_empty() || name.graphemes(true).count() > 100 {
+        FlashMessage::error("Give the token a name of at most 100 characters.").send();
+        return Ok(see_other("/admin/api-tokens"));
+    }
+    if scopes.iter().any(|scope| !role.can(*scope)) {
+        FlashMessage::error("A token can't be allowed to do more than you can.").send();
+        return Ok(see_other("/admin/api-tokens"));
+    }
+    let token = authentication::create_api_token(&pool, **user_id, &name, &scopes)
+        .await
+        .map_err(e500)?;
+    Ok(new_token_page(&name, &token))
+}
+
+#[tracing::instrument(name = "Revoke an api token", skip(pool), fields(user_id=%*user_id))]
+pub async fn revoke_api_token(
+    path: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if authentication::revoke_api_token(&pool, **user_id, path.into_inner())
+        .await
+        .map_err(e500)?
+    {
+        FlashMessage::info("The API token has been revoked.").send();
+    }
+    Ok(see_other("/admin/api-tokens"))
+}
@@ -1,33 +1,25 @@
 use actix_web::{HttpResponse, http::header::ContentType, web};
-use anyhow::Context;
 use sqlx::PgPool;
-use uuid::Uuid;
 
-use crate::authentication::UserId;
+use crate::authentication::{CsrfToken, UserId, get_username};
+use crate::domain::{Permission, Role};
 use crate::utils::e500;
 
-#[tracing::instrument(name = "Get username", skip(pool))]
-pub async fn get_username(user_id: Uuid, pool: &PgPool) -> Result<String, anyhow::Error> {
-    let row = sqlx::query!(
-        r#"
-        SELECT username
-        FROM users
-        WHERE user_id = $1
-        "#,
-        user_id,
-    )
-    .fetch_one(pool)
-    .await
-    .context("Failed to perform a query to retrieve a username.")?;
-    Ok(row.username)
-}
-
 pub async fn admin_dashboard(
     user_id: web::ReqData<UserId>,
+    role: web::ReqData<Role>,
     pool: web::Data<PgPool>,
+    csrf: CsrfToken,
 ) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_field = csrf.form_field();
     let user_id = user_id.into_inner();
     let username = get_username(*user_id, &pool).await.map_err(e500)?;
+    let role = role.into_inner();
+    let manage_users = if role.can(Permission::ManageUsers) {
+        r#"<li><a href="/admin/users">Manage users</a></li>"#
+    } else {
+        ""
+    };
     Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
         .body(format!(
@@ -39,17 +31,30 @@ pub async fn admin_dashboard(
             </head>
             <body>
                 <p>Welcome {username}!</p>
+                <p>Your role: {role}</p>
                 <p>Available actions:</p>
                 <ol>
                     <li><a href="/admin/password">Change password</a></li>
+                    <li><a href="/admin/two-factor">Two-factor authentication</a></li>
+                    <li><a href="/admin/sessions">Active sessions</a></li>
+                    <li><a href="/admin/api-tokens">API tokens</a></li>
                     <li><a href="/admin/newsletter">Send a newsletter issue</a></li>
+                    <li><a href="/admin/subscribers">Manage subscribers</a></li>
+                    <li><a href="/admin/tags">Manage subscriber tags</a></li>
+                    <li><a href="/admin/segments">Manage segments</a></li>
+                    <li><a href="/admin/fields">Manage custom fields</a></li>
+                    <li><a href="/admin/suppressions">Manage the suppression list</a></li>
+                    {manage_users}
+                    <li><a href="/admin/lockouts">Recent login lockouts</a></li>
                     <li>
                         <form name="logoutForm" action="/admin/logout" method="post">
+                            {csrf_field}
                             <input type="submit" value="Logout">
                         </form>
                     </li>
                 </ol>
             </body>
-            </html>"#
+            </html>"#,
+            role = role.as_str(),
         )))
 }
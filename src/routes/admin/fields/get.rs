@@ -0,0 +1,96 @@
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::authentication::CsrfToken;
+use crate::custom_fields::list_field_definitions;
+use crate::utils::e500;
+
+pub async fn custom_fields_form(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    csrf: CsrfToken,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_field = csrf.form_field();
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap()
+    }
+
+    let mut fields_html = String::new();
+    for definition in list_field_definitions(&pool).await.map_err(e500)? {
+        writeln!(
+            fields_html,
+            "<tr><td><code>{}</code></td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            definition.name,
+            encode_minimal(&definition.label),
+            definition.field_type.as_str(),
+            encode_minimal(&definition.field_type.options().join(", ")),
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Custom subscriber fields</title>
+            </head>
+            <body>
+                {msg_html}
+                <table>
+                    <tr><th>Name</th><th>Label</th><th>Type</th><th>Options</th></tr>
+                    {fields_html}
+                </table>
+                <form action="/admin/fields" method="post">
+                    {csrf_field}
+                    <label>Name
+                        <input
+                            type="text"
+                            placeholder="company"
+                            name="name"
+                        >
+                    </label>
+                    <br>
+                    <label>Label
+                        <input
+                            type="text"
+                            placeholder="Company"
+                            name="label"
+                        >
+                    </label>
+                    <br>
+                    <label>Type
+                        <select name="field_type">
+                            <option value="text">Text</option>
+                            <option value="number">Number</option>
+                            <option value="boolean">Boolean</option>
+                            <option value="enum">Enum</option>
+                        </select>
+                    </label>
+                    <br>
+                    <label>Enum options
+                        <input
+                            type="text"
+                            placeholder="Engineer, Designer, Other"
+                            name="options"
+                        >
+                    </label>
+                    <br>
+                    <button type="submit">Add field</button>
+                </form>
+                <p>
+                    Signup forms submit a field by its name, segments can match it with
+                    <code>field:NAME=VALUE</code> and newsletter issues can include it
+                    with <code>{{{{NAME}}}}</code>.
+                </p>
+                <p><a href="/admin/dashboard">&lt;- Back</a></p>
+            </body>
+            </html>"#
+        )))
+}
@@ -0,0 +1,54 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+
+use crate::custom_fields::insert_field_definition;
+use crate::domain::{CustomFieldDefinition, CustomFieldType};
+use crate::utils::{e500, see_other};
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    name: String,
+    label: String,
+    field_type: String,
+    #[serde(default)]
+    options: String,
+}
+
+#[tracing::instrument(name = "Create a custom field", skip(form, pool), fields(field_name = %form.name))]
+pub async fn create_custom_field(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let FormData {
+        name,
+        label,
+        field_type,
+        options,
+    } = form.0;
+    let options: Vec<String> = options.split(',').map(|o| o.to_string()).collect();
+    let definition = match CustomFieldType::parse(&field_type, &options)
+        .and_then(|field_type| CustomFieldDefinition::parse(name, label, field_type))
+    {
+        Ok(definition) => definition,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/fields"));
+        }
+    };
+
+    match insert_field_definition(&pool, &definition).await {
+        Ok(()) => {
+            FlashMessage::info("The custom field has been added.").send();
+        }
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            FlashMessage::error("A custom field with that name already exists.").send();
+        }
+        Err(e) => {
+            return Err(e500(
+                anyhow::Error::from(e).context("Failed to save the custom field"),
+            ));
+        }
+    }
+    Ok(see_other("/admin/fields"))
+}
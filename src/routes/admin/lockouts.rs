@@ -0,0 +1,48 @@
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::login_protection::list_recent_lockouts;
+use crate::utils::e500;
+
+// so admins can tell when someone has been guessing at logins
+pub async fn login_lockouts(pool: web::Data<PgPool>) -> Result<HttpResponse, actix_web::Error> {
+    let mut lockouts_html = String::new();
+    for lockout in list_recent_lockouts(&pool, 100).await.map_err(e500)? {
+        let subject = match (&lockout.username, &lockout.ip) {
+            (Some(username), _) => format!("username {}", encode_minimal(username)),
+            (None, Some(ip)) => format!("ip {}", encode_minimal(ip)),
+            (None, None) => String::new(),
+        };
+        writeln!(
+            lockouts_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            lockout.created_at.format("%Y-%m-%d %H:%M"),
+            subject,
+            lockout.failures,
+            lockout.locked_until.format("%Y-%m-%d %H:%M"),
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Login lockouts</title>
+            </head>
+            <body>
+                <p>Logins locked out after too many failed attempts:</p>
+                <table>
+                    <tr><th>At</th><th>Locked out</th><th>Failed attempts</th><th>Until</th></tr>
+                    {lockouts_html}
+                </table>
+                <p><a href="/admin/dashboard">&lt;- Back</a></p>
+            </body>
+            </html>"#
+        )))
+}
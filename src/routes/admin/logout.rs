@@ -1,9 +1,20 @@
+use crate::authentication::{UserId, revoke_session};
 use crate::session_state::TypedSession;
-use crate::utils::see_other;
-use actix_web::HttpResponse;
+use crate::utils::{e500, see_other};
+use actix_web::{HttpResponse, web};
 use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
 
-pub async fn log_out(session: TypedSession) -> Result<HttpResponse, actix_web::Error> {
+pub async fn log_out(
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if let Some(session_id) = session.get_session_id().map_err(e500)? {
+        revoke_session(&pool, *user_id.into_inner(), session_id)
+            .await
+            .map_err(e500)?;
+    }
     session.log_out();
     FlashMessage::info("You have successfully logged out.").send();
     Ok(see_other("/login"))
@@ -1,9 +1,29 @@
+mod api_tokens;
 mod dashboard;
+mod fields;
+mod lockouts;
 mod logout;
 mod newsletter;
 mod password;
+mod segments;
+mod sessions;
+mod subscribers;
+mod suppressions;
+mod tags;
+mod two_factor;
+mod users;
 
+pub use api_tokens::*;
 pub use dashboard::admin_dashboard;
+pub use fields::*;
+pub use lockouts::login_lockouts;
 pub use logout::log_out;
 pub use newsletter::*;
 pub use password::*;
+pub use segments::*;
+pub use sessions::*;
+pub use subscribers::*;
+pub use suppressions::*;
+pub use tags::*;
+pub use two_factor::*;
+pub use users::*;
@@ -1,15 +1,44 @@
-use actix_web::{HttpResponse, http::header::ContentType};
+use actix_web::{HttpResponse, http::header::ContentType, web};
 use actix_web_flash_messages::IncomingFlashMessages;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
 use std::fmt::Write;
 
+use crate::authentication::CsrfToken;
+use crate::custom_fields::list_field_definitions;
+use crate::segmentation::{count_recipients, list_segments};
+use crate::utils::e500;
+
 pub async fn publish_newsletter_form(
     flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    csrf: CsrfToken,
 ) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_field = csrf.form_field();
     let mut msg_html = String::new();
     for m in flash_messages.iter() {
         writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap()
     }
 
+    // show how many people each option would reach before anything is sent
+    let all_recipients = count_recipients(&pool, None).await.map_err(e500)?;
+    let mut segment_options = format!(
+        r#"<option value="">All confirmed subscribers ({all_recipients} recipients)</option>"#
+    );
+    let field_definitions = list_field_definitions(&pool).await.map_err(e500)?;
+    for segment in list_segments(&pool).await.map_err(e500)? {
+        let filter = segment.filter(&field_definitions).map_err(e500)?;
+        let recipients = count_recipients(&pool, Some(&filter)).await.map_err(e500)?;
+        write!(
+            segment_options,
+            r#"<option value="{}">{} ({} recipients)</option>"#,
+            segment.segment_id,
+            encode_minimal(&segment.name),
+            recipients
+        )
+        .unwrap();
+    }
+
     let idempotency_key = uuid::Uuid::new_v4();
     Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
@@ -23,6 +52,7 @@ pub async fn publish_newsletter_form(
             <body>
                 {msg_html}
                 <form action="/admin/newsletter" method="post">
+                    {csrf_field}
                     <label>Title
                         <input
                             type="text"
@@ -44,6 +74,10 @@ pub async fn publish_newsletter_form(
                         >
                     </label>
                     <br>
+                    <label>Send to
+                        <select name="segment_id">{segment_options}</select>
+                    </label>
+                    <br>
                     <input hidden type="text" name="idempotency_key" value="{idempotency_key}">
                     <button type="submit">Send newsletter</button>
                 </form>
@@ -1,11 +1,14 @@
 use actix_web::{HttpResponse, web};
 use actix_web_flash_messages::FlashMessage;
 use anyhow::Context;
-use sqlx::{Executor, PgPool, Postgres, Transaction};
+use sqlx::PgPool;
 use uuid::Uuid;
 
 use crate::authentication::UserId;
+use crate::custom_fields::list_field_definitions;
 use crate::idempotency::{IdempotencyKey, NextAction, save_response, try_processing};
+use crate::newsletter_issues::{insert_newsletter_issue, publish_newsletter_issue};
+use crate::segmentation::get_segment;
 use crate::utils::{e400, e500, see_other};
 
 #[derive(serde::Deserialize)]
@@ -14,6 +17,9 @@ pub struct FormData {
     text_content: String,
     html_content: String,
     idempotency_key: String,
+    // empty or missing means "every confirmed subscriber"
+    #[serde(default)]
+    segment_id: Option<String>,
 }
 
 fn success_message() -> FlashMessage {
@@ -37,9 +43,28 @@ pub async fn publish_newsletter(
         text_content,
         html_content,
         idempotency_key,
+        segment_id,
     } = form.0;
     let idempotency_key: IdempotencyKey = idempotency_key.try_into().map_err(e400)?;
-    let mut transaction = match try_processing(&pool, &idempotency_key, *user_id)
+    let segment = match segment_id.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(segment_id) => {
+            let segment_id = Uuid::parse_str(segment_id).map_err(e400)?;
+            let segment = get_segment(&pool, segment_id)
+                .await
+                .context("Failed to retrieve the selected segment")
+                .map_err(e500)?
+                .ok_or_else(|| e400("The selected segment does not exist."))?;
+            Some(segment)
+        }
+    };
+    let field_definitions = list_field_definitions(&pool).await.map_err(e500)?;
+    let filter = segment
+        .as_ref()
+        .map(|s| s.filter(&field_definitions))
+        .transpose()
+        .map_err(e500)?;
+    let mut transaction = match try_processing(&pool, &idempotency_key, *user_id, None)
         .await
         .map_err(e500)?
     {
@@ -48,13 +73,23 @@ pub async fn publish_newsletter(
             success_message().send();
             return Ok(saved_response);
         }
+        // the form's keys are never used by the api
+        NextAction::RejectReusedKey => {
+            return Err(e400("This form has already been used for something else."));
+        }
     };
 
-    let issue_id = insert_newsletter_issue(&mut transaction, &title, &text_content, &html_content)
-        .await
-        .context("Failed to store newsletter issue details")
-        .map_err(e500)?;
-    enqueue_delivery_tasks(&mut transaction, issue_id)
+    let issue_id = insert_newsletter_issue(
+        &mut *transaction,
+        &title,
+        &text_content,
+        &html_content,
+        segment.as_ref().map(|s| s.segment_id),
+    )
+    .await
+    .context("Failed to store newsletter issue details")
+    .map_err(e500)?;
+    publish_newsletter_issue(&mut transaction, issue_id, filter.as_ref())
         .await
         .context("Failed to enqueue delivery tasks")
         .map_err(e500)?;
@@ -65,52 +100,3 @@ pub async fn publish_newsletter(
     success_message().send();
     Ok(response)
 }
-
-#[tracing::instrument(skip_all)]
-async fn insert_newsletter_issue(
-    transaction: &mut Transaction<'_, Postgres>,
-    title: &str,
-    text_content: &str,
-    html_content: &str,
-) -> Result<Uuid, sqlx::Error> {
-    let newsletter_issue_id = Uuid::new_v4();
-    let query = sqlx::query!(
-        r#"
-        INSERT INTO newsletter_issues (
-            newsletter_issue_id,
-            title,
-            text_content,
-            html_content,
-            published_at
-        )
-        VALUES ($1, $2, $3, $4, now())
-        "#,
-        newsletter_issue_id,
-        title,
-        text_content,
-        html_content
-    );
-    transaction.execute(query).await?;
-    Ok(newsletter_issue_id)
-}
-
-#[tracing::instrument(skip_all)]
-async fn enqueue_delivery_tasks(
-    transaction: &mut Transaction<'_, Postgres>,
-    newsletter_issue_id: Uuid,
-) -> Result<(), sqlx::Error> {
-    let query = sqlx::query!(
-        r#"
-        INSERT INTO issue_delivery_queue (
-            newsletter_issue_id,
-            subscriber_email
-        )
-        SELECT $1, email
-        FROM subscriptions
-        WHERE status = 'confirmed'
-        "#,
-        newsletter_issue_id,
-    );
-    transaction.execute(query).await?;
-    Ok(())
-}
@@ -2,9 +2,13 @@ use actix_web::{HttpResponse, http::header::ContentType};
 use actix_web_flash_messages::IncomingFlashMessages;
 use std::fmt::Write;
 
+use crate::authentication::CsrfToken;
+
 pub async fn change_password_form(
     flash_messages: IncomingFlashMessages,
+    csrf: CsrfToken,
 ) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_field = csrf.form_field();
     let mut msg_html = String::new();
     for m in flash_messages.iter() {
         writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
@@ -22,6 +26,7 @@ pub async fn change_password_form(
         <body>
             {msg_html}
             <form action="/admin/password" method="post">
+                {csrf_field}
                 <label>Current password
                     <input
                         type="password"
@@ -1,10 +1,14 @@
 use actix_web::{HttpResponse, web};
 use actix_web_flash_messages::FlashMessage;
-use secrecy::{ExposeSecret, SecretString};
+use secrecy::SecretString;
 use sqlx::PgPool;
 
-use crate::authentication::{AuthError, Credentials, UserId, validate_credentials};
-use crate::routes::admin::dashboard::get_username;
+use crate::authentication::{
+    AuthError, Credentials, DummyPasswordHash, UserId, get_username, revoke_all_sessions,
+    validate_credentials, validate_new_password,
+};
+use crate::configuration::PasswordHashingSettings;
+use crate::session_state::TypedSession;
 use crate::utils::{e500, see_other};
 
 #[derive(serde::Deserialize)]
@@ -18,28 +22,14 @@ pub async fn change_password(
     form: web::Form<FormData>,
     pool: web::Data<PgPool>,
     user_id: web::ReqData<UserId>,
+    hashing: web::Data<PasswordHashingSettings>,
+    dummy_hash: web::Data<DummyPasswordHash>,
+    session: TypedSession,
 ) -> Result<HttpResponse, actix_web::Error> {
     let user_id = user_id.into_inner();
 
-    if form.new_password.expose_secret().len() < 13 {
-        FlashMessage::error("Your new password is too short - it must be at least 13 characters.")
-            .send();
-        return Ok(see_other("/admin/password"));
-    }
-
-    if form.new_password.expose_secret().len() > 128 {
-        FlashMessage::error(
-            "Your new password is too long - it must be shorter than 129 characters.",
-        )
-        .send();
-        return Ok(see_other("/admin/password"));
-    }
-
-    if form.new_password.expose_secret() != form.new_password_check.expose_secret() {
-        FlashMessage::error(
-            "You entered two different new passwords - the field values must match.",
-        )
-        .send();
+    if let Err(e) = validate_new_password(&form.new_password, &form.new_password_check) {
+        FlashMessage::error(e).send();
         return Ok(see_other("/admin/password"));
     }
 
@@ -48,7 +38,7 @@ pub async fn change_password(
         username,
         password: form.0.current_password,
     };
-    if let Err(e) = validate_credentials(credentials, &pool).await {
+    if let Err(e) = validate_credentials(credentials, &pool, &hashing, &dummy_hash).await {
         return match e {
             AuthError::InvalidCredentials(_) => {
                 FlashMessage::error("The current password is incorrect.").send();
@@ -57,7 +47,12 @@ pub async fn change_password(
             AuthError::UnexpectedError(_) => Err(e500(e)),
         };
     }
-    crate::authentication::change_password(*user_id, form.0.new_password, &pool)
+    crate::authentication::change_password(*user_id, form.0.new_password, &pool, &hashing)
+        .await
+        .map_err(e500)?;
+    // whoever knew the old password is logged out, this session stays
+    let current_session_id = session.get_session_id().map_err(e500)?;
+    revoke_all_sessions(&pool, *user_id, current_session_id)
         .await
         .map_err(e500)?;
     FlashMessage::error("Your password has been changed.").send();
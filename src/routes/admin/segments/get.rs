@@ -0,0 +1,83 @@
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::authentication::CsrfToken;
+use crate::custom_fields::list_field_definitions;
+use crate::segmentation::{count_recipients, list_segments};
+use crate::utils::e500;
+
+pub async fn segments_form(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    csrf: CsrfToken,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_field = csrf.form_field();
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap()
+    }
+
+    let mut segments_html = String::new();
+    let field_definitions = list_field_definitions(&pool).await.map_err(e500)?;
+    for segment in list_segments(&pool).await.map_err(e500)? {
+        let filter = segment.filter(&field_definitions).map_err(e500)?;
+        let recipients = count_recipients(&pool, Some(&filter)).await.map_err(e500)?;
+        writeln!(
+            segments_html,
+            "<tr><td>{}</td><td><code>{}</code></td><td>{}</td></tr>",
+            encode_minimal(&segment.name),
+            encode_minimal(&segment.definition),
+            recipients
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Segments</title>
+            </head>
+            <body>
+                {msg_html}
+                <table>
+                    <tr><th>Name</th><th>Definition</th><th>Confirmed recipients</th></tr>
+                    {segments_html}
+                </table>
+                <form action="/admin/segments" method="post">
+                    {csrf_field}
+                    <label>Name
+                        <input
+                            type="text"
+                            placeholder="Enter segment name"
+                            name="name"
+                        >
+                    </label>
+                    <br>
+                    <label>Definition
+                        <input
+                            type="text"
+                            placeholder="tag:vip AND NOT subscribed_before:2026-01-01"
+                            name="definition"
+                        >
+                    </label>
+                    <br>
+                    <button type="submit">Save segment</button>
+                </form>
+                <p>
+                    Combine <code>tag:NAME</code>, <code>status:STATUS</code>,
+                    <code>subscribed_before:YYYY-MM-DD</code> and
+                    <code>subscribed_after:YYYY-MM-DD</code> with
+                    <code>AND</code>, <code>OR</code>, <code>NOT</code> and parentheses.
+                </p>
+                <p><a href="/admin/dashboard">&lt;- Back</a></p>
+            </body>
+            </html>"#
+        )))
+}
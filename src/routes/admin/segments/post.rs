@@ -0,0 +1,49 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+
+use crate::custom_fields::list_field_definitions;
+use crate::domain::SegmentFilter;
+use crate::segmentation::insert_segment;
+use crate::utils::{e500, see_other};
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    name: String,
+    definition: String,
+}
+
+#[tracing::instrument(name = "Create a segment", skip(form, pool), fields(segment_name = %form.name))]
+pub async fn create_segment(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let name = form.name.trim();
+    if name.is_empty() {
+        FlashMessage::error("The segment name cannot be empty.").send();
+        return Ok(see_other("/admin/segments"));
+    }
+
+    let field_definitions = list_field_definitions(&pool).await.map_err(e500)?;
+    if let Err(e) = SegmentFilter::parse(&form.definition)
+        .and_then(|filter| filter.canonicalize(&field_definitions))
+    {
+        FlashMessage::error(e).send();
+        return Ok(see_other("/admin/segments"));
+    }
+
+    match insert_segment(&pool, name, form.definition.trim()).await {
+        Ok(_) => {
+            FlashMessage::info("The segment has been saved.").send();
+        }
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            FlashMessage::error("A segment with that name already exists.").send();
+        }
+        Err(e) => {
+            return Err(e500(
+                anyhow::Error::from(e).context("Failed to save the segment"),
+            ));
+        }
+    }
+    Ok(see_other("/admin/segments"))
+}
@@ -0,0 +1,79 @@
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::authentication::{CsrfToken, UserId, list_sessions};
+use crate::configuration::SessionTimeoutSettings;
+use crate::session_state::TypedSession;
+use crate::utils::e500;
+
+pub async fn sessions_page(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    session: TypedSession,
+    timeouts: web::Data<SessionTimeoutSettings>,
+    csrf: CsrfToken,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_field = csrf.form_field();
+    let current_session_id = session.get_session_id().map_err(e500)?;
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap()
+    }
+
+    let mut sessions_html = String::new();
+    for active in list_sessions(&pool, *user_id.into_inner(), &timeouts)
+        .await
+        .map_err(e500)?
+    {
+        // the current session is ended with the logout button instead
+        let action = if Some(active.session_id) == current_session_id {
+            "<i>this session</i>".to_string()
+        } else {
+            format!(
+                r#"<form action="/admin/sessions/{}/revoke" method="post">
+                    {csrf_field}
+                    <button type="submit">Revoke</button>
+                </form>"#,
+                active.session_id
+            )
+        };
+        writeln!(
+            sessions_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            active.created_at.format("%Y-%m-%d %H:%M"),
+            active.last_seen_at.format("%Y-%m-%d %H:%M"),
+            encode_minimal(&active.ip),
+            encode_minimal(&active.user_agent),
+            action,
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Active sessions</title>
+            </head>
+            <body>
+                {msg_html}
+                <table>
+                    <tr><th>Logged in at</th><th>Last active</th><th>IP</th><th>Browser</th><th></th></tr>
+                    {sessions_html}
+                </table>
+                <form action="/admin/sessions/logout-everywhere" method="post">
+                    {csrf_field}
+                    <button type="submit">Log out everywhere</button>
+                </form>
+                <p><a href="/admin/dashboard">&lt;- Back</a></p>
+            </body>
+            </html>"#
+        )))
+}
@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::sessions_page;
+pub use post::{log_out_everywhere, revoke_session};
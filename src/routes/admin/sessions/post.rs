@@ -0,0 +1,39 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::authentication::{self, UserId, revoke_all_sessions};
+use crate::session_state::TypedSession;
+use crate::utils::{e500, see_other};
+
+#[tracing::instrument(name = "Revoke a session", skip(pool), fields(user_id=%*user_id))]
+pub async fn revoke_session(
+    path: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    if authentication::revoke_session(&pool, *user_id, path.into_inner())
+        .await
+        .map_err(e500)?
+    {
+        FlashMessage::info("The session has been revoked.").send();
+    }
+    Ok(see_other("/admin/sessions"))
+}
+
+// this session included
+#[tracing::instrument(name = "Log out everywhere", skip(pool, session), fields(user_id=%*user_id))]
+pub async fn log_out_everywhere(
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    revoke_all_sessions(&pool, *user_id.into_inner(), None)
+        .await
+        .map_err(e500)?;
+    session.log_out();
+    FlashMessage::info("You have been logged out everywhere.").send();
+    Ok(see_other("/login"))
+}
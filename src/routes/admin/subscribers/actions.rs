@@ -0,0 +1,132 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::utils::{e500, see_other};
+
+#[tracing::instrument(name = "Manually confirm a subscriber", skip(pool))]
+pub async fn confirm_subscriber_manually(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id.into_inner();
+    // confirming twice keeps the original confirmation time
+    let updated = sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET
+            status = 'confirmed',
+            confirmed_at = CASE WHEN status = 'confirmed' THEN confirmed_at ELSE now() END
+        WHERE id = $1
+        "#,
+        subscriber_id
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to confirm the subscriber")
+    .map_err(e500)?
+    .rows_affected();
+    if updated == 0 {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    FlashMessage::info("The subscriber has been confirmed.").send();
+    Ok(see_other(&format!("/admin/subscribers/{subscriber_id}")))
+}
+
+#[tracing::instrument(name = "Manually unsubscribe a subscriber", skip(pool))]
+pub async fn unsubscribe_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id.into_inner();
+    let updated = sqlx::query!(
+        r#"UPDATE subscriptions SET status = 'unsubscribed' WHERE id = $1"#,
+        subscriber_id
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to unsubscribe the subscriber")
+    .map_err(e500)?
+    .rows_affected();
+    if updated == 0 {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    FlashMessage::info("The subscriber has been unsubscribed.").send();
+    Ok(see_other(&format!("/admin/subscribers/{subscriber_id}")))
+}
+
+// removes the subscriber and everything hanging off their id,
+// along with any deliveries still waiting in the queue for them.
+// false if there was no such subscriber
+pub async fn remove_subscriber(
+    transaction: &mut Transaction<'static, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<bool, anyhow::Error> {
+    transaction
+        .execute(sqlx::query!(
+            r#"
+            DELETE FROM issue_delivery_queue
+            WHERE subscriber_email = (SELECT email FROM subscriptions WHERE id = $1)
+            "#,
+            subscriber_id
+        ))
+        .await
+        .context("Failed to remove queued deliveries")?;
+    transaction
+        .execute(sqlx::query!(
+            r#"DELETE FROM subscription_tokens WHERE subscriber_id = $1"#,
+            subscriber_id
+        ))
+        .await
+        .context("Failed to remove subscription tokens")?;
+    transaction
+        .execute(sqlx::query!(
+            r#"DELETE FROM subscriber_tags WHERE subscriber_id = $1"#,
+            subscriber_id
+        ))
+        .await
+        .context("Failed to remove subscriber tags")?;
+    transaction
+        .execute(sqlx::query!(
+            r#"DELETE FROM subscriber_field_values WHERE subscriber_id = $1"#,
+            subscriber_id
+        ))
+        .await
+        .context("Failed to remove custom field values")?;
+    let deleted = transaction
+        .execute(sqlx::query!(
+            r#"DELETE FROM subscriptions WHERE id = $1"#,
+            subscriber_id
+        ))
+        .await
+        .context("Failed to remove the subscriber")?
+        .rows_affected();
+    Ok(deleted > 0)
+}
+
+#[tracing::instrument(name = "Delete a subscriber", skip(pool))]
+pub async fn delete_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    if !remove_subscriber(&mut transaction, subscriber_id.into_inner())
+        .await
+        .map_err(e500)?
+    {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to delete a subscriber.")
+        .map_err(e500)?;
+    FlashMessage::info("The subscriber has been deleted.").send();
+    Ok(see_other("/admin/subscribers"))
+}
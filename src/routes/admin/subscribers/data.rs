@@ -0,0 +1,58 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::routes::subscriber_data_response;
+use crate::subscriber_data::{collect_subscriber_data, erase_subscriber};
+use crate::utils::{e500, see_other};
+
+async fn get_subscriber_email(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Option<String>, anyhow::Error> {
+    let email = sqlx::query!(
+        r#"SELECT email FROM subscriptions WHERE id = $1"#,
+        subscriber_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the subscriber")?
+    .map(|r| r.email);
+    Ok(email)
+}
+
+#[tracing::instrument(name = "Download a subscriber's data", skip(pool))]
+pub async fn download_subscriber_data_as_admin(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(email) = get_subscriber_email(&pool, subscriber_id.into_inner())
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    match collect_subscriber_data(&pool, &email).await.map_err(e500)? {
+        Some(data) => subscriber_data_response(&data).map_err(e500),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+#[tracing::instrument(name = "Erase a subscriber's data", skip(pool))]
+pub async fn erase_subscriber_data(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id.into_inner();
+    let Some(email) = get_subscriber_email(&pool, subscriber_id)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    erase_subscriber(&pool, &email).await.map_err(e500)?;
+    FlashMessage::info("The subscriber's personal data has been erased.").send();
+    Ok(see_other(&format!("/admin/subscribers/{subscriber_id}")))
+}
@@ -0,0 +1,164 @@
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::authentication::CsrfToken;
+use crate::utils::e500;
+
+#[tracing::instrument(name = "Show subscriber details", skip(flash_messages, pool, csrf))]
+pub async fn subscriber_details(
+    flash_messages: IncomingFlashMessages,
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    csrf: CsrfToken,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_field = csrf.form_field();
+    let subscriber_id = subscriber_id.into_inner();
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap()
+    }
+
+    let subscriber = sqlx::query!(
+        r#"
+        SELECT email, name, status, subscribed_at, confirmed_at
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        subscriber_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to retrieve the subscriber")
+    .map_err(e500)?;
+    let Some(subscriber) = subscriber else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    let tags = sqlx::query!(
+        r#"SELECT tag FROM subscriber_tags WHERE subscriber_id = $1 ORDER BY tag"#,
+        subscriber_id
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to retrieve the subscriber's tags")
+    .map_err(e500)?;
+    let tags: Vec<String> = tags.into_iter().map(|r| encode_minimal(&r.tag)).collect();
+
+    let tokens = sqlx::query!(
+        r#"
+        SELECT subscription_token, created_at
+        FROM subscription_tokens
+        WHERE subscriber_id = $1
+        ORDER BY created_at
+        "#,
+        subscriber_id
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to retrieve the subscriber's confirmation tokens")
+    .map_err(e500)?;
+    // a full token confirms the subscription, so only enough of it to tell them apart
+    let mut tokens_html = String::new();
+    for token in tokens {
+        writeln!(
+            tokens_html,
+            "<tr><td><code>{}&hellip;</code></td><td>{}</td></tr>",
+            encode_minimal(&token.subscription_token.chars().take(4).collect::<String>()),
+            token.created_at.format("%Y-%m-%d %H:%M")
+        )
+        .unwrap();
+    }
+
+    let deliveries = sqlx::query!(
+        r#"
+        SELECT i.title, l.outcome, l.attempted_at
+        FROM issue_delivery_log l
+        JOIN newsletter_issues i ON i.newsletter_issue_id = l.newsletter_issue_id
+        WHERE l.subscriber_email = $1
+        ORDER BY l.attempted_at DESC
+        "#,
+        subscriber.email
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to retrieve the subscriber's delivery log")
+    .map_err(e500)?;
+    let mut deliveries_html = String::new();
+    for delivery in deliveries {
+        writeln!(
+            deliveries_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+            encode_minimal(&delivery.title),
+            delivery.outcome,
+            delivery.attempted_at.format("%Y-%m-%d %H:%M")
+        )
+        .unwrap();
+    }
+
+    let confirmed_at = subscriber
+        .confirmed_at
+        .map(|c| c.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|| "-".into());
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Subscriber details</title>
+            </head>
+            <body>
+                {msg_html}
+                <table>
+                    <tr><th>Email</th><td>{email}</td></tr>
+                    <tr><th>Name</th><td>{name}</td></tr>
+                    <tr><th>Status</th><td>{status}</td></tr>
+                    <tr><th>Subscribed at</th><td>{subscribed_at}</td></tr>
+                    <tr><th>Confirmed at</th><td>{confirmed_at}</td></tr>
+                    <tr><th>Tags</th><td>{tags}</td></tr>
+                </table>
+                <p><a href="/admin/subscribers/{subscriber_id}/fields">Edit custom fields</a></p>
+                <p><a href="/admin/subscribers/{subscriber_id}/data">Download all stored data (JSON)</a></p>
+                <h2>Confirmation tokens</h2>
+                <table>
+                    <tr><th>Token</th><th>Issued at</th></tr>
+                    {tokens_html}
+                </table>
+                <h2>Delivery log</h2>
+                <table>
+                    <tr><th>Issue</th><th>Outcome</th><th>Attempted at</th></tr>
+                    {deliveries_html}
+                </table>
+                <h2>Actions</h2>
+                <form action="/admin/subscribers/{subscriber_id}/confirm" method="post">
+                    {csrf_field}
+                    <button type="submit">Confirm</button>
+                </form>
+                <form action="/admin/subscribers/{subscriber_id}/unsubscribe" method="post">
+                    {csrf_field}
+                    <button type="submit">Unsubscribe</button>
+                </form>
+                <form action="/admin/subscribers/{subscriber_id}/delete" method="post">
+                    {csrf_field}
+                    <button type="submit">Delete</button>
+                </form>
+                <form action="/admin/subscribers/{subscriber_id}/erase" method="post">
+                    {csrf_field}
+                    <button type="submit">Erase personal data</button>
+                </form>
+                <p><a href="/admin/subscribers">&lt;- Back</a></p>
+            </body>
+            </html>"#,
+            email = encode_minimal(&subscriber.email),
+            name = encode_minimal(&subscriber.name),
+            status = subscriber.status,
+            subscribed_at = subscriber.subscribed_at.format("%Y-%m-%d %H:%M"),
+            tags = tags.join(", "),
+        )))
+}
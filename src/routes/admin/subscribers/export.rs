@@ -0,0 +1,270 @@
+use actix_web::http::header::{ContentDisposition, ContentType, DispositionParam, DispositionType};
+use actix_web::{HttpResponse, web};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use futures_util::{TryStreamExt, stream};
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use std::collections::BTreeMap;
+use tokio::sync::mpsc;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::custom_fields::list_field_definitions;
+use crate::domain::SegmentFilter;
+use crate::segmentation::{get_segment, push_segment_filter};
+use crate::utils::{e400, e500};
+
+// rows serialized ahead of a slow client, before the export stops reading from Postgres
+const BUFFERED_CHUNKS: usize = 64;
+
+#[derive(serde::Deserialize)]
+pub struct ExportParameters {
+    format: Option<String>,
+    status: Option<String>,
+    segment_id: Option<String>,
+}
+
+#[derive(Clone, Copy)]
+enum ExportFormat {
+    Csv,
+    NdJson,
+}
+
+#[derive(sqlx::FromRow)]
+struct ExportRow {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+    confirmed_at: Option<DateTime<Utc>>,
+    tags: Vec<String>,
+    field_names: Vec<String>,
+    field_values: Vec<String>,
+}
+
+#[derive(serde::Serialize)]
+struct ExportRecord<'a> {
+    id: Uuid,
+    email: &'a str,
+    name: &'a str,
+    status: &'a str,
+    subscribed_at: String,
+    confirmed_at: Option<String>,
+    tags: &'a [String],
+    custom_fields: BTreeMap<&'a str, &'a str>,
+}
+
+const CSV_COLUMNS: [&str; 7] = [
+    "id",
+    "email",
+    "name",
+    "status",
+    "subscribed_at",
+    "confirmed_at",
+    "tags",
+];
+
+impl ExportRow {
+    fn custom_fields(&self) -> BTreeMap<&str, &str> {
+        self.field_names
+            .iter()
+            .map(String::as_str)
+            .zip(self.field_values.iter().map(String::as_str))
+            .collect()
+    }
+
+    // one line in the chosen format, including the trailing newline
+    fn serialize(
+        &self,
+        format: ExportFormat,
+        field_names: &[String],
+    ) -> Result<Vec<u8>, anyhow::Error> {
+        match format {
+            ExportFormat::Csv => {
+                let fields = self.custom_fields();
+                let mut record = vec![
+                    self.id.to_string(),
+                    self.email.clone(),
+                    self.name.clone(),
+                    self.status.clone(),
+                    self.subscribed_at.to_rfc3339(),
+                    self.confirmed_at
+                        .map(|t| t.to_rfc3339())
+                        .unwrap_or_default(),
+                    self.tags.join(","),
+                ];
+                record.extend(field_names.iter().map(|name| {
+                    fields
+                        .get(name.as_str())
+                        .copied()
+                        .unwrap_or_default()
+                        .to_owned()
+                }));
+                csv_line(&record)
+            }
+            ExportFormat::NdJson => {
+                let mut line = serde_json::to_vec(&ExportRecord {
+                    id: self.id,
+                    email: &self.email,
+                    name: &self.name,
+                    status: &self.status,
+                    subscribed_at: self.subscribed_at.to_rfc3339(),
+                    confirmed_at: self.confirmed_at.map(|t| t.to_rfc3339()),
+                    tags: &self.tags,
+                    custom_fields: self.custom_fields(),
+                })?;
+                line.push(b'\n');
+                Ok(line)
+            }
+        }
+    }
+}
+
+fn csv_line<I, T>(record: I) -> Result<Vec<u8>, anyhow::Error>
+where
+    I: IntoIterator<Item = T>,
+    T: AsRef<[u8]>,
+{
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(record)?;
+    Ok(writer.into_inner().map_err(|e| e.into_error())?)
+}
+
+// the export is streamed: a background task reads rows from Postgres as the
+// client consumes them, so memory use doesn't grow with the subscriber list
+#[tracing::instrument(name = "Export subscribers", skip(parameters, pool))]
+pub async fn export_subscribers(
+    parameters: web::Query<ExportParameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let parameters = parameters.into_inner();
+    let format = match parameters.format.as_deref().unwrap_or("csv") {
+        "csv" => ExportFormat::Csv,
+        "ndjson" => ExportFormat::NdJson,
+        other => return Err(e400(format!("{} is not a supported export format.", other))),
+    };
+    let status = parameters.status.filter(|s| !s.trim().is_empty());
+    let field_definitions = list_field_definitions(&pool).await.map_err(e500)?;
+    let segment_filter = match parameters.segment_id.as_deref().filter(|s| !s.is_empty()) {
+        Some(segment_id) => {
+            let segment_id = Uuid::parse_str(segment_id).map_err(e400)?;
+            let segment = get_segment(&pool, segment_id)
+                .await
+                .map_err(e500)?
+                .ok_or_else(|| e400("The selected segment does not exist."))?;
+            Some(segment.filter(&field_definitions).map_err(e500)?)
+        }
+        None => None,
+    };
+    let field_names: Vec<String> = field_definitions.into_iter().map(|d| d.name).collect();
+
+    let (sender, receiver) = mpsc::channel(BUFFERED_CHUNKS);
+    tokio::spawn(
+        stream_rows(
+            pool.get_ref().clone(),
+            format,
+            status,
+            segment_filter,
+            field_names,
+            sender,
+        )
+        .in_current_span(),
+    );
+    let body = stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|chunk| (chunk, receiver))
+    });
+
+    let (content_type, extension) = match format {
+        ExportFormat::Csv => ("text/csv; charset=utf-8", "csv"),
+        ExportFormat::NdJson => ("application/x-ndjson", "ndjson"),
+    };
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType(content_type.parse().unwrap()))
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(format!(
+                "subscribers-{}.{}",
+                Utc::now().format("%Y-%m-%d"),
+                extension
+            ))],
+        })
+        .streaming(body))
+}
+
+async fn stream_rows(
+    pool: PgPool,
+    format: ExportFormat,
+    status: Option<String>,
+    segment_filter: Option<SegmentFilter>,
+    field_names: Vec<String>,
+    sender: mpsc::Sender<Result<web::Bytes, anyhow::Error>>,
+) {
+    if let Err(e) = send_rows(&pool, format, status, segment_filter, &field_names, &sender).await {
+        tracing::error!(error.cause_chain = ?e, "Failed to export subscribers");
+        // surfacing the error aborts the response, so the client can't mistake it for a full export
+        let _ = sender.send(Err(e)).await;
+    }
+}
+
+async fn send_rows(
+    pool: &PgPool,
+    format: ExportFormat,
+    status: Option<String>,
+    segment_filter: Option<SegmentFilter>,
+    field_names: &[String],
+    sender: &mpsc::Sender<Result<web::Bytes, anyhow::Error>>,
+) -> Result<(), anyhow::Error> {
+    if let ExportFormat::Csv = format {
+        let header = csv_line(
+            CSV_COLUMNS
+                .iter()
+                .copied()
+                .chain(field_names.iter().map(String::as_str)),
+        )?;
+        if sender.send(Ok(header.into())).await.is_err() {
+            return Ok(());
+        }
+    }
+
+    let mut builder = QueryBuilder::<Postgres>::new(
+        r#"
+        SELECT s.id, s.email, s.name, s.status, s.subscribed_at, s.confirmed_at,
+            ARRAY(
+                SELECT t.tag FROM subscriber_tags t
+                WHERE t.subscriber_id = s.id ORDER BY t.tag
+            ) AS tags,
+            ARRAY(
+                SELECT f.field_name FROM subscriber_field_values f
+                WHERE f.subscriber_id = s.id ORDER BY f.field_name
+            ) AS field_names,
+            ARRAY(
+                SELECT f.value FROM subscriber_field_values f
+                WHERE f.subscriber_id = s.id ORDER BY f.field_name
+            ) AS field_values
+        FROM subscriptions s
+        WHERE TRUE"#,
+    );
+    if let Some(status) = status {
+        builder.push(" AND s.status = ").push_bind(status);
+    }
+    if let Some(filter) = &segment_filter {
+        builder.push(" AND ");
+        push_segment_filter(&mut builder, filter);
+    }
+    builder.push(" ORDER BY s.subscribed_at, s.id");
+
+    let mut rows = builder.build_query_as::<ExportRow>().fetch(pool);
+    while let Some(row) = rows
+        .try_next()
+        .await
+        .context("Failed to fetch subscribers to export")?
+    {
+        let line = row.serialize(format, field_names)?;
+        // the client went away, stop reading
+        if sender.send(Ok(line.into())).await.is_err() {
+            return Ok(());
+        }
+    }
+    Ok(())
+}
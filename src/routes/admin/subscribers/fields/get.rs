@@ -0,0 +1,112 @@
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use htmlescape::{encode_attribute, encode_minimal};
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::authentication::CsrfToken;
+use crate::custom_fields::{get_field_values, list_field_definitions};
+use crate::domain::CustomFieldType;
+use crate::utils::e500;
+
+pub async fn subscriber_fields_form(
+    flash_messages: IncomingFlashMessages,
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    csrf: CsrfToken,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_field = csrf.form_field();
+    let subscriber_id = subscriber_id.into_inner();
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap()
+    }
+
+    let subscriber = sqlx::query!(
+        r#"SELECT email FROM subscriptions WHERE id = $1"#,
+        subscriber_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to retrieve the subscriber")
+    .map_err(e500)?;
+    let Some(subscriber) = subscriber else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    let definitions = list_field_definitions(&pool).await.map_err(e500)?;
+    let values = get_field_values(&pool, subscriber_id)
+        .await
+        .context("Failed to retrieve the subscriber's custom fields")
+        .map_err(e500)?;
+
+    let mut inputs_html = String::new();
+    for definition in definitions {
+        let current = values
+            .iter()
+            .find(|v| v.name == definition.name)
+            .map(|v| v.value.as_str())
+            .unwrap_or_default();
+        let input = match &definition.field_type {
+            CustomFieldType::Text | CustomFieldType::Number => format!(
+                r#"<input type="text" name="{}" value="{}">"#,
+                definition.name,
+                encode_attribute(current)
+            ),
+            CustomFieldType::Boolean => select_html(&definition.name, &["true", "false"], current),
+            CustomFieldType::Enum(options) => {
+                let options: Vec<&str> = options.iter().map(String::as_str).collect();
+                select_html(&definition.name, &options, current)
+            }
+        };
+        writeln!(
+            inputs_html,
+            "<label>{} {}</label><br>",
+            encode_minimal(&definition.label),
+            input
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Subscriber fields</title>
+            </head>
+            <body>
+                {msg_html}
+                <p>Custom fields for {email}</p>
+                <form action="/admin/subscribers/{subscriber_id}/fields" method="post">
+                    {csrf_field}
+                    {inputs_html}
+                    <button type="submit">Save fields</button>
+                </form>
+                <p><a href="/admin/subscribers/{subscriber_id}">&lt;- Back</a></p>
+            </body>
+            </html>"#,
+            email = encode_minimal(&subscriber.email),
+        )))
+}
+
+// an empty option lets the admin clear the value
+fn select_html(name: &str, options: &[&str], current: &str) -> String {
+    let mut html = format!(r#"<select name="{name}"><option value=""></option>"#);
+    for option in options {
+        let selected = if *option == current { " selected" } else { "" };
+        write!(
+            html,
+            r#"<option value="{0}"{1}>{0}</option>"#,
+            encode_attribute(option),
+            selected
+        )
+        .unwrap();
+    }
+    html.push_str("</select>");
+    html
+}
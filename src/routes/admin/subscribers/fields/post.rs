@@ -0,0 +1,61 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::custom_fields::{delete_field_value, list_field_definitions, store_field_values};
+use crate::utils::{e500, see_other};
+
+#[tracing::instrument(name = "Update subscriber custom fields", skip(form, pool))]
+pub async fn update_subscriber_fields(
+    form: web::Form<HashMap<String, String>>,
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id.into_inner();
+    let location = format!("/admin/subscribers/{subscriber_id}/fields");
+    let subscriber = sqlx::query!(
+        r#"SELECT id FROM subscriptions WHERE id = $1"#,
+        subscriber_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to retrieve the subscriber")
+    .map_err(e500)?;
+    if subscriber.is_none() {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    let definitions = list_field_definitions(&pool).await.map_err(e500)?;
+
+    // validate everything before writing anything
+    let mut values = Vec::new();
+    let mut cleared = Vec::new();
+    for definition in &definitions {
+        match form.get(&definition.name).map(|v| v.trim()) {
+            None => {}
+            Some("") => cleared.push(definition.name.as_str()),
+            Some(raw) => match definition.parse_value(raw) {
+                Ok(value) => values.push(value),
+                Err(e) => {
+                    FlashMessage::error(e).send();
+                    return Ok(see_other(&location));
+                }
+            },
+        }
+    }
+
+    store_field_values(pool.get_ref(), subscriber_id, &values)
+        .await
+        .context("Failed to store custom field values")
+        .map_err(e500)?;
+    for field_name in cleared {
+        delete_field_value(&pool, subscriber_id, field_name)
+            .await
+            .context("Failed to clear a custom field value")
+            .map_err(e500)?;
+    }
+    FlashMessage::info("The subscriber's fields have been updated.").send();
+    Ok(see_other(&location))
+}
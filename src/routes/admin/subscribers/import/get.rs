@@ -0,0 +1,54 @@
+use actix_web::{HttpResponse, http::header::ContentType};
+use actix_web_flash_messages::IncomingFlashMessages;
+use std::fmt::Write;
+
+use crate::authentication::CsrfToken;
+
+pub async fn import_subscribers_form(
+    flash_messages: IncomingFlashMessages,
+    csrf: CsrfToken,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_field = csrf.form_field();
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap()
+    }
+
+    // `mode` has to come before `file` in the form, the upload is processed as it streams in
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Import subscribers</title>
+            </head>
+            <body>
+                {msg_html}
+                <p>
+                    Upload a CSV file with a header row. The <code>email</code> and
+                    <code>name</code> columns are required, <code>tags</code> takes a
+                    comma-separated list and any other column is matched against the
+                    custom subscriber fields by name.
+                </p>
+                <form action="/admin/subscribers/import" method="post" enctype="multipart/form-data">
+                    {csrf_field}
+                    <label>Imported subscribers
+                        <select name="mode">
+                            <option value="send_confirmation">Send them a confirmation email</option>
+                            <option value="confirmed">Mark them as confirmed</option>
+                        </select>
+                    </label>
+                    <br>
+                    <label>CSV file
+                        <input type="file" name="file" accept=".csv,text/csv">
+                    </label>
+                    <br>
+                    <button type="submit">Import</button>
+                </form>
+                <p><a href="/admin/subscribers">&lt;- Back</a></p>
+            </body>
+            </html>"#
+        )))
+}
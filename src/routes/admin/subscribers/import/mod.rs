@@ -0,0 +1,6 @@
+mod get;
+mod post;
+mod records;
+
+pub use get::import_subscribers_form;
+pub use post::import_subscribers;
@@ -0,0 +1,407 @@
+use actix_multipart::{Field, Multipart};
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use anyhow::Context;
+use futures_util::TryStreamExt;
+use htmlescape::encode_minimal;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use uuid::Uuid;
+
+use super::records::RecordParser;
+use crate::custom_fields::{list_field_definitions, store_field_values};
+use crate::domain::{CustomFieldDefinition, NewSubscriber};
+use crate::email_queue::enqueue_emails;
+use crate::routes::{FormData, confirmation_email, generate_subscription_token};
+use crate::segmentation::add_tags;
+use crate::startup::ApplicationBaseUrl;
+use crate::suppression::email_hash;
+use crate::utils::{e400, e500};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ImportMode {
+    Confirmed,
+    SendConfirmation,
+}
+
+impl ImportMode {
+    fn parse(s: &str) -> Result<ImportMode, String> {
+        match s.trim() {
+            "confirmed" => Ok(Self::Confirmed),
+            "send_confirmation" => Ok(Self::SendConfirmation),
+            other => Err(format!("{} is not a valid import mode.", other)),
+        }
+    }
+}
+
+#[derive(Default)]
+struct ImportReport {
+    imported: usize,
+    duplicates: usize,
+    suppressed: usize,
+    // (row number, reason), counting the header as row 1
+    errors: Vec<(usize, String)>,
+}
+
+// rows are written this many at a time, each batch in its own transaction
+const BATCH_SIZE: usize = 500;
+
+// validates records as the parser hands them over and stores them in batches
+struct Importer<'a> {
+    pool: &'a PgPool,
+    base_url: &'a str,
+    definitions: Vec<CustomFieldDefinition>,
+    mode: ImportMode,
+    columns: Option<Vec<String>>,
+    row: usize,
+    // (row number, subscriber) waiting for the next batch
+    pending: Vec<(usize, NewSubscriber)>,
+    pending_emails: HashSet<String>,
+    report: ImportReport,
+}
+
+impl Importer<'_> {
+    // only fails on a malformed header, everything after that ends up in the report
+    async fn import_record(&mut self, record: Vec<String>) -> Result<(), anyhow::Error> {
+        self.row += 1;
+        let Some(columns) = &self.columns else {
+            let columns: Vec<String> = record.iter().map(|c| c.trim().to_lowercase()).collect();
+            if !columns.iter().any(|c| c == "email") || !columns.iter().any(|c| c == "name") {
+                anyhow::bail!("The header row must contain an `email` and a `name` column.");
+            }
+            self.columns = Some(columns);
+            return Ok(());
+        };
+        if record.iter().all(|v| v.trim().is_empty()) {
+            return Ok(());
+        }
+
+        let mut values: HashMap<String, String> = columns.iter().cloned().zip(record).collect();
+        let form = FormData {
+            email: values.remove("email").unwrap_or_default(),
+            name: values.remove("name").unwrap_or_default(),
+            tags: values.remove("tags"),
+            custom_fields: values
+                .into_iter()
+                .map(|(column, value)| (column, serde_json::Value::String(value)))
+                .collect(),
+        };
+        let new_subscriber: NewSubscriber = match (form, self.definitions.as_slice()).try_into() {
+            Ok(new_subscriber) => new_subscriber,
+            Err(e) => {
+                self.report.errors.push((self.row, e));
+                return Ok(());
+            }
+        };
+        // uniqueness is case-insensitive, the database catches duplicates across batches
+        if !self
+            .pending_emails
+            .insert(new_subscriber.email.as_ref().to_lowercase())
+        {
+            self.report.duplicates += 1;
+            return Ok(());
+        }
+        self.pending.push((self.row, new_subscriber));
+        if self.pending.len() >= BATCH_SIZE {
+            self.flush().await;
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self) {
+        let batch = std::mem::take(&mut self.pending);
+        self.pending_emails.clear();
+        if batch.is_empty() {
+            return;
+        }
+        match store_batch(self.pool, &batch, self.mode, self.base_url).await {
+            Ok((imported, suppressed)) => {
+                self.report.imported += imported;
+                self.report.suppressed += suppressed;
+                self.report.duplicates += batch.len() - imported - suppressed;
+            }
+            // the batch was rolled back, earlier batches stay imported
+            Err(e) => {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "Failed to store a batch of imported subscribers"
+                );
+                self.report.errors.extend(batch.into_iter().map(|(row, _)| {
+                    (
+                        row,
+                        "Not imported because of a database error, please upload it again."
+                            .to_string(),
+                    )
+                }));
+            }
+        }
+    }
+}
+
+// stores the subscribers that aren't already there or suppressed, with their tags,
+// custom fields and, when asked for, a confirmation token and queued confirmation email.
+// returns how many were new and how many were suppressed
+#[tracing::instrument(
+    name = "Saving a batch of imported subscribers in the database",
+    skip_all,
+    fields(batch_size = batch.len())
+)]
+async fn store_batch(
+    pool: &PgPool,
+    batch: &[(usize, NewSubscriber)],
+    mode: ImportMode,
+    base_url: &str,
+) -> Result<(usize, usize), anyhow::Error> {
+    let ids: Vec<Uuid> = batch.iter().map(|_| Uuid::new_v4()).collect();
+    let emails: Vec<String> = batch
+        .iter()
+        .map(|(_, s)| s.email.as_ref().to_owned())
+        .collect();
+    let names: Vec<String> = batch
+        .iter()
+        .map(|(_, s)| s.name.as_ref().to_owned())
+        .collect();
+    let email_hashes: Vec<String> = emails.iter().map(|e| email_hash(e)).collect();
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let suppressed = count_suppressed(&mut transaction, &email_hashes)
+        .await
+        .context("Failed to check imported subscribers against the suppression list.")?;
+    let inserted: HashSet<Uuid> = insert_imported_subscribers(
+        &mut transaction,
+        &ids,
+        &emails,
+        &names,
+        &email_hashes,
+        mode == ImportMode::Confirmed,
+    )
+    .await
+    .context("Failed to insert imported subscribers in the database.")?
+    .into_iter()
+    .collect();
+
+    let mut confirmation_emails = Vec::new();
+    let mut token_values = Vec::new();
+    let mut token_subscribers = Vec::new();
+    for (id, (_, subscriber)) in ids.iter().zip(batch) {
+        if !inserted.contains(id) {
+            continue;
+        }
+        add_tags(&mut *transaction, *id, &subscriber.tags)
+            .await
+            .context("Failed to store the tags of an imported subscriber.")?;
+        store_field_values(&mut *transaction, *id, &subscriber.custom_fields)
+            .await
+            .context("Failed to store the custom fields of an imported subscriber.")?;
+        if mode == ImportMode::SendConfirmation {
+            let token = generate_subscription_token();
+            confirmation_emails.push(confirmation_email(&subscriber.email, base_url, &token));
+            token_values.push(token);
+            token_subscribers.push(*id);
+        }
+    }
+    sqlx::query!(
+        r#"
+        INSERT INTO subscription_tokens (subscription_token, subscriber_id, created_at)
+        SELECT token, subscriber_id, now()
+        FROM UNNEST($1::text[], $2::uuid[]) AS t(token, subscriber_id)
+        "#,
+        &token_values,
+        &token_subscribers,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to store the confirmation tokens of imported subscribers.")?;
+    enqueue_emails(&mut *transaction, &confirmation_emails)
+        .await
+        .context("Failed to queue confirmation emails for imported subscribers.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to store imported subscribers.")?;
+    Ok((inserted.len(), suppressed))
+}
+
+#[tracing::instrument(
+    name = "Import subscribers from a CSV upload",
+    skip(payload, pool, base_url)
+)]
+pub async fn import_subscribers(
+    mut payload: Multipart,
+    pool: web::Data<PgPool>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let definitions = list_field_definitions(&pool).await.map_err(e500)?;
+    let mut mode = None;
+    let mut report = None;
+    while let Some(mut field) = payload.try_next().await.map_err(e400)? {
+        match field.name() {
+            Some("mode") => {
+                let value = read_text(&mut field).await?;
+                mode = Some(ImportMode::parse(&value).map_err(e400)?);
+            }
+            Some("file") => {
+                let mode =
+                    mode.ok_or_else(|| e400("The import mode must be sent before the file."))?;
+                let mut importer = Importer {
+                    pool: &pool,
+                    base_url: &base_url.0,
+                    definitions: definitions.clone(),
+                    mode,
+                    columns: None,
+                    row: 0,
+                    pending: Vec::new(),
+                    pending_emails: HashSet::new(),
+                    report: ImportReport::default(),
+                };
+                import_file(&mut field, &mut importer).await?;
+                report = Some(importer.report);
+            }
+            _ => while field.try_next().await.map_err(e400)?.is_some() {},
+        }
+    }
+    let report = report.ok_or_else(|| e400("No CSV file was uploaded."))?;
+    tracing::info!(
+        imported = report.imported,
+        duplicates = report.duplicates,
+        suppressed = report.suppressed,
+        errors = report.errors.len(),
+        "Finished importing subscribers"
+    );
+    Ok(report_page(&report))
+}
+
+async fn import_file(
+    field: &mut Field,
+    importer: &mut Importer<'_>,
+) -> Result<(), actix_web::Error> {
+    let mut parser = RecordParser::new();
+    let mut records = Vec::new();
+    while let Some(chunk) = field.try_next().await.map_err(e400)? {
+        parser.feed(&chunk, &mut records).map_err(e400)?;
+        for record in records.drain(..) {
+            import_record(importer, record).await?;
+        }
+    }
+    parser.finish(&mut records).map_err(e400)?;
+    for record in records {
+        import_record(importer, record).await?;
+    }
+    if importer.columns.is_none() {
+        return Err(e400("The uploaded file is empty."));
+    }
+    importer.flush().await;
+    Ok(())
+}
+
+async fn import_record(
+    importer: &mut Importer<'_>,
+    record: Vec<String>,
+) -> Result<(), actix_web::Error> {
+    importer.import_record(record).await.map_err(e400)
+}
+
+async fn read_text(field: &mut Field) -> Result<String, actix_web::Error> {
+    let mut bytes = Vec::new();
+    while let Some(chunk) = field.try_next().await.map_err(e400)? {
+        if bytes.len() + chunk.len() > 1024 {
+            return Err(e400("A form value is too long."));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    String::from_utf8(bytes).map_err(e400)
+}
+
+fn report_page(report: &ImportReport) -> HttpResponse {
+    let mut errors_html = String::new();
+    for (row, error) in &report.errors {
+        writeln!(
+            errors_html,
+            "<tr><td>{}</td><td>{}</td></tr>",
+            row,
+            encode_minimal(error)
+        )
+        .unwrap();
+    }
+    let imported = report.imported;
+    let duplicates = report.duplicates;
+    let suppressed = report.suppressed;
+    let failed = report.errors.len();
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Import results</title>
+            </head>
+            <body>
+                <p>Imported {imported} subscribers.</p>
+                <p>Skipped {duplicates} rows with an email that is already subscribed.</p>
+                <p>Skipped {suppressed} rows with an email on the suppression list.</p>
+                <p>{failed} rows had errors.</p>
+                <table>
+                    <tr><th>Row</th><th>Error</th></tr>
+                    {errors_html}
+                </table>
+                <p><a href="/admin/subscribers/import">Import another file</a></p>
+                <p><a href="/admin/subscribers">&lt;- Back</a></p>
+            </body>
+            </html>"#
+        ))
+}
+
+// existing emails are left untouched, the caller counts them as duplicates.
+// suppressed emails aren't inserted either, so they can't be emailed or re-confirmed.
+// returns the ids of the subscribers that were inserted
+async fn insert_imported_subscribers(
+    transaction: &mut Transaction<'_, Postgres>,
+    ids: &[Uuid],
+    emails: &[String],
+    names: &[String],
+    email_hashes: &[String],
+    confirmed: bool,
+) -> Result<Vec<Uuid>, sqlx::Error> {
+    let records = sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status, confirmed_at)
+        SELECT
+            id, email, name, now(),
+            CASE WHEN $4 THEN 'confirmed' ELSE 'pending_confirmation' END,
+            CASE WHEN $4 THEN now() END
+        FROM UNNEST($1::uuid[], $2::text[], $3::text[], $5::text[])
+            AS s(id, email, name, email_hash)
+        WHERE NOT EXISTS (
+            SELECT 1 FROM email_suppressions e WHERE e.email_hash = s.email_hash
+        )
+        ON CONFLICT DO NOTHING
+        RETURNING id
+        "#,
+        ids,
+        emails,
+        names,
+        confirmed,
+        email_hashes
+    )
+    .fetch_all(&mut **transaction)
+    .await?;
+    Ok(records.into_iter().map(|r| r.id).collect())
+}
+
+async fn count_suppressed(
+    transaction: &mut Transaction<'_, Postgres>,
+    email_hashes: &[String],
+) -> Result<usize, sqlx::Error> {
+    let record = sqlx::query!(
+        r#"SELECT count(*) AS "count!" FROM email_suppressions WHERE email_hash = ANY($1)"#,
+        email_hashes
+    )
+    .fetch_one(&mut **transaction)
+    .await?;
+    Ok(record.count as usize)
+}
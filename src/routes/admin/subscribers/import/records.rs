@@ -0,0 +1,140 @@
+use csv_core::{ReadRecordResult, Reader};
+
+// a single record is never allowed to grow past this, however the input is chunked
+const MAX_RECORD_BYTES: usize = 64 * 1024;
+
+// incremental CSV parsing over an upload that arrives in arbitrary chunks,
+// so we never hold more than one record in memory
+pub struct RecordParser {
+    reader: Reader,
+    output: Vec<u8>,
+    output_len: usize,
+    ends: Vec<usize>,
+    ends_len: usize,
+}
+
+impl RecordParser {
+    pub fn new() -> Self {
+        Self {
+            reader: Reader::new(),
+            output: vec![0; 1024],
+            output_len: 0,
+            ends: vec![0; 16],
+            ends_len: 0,
+        }
+    }
+
+    // parse as many complete records as `input` finishes, appending them to `records`
+    pub fn feed(&mut self, mut input: &[u8], records: &mut Vec<Vec<String>>) -> Result<(), String> {
+        loop {
+            let (result, n_in, n_out, n_ends) = self.reader.read_record(
+                input,
+                &mut self.output[self.output_len..],
+                &mut self.ends[self.ends_len..],
+            );
+            input = &input[n_in..];
+            self.output_len += n_out;
+            self.ends_len += n_ends;
+            match result {
+                ReadRecordResult::InputEmpty | ReadRecordResult::End => return Ok(()),
+                ReadRecordResult::OutputFull => {
+                    if self.output.len() >= MAX_RECORD_BYTES {
+                        return Err(format!(
+                            "A CSV record is longer than {} bytes.",
+                            MAX_RECORD_BYTES
+                        ));
+                    }
+                    self.output.resize(self.output.len() * 2, 0);
+                }
+                ReadRecordResult::OutputEndsFull => {
+                    // empty fields add nothing to the output, only to the ends,
+                    // and every field took at least a byte of input
+                    if self.ends.len() >= MAX_RECORD_BYTES {
+                        return Err(format!(
+                            "A CSV record has more than {} fields.",
+                            MAX_RECORD_BYTES
+                        ));
+                    }
+                    self.ends.resize(self.ends.len() * 2, 0);
+                }
+                ReadRecordResult::Record => {
+                    records.push(self.take_record());
+                }
+            }
+        }
+    }
+
+    // flush the final record if the upload doesn't end with a newline
+    pub fn finish(&mut self, records: &mut Vec<Vec<String>>) -> Result<(), String> {
+        self.feed(&[], records)
+    }
+
+    fn take_record(&mut self) -> Vec<String> {
+        let mut start = 0;
+        let fields = self.ends[..self.ends_len]
+            .iter()
+            .map(|&end| {
+                let field = String::from_utf8_lossy(&self.output[start..end]).into_owned();
+                start = end;
+                field
+            })
+            .collect();
+        self.output_len = 0;
+        self.ends_len = 0;
+        fields
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RecordParser;
+
+    fn parse_in_chunks(input: &str, chunk_size: usize) -> Vec<Vec<String>> {
+        let mut parser = RecordParser::new();
+        let mut records = Vec::new();
+        for chunk in input.as_bytes().chunks(chunk_size) {
+            parser.feed(chunk, &mut records).unwrap();
+        }
+        parser.finish(&mut records).unwrap();
+        records
+    }
+
+    #[test]
+    fn records_split_across_chunks_are_reassembled() {
+        let input = "email,name\nursula@example.com,\"Le Guin, Ursula\"\nged@example.com,Ged";
+        for chunk_size in [1, 3, 7, 1024] {
+            assert_eq!(
+                parse_in_chunks(input, chunk_size),
+                vec![
+                    vec!["email", "name"],
+                    vec!["ursula@example.com", "Le Guin, Ursula"],
+                    vec!["ged@example.com", "Ged"],
+                ],
+                "chunk size {}",
+                chunk_size
+            );
+        }
+    }
+
+    #[test]
+    fn quoted_newlines_stay_inside_their_field() {
+        let records = parse_in_chunks("a,\"multi\nline\"\r\nb,c\r\n", 4);
+        assert_eq!(records, vec![vec!["a", "multi\nline"], vec!["b", "c"]]);
+    }
+
+    #[test]
+    fn oversized_records_are_rejected() {
+        let mut parser = RecordParser::new();
+        let mut records = Vec::new();
+        let huge = "a".repeat(200 * 1024);
+        assert!(parser.feed(huge.as_bytes(), &mut records).is_err());
+    }
+
+    #[test]
+    fn records_with_too_many_fields_are_rejected() {
+        let mut parser = RecordParser::new();
+        let mut records = Vec::new();
+        let empty_fields = ",".repeat(200 * 1024);
+        assert!(parser.feed(empty_fields.as_bytes(), &mut records).is_err());
+    }
+}
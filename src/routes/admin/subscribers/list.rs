@@ -0,0 +1,270 @@
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use htmlescape::{encode_attribute, encode_minimal};
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::segmentation::list_segments;
+use crate::utils::{e400, e500};
+
+pub const PAGE_SIZE: i64 = 25;
+const STATUSES: [&str; 6] = [
+    "pending_confirmation",
+    "confirmed",
+    "unsubscribed",
+    "bounced",
+    "complained",
+    "erased",
+];
+
+// every parameter is optional, and the filter form submits empty strings for unset ones
+#[derive(serde::Deserialize, Default, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListParameters {
+    pub page: Option<i64>,
+    status: Option<String>,
+    subscribed_after: Option<String>,
+    subscribed_before: Option<String>,
+    q: Option<String>,
+}
+
+pub struct SubscriberFilter {
+    status: Option<String>,
+    subscribed_after: Option<DateTime<Utc>>,
+    subscribed_before: Option<DateTime<Utc>>,
+    search: Option<String>,
+}
+
+#[derive(sqlx::FromRow)]
+pub struct SubscriberRow {
+    pub id: Uuid,
+    pub email: String,
+    pub name: String,
+    pub status: String,
+    pub subscribed_at: DateTime<Utc>,
+}
+
+fn non_empty(s: &Option<String>) -> Option<&str> {
+    s.as_deref().map(str::trim).filter(|s| !s.is_empty())
+}
+
+fn parse_day(s: &str) -> Result<DateTime<Utc>, String> {
+    let date = NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .map_err(|e| format!("{} is not a valid date: {}", s, e))?;
+    Ok(Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap()))
+}
+
+impl TryFrom<&ListParameters> for SubscriberFilter {
+    type Error = String;
+
+    fn try_from(p: &ListParameters) -> Result<Self, Self::Error> {
+        Ok(Self {
+            status: non_empty(&p.status).map(str::to_owned),
+            subscribed_after: non_empty(&p.subscribed_after).map(parse_day).transpose()?,
+            subscribed_before: non_empty(&p.subscribed_before).map(parse_day).transpose()?,
+            search: non_empty(&p.q).map(str::to_owned),
+        })
+    }
+}
+
+impl SubscriberFilter {
+    fn push_where(&self, builder: &mut QueryBuilder<'_, Postgres>) {
+        builder.push(" WHERE TRUE");
+        if let Some(status) = &self.status {
+            builder.push(" AND s.status = ").push_bind(status.clone());
+        }
+        if let Some(after) = self.subscribed_after {
+            builder.push(" AND s.subscribed_at >= ").push_bind(after);
+        }
+        if let Some(before) = self.subscribed_before {
+            builder.push(" AND s.subscribed_at < ").push_bind(before);
+        }
+        if let Some(search) = &self.search {
+            // match the search literally, not as a LIKE pattern
+            let escaped = search
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_");
+            let pattern = format!("%{}%", escaped);
+            builder
+                .push(" AND (s.email ILIKE ")
+                .push_bind(pattern.clone())
+                .push(" OR s.name ILIKE ")
+                .push_bind(pattern)
+                .push(")");
+        }
+    }
+}
+
+// one page of the subscribers matching the filter, along with how many match in total
+pub async fn search_subscribers(
+    pool: &PgPool,
+    filter: &SubscriberFilter,
+    page: i64,
+) -> Result<(i64, Vec<SubscriberRow>), sqlx::Error> {
+    let mut count_query = QueryBuilder::<Postgres>::new("SELECT COUNT(*) FROM subscriptions s");
+    filter.push_where(&mut count_query);
+    let (total,): (i64,) = count_query.build_query_as().fetch_one(pool).await?;
+
+    let mut list_query = QueryBuilder::<Postgres>::new(
+        "SELECT s.id, s.email, s.name, s.status, s.subscribed_at FROM subscriptions s",
+    );
+    filter.push_where(&mut list_query);
+    list_query
+        .push(" ORDER BY s.subscribed_at DESC, s.id LIMIT ")
+        .push_bind(PAGE_SIZE)
+        .push(" OFFSET ")
+        .push_bind((page - 1) * PAGE_SIZE);
+    let subscribers = list_query.build_query_as().fetch_all(pool).await?;
+    Ok((total, subscribers))
+}
+
+#[tracing::instrument(name = "List subscribers", skip_all)]
+pub async fn list_subscribers(
+    flash_messages: IncomingFlashMessages,
+    parameters: web::Query<ListParameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap()
+    }
+
+    let filter = SubscriberFilter::try_from(&parameters.0).map_err(e400)?;
+    let page = parameters.page.unwrap_or(1).max(1);
+    let (total, subscribers) = search_subscribers(&pool, &filter, page)
+        .await
+        .map_err(e500)?;
+
+    let mut rows_html = String::new();
+    for s in subscribers {
+        writeln!(
+            rows_html,
+            r#"<tr><td><a href="/admin/subscribers/{}">{}</a></td><td>{}</td><td>{}</td><td>{}</td></tr>"#,
+            s.id,
+            encode_minimal(&s.email),
+            encode_minimal(&s.name),
+            s.status,
+            s.subscribed_at.format("%Y-%m-%d %H:%M"),
+        )
+        .unwrap();
+    }
+
+    let mut status_options = String::from(r#"<option value="">Any status</option>"#);
+    for status in STATUSES {
+        let selected = if non_empty(&parameters.status) == Some(status) {
+            " selected"
+        } else {
+            ""
+        };
+        write!(
+            status_options,
+            r#"<option value="{status}"{selected}>{status}</option>"#
+        )
+        .unwrap();
+    }
+
+    let mut segment_options = String::from(r#"<option value="">All subscribers</option>"#);
+    for segment in list_segments(&pool).await.map_err(e500)? {
+        write!(
+            segment_options,
+            r#"<option value="{}">{}</option>"#,
+            segment.segment_id,
+            encode_minimal(&segment.name)
+        )
+        .unwrap();
+    }
+
+    let last_page = ((total + PAGE_SIZE - 1) / PAGE_SIZE).max(1);
+    let mut pagination_html = format!("Page {page} of {last_page} ({total} subscribers)");
+    if page > 1 {
+        write!(
+            pagination_html,
+            r#" <a href="{}">Previous</a>"#,
+            page_link(&parameters, page - 1)
+        )
+        .unwrap();
+    }
+    if page < last_page {
+        write!(
+            pagination_html,
+            r#" <a href="{}">Next</a>"#,
+            page_link(&parameters, page + 1)
+        )
+        .unwrap();
+    }
+
+    let value = |s: &Option<String>| encode_attribute(non_empty(s).unwrap_or_default());
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Subscribers</title>
+            </head>
+            <body>
+                {msg_html}
+                <form action="/admin/subscribers" method="get">
+                    <label>Search
+                        <input type="text" name="q" placeholder="Email or name" value="{q}">
+                    </label>
+                    <label>Status
+                        <select name="status">{status_options}</select>
+                    </label>
+                    <label>Subscribed on or after
+                        <input type="date" name="subscribed_after" value="{after}">
+                    </label>
+                    <label>Subscribed before
+                        <input type="date" name="subscribed_before" value="{before}">
+                    </label>
+                    <button type="submit">Filter</button>
+                </form>
+                <table>
+                    <tr><th>Email</th><th>Name</th><th>Status</th><th>Subscribed at</th></tr>
+                    {rows_html}
+                </table>
+                <p>{pagination_html}</p>
+                <form action="/admin/subscribers/export" method="get">
+                    <label>Export
+                        <select name="format">
+                            <option value="csv">CSV</option>
+                            <option value="ndjson">Newline-delimited JSON</option>
+                        </select>
+                    </label>
+                    <label>Status
+                        <select name="status">{status_options}</select>
+                    </label>
+                    <label>Segment
+                        <select name="segment_id">{segment_options}</select>
+                    </label>
+                    <button type="submit">Download</button>
+                </form>
+                <p><a href="/admin/subscribers/import">Import subscribers from a CSV file</a></p>
+                <p><a href="/admin/dashboard">&lt;- Back</a></p>
+            </body>
+            </html>"#,
+            q = value(&parameters.q),
+            after = value(&parameters.subscribed_after),
+            before = value(&parameters.subscribed_before),
+        )))
+}
+
+// link to another page of the same listing, keeping the current filters
+fn page_link(parameters: &ListParameters, page: i64) -> String {
+    let mut link = format!("/admin/subscribers?page={page}");
+    for (key, value) in [
+        ("q", &parameters.q),
+        ("status", &parameters.status),
+        ("subscribed_after", &parameters.subscribed_after),
+        ("subscribed_before", &parameters.subscribed_before),
+    ] {
+        if let Some(value) = non_empty(value) {
+            write!(link, "&amp;{}={}", key, urlencoding::encode(value)).unwrap();
+        }
+    }
+    link
+}
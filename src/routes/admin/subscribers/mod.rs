@@ -0,0 +1,20 @@
+mod actions;
+mod data;
+mod detail;
+mod export;
+mod fields;
+mod import;
+mod list;
+
+pub use actions::{
+    confirm_subscriber_manually, delete_subscriber, remove_subscriber, unsubscribe_subscriber,
+};
+pub use data::{download_subscriber_data_as_admin, erase_subscriber_data};
+pub use detail::subscriber_details;
+pub use export::export_subscribers;
+pub use fields::*;
+pub use import::*;
+pub use list::{
+    ListParameters, PAGE_SIZE, SubscriberFilter, SubscriberRow, list_subscribers,
+    search_subscribers,
+};
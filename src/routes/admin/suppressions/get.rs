@@ -0,0 +1,77 @@
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::authentication::CsrfToken;
+use crate::suppression::list_suppressions;
+use crate::utils::e500;
+
+pub async fn suppressions_form(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    csrf: CsrfToken,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_field = csrf.form_field();
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap()
+    }
+
+    let mut suppressions_html = String::new();
+    for suppression in list_suppressions(&pool).await.map_err(e500)? {
+        let email = suppression
+            .email
+            .as_deref()
+            .map(encode_minimal)
+            .unwrap_or_else(|| "<i>erased subscriber</i>".into());
+        writeln!(
+            suppressions_html,
+            r#"<tr><td>{}</td><td>{}</td><td>{}</td><td>
+                <form action="/admin/suppressions/delete" method="post">
+                    {csrf_field}
+                    <input type="hidden" name="email_hash" value="{}">
+                    <button type="submit">Remove</button>
+                </form>
+            </td></tr>"#,
+            email,
+            suppression.reason.as_str(),
+            suppression.created_at.format("%Y-%m-%d %H:%M"),
+            suppression.email_hash,
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Suppression list</title>
+            </head>
+            <body>
+                {msg_html}
+                <p>No email is ever sent to these addresses, even if they subscribe again.</p>
+                <table>
+                    <tr><th>Email</th><th>Reason</th><th>Added at</th><th></th></tr>
+                    {suppressions_html}
+                </table>
+                <form action="/admin/suppressions" method="post">
+                    {csrf_field}
+                    <label>Email
+                        <input
+                            type="text"
+                            placeholder="Enter an email address"
+                            name="email"
+                        >
+                    </label>
+                    <button type="submit">Suppress</button>
+                </form>
+                <p><a href="/admin/dashboard">&lt;- Back</a></p>
+            </body>
+            </html>"#
+        )))
+}
@@ -0,0 +1,55 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::PgPool;
+
+use crate::domain::{SubscriberEmail, SuppressionReason};
+use crate::suppression::{remove_suppression, suppress};
+use crate::utils::{e500, see_other};
+
+#[derive(serde::Deserialize)]
+pub struct AddFormData {
+    email: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct DeleteFormData {
+    email_hash: String,
+}
+
+#[tracing::instrument(name = "Manually suppress an email address", skip(form, pool))]
+pub async fn add_suppression(
+    form: web::Form<AddFormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let email = match SubscriberEmail::parse(form.0.email) {
+        Ok(email) => email,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/suppressions"));
+        }
+    };
+    suppress(pool.get_ref(), email.as_ref(), SuppressionReason::Manual)
+        .await
+        .context("Failed to suppress the email address")
+        .map_err(e500)?;
+    FlashMessage::info("The email address has been suppressed.").send();
+    Ok(see_other("/admin/suppressions"))
+}
+
+#[tracing::instrument(name = "Remove a suppression", skip(form, pool))]
+pub async fn delete_suppression(
+    form: web::Form<DeleteFormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let removed = remove_suppression(&pool, &form.email_hash)
+        .await
+        .context("Failed to remove the suppression")
+        .map_err(e500)?;
+    if removed {
+        FlashMessage::info("The suppression has been removed.").send();
+    } else {
+        FlashMessage::error("That address is not suppressed.").send();
+    }
+    Ok(see_other("/admin/suppressions"))
+}
@@ -0,0 +1,75 @@
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::authentication::CsrfToken;
+use crate::segmentation::tag_counts;
+use crate::utils::e500;
+
+pub async fn tags_form(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    csrf: CsrfToken,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_field = csrf.form_field();
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap()
+    }
+
+    let mut tags_html = String::new();
+    for (tag, count) in tag_counts(&pool).await.map_err(e500)? {
+        writeln!(
+            tags_html,
+            "<tr><td>{}</td><td>{}</td></tr>",
+            encode_minimal(&tag),
+            count
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Subscriber tags</title>
+            </head>
+            <body>
+                {msg_html}
+                <table>
+                    <tr><th>Tag</th><th>Subscribers</th></tr>
+                    {tags_html}
+                </table>
+                <form action="/admin/tags" method="post">
+                    {csrf_field}
+                    <label>Subscriber email
+                        <input
+                            type="text"
+                            placeholder="Enter subscriber email"
+                            name="email"
+                        >
+                    </label>
+                    <br>
+                    <label>Tags
+                        <input
+                            type="text"
+                            placeholder="vip, beta"
+                            name="tags"
+                        >
+                    </label>
+                    <br>
+                    <label><input type="radio" name="action" value="add" checked>Add</label>
+                    <label><input type="radio" name="action" value="remove">Remove</label>
+                    <br>
+                    <button type="submit">Update tags</button>
+                </form>
+                <p><a href="/admin/dashboard">&lt;- Back</a></p>
+            </body>
+            </html>"#
+        )))
+}
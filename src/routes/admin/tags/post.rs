@@ -0,0 +1,83 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::SubscriberTag;
+use crate::segmentation::{add_tags, remove_tag};
+use crate::utils::{e500, see_other};
+
+#[derive(serde::Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum TagAction {
+    Add,
+    Remove,
+}
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    email: String,
+    tags: String,
+    action: TagAction,
+}
+
+#[tracing::instrument(name = "Update subscriber tags", skip(form, pool), fields(subscriber_email = %form.email))]
+pub async fn update_tags(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let tags = match SubscriberTag::parse_list(&form.tags) {
+        Ok(tags) if !tags.is_empty() => tags,
+        Ok(_) => {
+            FlashMessage::error("Enter at least one tag.").send();
+            return Ok(see_other("/admin/tags"));
+        }
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/tags"));
+        }
+    };
+
+    let Some(subscriber_id) = get_subscriber_id_from_email(&pool, form.email.trim())
+        .await
+        .map_err(e500)?
+    else {
+        FlashMessage::error("There is no subscriber with that email address.").send();
+        return Ok(see_other("/admin/tags"));
+    };
+
+    match form.action {
+        TagAction::Add => {
+            add_tags(pool.get_ref(), subscriber_id, &tags)
+                .await
+                .context("Failed to add tags")
+                .map_err(e500)?;
+        }
+        TagAction::Remove => {
+            for tag in &tags {
+                remove_tag(&pool, subscriber_id, tag)
+                    .await
+                    .context("Failed to remove tag")
+                    .map_err(e500)?;
+            }
+        }
+    }
+    FlashMessage::info("The subscriber's tags have been updated.").send();
+    Ok(see_other("/admin/tags"))
+}
+
+#[tracing::instrument(name = "Get subscriber_id from email", skip(pool))]
+async fn get_subscriber_id_from_email(
+    pool: &PgPool,
+    email: &str,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"SELECT id FROM subscriptions WHERE lower(email) = lower($1)"#,
+        email
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look up subscriber by email.")?;
+    Ok(row.map(|r| r.id))
+}
@@ -0,0 +1,97 @@
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use qrcode::{QrCode, render::svg};
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::authentication::{CsrfToken, TwoFactorState, UserId, two_factor_state};
+use crate::utils::e500;
+
+// asks for a current code (or a recovery code) before changing anything
+fn code_form(action: &str, label: &str, csrf_field: &str) -> String {
+    format!(
+        r#"<form action="{action}" method="post">
+            {csrf_field}
+            <label>Code
+                <input type="text" autocomplete="one-time-code" placeholder="Enter code" name="code">
+            </label>
+            <button type="submit">{label}</button>
+        </form>"#
+    )
+}
+
+pub async fn two_factor_form(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    csrf: CsrfToken,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_field = csrf.form_field();
+    let user_id = user_id.into_inner();
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    let content = match two_factor_state(&pool, *user_id).await.map_err(e500)? {
+        TwoFactorState::Disabled => format!(
+            r#"<p>Two-factor authentication is off.</p>
+            <form action="/admin/two-factor/setup" method="post">
+                {csrf_field}
+                <button type="submit">Set up two-factor authentication</button>
+            </form>"#
+        ),
+        TwoFactorState::Pending {
+            secret,
+            otpauth_url,
+        } => {
+            let qr_code = QrCode::new(otpauth_url.as_bytes())
+                .map_err(e500)?
+                .render::<svg::Color>()
+                .min_dimensions(200, 200)
+                .build();
+            format!(
+                r#"<p>Scan this code with your authenticator app,
+                or enter the key <code>{secret}</code> by hand.</p>
+                {qr_code}
+                <p>Then enter the code it shows to finish.</p>
+                {confirm_form}
+                <form action="/admin/two-factor/disable" method="post">
+                    {csrf_field}
+                    <button type="submit">Cancel</button>
+                </form>"#,
+                confirm_form = code_form("/admin/two-factor/confirm", "Turn on", &csrf_field),
+            )
+        }
+        TwoFactorState::Enabled {
+            recovery_codes_left,
+        } => format!(
+            r#"<p>Two-factor authentication is on.
+            You have {recovery_codes_left} unused recovery codes.</p>
+            <p>Get a new set of recovery codes:</p>
+            {regenerate_form}
+            <p>Turn two-factor authentication off:</p>
+            {disable_form}"#,
+            regenerate_form =
+                code_form("/admin/two-factor/recovery-codes", "New codes", &csrf_field),
+            disable_form = code_form("/admin/two-factor/disable", "Turn off", &csrf_field),
+        ),
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Two-factor authentication</title>
+            </head>
+            <body>
+                {msg_html}
+                {content}
+                <p><a href="/admin/dashboard">&lt;- Back</a></p>
+            </body>
+            </html>"#
+        )))
+}
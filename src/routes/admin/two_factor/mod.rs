@@ -0,0 +1,7 @@
+mod get;
+mod post;
+
+pub use get::two_factor_form;
+pub use post::{
+    confirm_two_factor_setup, disable_two_factor, regenerate_recovery_codes, start_two_factor_setup,
+};
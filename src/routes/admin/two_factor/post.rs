@@ -0,0 +1,113 @@
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::authentication::{self, TwoFactorState, UserId, two_factor_state, verify_second_factor};
+use crate::utils::{e500, see_other};
+
+#[derive(serde::Deserialize)]
+pub struct CodeFormData {
+    code: String,
+}
+
+// recovery codes are only ever shown here, right after they're generated
+fn recovery_codes_page(codes: &[String]) -> HttpResponse {
+    let mut codes_html = String::new();
+    for code in codes {
+        writeln!(codes_html, "<li><code>{}</code></li>", code).unwrap();
+    }
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Recovery codes</title>
+            </head>
+            <body>
+                <p>Two-factor authentication is on. Keep these recovery codes somewhere safe,
+                each of them can be used once instead of a code from your app.
+                They won't be shown again.</p>
+                <ul>
+                    {codes_html}
+                </ul>
+                <p><a href="/admin/two-factor">Done</a></p>
+            </body>
+            </html>"#
+        ))
+}
+
+#[tracing::instrument(name = "Start two-factor setup", skip(pool), fields(user_id=%*user_id))]
+pub async fn start_two_factor_setup(
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    authentication::start_two_factor_enrollment(&pool, **user_id)
+        .await
+        .map_err(e500)?;
+    Ok(see_other("/admin/two-factor"))
+}
+
+#[tracing::instrument(name = "Confirm two-factor setup", skip(form, pool), fields(user_id=%*user_id))]
+pub async fn confirm_two_factor_setup(
+    form: web::Form<CodeFormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    match authentication::confirm_two_factor_enrollment(&pool, **user_id, &form.0.code)
+        .await
+        .map_err(e500)?
+    {
+        Some(codes) => Ok(recovery_codes_page(&codes)),
+        None => {
+            FlashMessage::error("That code is not valid.").send();
+            Ok(see_other("/admin/two-factor"))
+        }
+    }
+}
+
+#[tracing::instrument(name = "Regenerate recovery codes", skip(form, pool), fields(user_id=%*user_id))]
+pub async fn regenerate_recovery_codes(
+    form: web::Form<CodeFormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if !verify_second_factor(&pool, **user_id, &form.0.code)
+        .await
+        .map_err(e500)?
+    {
+        FlashMessage::error("That code is not valid.").send();
+        return Ok(see_other("/admin/two-factor"));
+    }
+    let codes = authentication::regenerate_recovery_codes(&pool, **user_id)
+        .await
+        .map_err(e500)?;
+    Ok(recovery_codes_page(&codes))
+}
+
+// cancelling an unfinished setup needs no code
+#[tracing::instrument(name = "Disable two-factor", skip(form, pool), fields(user_id=%*user_id))]
+pub async fn disable_two_factor(
+    form: Option<web::Form<CodeFormData>>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let state = two_factor_state(&pool, **user_id).await.map_err(e500)?;
+    if let TwoFactorState::Enabled { .. } = state {
+        let code = form.map(|f| f.0.code).unwrap_or_default();
+        if !verify_second_factor(&pool, **user_id, &code)
+            .await
+            .map_err(e500)?
+        {
+            FlashMessage::error("That code is not valid.").send();
+            return Ok(see_other("/admin/two-factor"));
+        }
+        FlashMessage::info("Two-factor authentication has been turned off.").send();
+    }
+    authentication::disable_two_factor(&pool, **user_id)
+        .await
+        .map_err(e500)?;
+    Ok(see_other("/admin/two-factor"))
+}
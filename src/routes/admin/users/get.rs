@@ -0,0 +1,141 @@
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::authentication::{CsrfToken, UserId, list_pending_invitations, list_users};
+use crate::domain::Role;
+use crate::utils::e500;
+
+fn role_options(selected: Role) -> String {
+    Role::ALL
+        .iter()
+        .map(|role| {
+            format!(
+                r#"<option value="{0}"{1}>{0}</option>"#,
+                role.as_str(),
+                if *role == selected { " selected" } else { "" }
+            )
+        })
+        .collect()
+}
+
+pub async fn users_page(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    csrf: CsrfToken,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_field = csrf.form_field();
+    let current_user_id = *user_id.into_inner();
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap()
+    }
+
+    let mut users_html = String::new();
+    for user in list_users(&pool).await.map_err(e500)? {
+        // nobody can lock themselves out
+        let actions = if user.user_id == current_user_id {
+            "<i>you</i>".to_string()
+        } else {
+            let (toggle_action, toggle_label) = if user.is_active {
+                ("deactivate", "Deactivate")
+            } else {
+                ("activate", "Reactivate")
+            };
+            format!(
+                r#"<form action="/admin/users/{id}/role" method="post">
+                    {csrf_field}
+                    <select name="role">{options}</select>
+                    <button type="submit">Change role</button>
+                </form>
+                <form action="/admin/users/{id}/{toggle_action}" method="post">
+                    {csrf_field}
+                    <button type="submit">{toggle_label}</button>
+                </form>
+                <form action="/admin/users/{id}/delete" method="post">
+                    {csrf_field}
+                    <button type="submit">Remove</button>
+                </form>"#,
+                id = user.user_id,
+                options = role_options(user.role),
+            )
+        };
+        writeln!(
+            users_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            encode_minimal(&user.username),
+            encode_minimal(user.email.as_deref().unwrap_or("")),
+            user.role.as_str(),
+            if user.is_active {
+                "active"
+            } else {
+                "deactivated"
+            },
+            user.created_at.format("%Y-%m-%d %H:%M"),
+            actions,
+        )
+        .unwrap();
+    }
+
+    let invite_role_options = role_options(Role::Editor);
+    let mut invitations_html = String::new();
+    for invitation in list_pending_invitations(&pool).await.map_err(e500)? {
+        writeln!(
+            invitations_html,
+            r#"<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>
+                <form action="/admin/users/invitations/{}/revoke" method="post">
+                    {csrf_field}
+                    <button type="submit">Revoke</button>
+                </form>
+            </td></tr>"#,
+            encode_minimal(&invitation.email),
+            invitation.role.as_str(),
+            invitation.created_at.format("%Y-%m-%d %H:%M"),
+            invitation.expires_at.format("%Y-%m-%d %H:%M"),
+            invitation.id,
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Users</title>
+            </head>
+            <body>
+                {msg_html}
+                <table>
+                    <tr><th>Username</th><th>Email</th><th>Role</th><th>Status</th><th>Created at</th><th></th></tr>
+                    {users_html}
+                </table>
+                <p>Pending invitations:</p>
+                <table>
+                    <tr><th>Email</th><th>Role</th><th>Invited at</th><th>Expires at</th><th></th></tr>
+                    {invitations_html}
+                </table>
+                <form action="/admin/users/invite" method="post">
+                    {csrf_field}
+                    <label>Email
+                        <input
+                            type="text"
+                            placeholder="Enter an email address"
+                            name="email"
+                        >
+                    </label>
+                    <label>Role
+                        <select name="role">{invite_role_options}</select>
+                    </label>
+                    <button type="submit">Send invitation</button>
+                </form>
+                <p><a href="/admin/dashboard">&lt;- Back</a></p>
+            </body>
+            </html>"#
+        )))
+}
@@ -0,0 +1,7 @@
+mod get;
+mod post;
+
+pub use get::users_page;
+pub use post::{
+    activate_user, change_user_role, deactivate_user, delete_user, invite_user, revoke_invitation,
+};
@@ -0,0 +1,205 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::authentication::{self, INVITATION_LIFETIME_HOURS, UserId, email_in_use};
+use crate::domain::{Role, SubscriberEmail};
+use crate::email_client::EmailClient;
+use crate::startup::ApplicationBaseUrl;
+use crate::suppression::is_suppressed;
+use crate::utils::{e500, see_other};
+
+#[derive(serde::Deserialize)]
+pub struct InviteFormData {
+    email: String,
+    role: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct RoleFormData {
+    role: String,
+}
+
+// the actions below refuse to act on the user making the request
+fn is_current_user(user_id: &UserId, target: Uuid) -> bool {
+    if **user_id == target {
+        FlashMessage::error("You can't do that to your own account.").send();
+        true
+    } else {
+        false
+    }
+}
+
+#[tracing::instrument(
+    name = "Invite a user",
+    skip(form, pool, email_client, base_url),
+    fields(user_id=%*user_id)
+)]
+pub async fn invite_user(
+    form: web::Form<InviteFormData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let InviteFormData { email, role } = form.0;
+    let (email, role) = match (SubscriberEmail::parse(email), Role::parse(&role)) {
+        (Ok(email), Ok(role)) => (email, role),
+        (Err(e), _) | (_, Err(e)) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/users"));
+        }
+    };
+    if email_in_use(&pool, email.as_ref()).await.map_err(e500)? {
+        FlashMessage::error("A user with this email address already exists.").send();
+        return Ok(see_other("/admin/users"));
+    }
+    let suppressed = is_suppressed(pool.get_ref(), email.as_ref())
+        .await
+        .context("Failed to check the suppression list")
+        .map_err(e500)?;
+    if suppressed {
+        FlashMessage::error("This email address is on the suppression list.").send();
+        return Ok(see_other("/admin/users"));
+    }
+
+    let token = authentication::create_invitation(&pool, email.as_ref(), role, *user_id)
+        .await
+        .map_err(e500)?;
+    let link = format!(
+        "{}/invitations/accept?token={}",
+        base_url.0,
+        urlencoding::encode(&token)
+    );
+    let plain_body = format!(
+        "You've been invited to help run our newsletter.\n\
+        Visit {} to choose a username and password.\n\
+        The link is valid for {} hours.",
+        link, INVITATION_LIFETIME_HOURS
+    );
+    let html_body = format!(
+        "You've been invited to help run our newsletter.<br />\
+        Click <a href=\"{}\">here</a> to choose a username and password.<br />\
+        The link is valid for {} hours.",
+        link, INVITATION_LIFETIME_HOURS
+    );
+    email_client
+        .send_email(&email, "Your invitation", &html_body, &plain_body)
+        .await
+        .context("Failed to send an invitation email.")
+        .map_err(e500)?;
+    FlashMessage::info(format!("An invitation has been sent to {}.", email)).send();
+    Ok(see_other("/admin/users"))
+}
+
+#[tracing::instrument(name = "Revoke an invitation", skip(pool))]
+pub async fn revoke_invitation(
+    invitation_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if authentication::revoke_invitation(&pool, invitation_id.into_inner())
+        .await
+        .map_err(e500)?
+    {
+        FlashMessage::info("The invitation has been revoked.").send();
+    } else {
+        FlashMessage::error("That invitation is no longer pending.").send();
+    }
+    Ok(see_other("/admin/users"))
+}
+
+#[tracing::instrument(name = "Deactivate a user", skip(pool), fields(user_id=%*user_id))]
+pub async fn deactivate_user(
+    target: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let target = target.into_inner();
+    if is_current_user(&user_id, target) {
+        return Ok(see_other("/admin/users"));
+    }
+    if authentication::set_user_active(&pool, target, false)
+        .await
+        .map_err(e500)?
+    {
+        FlashMessage::info("The user has been deactivated.").send();
+    } else {
+        FlashMessage::error("That user does not exist.").send();
+    }
+    Ok(see_other("/admin/users"))
+}
+
+#[tracing::instrument(name = "Reactivate a user", skip(pool), fields(user_id=%*user_id))]
+pub async fn activate_user(
+    target: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let target = target.into_inner();
+    if is_current_user(&user_id, target) {
+        return Ok(see_other("/admin/users"));
+    }
+    if authentication::set_user_active(&pool, target, true)
+        .await
+        .map_err(e500)?
+    {
+        FlashMessage::info("The user has been reactivated.").send();
+    } else {
+        FlashMessage::error("That user does not exist.").send();
+    }
+    Ok(see_other("/admin/users"))
+}
+
+#[tracing::instrument(name = "Remove a user", skip(pool), fields(user_id=%*user_id))]
+pub async fn delete_user(
+    target: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let target = target.into_inner();
+    if is_current_user(&user_id, target) {
+        return Ok(see_other("/admin/users"));
+    }
+    if authentication::delete_user(&pool, target)
+        .await
+        .map_err(e500)?
+    {
+        FlashMessage::info("The user has been removed.").send();
+    } else {
+        FlashMessage::error("That user does not exist.").send();
+    }
+    Ok(see_other("/admin/users"))
+}
+
+#[tracing::instrument(name = "Change the role of a user", skip(form, pool), fields(user_id=%*user_id))]
+pub async fn change_user_role(
+    target: web::Path<Uuid>,
+    form: web::Form<RoleFormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let target = target.into_inner();
+    // an owner demoting themselves could leave nobody able to manage users
+    if is_current_user(&user_id, target) {
+        return Ok(see_other("/admin/users"));
+    }
+    let role = match Role::parse(&form.0.role) {
+        Ok(role) => role,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/users"));
+        }
+    };
+    if authentication::set_user_role(&pool, target, role)
+        .await
+        .map_err(e500)?
+    {
+        FlashMessage::info(format!("The user's role is now {}.", role.as_str())).send();
+    } else {
+        FlashMessage::error("That user does not exist.").send();
+    }
+    Ok(see_other("/admin/users"))
+}
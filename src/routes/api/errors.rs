@@ -0,0 +1,93 @@
+use actix_web::{HttpResponse, ResponseError, http::StatusCode};
+
+use crate::routes::{ConfirmError, SubscribeError};
+use crate::utils::{error_chain_fmt, json_error};
+
+#[derive(thiserror::Error)]
+pub enum ApiError {
+    #[error("{0}")]
+    ValidationError(String),
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Conflict(String),
+    #[error("Too many signup attempts, please try again later.")]
+    TooManyAttempts,
+    #[error("The idempotency key has already been used for a different request.")]
+    IdempotencyKeyReused,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl ApiError {
+    // the same codes `SubscribeError` uses for the embedded signup form
+    fn code(&self) -> &'static str {
+        match self {
+            ApiError::ValidationError(_) => "validation_error",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::TooManyAttempts => "too_many_attempts",
+            ApiError::IdempotencyKeyReused => "idempotency_key_reused",
+            ApiError::UnexpectedError(_) => "unexpected_error",
+        }
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::TooManyAttempts => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::IdempotencyKeyReused => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        // the details of unexpected errors stay in our logs
+        let message = match self {
+            ApiError::UnexpectedError(_) => "Something went wrong.".to_string(),
+            e => e.to_string(),
+        };
+        json_error(self.status_code(), self.code(), &message)
+    }
+}
+
+impl std::fmt::Debug for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl From<SubscribeError> for ApiError {
+    fn from(e: SubscribeError) -> Self {
+        match e {
+            SubscribeError::ValidationError(message) => ApiError::ValidationError(message),
+            SubscribeError::TooManyAttempts => ApiError::TooManyAttempts,
+            SubscribeError::UnexpectedError(e) => ApiError::UnexpectedError(e),
+        }
+    }
+}
+
+impl From<ConfirmError> for ApiError {
+    fn from(e: ConfirmError) -> Self {
+        match e {
+            ConfirmError::AlreadyConfirmed => ApiError::Conflict(e.to_string()),
+            ConfirmError::UnknownToken => ApiError::NotFound(e.to_string()),
+            ConfirmError::UnexpectedError(e) => ApiError::UnexpectedError(e),
+        }
+    }
+}
+
+impl From<sqlx::Error> for ApiError {
+    fn from(e: sqlx::Error) -> Self {
+        ApiError::UnexpectedError(e.into())
+    }
+}
+
+// malformed bodies, queries and paths get the same JSON errors as everything else
+pub fn extractor_error(e: impl std::fmt::Display) -> actix_web::Error {
+    ApiError::ValidationError(e.to_string()).into()
+}
@@ -0,0 +1,165 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::http::Method;
+use actix_web::middleware::Next;
+use actix_web::{FromRequest, HttpMessage, HttpRequest, web};
+use anyhow::Context;
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::future::{Ready, ready};
+use std::sync::Arc;
+use tokio::sync::{MappedMutexGuard, Mutex, MutexGuard};
+
+use super::ApiError;
+use crate::authentication::UserId;
+use crate::idempotency::{IdempotencyKey, NextAction, save_response, try_processing};
+
+pub const IDEMPOTENCY_KEY: &str = "Idempotency-Key";
+
+// the transaction a write request runs in, opened and committed by `idempotent_requests`.
+// with an idempotency key the saved response is committed along with the handler's work
+#[derive(Clone)]
+pub struct RequestTransaction(Arc<Mutex<Option<Transaction<'static, Postgres>>>>);
+
+impl RequestTransaction {
+    fn new(transaction: Transaction<'static, Postgres>) -> Self {
+        Self(Arc::new(Mutex::new(Some(transaction))))
+    }
+
+    pub async fn lock(
+        &self,
+    ) -> Result<MappedMutexGuard<'_, Transaction<'static, Postgres>>, ApiError> {
+        MutexGuard::try_map(self.0.lock().await, Option::as_mut)
+            .map_err(|_| anyhow::anyhow!("The request's transaction has already ended").into())
+    }
+
+    async fn take(&self) -> Option<Transaction<'static, Postgres>> {
+        self.0.lock().await.take()
+    }
+}
+
+impl FromRequest for RequestTransaction {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(
+            req.extensions()
+                .get::<RequestTransaction>()
+                .cloned()
+                .context("Write requests need to be wrapped in `idempotent_requests`")
+                .map_err(ApiError::UnexpectedError),
+        )
+    }
+}
+
+// a retried request with the same key gets the first response back instead of doing the work twice.
+// the key is held while the handler runs, so a concurrent retry waits for it to finish.
+// like the newsletter form, only successes are saved and failed requests can be retried with the same key.
+// writes without a key still run in a single transaction, committed only if they succeed
+pub async fn idempotent_requests(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    // reads are idempotent already
+    if matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS) {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    }
+    let pool = req
+        .app_data::<web::Data<PgPool>>()
+        .context("The database pool is missing")
+        .map_err(ApiError::UnexpectedError)?
+        .clone();
+    let idempotency = match req.headers().get(IDEMPOTENCY_KEY) {
+        Some(header) => {
+            let idempotency_key = parse_key(header.to_str().ok())?;
+            // set by `require_api_token`, which has to wrap this middleware
+            let user_id = *req
+                .extensions()
+                .get::<UserId>()
+                .copied()
+                .context("Idempotent requests need an authenticated user")
+                .map_err(ApiError::UnexpectedError)?;
+            Some((idempotency_key, user_id))
+        }
+        None => None,
+    };
+
+    let transaction = match &idempotency {
+        Some((idempotency_key, user_id)) => {
+            let fingerprint = request_fingerprint(&mut req).await?;
+            match try_processing(&pool, idempotency_key, *user_id, Some(&fingerprint))
+                .await
+                .map_err(ApiError::UnexpectedError)?
+            {
+                NextAction::StartProcessing(transaction) => transaction,
+                NextAction::ReturnSavedResponse(saved_response) => {
+                    return Ok(req.into_response(saved_response));
+                }
+                NextAction::RejectReusedKey => return Err(ApiError::IdempotencyKeyReused.into()),
+            }
+        }
+        None => pool
+            .begin()
+            .await
+            .context("Failed to acquire a Postgres connection from the pool")
+            .map_err(ApiError::UnexpectedError)?,
+    };
+    let transaction = RequestTransaction::new(transaction);
+    req.extensions_mut().insert(transaction.clone());
+
+    // dropping the transaction on failures rolls the work back and gives the key back
+    let response = next.call(req).await?;
+    let transaction = transaction
+        .take()
+        .await
+        .context("The request's transaction has gone missing")
+        .map_err(ApiError::UnexpectedError)?;
+    if !response.status().is_success() {
+        return Ok(response.map_into_boxed_body());
+    }
+    let (req, response) = response.into_parts();
+    let response = match idempotency {
+        Some((idempotency_key, user_id)) => save_response(
+            transaction,
+            &idempotency_key,
+            user_id,
+            response.map_into_boxed_body(),
+        )
+        .await
+        .map_err(ApiError::UnexpectedError)?,
+        None => {
+            transaction
+                .commit()
+                .await
+                .context("Failed to commit the request's SQL transaction")
+                .map_err(ApiError::UnexpectedError)?;
+            response.map_into_boxed_body()
+        }
+    };
+    Ok(ServiceResponse::new(req, response))
+}
+
+fn parse_key(header: Option<&str>) -> Result<IdempotencyKey, ApiError> {
+    header
+        .ok_or_else(|| {
+            ApiError::ValidationError("The idempotency key must be visible ASCII.".into())
+        })?
+        .to_owned()
+        .try_into()
+        .map_err(|e: anyhow::Error| ApiError::ValidationError(e.to_string()))
+}
+
+// what the key is bound to: the same key with another method, path or body is a different request.
+// the body is read here, so it's put back for the handler
+async fn request_fingerprint(req: &mut ServiceRequest) -> Result<String, actix_web::Error> {
+    let body = req.extract::<web::Bytes>().await?;
+    let mut hasher = Sha256::new();
+    hasher.update(req.method().as_str());
+    hasher.update(b"\n");
+    hasher.update(req.uri().to_string());
+    hasher.update(b"\n");
+    hasher.update(&body);
+    req.set_payload(Payload::from(body));
+    Ok(hex::encode(hasher.finalize()))
+}
@@ -0,0 +1,259 @@
+use actix_web::{HttpResponse, http::header::LOCATION, web};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::{ApiError, RequestTransaction};
+use crate::authentication::UserId;
+use crate::custom_fields::list_field_definitions;
+use crate::newsletter_issues::{
+    DeliveryStats, NewsletterIssue, delivery_stats, get_newsletter_issue, insert_newsletter_issue,
+    list_newsletter_issues, publish_newsletter_issue,
+};
+use crate::segmentation::get_segment;
+use crate::utils::ErrorBody;
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct NewIssue {
+    title: String,
+    text_content: String,
+    html_content: String,
+    // missing means "every confirmed subscriber"
+    #[serde(default)]
+    segment_id: Option<Uuid>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct Issue {
+    id: Uuid,
+    title: String,
+    text_content: String,
+    html_content: String,
+    #[schema(required)]
+    segment_id: Option<Uuid>,
+    created_at: DateTime<Utc>,
+    // null until the issue has been sent
+    #[schema(required)]
+    published_at: Option<DateTime<Utc>>,
+}
+
+impl From<NewsletterIssue> for Issue {
+    fn from(issue: NewsletterIssue) -> Self {
+        Self {
+            id: issue.newsletter_issue_id,
+            title: issue.title,
+            text_content: issue.text_content,
+            html_content: issue.html_content,
+            segment_id: issue.segment_id,
+            created_at: issue.created_at,
+            published_at: issue.published_at,
+        }
+    }
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct IssueList {
+    issues: Vec<Issue>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct IssueStats {
+    // still waiting for the delivery worker
+    queued: i64,
+    sent: i64,
+    failed: i64,
+    invalid_email: i64,
+    suppressed: i64,
+}
+
+impl From<DeliveryStats> for IssueStats {
+    fn from(stats: DeliveryStats) -> Self {
+        Self {
+            queued: stats.queued,
+            sent: stats.sent,
+            failed: stats.failed,
+            invalid_email: stats.invalid_email,
+            suppressed: stats.suppressed,
+        }
+    }
+}
+
+fn issue_not_found() -> ApiError {
+    ApiError::NotFound("There is no newsletter issue with that id.".into())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/issues",
+    tag = "issues",
+    responses((status = 200, description = "Every issue, newest first", body = IssueList))
+)]
+#[tracing::instrument(name = "List newsletter issues through the api", skip(pool))]
+pub async fn list_issues(pool: web::Data<PgPool>) -> Result<HttpResponse, ApiError> {
+    let issues = list_newsletter_issues(&pool).await?;
+    Ok(HttpResponse::Ok().json(IssueList {
+        issues: issues.into_iter().map(Issue::from).collect(),
+    }))
+}
+
+// issues are created as drafts, nothing goes out until they are sent
+#[utoipa::path(
+    post,
+    path = "/api/v1/issues",
+    tag = "issues",
+    request_body = NewIssue,
+    responses(
+        (status = 201, description = "The draft issue", body = Issue),
+        (status = 400, description = "The issue is incomplete", body = ErrorBody),
+    ),
+    security(("api_token" = ["publish"]))
+)]
+#[tracing::instrument(
+    name = "Create a newsletter issue through the api",
+    skip(body, pool, transaction),
+    fields(user_id=%*user_id)
+)]
+pub async fn create_issue(
+    body: web::Json<NewIssue>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    transaction: RequestTransaction,
+) -> Result<HttpResponse, ApiError> {
+    let NewIssue {
+        title,
+        text_content,
+        html_content,
+        segment_id,
+    } = body.into_inner();
+    if title.trim().is_empty() {
+        return Err(ApiError::ValidationError(
+            "The title cannot be empty.".into(),
+        ));
+    }
+    if text_content.trim().is_empty() || html_content.trim().is_empty() {
+        return Err(ApiError::ValidationError(
+            "Both the text and the html content are required.".into(),
+        ));
+    }
+    if let Some(segment_id) = segment_id {
+        get_segment(&pool, segment_id).await?.ok_or_else(|| {
+            ApiError::ValidationError("The selected segment does not exist.".into())
+        })?;
+    }
+
+    let mut transaction = transaction.lock().await?;
+    let issue_id = insert_newsletter_issue(
+        &mut **transaction,
+        &title,
+        &text_content,
+        &html_content,
+        segment_id,
+    )
+    .await
+    .context("Failed to store newsletter issue details")?;
+    let issue = get_newsletter_issue(&mut **transaction, issue_id)
+        .await?
+        .context("The new newsletter issue has gone missing")?;
+    Ok(HttpResponse::Created()
+        .insert_header((LOCATION, format!("/api/v1/issues/{issue_id}")))
+        .json(Issue::from(issue)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/issues/{issue_id}",
+    tag = "issues",
+    params(("issue_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "The issue", body = Issue),
+        (status = 404, description = "There is no such issue", body = ErrorBody),
+    )
+)]
+#[tracing::instrument(name = "Get a newsletter issue through the api", skip(pool))]
+pub async fn get_issue(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let issue = get_newsletter_issue(pool.get_ref(), issue_id.into_inner())
+        .await?
+        .ok_or_else(issue_not_found)?;
+    Ok(HttpResponse::Ok().json(Issue::from(issue)))
+}
+
+// queues the issue for every confirmed subscriber in its segment, an issue is only sent once
+#[utoipa::path(
+    post,
+    path = "/api/v1/issues/{issue_id}/send",
+    tag = "issues",
+    params(("issue_id" = Uuid, Path)),
+    responses(
+        (status = 202, description = "The issue, queued for delivery", body = Issue),
+        (status = 404, description = "There is no such issue", body = ErrorBody),
+        (status = 409, description = "The issue has already been sent", body = ErrorBody),
+    ),
+    security(("api_token" = ["publish"]))
+)]
+#[tracing::instrument(
+    name = "Send a newsletter issue through the api",
+    skip(pool, transaction)
+)]
+pub async fn send_issue(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    transaction: RequestTransaction,
+) -> Result<HttpResponse, ApiError> {
+    let issue_id = issue_id.into_inner();
+    let issue = get_newsletter_issue(pool.get_ref(), issue_id)
+        .await?
+        .ok_or_else(issue_not_found)?;
+    // segments that issues point at can't be deleted
+    let filter = match issue.segment_id {
+        Some(segment_id) => Some(
+            get_segment(&pool, segment_id)
+                .await?
+                .context("The issue's segment has gone missing")?
+                .filter(&list_field_definitions(&pool).await?)?,
+        ),
+        None => None,
+    };
+    let mut transaction = transaction.lock().await?;
+    if !publish_newsletter_issue(&mut transaction, issue_id, filter.as_ref())
+        .await
+        .context("Failed to enqueue delivery tasks")?
+    {
+        return Err(ApiError::Conflict(
+            "The newsletter issue has already been sent.".into(),
+        ));
+    }
+    let issue = get_newsletter_issue(&mut **transaction, issue_id)
+        .await?
+        .ok_or_else(issue_not_found)?;
+    Ok(HttpResponse::Accepted().json(Issue::from(issue)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/issues/{issue_id}/stats",
+    tag = "issues",
+    params(("issue_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "How the deliveries of the issue went", body = IssueStats),
+        (status = 404, description = "There is no such issue", body = ErrorBody),
+    )
+)]
+#[tracing::instrument(
+    name = "Get the delivery stats of an issue through the api",
+    skip(pool)
+)]
+pub async fn get_issue_stats(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let issue_id = issue_id.into_inner();
+    get_newsletter_issue(pool.get_ref(), issue_id)
+        .await?
+        .ok_or_else(issue_not_found)?;
+    let stats = delivery_stats(&pool, issue_id).await?;
+    Ok(HttpResponse::Ok().json(IssueStats::from(stats)))
+}
@@ -0,0 +1,11 @@
+mod errors;
+mod idempotency;
+mod issues;
+mod openapi;
+mod subscribers;
+
+pub use errors::{ApiError, extractor_error};
+pub use idempotency::{IDEMPOTENCY_KEY, RequestTransaction, idempotent_requests};
+pub use issues::{create_issue, get_issue, get_issue_stats, list_issues, send_issue};
+pub use openapi::openapi_document;
+pub use subscribers::{create_subscriber, delete_subscriber, list_subscribers};
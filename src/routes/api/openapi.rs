@@ -0,0 +1,140 @@
+use actix_web::HttpResponse;
+use utoipa::openapi::path::{Operation, ParameterBuilder, ParameterIn};
+use utoipa::openapi::schema::{ObjectBuilder, Type};
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::openapi::{ContentBuilder, Ref, Required, Response, ResponseBuilder};
+use utoipa::{Modify, OpenApi};
+
+use super::{IDEMPOTENCY_KEY, issues, subscribers};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "zero2prod",
+        description = "Manage newsletter issues and subscribers with a personal api token."
+    ),
+    paths(
+        issues::list_issues,
+        issues::create_issue,
+        issues::get_issue,
+        issues::send_issue,
+        issues::get_issue_stats,
+        subscribers::list_subscribers,
+        subscribers::create_subscriber,
+        subscribers::delete_subscriber,
+    ),
+    modifiers(&ApiTokenAuthentication, &IdempotencyKeyHeader),
+    security(("api_token" = [])),
+    tags(
+        (name = "issues", description = "Draft newsletter issues and send them"),
+        (name = "subscribers", description = "Look up, add and remove subscribers"),
+    )
+)]
+pub struct ApiDoc;
+
+// every endpoint takes a bearer token, and the ones that need a scope can refuse it
+struct ApiTokenAuthentication;
+
+impl Modify for ApiTokenAuthentication {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        // the crate has no license to advertise
+        openapi.info.license = None;
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "api_token",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .description(Some("A personal api token, created in the admin."))
+                    .build(),
+            ),
+        );
+        for (_, operation) in operations(openapi) {
+            let scoped = operation.security.is_some();
+            let responses = &mut operation.responses.responses;
+            responses.insert(
+                "401".into(),
+                error("The api token is missing, invalid or revoked").into(),
+            );
+            if scoped {
+                responses.insert(
+                    "403".into(),
+                    error("The api token lacks the scope, or its owner the permission").into(),
+                );
+            }
+        }
+    }
+}
+
+// the middleware takes the header on every request that changes something
+struct IdempotencyKeyHeader;
+
+impl Modify for IdempotencyKeyHeader {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        for (read_only, operation) in operations(openapi) {
+            if read_only {
+                continue;
+            }
+            operation.parameters.get_or_insert_with(Vec::new).push(
+                ParameterBuilder::new()
+                    .name(IDEMPOTENCY_KEY)
+                    .parameter_in(ParameterIn::Header)
+                    .required(Required::False)
+                    .description(Some(
+                        "Retries with the same key get the first successful response back. \
+                        A key belongs to the method, path and body it was first sent with.",
+                    ))
+                    .schema(Some(
+                        ObjectBuilder::new()
+                            .schema_type(Type::String)
+                            .min_length(Some(1))
+                            .max_length(Some(49)),
+                    ))
+                    .build(),
+            );
+            operation
+                .responses
+                .responses
+                .entry("400".into())
+                .or_insert_with(|| error("The idempotency key is malformed").into());
+            operation.responses.responses.insert(
+                "422".into(),
+                error("The idempotency key was used for a different request").into(),
+            );
+        }
+    }
+}
+
+// every operation, and whether it only reads
+fn operations(
+    openapi: &mut utoipa::openapi::OpenApi,
+) -> impl Iterator<Item = (bool, &mut Operation)> {
+    openapi.paths.paths.values_mut().flat_map(|item| {
+        [
+            (true, &mut item.get),
+            (false, &mut item.post),
+            (false, &mut item.put),
+            (false, &mut item.patch),
+            (false, &mut item.delete),
+        ]
+        .into_iter()
+        .filter_map(|(read_only, operation)| Some((read_only, operation.as_mut()?)))
+    })
+}
+
+fn error(description: &str) -> Response {
+    ResponseBuilder::new()
+        .description(description)
+        .content(
+            "application/json",
+            ContentBuilder::new()
+                .schema(Some(Ref::from_schema_name("ErrorBody")))
+                .build(),
+        )
+        .build()
+}
+
+// public, so clients can be generated without a token
+pub async fn openapi_document() -> HttpResponse {
+    HttpResponse::Ok().json(ApiDoc::openapi())
+}
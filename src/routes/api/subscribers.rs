@@ -0,0 +1,161 @@
+use actix_web::{HttpResponse, web};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::{ApiError, RequestTransaction};
+use crate::custom_fields::list_field_definitions;
+use crate::domain::NewSubscriber;
+use crate::email_queue::enqueue_emails;
+use crate::routes::{
+    FormData, ListParameters, PAGE_SIZE, SubscriberFilter, SubscriberRow, confirmation_email,
+    remove_subscriber, search_subscribers, store_pending_subscriber,
+};
+use crate::startup::ApplicationBaseUrl;
+use crate::utils::ErrorBody;
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct Subscriber {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+}
+
+impl From<SubscriberRow> for Subscriber {
+    fn from(row: SubscriberRow) -> Self {
+        Self {
+            id: row.id,
+            email: row.email,
+            name: row.name,
+            status: row.status,
+            subscribed_at: row.subscribed_at,
+        }
+    }
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct SubscriberPage {
+    subscribers: Vec<Subscriber>,
+    page: i64,
+    page_size: i64,
+    total: i64,
+}
+
+fn already_subscribed() -> ApiError {
+    ApiError::Conflict("There already is a subscriber with that email address.".into())
+}
+
+// takes the same filters as the subscribers page in the admin
+#[utoipa::path(
+    get,
+    path = "/api/v1/subscribers",
+    tag = "subscribers",
+    params(ListParameters),
+    responses(
+        (status = 200, description = "A page of matching subscribers", body = SubscriberPage),
+        (status = 400, description = "A filter is malformed", body = ErrorBody),
+    )
+)]
+#[tracing::instrument(name = "List subscribers through the api", skip_all)]
+pub async fn list_subscribers(
+    parameters: web::Query<ListParameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let filter = SubscriberFilter::try_from(&parameters.0).map_err(ApiError::ValidationError)?;
+    let page = parameters.page.unwrap_or(1).max(1);
+    let (total, subscribers) = search_subscribers(&pool, &filter, page).await?;
+    Ok(HttpResponse::Ok().json(SubscriberPage {
+        subscribers: subscribers.into_iter().map(Subscriber::from).collect(),
+        page,
+        page_size: PAGE_SIZE,
+        total,
+    }))
+}
+
+// the same as a signup, the subscriber still has to confirm
+#[utoipa::path(
+    post,
+    path = "/api/v1/subscribers",
+    tag = "subscribers",
+    request_body = FormData,
+    responses(
+        (status = 201, description = "The pending subscriber", body = Subscriber),
+        (status = 400, description = "The subscriber details are invalid", body = ErrorBody),
+        (status = 409, description = "The email address is already subscribed", body = ErrorBody),
+    ),
+    security(("api_token" = ["edit"]))
+)]
+#[tracing::instrument(
+    name = "Add a subscriber through the api",
+    skip(body, pool, base_url, transaction)
+)]
+pub async fn create_subscriber(
+    body: web::Json<FormData>,
+    pool: web::Data<PgPool>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    transaction: RequestTransaction,
+) -> Result<HttpResponse, ApiError> {
+    let definitions = list_field_definitions(&pool)
+        .await
+        .context("Failed to retrieve the custom field definitions")?;
+    let new_subscriber: NewSubscriber = (body.into_inner(), definitions.as_slice())
+        .try_into()
+        .map_err(ApiError::ValidationError)?;
+    // however it's cased, like the unique index on the address
+    let existing = sqlx::query!(
+        r#"SELECT id FROM subscriptions WHERE lower(email) = lower($1)"#,
+        new_subscriber.email.as_ref()
+    )
+    .fetch_optional(pool.get_ref())
+    .await?;
+    if existing.is_some() {
+        return Err(already_subscribed());
+    }
+    let mut transaction = transaction.lock().await?;
+    // a concurrent request for the same address can still win the insert
+    let (subscriber_id, subscription_token) =
+        store_pending_subscriber(&mut transaction, &new_subscriber)
+            .await?
+            .ok_or_else(already_subscribed)?;
+    // goes out once the subscriber is committed, along with the saved response
+    let email = confirmation_email(&new_subscriber.email, &base_url.0, &subscription_token);
+    enqueue_emails(&mut **transaction, &[email])
+        .await
+        .context("Failed to queue a confirmation email.")?;
+    let subscriber = sqlx::query_as!(
+        SubscriberRow,
+        r#"SELECT id, email, name, status, subscribed_at FROM subscriptions WHERE id = $1"#,
+        subscriber_id
+    )
+    .fetch_one(&mut **transaction)
+    .await?;
+    Ok(HttpResponse::Created().json(Subscriber::from(subscriber)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/subscribers/{subscriber_id}",
+    tag = "subscribers",
+    params(("subscriber_id" = Uuid, Path)),
+    responses(
+        (status = 204, description = "The subscriber is gone"),
+        (status = 404, description = "There is no such subscriber", body = ErrorBody),
+    ),
+    security(("api_token" = ["edit"]))
+)]
+#[tracing::instrument(name = "Delete a subscriber through the api", skip(transaction))]
+pub async fn delete_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    transaction: RequestTransaction,
+) -> Result<HttpResponse, ApiError> {
+    let mut transaction = transaction.lock().await?;
+    if !remove_subscriber(&mut transaction, subscriber_id.into_inner()).await? {
+        return Err(ApiError::NotFound(
+            "There is no subscriber with that id.".into(),
+        ));
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,76 @@
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use htmlescape::{encode_attribute, encode_minimal};
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::authentication::find_pending_invitation;
+use crate::utils::e500;
+
+#[derive(serde::Deserialize)]
+pub struct InvitationParameters {
+    token: String,
+}
+
+#[tracing::instrument(
+    name = "Show the invitation form",
+    skip(flash_messages, parameters, pool)
+)]
+pub async fn accept_invitation_form(
+    flash_messages: IncomingFlashMessages,
+    parameters: web::Query<InvitationParameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(invitation) = find_pending_invitation(&pool, &parameters.token)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(HttpResponse::NotFound()
+            .content_type(ContentType::html())
+            .body(
+                "<p>This invitation link is invalid, has expired or has already been used.</p>",
+            ));
+    };
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap()
+    }
+    let email = encode_minimal(&invitation.email);
+    let token = encode_attribute(&parameters.token);
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Accept your invitation</title>
+            </head>
+            <body>
+                {msg_html}
+                <p>You've been invited as {email}. Choose a username and password to get started.</p>
+                <form action="/invitations/accept" method="post">
+                    <input type="hidden" name="token" value="{token}">
+                    <label>Username
+                        <input type="text" placeholder="Enter a username" name="username">
+                    </label>
+                    <br>
+                    <label>Password
+                        <input type="password" placeholder="Enter a password" name="new_password">
+                    </label>
+                    <br>
+                    <label>Confirm password
+                        <input
+                            type="password"
+                            placeholder="Type the password again"
+                            name="new_password_check"
+                        >
+                    </label>
+                    <br>
+                    <button type="submit">Create account</button>
+                </form>
+            </body>
+            </html>"#
+        )))
+}
@@ -0,0 +1,65 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use secrecy::SecretString;
+use sqlx::PgPool;
+
+use crate::authentication::{self, AcceptInvitationOutcome, validate_new_password};
+use crate::configuration::PasswordHashingSettings;
+use crate::domain::Username;
+use crate::utils::{e500, see_other};
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    token: String,
+    username: String,
+    new_password: SecretString,
+    new_password_check: SecretString,
+}
+
+#[tracing::instrument(name = "Accept an invitation", skip(form, pool, hashing))]
+pub async fn accept_invitation(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    hashing: web::Data<PasswordHashingSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let form = form.0;
+    // back to the form, which keeps working until the invitation is used
+    let retry = || {
+        see_other(&format!(
+            "/invitations/accept?token={}",
+            urlencoding::encode(&form.token)
+        ))
+    };
+    let username = match Username::parse(form.username.clone()) {
+        Ok(username) => username,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(retry());
+        }
+    };
+    if let Err(e) = validate_new_password(&form.new_password, &form.new_password_check) {
+        FlashMessage::error(e).send();
+        return Ok(retry());
+    }
+
+    let outcome = authentication::accept_invitation(
+        &pool,
+        &form.token,
+        &username,
+        form.new_password.clone(),
+        &hashing,
+    )
+    .await
+    .map_err(e500)?;
+    match outcome {
+        AcceptInvitationOutcome::Accepted => {
+            FlashMessage::info("Your account has been created, you can log in now.").send();
+            Ok(see_other("/login"))
+        }
+        AcceptInvitationOutcome::InvalidToken => Ok(retry()),
+        AcceptInvitationOutcome::UsernameTaken => {
+            FlashMessage::error("That username is already taken.").send();
+            Ok(retry())
+        }
+    }
+}
@@ -2,7 +2,10 @@ use actix_web::{HttpResponse, http::header::ContentType};
 use actix_web_flash_messages::IncomingFlashMessages;
 use std::fmt::Write;
 
-pub async fn login_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
+use crate::authentication::CsrfToken;
+
+pub async fn login_form(flash_messages: IncomingFlashMessages, csrf: CsrfToken) -> HttpResponse {
+    let csrf_field = csrf.form_field();
     let mut error_html = String::new();
     for m in flash_messages.iter() {
         writeln!(error_html, "<p><i>{}</i></p>", m.content()).unwrap();
@@ -19,6 +22,7 @@ pub async fn login_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
             <body>
                 {error_html}
                 <form action="/login" method="post">
+                    {csrf_field}
                     <label>Username
                         <input
                             type="text"
@@ -35,6 +39,7 @@ pub async fn login_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
                     </label>
                     <button type="submit">Login</button>
                 </form>
+                <p><a href="/password-reset">Forgot your password?</a></p>
             </body>
         </html>"#,
         ))
@@ -1,9 +1,20 @@
-use actix_web::{HttpResponse, error::InternalError, http::header::LOCATION, web};
+use actix_web::{
+    HttpRequest, HttpResponse,
+    error::InternalError,
+    http::header::{LOCATION, USER_AGENT},
+    web,
+};
 use actix_web_flash_messages::FlashMessage;
 use secrecy::SecretString;
 use sqlx::PgPool;
 
-use crate::authentication::{AuthError, Credentials, validate_credentials};
+use crate::authentication::{
+    AuthError, Credentials, DummyPasswordHash, start_session, two_factor_enabled,
+    validate_credentials,
+};
+use crate::client_ip::client_ip;
+use crate::configuration::PasswordHashingSettings;
+use crate::login_protection::{LoginProtection, record_failed_login};
 use crate::session_state::TypedSession;
 use crate::utils::error_chain_fmt;
 
@@ -17,6 +28,8 @@ pub struct FormData {
 pub enum LoginError {
     #[error("Authentication failed")]
     AuthError(#[source] anyhow::Error),
+    #[error("Too many failed attempts, please try again later.")]
+    TooManyAttempts,
     #[error("Something went wrong")]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -28,33 +41,73 @@ impl std::fmt::Debug for LoginError {
 }
 
 #[tracing::instrument(
-    skip(form, pool, session),
+    skip(request, form, pool, session, login_protection, hashing, dummy_hash),
     fields(username=tracing::field::Empty, user_id=tracing::field::Empty)
 )]
 pub async fn login(
+    request: HttpRequest,
     form: web::Form<FormData>,
     pool: web::Data<PgPool>,
     session: TypedSession,
+    login_protection: web::Data<LoginProtection>,
+    hashing: web::Data<PasswordHashingSettings>,
+    dummy_hash: web::Data<DummyPasswordHash>,
 ) -> Result<HttpResponse, InternalError<LoginError>> {
+    let username = form.0.username;
+    let ip = client_ip(&request);
+    // turned away before the password is hashed, so a flood of guesses stays cheap
+    if login_protection
+        .is_locked(&username, &ip)
+        .await
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?
+    {
+        return Err(login_redirect(LoginError::TooManyAttempts));
+    }
     let credentials = Credentials {
-        username: form.0.username,
+        username: username.clone(),
         password: form.0.password,
     };
 
-    match validate_credentials(credentials, &pool).await {
+    match validate_credentials(credentials, &pool, &hashing, &dummy_hash).await {
         Ok(user_id) => {
             tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+            let second_factor = two_factor_enabled(&pool, user_id)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
             session.renew();
-            session
-                .insert_user_id(user_id)
-                .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+            // the session only counts as logged in once the second factor is in
+            let location = if second_factor {
+                session
+                    .insert_pending_user_id(user_id)
+                    .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+                "/login/two-factor"
+            } else {
+                // with a second factor the failures are only cleared once the code is in too
+                login_protection
+                    .record_success(&username)
+                    .await
+                    .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+                let session_id = start_session(&pool, user_id, &ip, &user_agent(&request))
+                    .await
+                    .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+                session
+                    .insert_user_id(user_id)
+                    .and_then(|_| session.insert_session_id(session_id))
+                    .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+                "/admin/dashboard"
+            };
             Ok(HttpResponse::SeeOther()
-                .insert_header((LOCATION, "/admin/dashboard"))
+                .insert_header((LOCATION, location))
                 .finish())
         }
         Err(e) => {
             let e = match e {
-                AuthError::InvalidCredentials(_) => LoginError::AuthError(e.into()),
+                AuthError::InvalidCredentials(_) => {
+                    record_failed_login(&login_protection, &pool, &username, &ip)
+                        .await
+                        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+                    LoginError::AuthError(e.into())
+                }
                 AuthError::UnexpectedError(_) => LoginError::UnexpectedError(e.into()),
             };
             Err(login_redirect(e))
@@ -62,6 +115,16 @@ pub async fn login(
     }
 }
 
+// shown on the sessions page, so users can tell their devices apart
+pub(super) fn user_agent(request: &HttpRequest) -> String {
+    request
+        .headers()
+        .get(USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("unknown")
+        .to_string()
+}
+
 // redirect to the login page with an error message
 fn login_redirect(e: LoginError) -> InternalError<LoginError> {
     FlashMessage::error(e.to_string()).send();
@@ -0,0 +1,50 @@
+use actix_web::{HttpResponse, http::header::ContentType};
+use actix_web_flash_messages::IncomingFlashMessages;
+use std::fmt::Write;
+
+use crate::authentication::CsrfToken;
+use crate::session_state::TypedSession;
+use crate::utils::{e500, see_other};
+
+pub async fn two_factor_login_form(
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+    csrf: CsrfToken,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_field = csrf.form_field();
+    if session.get_pending_user_id().map_err(e500)?.is_none() {
+        return Ok(see_other("/login"));
+    }
+    let mut error_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(error_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Two-factor authentication</title>
+            </head>
+            <body>
+                {error_html}
+                <form action="/login/two-factor" method="post">
+                    {csrf_field}
+                    <label>Code from your authenticator app, or a recovery code
+                        <input
+                            type="text"
+                            inputmode="numeric"
+                            autocomplete="one-time-code"
+                            placeholder="Enter code"
+                            name="code"
+                        >
+                    </label>
+                    <button type="submit">Verify</button>
+                </form>
+                <p><a href="/login">Log in as someone else</a></p>
+            </body>
+        </html>"#,
+        )))
+}
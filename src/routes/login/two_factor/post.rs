@@ -0,0 +1,66 @@
+use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+
+use crate::authentication::{get_username, start_session, verify_second_factor};
+use crate::client_ip::client_ip;
+use crate::login_protection::{LoginProtection, record_failed_login};
+use crate::routes::login::post::user_agent;
+use crate::session_state::TypedSession;
+use crate::utils::{e500, see_other};
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    code: String,
+}
+
+#[tracing::instrument(
+    name = "Verify second factor at login",
+    skip(request, form, pool, session, login_protection),
+    fields(user_id=tracing::field::Empty)
+)]
+pub async fn two_factor_login(
+    request: HttpRequest,
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    session: TypedSession,
+    login_protection: web::Data<LoginProtection>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(user_id) = session.get_pending_user_id().map_err(e500)? else {
+        return Ok(see_other("/login"));
+    };
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+    // guessing codes counts against the same limits as guessing passwords
+    let username = get_username(user_id, &pool).await.map_err(e500)?;
+    let ip = client_ip(&request);
+    if login_protection
+        .is_locked(&username, &ip)
+        .await
+        .map_err(e500)?
+    {
+        FlashMessage::error("Too many failed attempts, please try again later.").send();
+        return Ok(see_other("/login/two-factor"));
+    }
+    if !verify_second_factor(&pool, user_id, &form.0.code)
+        .await
+        .map_err(e500)?
+    {
+        record_failed_login(&login_protection, &pool, &username, &ip)
+            .await
+            .map_err(e500)?;
+        FlashMessage::error("That code is not valid.").send();
+        return Ok(see_other("/login/two-factor"));
+    }
+    login_protection
+        .record_success(&username)
+        .await
+        .map_err(e500)?;
+    let session_id = start_session(&pool, user_id, &ip, &user_agent(&request))
+        .await
+        .map_err(e500)?;
+    session.renew();
+    session.remove_pending_user_id();
+    session.insert_user_id(user_id).map_err(e500)?;
+    session.insert_session_id(session_id).map_err(e500)?;
+    Ok(see_other("/admin/dashboard"))
+}
@@ -1,13 +1,24 @@
 mod admin;
+pub mod api;
 mod health_check;
 mod home;
+mod invitations;
 mod login;
+mod password_reset;
+mod subscribe_widget;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_data;
+mod webhooks;
 
 pub use admin::*;
 pub use health_check::*;
 pub use home::*;
+pub use invitations::*;
 pub use login::*;
+pub use password_reset::*;
+pub use subscribe_widget::*;
 pub use subscriptions::*;
 pub use subscriptions_confirm::*;
+pub use subscriptions_data::*;
+pub use webhooks::*;
@@ -0,0 +1,101 @@
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use htmlescape::encode_attribute;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::authentication::password_reset_is_pending;
+use crate::utils::e500;
+
+#[derive(serde::Deserialize)]
+pub struct ResetParameters {
+    token: String,
+}
+
+pub async fn password_reset_request_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Forgot your password?</title>
+            </head>
+            <body>
+                {msg_html}
+                <p>We'll email a link to reset your password to the address on your account.</p>
+                <form action="/password-reset" method="post">
+                    <label>Username
+                        <input type="text" placeholder="Enter Username" name="username">
+                    </label>
+                    <button type="submit">Send me the link</button>
+                </form>
+                <p><a href="/login">&lt;- Back to login</a></p>
+            </body>
+            </html>"#
+        ))
+}
+
+#[tracing::instrument(
+    name = "Show the password reset form",
+    skip(flash_messages, parameters, pool)
+)]
+pub async fn password_reset_form(
+    flash_messages: IncomingFlashMessages,
+    parameters: web::Query<ResetParameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if !password_reset_is_pending(&pool, &parameters.token)
+        .await
+        .map_err(e500)?
+    {
+        return Ok(HttpResponse::NotFound()
+            .content_type(ContentType::html())
+            .body("<p>This reset link is invalid, has expired or has already been used.</p>"));
+    }
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let token = encode_attribute(&parameters.token);
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Reset your password</title>
+            </head>
+            <body>
+                {msg_html}
+                <form action="/password-reset/confirm" method="post">
+                    <input type="hidden" name="token" value="{token}">
+                    <label>New password
+                        <input
+                            type="password"
+                            placeholder="Enter new password"
+                            name="new_password"
+                        >
+                    </label>
+                    <br>
+                    <label>Confirm new password
+                        <input
+                            type="password"
+                            placeholder="Type the new password again"
+                            name="new_password_check"
+                        >
+                    </label>
+                    <br>
+                    <button type="submit">Reset password</button>
+                </form>
+            </body>
+            </html>"#
+        )))
+}
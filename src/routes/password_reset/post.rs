@@ -0,0 +1,151 @@
+use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use secrecy::SecretString;
+use sqlx::PgPool;
+use tracing::Instrument;
+
+use crate::authentication::{
+    self, PASSWORD_RESET_LIFETIME_MINUTES, create_password_reset, validate_new_password,
+};
+use crate::client_ip::client_ip;
+use crate::configuration::PasswordHashingSettings;
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::link_request_protection::LinkRequestProtection;
+use crate::startup::ApplicationBaseUrl;
+use crate::suppression::is_suppressed;
+use crate::utils::{e500, see_other};
+
+// names this form's counters among the link request limits
+const PASSWORD_RESET_FORM: &str = "password-reset";
+
+#[derive(serde::Deserialize)]
+pub struct RequestFormData {
+    username: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ResetFormData {
+    token: String,
+    new_password: SecretString,
+    new_password_check: SecretString,
+}
+
+// the response is the same, and as quick, whether or not the username exists,
+// so the form can't be used to find out who has an account
+#[tracing::instrument(
+    name = "Request a password reset",
+    skip(request, form, pool, email_client, base_url, link_request_protection)
+)]
+pub async fn request_password_reset(
+    request: HttpRequest,
+    form: web::Form<RequestFormData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    link_request_protection: web::Data<LinkRequestProtection>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let username = form.0.username.trim().to_string();
+    if !link_request_protection
+        .allow_ip(PASSWORD_RESET_FORM, &client_ip(&request))
+        .await
+        .map_err(e500)?
+    {
+        FlashMessage::error("Too many password reset requests, please try again later.").send();
+        return Ok(see_other("/password-reset"));
+    }
+    // a username over its limit gets the usual answer, just no more emails
+    if link_request_protection
+        .allow_recipient(PASSWORD_RESET_FORM, &username)
+        .await
+        .map_err(e500)?
+    {
+        tokio::spawn(
+            async move {
+                if let Err(e) = send_reset_link(&pool, &email_client, &base_url, &username).await {
+                    tracing::error!(
+                        error.cause_chain = ?e,
+                        error.message = %e,
+                        "Failed to send a password reset email",
+                    );
+                }
+            }
+            .in_current_span(),
+        );
+    } else {
+        tracing::info!("Not sending another password reset email for the same username");
+    }
+    FlashMessage::info(
+        "If that account has an email address, we've sent it a link to reset the password.",
+    )
+    .send();
+    Ok(see_other("/login"))
+}
+
+// runs after the response has gone out
+async fn send_reset_link(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &ApplicationBaseUrl,
+    username: &str,
+) -> Result<(), anyhow::Error> {
+    let Some(reset) = create_password_reset(pool, username).await? else {
+        return Ok(());
+    };
+    if is_suppressed(pool, &reset.email)
+        .await
+        .context("Failed to check the suppression list")?
+    {
+        return Ok(());
+    }
+    // addresses stored on users were validated when they were invited
+    let email = SubscriberEmail::parse(reset.email).map_err(anyhow::Error::msg)?;
+    let link = format!(
+        "{}/password-reset/confirm?token={}",
+        base_url.0,
+        urlencoding::encode(&reset.token)
+    );
+    let plain_body = format!(
+        "Visit {} to choose a new password.\n\
+        The link is valid for {} minutes. If you didn't ask for it, you can ignore this email.",
+        link, PASSWORD_RESET_LIFETIME_MINUTES
+    );
+    let html_body = format!(
+        "Click <a href=\"{}\">here</a> to choose a new password.<br />\
+        The link is valid for {} minutes. If you didn't ask for it, you can ignore this email.",
+        link, PASSWORD_RESET_LIFETIME_MINUTES
+    );
+    email_client
+        .send_email(&email, "Reset your password", &html_body, &plain_body)
+        .await
+        .context("Failed to send a password reset email.")
+}
+
+#[tracing::instrument(name = "Reset a password", skip(form, pool, hashing))]
+pub async fn reset_password(
+    form: web::Form<ResetFormData>,
+    pool: web::Data<PgPool>,
+    hashing: web::Data<PasswordHashingSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let form = form.0;
+    // back to the form, which keeps working until the link is used
+    let retry = || {
+        see_other(&format!(
+            "/password-reset/confirm?token={}",
+            urlencoding::encode(&form.token)
+        ))
+    };
+    if let Err(e) = validate_new_password(&form.new_password, &form.new_password_check) {
+        FlashMessage::error(e).send();
+        return Ok(retry());
+    }
+    if !authentication::reset_password(&pool, &form.token, form.new_password.clone(), &hashing)
+        .await
+        .map_err(e500)?
+    {
+        return Ok(retry());
+    }
+    FlashMessage::info("Your password has been reset, you can log in now.").send();
+    Ok(see_other("/login"))
+}
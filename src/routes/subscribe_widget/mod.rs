@@ -0,0 +1,9 @@
+use actix_web::HttpResponse;
+
+// the embeddable signup form, see the comment at the top of the script for the snippet
+pub async fn subscribe_widget() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("application/javascript; charset=utf-8")
+        .insert_header(("Cache-Control", "public, max-age=3600"))
+        .body(include_str!("subscribe.js"))
+}
@@ -0,0 +1,100 @@
+// embeddable newsletter signup form. paste this where the form should appear:
+//
+//   <div data-newsletter-subscribe data-tags="landing-page"></div>
+//   <script src="https://<newsletter host>/subscriptions/widget.js" defer></script>
+//
+// the page's origin has to be in the `cors.allowed_origins` setting.
+// `data-tags` is optional, a comma-separated list of tags for new subscribers
+(function () {
+    "use strict";
+
+    var script = document.currentScript;
+    var endpoint = new URL("/subscriptions", script ? script.src : window.location.href).href;
+
+    function input(type, name, label) {
+        var wrapper = document.createElement("label");
+        wrapper.textContent = label + " ";
+        var field = document.createElement("input");
+        field.type = type;
+        field.name = name;
+        field.required = true;
+        wrapper.appendChild(field);
+        return wrapper;
+    }
+
+    // people never see this field, bots fill it in
+    function honeypot() {
+        var field = document.createElement("input");
+        field.type = "text";
+        field.name = "website";
+        field.tabIndex = -1;
+        field.autocomplete = "off";
+        field.setAttribute("aria-hidden", "true");
+        field.style.position = "absolute";
+        field.style.left = "-10000px";
+        return field;
+    }
+
+    function render(container) {
+        var form = document.createElement("form");
+        var status = document.createElement("p");
+        var button = document.createElement("button");
+        button.type = "submit";
+        button.textContent = "Subscribe";
+        form.appendChild(input("text", "name", "Name"));
+        form.appendChild(input("email", "email", "Email"));
+        form.appendChild(honeypot());
+        form.appendChild(button);
+        form.appendChild(status);
+
+        form.addEventListener("submit", function (event) {
+            event.preventDefault();
+            var body = {
+                name: form.elements.name.value,
+                email: form.elements.email.value,
+                website: form.elements.website.value
+            };
+            if (container.dataset.tags) {
+                body.tags = container.dataset.tags;
+            }
+            button.disabled = true;
+            status.textContent = "";
+            fetch(endpoint, {
+                method: "POST",
+                headers: {
+                    "Content-Type": "application/json",
+                    "Accept": "application/json"
+                },
+                body: JSON.stringify(body)
+            })
+                .then(function (response) {
+                    return response.json().then(function (json) {
+                        if (response.ok) {
+                            form.reset();
+                            status.textContent = "Thanks! Check your inbox to confirm your subscription.";
+                        } else {
+                            status.textContent = json.error.message;
+                        }
+                    });
+                })
+                .catch(function () {
+                    status.textContent = "Something went wrong, please try again later.";
+                })
+                .then(function () {
+                    button.disabled = false;
+                });
+        });
+        container.appendChild(form);
+    }
+
+    function init() {
+        var containers = document.querySelectorAll("[data-newsletter-subscribe]");
+        Array.prototype.forEach.call(containers, render);
+    }
+
+    if (document.readyState === "loading") {
+        document.addEventListener("DOMContentLoaded", init);
+    } else {
+        init();
+    }
+})();
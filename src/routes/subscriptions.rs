@@ -1,32 +1,88 @@
-use actix_web::{HttpResponse, ResponseError, http::StatusCode, web};
+use actix_web::http::header::ACCEPT;
+use actix_web::{
+    HttpMessage, HttpRequest, HttpResponse, ResponseError, error::InternalError, http::StatusCode,
+    web,
+};
 use anyhow::Context;
 use chrono::Utc;
 use rand::{Rng, distr::Alphanumeric, rng};
 use sqlx::{Executor, PgPool, Postgres, Transaction};
+use std::collections::HashMap;
 use uuid::Uuid;
 
 use crate::{
-    domain::{NewSubscriber, SubscriberEmail, SubscriberName},
+    client_ip::client_ip,
+    custom_fields::{list_field_definitions, store_field_values},
+    domain::{
+        CustomFieldDefinition, NewSubscriber, SubscriberEmail, SubscriberName, SubscriberTag,
+    },
     email_client::EmailClient,
+    email_queue::QueuedEmail,
+    segmentation::add_tags,
+    signup_protection::SignupProtection,
     startup::ApplicationBaseUrl,
-    utils::error_chain_fmt,
+    suppression::is_suppressed,
+    utils::{error_chain_fmt, json_error},
 };
 
+// signup forms include this field, hidden from people, so anything that fills it in is a bot
+pub const HONEYPOT_FIELD: &str = "website";
+
 // --- SECTION: structs and implementations ---
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, utoipa::ToSchema)]
 pub struct FormData {
     pub email: String,
     pub name: String,
+    // comma-separated, usually set by a hidden field on the signup form
+    #[serde(default)]
+    pub tags: Option<String>,
+    // any remaining form fields are candidates for admin-defined custom fields.
+    // JSON bodies can send them as numbers or booleans too
+    #[serde(flatten)]
+    pub custom_fields: HashMap<String, serde_json::Value>,
+}
+
+// form bodies only carry strings, JSON scalars are read the way they'd be typed in.
+// `None` for a null, which leaves the field unset
+fn custom_field_text(name: &str, value: &serde_json::Value) -> Result<Option<String>, String> {
+    match value {
+        serde_json::Value::Null => Ok(None),
+        serde_json::Value::String(s) => Ok(Some(s.clone())),
+        serde_json::Value::Number(n) => Ok(Some(n.to_string())),
+        serde_json::Value::Bool(b) => Ok(Some(b.to_string())),
+        _ => Err(format!("{} must be a single value.", name)),
+    }
 }
 
-impl TryFrom<FormData> for NewSubscriber {
+impl TryFrom<(FormData, &[CustomFieldDefinition])> for NewSubscriber {
     type Error = String;
 
-    fn try_from(value: FormData) -> Result<Self, Self::Error> {
+    fn try_from(
+        (value, definitions): (FormData, &[CustomFieldDefinition]),
+    ) -> Result<Self, Self::Error> {
         let name = SubscriberName::parse(value.name)?;
         let email = SubscriberEmail::parse(value.email)?;
-        Ok(Self { email, name })
+        let tags = SubscriberTag::parse_list(value.tags.as_deref().unwrap_or_default())?;
+        // unknown fields are ignored and empty ones are left unset
+        let mut custom_fields = Vec::new();
+        for definition in definitions {
+            let Some(raw) = value.custom_fields.get(&definition.name) else {
+                continue;
+            };
+            match custom_field_text(&definition.label, raw)? {
+                Some(raw) if !raw.trim().is_empty() => {
+                    custom_fields.push(definition.parse_value(&raw)?);
+                }
+                _ => {}
+            }
+        }
+        Ok(Self {
+            email,
+            name,
+            tags,
+            custom_fields,
+        })
     }
 }
 
@@ -38,6 +94,8 @@ pub struct StoreTokenError(sqlx::Error);
 pub enum SubscribeError {
     #[error("{0}")]
     ValidationError(String),
+    #[error("Too many signup attempts, please try again later.")]
+    TooManyAttempts,
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -46,6 +104,7 @@ impl ResponseError for SubscribeError {
     fn status_code(&self) -> StatusCode {
         match self {
             SubscribeError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            SubscribeError::TooManyAttempts => StatusCode::TOO_MANY_REQUESTS,
             SubscribeError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
@@ -74,6 +133,17 @@ impl std::error::Error for StoreTokenError {
     }
 }
 
+impl SubscribeError {
+    // a stable identifier for JSON clients, which shouldn't have to match on messages
+    pub fn code(&self) -> &'static str {
+        match self {
+            SubscribeError::ValidationError(_) => "validation_error",
+            SubscribeError::TooManyAttempts => "too_many_attempts",
+            SubscribeError::UnexpectedError(_) => "unexpected_error",
+        }
+    }
+}
+
 impl std::fmt::Debug for SubscribeError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         error_chain_fmt(self, f)
@@ -82,7 +152,7 @@ impl std::fmt::Debug for SubscribeError {
 
 // --- SECTION: utility functions ---
 
-fn generate_subscription_token() -> String {
+pub fn generate_subscription_token() -> String {
     let mut rng = rng();
     std::iter::repeat_with(|| rng.sample(Alphanumeric))
         .map(char::from)
@@ -90,45 +160,162 @@ fn generate_subscription_token() -> String {
         .collect()
 }
 
+fn accepts_json(request: &HttpRequest) -> bool {
+    request
+        .headers()
+        .get(ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("application/json"))
+}
+
+// embedded forms post JSON, plain HTML forms post urlencoded bodies
+fn parse_body(request: &HttpRequest, body: &[u8]) -> Result<FormData, SubscribeError> {
+    if request.content_type() == "application/json" {
+        serde_json::from_slice(body)
+            .map_err(|e| SubscribeError::ValidationError(format!("Invalid JSON body: {}", e)))
+    } else {
+        serde_urlencoded::from_bytes(body)
+            .map_err(|e| SubscribeError::ValidationError(format!("Invalid form body: {}", e)))
+    }
+}
+
+fn subscribe_error(e: SubscribeError, as_json: bool) -> InternalError<SubscribeError> {
+    let response = if as_json {
+        // the details of unexpected errors stay in our logs
+        let message = match &e {
+            SubscribeError::UnexpectedError(_) => "Something went wrong.".to_string(),
+            e => e.to_string(),
+        };
+        json_error(e.status_code(), e.code(), &message)
+    } else {
+        e.error_response()
+    };
+    InternalError::from_response(e, response)
+}
+
 // --- SECTION: web actions ---
 
 #[tracing::instrument(
     name = "Adding a new subscriber",
-    skip(form, pool, email_client, base_url),
+    skip(request, body, pool, email_client, base_url, signup_protection),
     fields(
-        subscriber_email = %form.email,
-        subscriber_name = %form.name,
+        subscriber_email = tracing::field::Empty,
+        subscriber_name = tracing::field::Empty,
     )
 )]
 pub async fn subscribe(
-    form: web::Form<FormData>,
+    request: HttpRequest,
+    body: web::Bytes,
     pool: web::Data<PgPool>,
     email_client: web::Data<EmailClient>,
     base_url: web::Data<ApplicationBaseUrl>,
-) -> Result<HttpResponse, SubscribeError> {
-    let new_subscriber = form.0.try_into().map_err(SubscribeError::ValidationError)?;
-    let mut transaction = pool
-        .begin()
-        .await
-        .context("Failed to acquire a Postgres connection from the pool")?;
+    signup_protection: web::Data<SignupProtection>,
+) -> Result<HttpResponse, InternalError<SubscribeError>> {
+    let as_json = accepts_json(&request);
+    match add_subscriber(
+        &request,
+        &body,
+        &pool,
+        &email_client,
+        &base_url,
+        &signup_protection,
+    )
+    .await
+    {
+        Ok(()) if as_json => {
+            Ok(HttpResponse::Ok().json(serde_json::json!({ "status": "pending_confirmation" })))
+        }
+        Ok(()) => Ok(HttpResponse::Ok().finish()),
+        Err(e) => Err(subscribe_error(e, as_json)),
+    }
+}
 
-    let subscriber_id = insert_subscriber(&mut transaction, &new_subscriber)
-        .await
-        .context("Failed to insert new subscriber in the database.")?;
+async fn add_subscriber(
+    request: &HttpRequest,
+    body: &[u8],
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &ApplicationBaseUrl,
+    signup_protection: &SignupProtection,
+) -> Result<(), SubscribeError> {
+    let form = parse_body(request, body)?;
+    let span = tracing::Span::current();
+    span.record("subscriber_email", tracing::field::display(&form.email));
+    span.record("subscriber_name", tracing::field::display(&form.name));
+    // look like a successful signup, so bots don't learn to leave the field alone
+    if form
+        .custom_fields
+        .get(HONEYPOT_FIELD)
+        .is_some_and(|v| match v {
+            serde_json::Value::Null => false,
+            serde_json::Value::String(v) => !v.trim().is_empty(),
+            _ => true,
+        })
+    {
+        tracing::info!("Ignoring a signup that filled in the honeypot field");
+        return Ok(());
+    }
+    let ip = client_ip(request);
+    if !signup_protection.allow_ip(&ip).await? {
+        return Err(SubscribeError::TooManyAttempts);
+    }
 
-    let subscription_token = generate_subscription_token();
+    let definitions = list_field_definitions(pool).await?;
+    let new_subscriber: NewSubscriber = (form, definitions.as_slice())
+        .try_into()
+        .map_err(SubscribeError::ValidationError)?;
+    if signup_protection.is_blocked(&new_subscriber.email) {
+        return Err(SubscribeError::ValidationError(format!(
+            "We don't accept signups from {} addresses.",
+            new_subscriber.email.domain()
+        )));
+    }
+    if !signup_protection.allow_email(&new_subscriber.email).await? {
+        return Err(SubscribeError::TooManyAttempts);
+    }
+    // signing up again looks the same as signing up
+    if register_subscriber(pool, email_client, base_url, new_subscriber)
+        .await?
+        .is_none()
+    {
+        tracing::info!("Ignoring a signup for an address that is already subscribed");
+    }
+    Ok(())
+}
 
-    store_token(&mut transaction, subscriber_id, &subscription_token)
+// stores a pending subscriber and sends them the link to confirm with.
+// an address that is still pending gets its first link again, since that email may
+// have gone missing. `None` if the address is already confirmed, in which case
+// nothing is stored or sent
+async fn register_subscriber(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &ApplicationBaseUrl,
+    new_subscriber: NewSubscriber,
+) -> Result<Option<Uuid>, SubscribeError> {
+    let mut transaction = pool
+        .begin()
         .await
-        .context("Failed to store the confirmation token for a new subscriber.")?;
-
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let (subscriber_id, subscription_token) =
+        match store_pending_subscriber(&mut transaction, &new_subscriber).await? {
+            Some(stored) => stored,
+            None => match get_pending_subscription(&mut transaction, &new_subscriber.email)
+                .await
+                .context("Failed to look up the pending subscription of an existing address.")?
+            {
+                Some(pending) => pending,
+                None => return Ok(None),
+            },
+        };
     transaction
         .commit()
         .await
         .context("Failed to commit SQL transaction to store a new subscriber.")?;
 
     send_confirmation_email(
-        &email_client,
+        pool,
+        email_client,
         new_subscriber,
         &base_url.0,
         &subscription_token,
@@ -136,35 +323,99 @@ pub async fn subscribe(
     .await
     .context("Failed to send a confirmation email.")?;
 
-    Ok(HttpResponse::Ok().finish())
+    Ok(Some(subscriber_id))
+}
+
+// the subscriber's id and the token they confirm with, whether they signed up
+// themselves or were added through the api. nothing is committed or sent.
+// `None` if the address is already subscribed
+pub async fn store_pending_subscriber(
+    transaction: &mut Transaction<'static, Postgres>,
+    new_subscriber: &NewSubscriber,
+) -> Result<Option<(Uuid, String)>, anyhow::Error> {
+    let Some(subscriber_id) = insert_subscriber(transaction, new_subscriber)
+        .await
+        .context("Failed to insert new subscriber in the database.")?
+    else {
+        return Ok(None);
+    };
+
+    add_tags(&mut **transaction, subscriber_id, &new_subscriber.tags)
+        .await
+        .context("Failed to store the tags of a new subscriber.")?;
+
+    store_field_values(
+        &mut **transaction,
+        subscriber_id,
+        &new_subscriber.custom_fields,
+    )
+    .await
+    .context("Failed to store the custom fields of a new subscriber.")?;
+
+    let subscription_token = generate_subscription_token();
+
+    store_token(transaction, subscriber_id, &subscription_token)
+        .await
+        .context("Failed to store the confirmation token for a new subscriber.")?;
+
+    Ok(Some((subscriber_id, subscription_token)))
 }
 
+// suppressed addresses are silently skipped, the caller can't tell them apart
+// from a successful send
 #[tracing::instrument(
     name = "Send a confirmation email to a new subscriber",
-    skip(email_client, new_subscriber, base_url)
+    skip(pool, email_client, new_subscriber, base_url)
 )]
 pub async fn send_confirmation_email(
+    pool: &PgPool,
     email_client: &EmailClient,
     new_subscriber: NewSubscriber,
     base_url: &str,
     subscription_token: &str,
-) -> Result<(), reqwest::Error> {
+) -> Result<(), anyhow::Error> {
+    if is_suppressed(pool, new_subscriber.email.as_ref())
+        .await
+        .context("Failed to check the suppression list")?
+    {
+        tracing::info!("Not sending a confirmation email to a suppressed address");
+        return Ok(());
+    }
+    let email = confirmation_email(&new_subscriber.email, base_url, subscription_token);
+    email_client
+        .send_email(
+            &new_subscriber.email,
+            &email.subject,
+            &email.html_body,
+            &email.text_body,
+        )
+        .await?;
+    Ok(())
+}
+
+// also queued for the background worker by bulk imports
+pub fn confirmation_email(
+    recipient: &SubscriberEmail,
+    base_url: &str,
+    subscription_token: &str,
+) -> QueuedEmail {
     let confirmation_link = format!(
         "{}/subscriptions/confirm?subscription_token={}",
         base_url, subscription_token,
     );
-    let plain_body = format!(
-        "Welcome to our newsletter!\nVisit {} to confirm your subscription.",
-        confirmation_link
-    );
-    let html_body = format!(
-        "Welcome to our newsletter!<br />\
-        Click <a href=\"{}\">here</a> to confirm your subscription.",
-        confirmation_link
-    );
-    email_client
-        .send_email(&new_subscriber.email, "Welcome!", &html_body, &plain_body)
-        .await
+    QueuedEmail {
+        recipient: recipient.as_ref().to_owned(),
+        subject: "Welcome!".into(),
+        html_body: format!(
+            "Welcome to our newsletter!<br />\
+            Click <a href=\"{}\">here</a> to confirm your subscription.",
+            confirmation_link
+        ),
+        text_body: format!(
+            "Welcome to our newsletter!\nVisit {} to confirm your subscription.",
+            confirmation_link
+        ),
+    }
 }
 
 // --- SECTION: database actions ---
@@ -179,8 +430,8 @@ pub async fn store_token(
     subscription_token: &str,
 ) -> Result<(), StoreTokenError> {
     let query = sqlx::query!(
-        r#"INSERT INTO subscription_tokens (subscription_token, subscriber_id)
-        VALUES ($1, $2)"#,
+        r#"INSERT INTO subscription_tokens (subscription_token, subscriber_id, created_at)
+        VALUES ($1, $2, now())"#,
         subscription_token,
         subscriber_id
     );
@@ -192,6 +443,7 @@ pub async fn store_token(
     Ok(())
 }
 
+// `None` if the address is already subscribed, however it's cased
 #[tracing::instrument(
     name = "Saving new subscriber details in the database",
     skip(transaction, new_subscriber)
@@ -199,13 +451,14 @@ pub async fn store_token(
 pub async fn insert_subscriber(
     transaction: &mut Transaction<'_, Postgres>,
     new_subscriber: &NewSubscriber,
-) -> Result<Uuid, sqlx::Error> {
+) -> Result<Option<Uuid>, sqlx::Error> {
     // why can't sqlx::query see this??
     let subscriber_id = Uuid::new_v4();
     let query = sqlx::query!(
         r#"
     INSERT INTO subscriptions (id, email, name, subscribed_at, status)
     VALUES($1, $2, $3, $4, 'pending_confirmation')
+    ON CONFLICT DO NOTHING
         "#,
         subscriber_id,
         new_subscriber.email.as_ref(),
@@ -213,6 +466,27 @@ pub async fn insert_subscriber(
         Utc::now()
     );
 
-    transaction.execute(query).await?;
-    Ok(subscriber_id)
+    let inserted = transaction.execute(query).await?.rows_affected();
+    Ok((inserted > 0).then_some(subscriber_id))
+}
+
+// the first confirmation token of an address that hasn't confirmed yet
+async fn get_pending_subscription(
+    transaction: &mut Transaction<'_, Postgres>,
+    email: &SubscriberEmail,
+) -> Result<Option<(Uuid, String)>, sqlx::Error> {
+    let pending = sqlx::query!(
+        r#"
+        SELECT s.id, t.subscription_token
+        FROM subscriptions s
+        JOIN subscription_tokens t ON t.subscriber_id = s.id
+        WHERE lower(s.email) = lower($1) AND s.status = 'pending_confirmation'
+        ORDER BY t.created_at
+        LIMIT 1
+        "#,
+        email.as_ref()
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+    Ok(pending.map(|r| (r.id, r.subscription_token)))
 }
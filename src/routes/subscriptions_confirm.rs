@@ -70,7 +70,7 @@ pub async fn confirm_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<()
     }    
     
     sqlx::query!(
-        r#"UPDATE subscriptions SET status = 'confirmed' WHERE id = $1"#,
+        r#"UPDATE subscriptions SET status = 'confirmed', confirmed_at = now() WHERE id = $1"#,
         subscriber_id,
     )
     .execute(pool)
@@ -0,0 +1,227 @@
+use actix_web::http::header::{ContentDisposition, ContentType, DispositionParam, DispositionType};
+use actix_web::{HttpRequest, HttpResponse, ResponseError, http::StatusCode, web};
+use anyhow::Context;
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use tracing::Instrument;
+
+use crate::{
+    client_ip::client_ip,
+    domain::SubscriberEmail,
+    email_client::EmailClient,
+    link_request_protection::LinkRequestProtection,
+    startup::{ApplicationBaseUrl, HmacSecret},
+    subscriber_data::{
+        SubscriberData, collect_subscriber_data, sign_data_request, verify_data_request,
+    },
+    suppression::is_suppressed,
+    utils::error_chain_fmt,
+};
+
+// how long the link in a data access email stays valid
+const LINK_LIFETIME_HOURS: i64 = 24;
+// keeps this form's request counters apart from the password reset form's
+const DATA_REQUEST_FORM: &str = "data";
+
+// --- SECTION: structs and implementations ---
+
+#[derive(serde::Deserialize)]
+pub struct DataRequestFormData {
+    email: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct DataLinkParameters {
+    email: String,
+    expires: i64,
+    signature: String,
+}
+
+// --- SECTION: errors ---
+
+#[derive(thiserror::Error)]
+pub enum SubscriberDataError {
+    #[error("{0}")]
+    ValidationError(String),
+    #[error("{0}")]
+    InvalidLink(String),
+    #[error("No data is stored about this email address.")]
+    NotFound,
+    #[error("Too many requests, please try again later.")]
+    TooManyRequests,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl ResponseError for SubscriberDataError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            SubscriberDataError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            SubscriberDataError::InvalidLink(_) => StatusCode::UNAUTHORIZED,
+            SubscriberDataError::NotFound => StatusCode::NOT_FOUND,
+            SubscriberDataError::TooManyRequests => StatusCode::TOO_MANY_REQUESTS,
+            SubscriberDataError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl std::fmt::Debug for SubscriberDataError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+// --- SECTION: utility functions ---
+
+// shared with the admin download, so both hand over the same document
+pub fn subscriber_data_response(data: &SubscriberData) -> Result<HttpResponse, anyhow::Error> {
+    let body = serde_json::to_string_pretty(data).context("Failed to serialize subscriber data")?;
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::json())
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename("subscriber-data.json".into())],
+        })
+        .body(body))
+}
+
+// --- SECTION: web actions ---
+
+pub async fn data_request_form() -> HttpResponse {
+    HttpResponse::Ok().content_type(ContentType::html()).body(
+        r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Request your data</title>
+            </head>
+            <body>
+                <p>We'll email you a link to download everything we store about your address.</p>
+                <form action="/subscriptions/data/request" method="post">
+                    <label>Email
+                        <input type="email" placeholder="Enter your email" name="email">
+                    </label>
+                    <button type="submit">Send me the link</button>
+                </form>
+            </body>
+            </html>"#,
+    )
+}
+
+// the response is the same, and as quick, whether or not we know the address,
+// so the form can't be used to find out who is subscribed
+#[tracing::instrument(
+    name = "Request subscriber data",
+    skip(
+        request,
+        form,
+        pool,
+        email_client,
+        base_url,
+        hmac_secret,
+        link_request_protection
+    )
+)]
+pub async fn request_subscriber_data(
+    request: HttpRequest,
+    form: web::Form<DataRequestFormData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    hmac_secret: web::Data<HmacSecret>,
+    link_request_protection: web::Data<LinkRequestProtection>,
+) -> Result<HttpResponse, SubscriberDataError> {
+    let email =
+        SubscriberEmail::parse(form.0.email).map_err(SubscriberDataError::ValidationError)?;
+    if !link_request_protection
+        .allow_ip(DATA_REQUEST_FORM, &client_ip(&request))
+        .await?
+    {
+        return Err(SubscriberDataError::TooManyRequests);
+    }
+    // an address over its limit gets the usual answer, just no more emails
+    if link_request_protection
+        .allow_recipient(DATA_REQUEST_FORM, email.as_ref())
+        .await?
+    {
+        tokio::spawn(
+            async move {
+                if let Err(e) =
+                    send_data_link(&pool, &email_client, &base_url, &hmac_secret, email).await
+                {
+                    tracing::error!(
+                        error.cause_chain = ?e,
+                        error.message = %e,
+                        "Failed to send a data access email",
+                    );
+                }
+            }
+            .in_current_span(),
+        );
+    } else {
+        tracing::info!("Not sending another data access email to the same address");
+    }
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body("<p>If we store any data about this address, we've emailed it a download link.</p>"))
+}
+
+// runs after the response has gone out
+async fn send_data_link(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &ApplicationBaseUrl,
+    hmac_secret: &HmacSecret,
+    email: SubscriberEmail,
+) -> Result<(), anyhow::Error> {
+    let is_known = collect_subscriber_data(pool, email.as_ref())
+        .await?
+        .is_some();
+    let is_suppressed = is_suppressed(pool, email.as_ref())
+        .await
+        .context("Failed to check the suppression list")?;
+    if !is_known || is_suppressed {
+        return Ok(());
+    }
+    let expires_at = Utc::now() + Duration::hours(LINK_LIFETIME_HOURS);
+    let link = format!(
+        "{}/subscriptions/data?email={}&expires={}&signature={}",
+        base_url.0,
+        urlencoding::encode(email.as_ref()),
+        expires_at.timestamp(),
+        sign_data_request(&hmac_secret.0, email.as_ref(), expires_at),
+    );
+    let plain_body = format!(
+        "Visit {} to download the data we store about you.\n\
+        The link is valid for {} hours.",
+        link, LINK_LIFETIME_HOURS
+    );
+    let html_body = format!(
+        "Click <a href=\"{}\">here</a> to download the data we store about you.<br />\
+        The link is valid for {} hours.",
+        link, LINK_LIFETIME_HOURS
+    );
+    email_client
+        .send_email(&email, "Your data", &html_body, &plain_body)
+        .await
+        .context("Failed to send a data access email.")
+}
+
+#[tracing::instrument(name = "Download subscriber data", skip(parameters, pool, hmac_secret))]
+pub async fn download_subscriber_data(
+    parameters: web::Query<DataLinkParameters>,
+    pool: web::Data<PgPool>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, SubscriberDataError> {
+    verify_data_request(
+        &hmac_secret.0,
+        &parameters.email,
+        parameters.expires,
+        &parameters.signature,
+    )
+    .map_err(SubscriberDataError::InvalidLink)?;
+    let data = collect_subscriber_data(&pool, &parameters.email)
+        .await?
+        .ok_or(SubscriberDataError::NotFound)?;
+    Ok(subscriber_data_response(&data)?)
+}
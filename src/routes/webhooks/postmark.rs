@@ -0,0 +1,264 @@
+use actix_web::http::header::{self, HeaderMap, HeaderValue};
+use actix_web::{HttpRequest, HttpResponse, ResponseError, http::StatusCode, web};
+use anyhow::Context;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use secrecy::ExposeSecret;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use subtle::ConstantTimeEq;
+use uuid::Uuid;
+
+use crate::{
+    configuration::PostmarkWebhookSettings,
+    domain::{SubscriberEmail, SuppressionReason},
+    suppression::suppress,
+    utils::error_chain_fmt,
+};
+
+// --- SECTION: structs and implementations ---
+
+// the subset of Postmark's bounce and spam complaint payloads we act on,
+// see https://postmarkapp.com/developer/webhooks/bounce-webhook
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PostmarkEvent {
+    record_type: String,
+    #[serde(rename = "ID")]
+    id: Option<i64>,
+    #[serde(rename = "Type")]
+    kind: Option<String>,
+    email: Option<String>,
+    description: Option<String>,
+    bounced_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EmailEventType {
+    HardBounce,
+    // any other bounce, e.g. a full mailbox, which doesn't warrant suppression
+    SoftBounce,
+    SpamComplaint,
+}
+
+impl EmailEventType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::HardBounce => "hard_bounce",
+            Self::SoftBounce => "soft_bounce",
+            Self::SpamComplaint => "spam_complaint",
+        }
+    }
+
+    // the subscriber status and suppression reason, for events that stop future sends
+    fn consequence(&self) -> Option<(&'static str, SuppressionReason)> {
+        match self {
+            Self::HardBounce => Some(("bounced", SuppressionReason::HardBounce)),
+            Self::SoftBounce => None,
+            Self::SpamComplaint => Some(("complained", SuppressionReason::SpamComplaint)),
+        }
+    }
+}
+
+struct EmailEvent {
+    provider_event_id: String,
+    event_type: EmailEventType,
+    email: SubscriberEmail,
+    description: Option<String>,
+    occurred_at: DateTime<Utc>,
+}
+
+impl EmailEvent {
+    // `None` for record types we don't handle, such as deliveries and opens
+    fn parse(value: PostmarkEvent) -> Result<Option<EmailEvent>, String> {
+        let event_type = match (value.record_type.as_str(), value.kind.as_deref()) {
+            ("Bounce", Some("HardBounce")) => EmailEventType::HardBounce,
+            ("Bounce", _) => EmailEventType::SoftBounce,
+            ("SpamComplaint", _) => EmailEventType::SpamComplaint,
+            _ => return Ok(None),
+        };
+        let id = value.id.ok_or("The event has no ID.")?;
+        let email = SubscriberEmail::parse(value.email.ok_or("The event has no Email.")?)?;
+        let occurred_at = match value.bounced_at {
+            Some(at) => DateTime::parse_from_rfc3339(&at)
+                .map_err(|_| format!("{} is not a valid BouncedAt timestamp.", at))?
+                .with_timezone(&Utc),
+            None => Utc::now(),
+        };
+        Ok(Some(EmailEvent {
+            provider_event_id: id.to_string(),
+            event_type,
+            email,
+            description: value.description,
+            occurred_at,
+        }))
+    }
+}
+
+// --- SECTION: errors ---
+
+#[derive(thiserror::Error)]
+pub enum WebhookError {
+    #[error("Authentication failed.")]
+    InvalidCredentials(#[source] anyhow::Error),
+    #[error("{0}")]
+    ValidationError(String),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl ResponseError for WebhookError {
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            WebhookError::InvalidCredentials(_) => {
+                let mut response = HttpResponse::new(StatusCode::UNAUTHORIZED);
+                let header_value = HeaderValue::from_str(r#"Basic realm="webhooks""#).unwrap();
+                response
+                    .headers_mut()
+                    .insert(header::WWW_AUTHENTICATE, header_value);
+                response
+            }
+            _ => HttpResponse::new(self.status_code()),
+        }
+    }
+
+    fn status_code(&self) -> StatusCode {
+        match self {
+            WebhookError::InvalidCredentials(_) => StatusCode::UNAUTHORIZED,
+            WebhookError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            WebhookError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl std::fmt::Debug for WebhookError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+// --- SECTION: utility functions ---
+
+fn basic_authentication(headers: &HeaderMap) -> Result<(String, String), anyhow::Error> {
+    let header_value = headers
+        .get("Authorization")
+        .context("The 'Authorization' header was missing")?
+        .to_str()
+        .context("The 'Authorization' header was not a valid UTF8 string.")?;
+    let base64encoded_segment = header_value
+        .strip_prefix("Basic ")
+        .context("The authorization scheme was not 'Basic'.")?;
+    let decoded_bytes = base64::engine::general_purpose::STANDARD
+        .decode(base64encoded_segment)
+        .context("Failed to base64-decode 'Basic' credentials.")?;
+    let decoded_credentials = String::from_utf8(decoded_bytes)
+        .context("The decoded credential string is not valid UTF8.")?;
+    let (username, password) = decoded_credentials
+        .split_once(':')
+        .context("The credentials were not formatted as 'username:password'.")?;
+    Ok((username.to_string(), password.to_string()))
+}
+
+// compared in constant time, so response timings don't leak the secret
+fn check_credentials(
+    headers: &HeaderMap,
+    settings: &PostmarkWebhookSettings,
+) -> Result<(), anyhow::Error> {
+    let (username, password) = basic_authentication(headers)?;
+    let username_matches = username.as_bytes().ct_eq(settings.username.as_bytes());
+    let password_matches = password
+        .as_bytes()
+        .ct_eq(settings.password.expose_secret().as_bytes());
+    if bool::from(username_matches & password_matches) {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("Invalid webhook credentials."))
+    }
+}
+
+// --- SECTION: web actions ---
+
+#[tracing::instrument(
+    name = "Handle a Postmark webhook",
+    skip(request, body, pool, settings),
+    fields(event_type = tracing::field::Empty)
+)]
+pub async fn postmark_webhook(
+    request: HttpRequest,
+    body: web::Bytes,
+    pool: web::Data<PgPool>,
+    settings: web::Data<PostmarkWebhookSettings>,
+) -> Result<HttpResponse, WebhookError> {
+    // authenticate before looking at the payload at all
+    check_credentials(request.headers(), &settings).map_err(WebhookError::InvalidCredentials)?;
+    let payload: PostmarkEvent = serde_json::from_slice(&body)
+        .map_err(|e| WebhookError::ValidationError(format!("Invalid payload: {}", e)))?;
+    let event = EmailEvent::parse(payload).map_err(WebhookError::ValidationError)?;
+    // acknowledge everything else, or Postmark keeps retrying
+    let Some(event) = event else {
+        return Ok(HttpResponse::Ok().finish());
+    };
+    tracing::Span::current().record("event_type", event.event_type.as_str());
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    // a redelivered event has already been acted on
+    if !record_event(&mut transaction, &event)
+        .await
+        .context("Failed to record the email event")?
+    {
+        return Ok(HttpResponse::Ok().finish());
+    }
+    if let Some((status, reason)) = event.event_type.consequence() {
+        transaction
+            .execute(sqlx::query!(
+                r#"
+                UPDATE subscriptions SET status = $2
+                WHERE lower(email) = lower($1) AND status <> 'erased'
+                "#,
+                event.email.as_ref(),
+                status
+            ))
+            .await
+            .context("Failed to update the subscriber status")?;
+        suppress(&mut *transaction, event.email.as_ref(), reason)
+            .await
+            .context("Failed to suppress the address")?;
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to handle an email event.")?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+// --- SECTION: database actions ---
+
+// returns whether the event is new
+#[tracing::instrument(name = "Record an email event", skip(transaction, event))]
+async fn record_event(
+    transaction: &mut Transaction<'_, Postgres>,
+    event: &EmailEvent,
+) -> Result<bool, sqlx::Error> {
+    let inserted = transaction
+        .execute(sqlx::query!(
+            r#"
+            INSERT INTO email_events (
+                id, provider, provider_event_id, event_type,
+                email, description, occurred_at, received_at
+            )
+            VALUES ($1, 'postmark', $2, $3, $4, $5, $6, now())
+            ON CONFLICT (provider, provider_event_id) DO NOTHING
+            "#,
+            Uuid::new_v4(),
+            event.provider_event_id,
+            event.event_type.as_str(),
+            event.email.as_ref(),
+            event.description,
+            event.occurred_at
+        ))
+        .await?
+        .rows_affected();
+    Ok(inserted > 0)
+}
@@ -0,0 +1,7 @@
+mod filter_sql;
+mod segments;
+mod tags;
+
+pub use filter_sql::push_segment_filter;
+pub use segments::{Segment, count_recipients, get_segment, insert_segment, list_segments};
+pub use tags::{add_tags, remove_tag, tag_counts};
@@ -0,0 +1,91 @@
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use super::push_segment_filter;
+use crate::domain::{CustomFieldDefinition, SegmentFilter};
+
+// a saved, named segment definition
+pub struct Segment {
+    pub segment_id: Uuid,
+    pub name: String,
+    pub definition: String,
+}
+
+impl Segment {
+    // definitions are validated before they are stored, so this only fails
+    // if the grammar or the custom fields have changed since the segment was saved
+    pub fn filter(
+        &self,
+        field_definitions: &[CustomFieldDefinition],
+    ) -> Result<SegmentFilter, anyhow::Error> {
+        SegmentFilter::parse(&self.definition)
+            .and_then(|filter| filter.canonicalize(field_definitions))
+            .map_err(|e| {
+                anyhow::anyhow!(
+                    "The stored definition of segment {} is invalid: {}",
+                    self.name,
+                    e
+                )
+            })
+    }
+}
+
+// callers are expected to have validated `definition` with `SegmentFilter::parse`
+#[tracing::instrument(name = "Save a new segment", skip(pool))]
+pub async fn insert_segment(
+    pool: &PgPool,
+    name: &str,
+    definition: &str,
+) -> Result<Uuid, sqlx::Error> {
+    let segment_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO segments (segment_id, name, definition, created_at)
+        VALUES ($1, $2, $3, now())
+        "#,
+        segment_id,
+        name,
+        definition,
+    )
+    .execute(pool)
+    .await?;
+    Ok(segment_id)
+}
+
+#[tracing::instrument(name = "List segments", skip(pool))]
+pub async fn list_segments(pool: &PgPool) -> Result<Vec<Segment>, sqlx::Error> {
+    sqlx::query_as!(
+        Segment,
+        r#"SELECT segment_id, name, definition FROM segments ORDER BY name"#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+#[tracing::instrument(name = "Get segment", skip(pool))]
+pub async fn get_segment(pool: &PgPool, segment_id: Uuid) -> Result<Option<Segment>, sqlx::Error> {
+    sqlx::query_as!(
+        Segment,
+        r#"SELECT segment_id, name, definition FROM segments WHERE segment_id = $1"#,
+        segment_id,
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+// how many confirmed subscribers a newsletter issue sent to this segment would reach
+#[tracing::instrument(name = "Count segment recipients", skip(pool, filter))]
+pub async fn count_recipients(
+    pool: &PgPool,
+    filter: Option<&SegmentFilter>,
+) -> Result<i64, sqlx::Error> {
+    let mut builder = QueryBuilder::<Postgres>::new(
+        "SELECT COUNT(*) FROM subscriptions s WHERE s.status = 'confirmed'",
+    );
+    if let Some(filter) = filter {
+        builder.push(" AND ");
+        push_segment_filter(&mut builder, filter);
+    }
+    let (count,): (i64,) = builder.build_query_as().fetch_one(pool).await?;
+    Ok(count)
+}
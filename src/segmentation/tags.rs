@@ -0,0 +1,65 @@
+use sqlx::{Executor, PgPool, Postgres};
+use uuid::Uuid;
+
+use crate::domain::SubscriberTag;
+
+// attach tags to a subscriber, ignoring tags they already have
+#[tracing::instrument(name = "Add tags to a subscriber", skip(executor, tags))]
+pub async fn add_tags<'c, E>(
+    executor: E,
+    subscriber_id: Uuid,
+    tags: &[SubscriberTag],
+) -> Result<(), sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    if tags.is_empty() {
+        return Ok(());
+    }
+    let tags: Vec<String> = tags.iter().map(|t| t.as_ref().to_owned()).collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriber_tags (subscriber_id, tag, tagged_at)
+        SELECT $1, tag, now()
+        FROM UNNEST($2::text[]) AS tag
+        ON CONFLICT DO NOTHING
+        "#,
+        subscriber_id,
+        &tags,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Remove a tag from a subscriber", skip(pool))]
+pub async fn remove_tag(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    tag: &SubscriberTag,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"DELETE FROM subscriber_tags WHERE subscriber_id = $1 AND tag = $2"#,
+        subscriber_id,
+        tag.as_ref(),
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+// every tag in use, along with how many subscribers carry it
+#[tracing::instrument(name = "Count subscribers per tag", skip(pool))]
+pub async fn tag_counts(pool: &PgPool) -> Result<Vec<(String, i64)>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT tag, COUNT(*) as "count!"
+        FROM subscriber_tags
+        GROUP BY tag
+        ORDER BY tag
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(|r| (r.tag, r.count)).collect())
+}
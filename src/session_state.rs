@@ -8,6 +8,12 @@ pub struct TypedSession(Session);
 
 impl TypedSession {
     const USER_ID_KEY: &'static str = "user_id";
+    // set once the password checks out, for users who still owe a second factor
+    const PENDING_USER_ID_KEY: &'static str = "pending_second_factor_user_id";
+    // sent back with every form, see `authentication::CsrfToken`
+    const CSRF_TOKEN_KEY: &'static str = "csrf_token";
+    // the session's row in `user_sessions`, looked up on every request
+    const SESSION_ID_KEY: &'static str = "session_id";
 
     pub fn renew(&self) {
         self.0.renew();
@@ -17,10 +23,38 @@ impl TypedSession {
         self.0.insert(Self::USER_ID_KEY, user_id)
     }
 
+    pub fn insert_session_id(&self, session_id: Uuid) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::SESSION_ID_KEY, session_id)
+    }
+
+    pub fn get_session_id(&self) -> Result<Option<Uuid>, SessionGetError> {
+        self.0.get(Self::SESSION_ID_KEY)
+    }
+
     pub fn get_user_id(&self) -> Result<Option<Uuid>, SessionGetError> {
         self.0.get(Self::USER_ID_KEY)
     }
 
+    pub fn insert_pending_user_id(&self, user_id: Uuid) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::PENDING_USER_ID_KEY, user_id)
+    }
+
+    pub fn get_pending_user_id(&self) -> Result<Option<Uuid>, SessionGetError> {
+        self.0.get(Self::PENDING_USER_ID_KEY)
+    }
+
+    pub fn remove_pending_user_id(&self) {
+        self.0.remove(Self::PENDING_USER_ID_KEY);
+    }
+
+    pub fn insert_csrf_token(&self, token: &str) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::CSRF_TOKEN_KEY, token)
+    }
+
+    pub fn get_csrf_token(&self) -> Result<Option<String>, SessionGetError> {
+        self.0.get(Self::CSRF_TOKEN_KEY)
+    }
+
     pub fn log_out(self) {
         self.0.purge();
     }
@@ -0,0 +1,249 @@
+use actix_session::storage::{
+    LoadError, RedisSessionStore, SaveError, SessionKey, SessionStore, UpdateError,
+    generate_session_key,
+};
+use actix_web::cookie::time::Duration;
+use anyhow::Context;
+use secrecy::{ExposeSecret, SecretString};
+use sqlx::PgPool;
+use std::collections::HashMap;
+
+use crate::authentication::delete_expired_user_sessions;
+use crate::configuration::{SessionStoreKind, SessionTimeoutSettings, Settings};
+use crate::counter_store::delete_expired_counters;
+use crate::startup::get_connection_pool;
+
+type SessionState = HashMap<String, String>;
+
+// the session middleware takes a single store type, whichever one is configured
+#[derive(Clone)]
+pub enum AppSessionStore {
+    Redis(RedisSessionStore),
+    Postgres(PgSessionStore),
+}
+
+impl AppSessionStore {
+    pub async fn build(
+        kind: SessionStoreKind,
+        redis_uri: Option<&SecretString>,
+        pool: &PgPool,
+    ) -> Result<Self, anyhow::Error> {
+        match kind {
+            SessionStoreKind::Redis => {
+                let redis_uri =
+                    redis_uri.context("`redis_uri` is required to store sessions in redis")?;
+                Ok(Self::Redis(
+                    RedisSessionStore::new(redis_uri.expose_secret()).await?,
+                ))
+            }
+            SessionStoreKind::Postgres => Ok(Self::Postgres(PgSessionStore::new(pool.clone()))),
+        }
+    }
+}
+
+impl SessionStore for AppSessionStore {
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<SessionState>, LoadError> {
+        match self {
+            Self::Redis(store) => store.load(session_key).await,
+            Self::Postgres(store) => store.load(session_key).await,
+        }
+    }
+
+    async fn save(
+        &self,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, SaveError> {
+        match self {
+            Self::Redis(store) => store.save(session_state, ttl).await,
+            Self::Postgres(store) => store.save(session_state, ttl).await,
+        }
+    }
+
+    async fn update(
+        &self,
+        session_key: SessionKey,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, UpdateError> {
+        match self {
+            Self::Redis(store) => store.update(session_key, session_state, ttl).await,
+            Self::Postgres(store) => store.update(session_key, session_state, ttl).await,
+        }
+    }
+
+    async fn update_ttl(&self, session_key: &SessionKey, ttl: &Duration) -> anyhow::Result<()> {
+        match self {
+            Self::Redis(store) => store.update_ttl(session_key, ttl).await,
+            Self::Postgres(store) => store.update_ttl(session_key, ttl).await,
+        }
+    }
+
+    async fn delete(&self, session_key: &SessionKey) -> anyhow::Result<()> {
+        match self {
+            Self::Redis(store) => store.delete(session_key).await,
+            Self::Postgres(store) => store.delete(session_key).await,
+        }
+    }
+}
+
+// sessions as rows of `session_states`. expired rows are never loaded,
+// and are deleted by the cleanup worker
+#[derive(Clone)]
+pub struct PgSessionStore {
+    pool: PgPool,
+}
+
+impl PgSessionStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+impl SessionStore for PgSessionStore {
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<SessionState>, LoadError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT state FROM session_states
+            WHERE session_key = $1 AND expires_at > now()
+            "#,
+            session_key.as_ref()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to load the session")
+        .map_err(LoadError::Other)?;
+        row.map(|r| serde_json::from_str(&r.state))
+            .transpose()
+            .context("Failed to deserialize the session")
+            .map_err(LoadError::Deserialization)
+    }
+
+    async fn save(
+        &self,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, SaveError> {
+        let state = serde_json::to_string(&session_state)
+            .context("Failed to serialize the session")
+            .map_err(SaveError::Serialization)?;
+        let session_key = generate_session_key();
+        sqlx::query!(
+            r#"
+            INSERT INTO session_states (session_key, state, expires_at)
+            VALUES ($1, $2, now() + make_interval(secs => $3))
+            "#,
+            session_key.as_ref(),
+            state,
+            ttl.as_seconds_f64()
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to save the session")
+        .map_err(SaveError::Other)?;
+        Ok(session_key)
+    }
+
+    async fn update(
+        &self,
+        session_key: SessionKey,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, UpdateError> {
+        let state = serde_json::to_string(&session_state)
+            .context("Failed to serialize the session")
+            .map_err(UpdateError::Serialization)?;
+        let updated = sqlx::query!(
+            r#"
+            UPDATE session_states
+            SET state = $2, expires_at = now() + make_interval(secs => $3)
+            WHERE session_key = $1 AND expires_at > now()
+            "#,
+            session_key.as_ref(),
+            state,
+            ttl.as_seconds_f64()
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to update the session")
+        .map_err(UpdateError::Other)?
+        .rows_affected();
+        if updated > 0 {
+            return Ok(session_key);
+        }
+        // it expired since it was loaded, so it's saved under a fresh key instead
+        self.save(session_state, ttl).await.map_err(|e| match e {
+            SaveError::Serialization(e) => UpdateError::Serialization(e),
+            SaveError::Other(e) => UpdateError::Other(e),
+        })
+    }
+
+    async fn update_ttl(&self, session_key: &SessionKey, ttl: &Duration) -> anyhow::Result<()> {
+        sqlx::query!(
+            r#"
+            UPDATE session_states
+            SET expires_at = now() + make_interval(secs => $2)
+            WHERE session_key = $1
+            "#,
+            session_key.as_ref(),
+            ttl.as_seconds_f64()
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to update the session's expiry")?;
+        Ok(())
+    }
+
+    async fn delete(&self, session_key: &SessionKey) -> anyhow::Result<()> {
+        sqlx::query!(
+            r#"DELETE FROM session_states WHERE session_key = $1"#,
+            session_key.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to delete the session")?;
+        Ok(())
+    }
+}
+
+pub async fn delete_expired_sessions(pool: &PgPool) -> Result<u64, anyhow::Error> {
+    let deleted = sqlx::query!(r#"DELETE FROM session_states WHERE expires_at <= now()"#)
+        .execute(pool)
+        .await
+        .context("Failed to delete expired sessions")?
+        .rows_affected();
+    Ok(deleted)
+}
+
+// sweeps up the expired sessions and counters that postgres, unlike redis, keeps around,
+// and the `user_sessions` rows of sessions that timed out
+pub async fn run_cleanup_worker_until_stopped(
+    configuration: Settings,
+) -> Result<(), anyhow::Error> {
+    let pool = get_connection_pool(&configuration.database);
+    loop {
+        if let Err(e) = delete_expired_state(&pool, &configuration.session_timeouts).await {
+            tracing::error!(error.cause_chain = ?e, "Failed to delete expired session state");
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+    }
+}
+
+#[tracing::instrument(name = "Delete expired session state", skip(pool, timeouts))]
+async fn delete_expired_state(
+    pool: &PgPool,
+    timeouts: &SessionTimeoutSettings,
+) -> Result<(), anyhow::Error> {
+    let sessions = delete_expired_sessions(pool).await?;
+    let counters = delete_expired_counters(pool).await?;
+    let user_sessions = delete_expired_user_sessions(pool, timeouts).await?;
+    if sessions + counters + user_sessions > 0 {
+        tracing::info!(
+            sessions,
+            counters,
+            user_sessions,
+            "Deleted expired session state"
+        );
+    }
+    Ok(())
+}
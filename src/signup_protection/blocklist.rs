@@ -0,0 +1,55 @@
+// domains we don't accept signups from, typically disposable email providers.
+// a listed domain also blocks its subdomains
+pub struct DomainBlocklist(Vec<String>);
+
+impl DomainBlocklist {
+    pub fn new(domains: &[String]) -> Self {
+        let domains = domains
+            .iter()
+            .map(|d| d.trim().trim_start_matches(['@', '.']).to_lowercase())
+            .filter(|d| !d.is_empty())
+            .collect();
+        Self(domains)
+    }
+
+    pub fn contains(&self, domain: &str) -> bool {
+        let domain = domain.trim_end_matches('.').to_lowercase();
+        self.0.iter().any(|blocked| {
+            domain == *blocked
+                || domain
+                    .strip_suffix(blocked.as_str())
+                    .is_some_and(|rest| rest.ends_with('.'))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DomainBlocklist;
+
+    fn blocklist() -> DomainBlocklist {
+        DomainBlocklist::new(&[
+            "Mailinator.com".to_string(),
+            " @yopmail.com".to_string(),
+            "".to_string(),
+        ])
+    }
+
+    #[test]
+    fn listed_domains_are_blocked_case_insensitively() {
+        assert!(blocklist().contains("mailinator.com"));
+        assert!(blocklist().contains("MAILINATOR.COM"));
+        assert!(blocklist().contains("yopmail.com"));
+    }
+
+    #[test]
+    fn subdomains_of_listed_domains_are_blocked() {
+        assert!(blocklist().contains("eu.mailinator.com"));
+    }
+
+    #[test]
+    fn domains_that_merely_end_with_a_listed_name_are_not_blocked() {
+        assert!(!blocklist().contains("notmailinator.com"));
+        assert!(!blocklist().contains("gmail.com"));
+    }
+}
@@ -0,0 +1,50 @@
+mod blocklist;
+mod rate_limiter;
+
+pub use blocklist::DomainBlocklist;
+pub use rate_limiter::RateLimiter;
+
+use crate::configuration::SignupProtectionSettings;
+use crate::counter_store::CounterStore;
+use crate::domain::SubscriberEmail;
+use crate::suppression::email_hash;
+
+// the checks `POST /subscriptions` runs before it stores anyone or sends an email
+pub struct SignupProtection {
+    rate_limiter: RateLimiter,
+    blocklist: DomainBlocklist,
+    max_signups_per_ip: u64,
+    max_signups_per_email: u64,
+}
+
+impl SignupProtection {
+    pub fn new(settings: SignupProtectionSettings, counters: CounterStore) -> Self {
+        let rate_limiter = RateLimiter::new(counters, settings.key_prefix, settings.window_seconds);
+        Self {
+            rate_limiter,
+            blocklist: DomainBlocklist::new(&settings.blocked_domains),
+            max_signups_per_ip: settings.max_signups_per_ip,
+            max_signups_per_email: settings.max_signups_per_email,
+        }
+    }
+
+    pub async fn allow_ip(&self, ip: &str) -> Result<bool, anyhow::Error> {
+        self.rate_limiter
+            .hit(&format!("ip:{}", ip), self.max_signups_per_ip)
+            .await
+    }
+
+    // keyed by the address hash, so redis never sees the address itself
+    pub async fn allow_email(&self, email: &SubscriberEmail) -> Result<bool, anyhow::Error> {
+        self.rate_limiter
+            .hit(
+                &format!("email:{}", email_hash(email.as_ref())),
+                self.max_signups_per_email,
+            )
+            .await
+    }
+
+    pub fn is_blocked(&self, email: &SubscriberEmail) -> bool {
+        self.blocklist.contains(email.domain())
+    }
+}
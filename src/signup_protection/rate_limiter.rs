@@ -0,0 +1,34 @@
+use anyhow::Context;
+
+use crate::counter_store::CounterStore;
+
+// fixed-window counters in the store that also holds the sessions
+pub struct RateLimiter {
+    counters: CounterStore,
+    // lets several deployments share a redis instance without sharing counters
+    key_prefix: String,
+    window_seconds: u64,
+}
+
+impl RateLimiter {
+    pub fn new(counters: CounterStore, key_prefix: String, window_seconds: u64) -> Self {
+        Self {
+            counters,
+            key_prefix,
+            window_seconds,
+        }
+    }
+
+    // counts an attempt against `key`, returning whether it is still within `limit`
+    // for the current window. the window starts with the first attempt
+    #[tracing::instrument(name = "Check a rate limit", skip(self))]
+    pub async fn hit(&self, key: &str, limit: u64) -> Result<bool, anyhow::Error> {
+        let key = format!("{}:{}", self.key_prefix, key);
+        let attempts = self
+            .counters
+            .increment(&key, self.window_seconds)
+            .await
+            .context("Failed to count a rate limited attempt")?;
+        Ok(attempts <= limit)
+    }
+}
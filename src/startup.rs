@@ -1,15 +1,34 @@
-use actix_session::{SessionMiddleware, storage::RedisSessionStore};
-use actix_web::{App, HttpServer, cookie::Key, dev::Server, middleware::from_fn, web, web::Data};
+use actix_cors::Cors;
+use actix_session::{SessionMiddleware, config::BrowserSession};
+use actix_web::{
+    App, HttpServer,
+    cookie::{Key, time::Duration},
+    dev::Server,
+    http::header,
+    middleware::from_fn,
+    web,
+    web::Data,
+};
 use actix_web_flash_messages::{FlashMessagesFramework, storage::CookieMessageStore};
 use secrecy::{ExposeSecret, SecretString};
 use sqlx::{PgPool, postgres::PgPoolOptions};
 use std::net::TcpListener;
 use tracing_actix_web::TracingLogger;
 
-use crate::authentication::reject_anonymous_users;
-use crate::configuration::{DatabaseSettings, Settings};
-use crate::email_client::EmailClient;
+use crate::authentication::{
+    DummyPasswordHash, reject_anonymous_users, reject_api_tokens, require_api_token,
+    require_permission, require_read_scope, verify_csrf_token,
+};
+use crate::client_ip::TrustedProxies;
+use crate::configuration::{CorsSettings, DatabaseSettings, Settings};
+use crate::counter_store::CounterStore;
+use crate::domain::Permission;
+use crate::link_request_protection::LinkRequestProtection;
+use crate::login_protection::LoginProtection;
+use crate::routes::api::{self, extractor_error, idempotent_requests};
 use crate::routes::*;
+use crate::session_store::AppSessionStore;
+use crate::signup_protection::SignupProtection;
 
 // wrapper type for SecretString
 #[derive(Clone)]
@@ -25,7 +44,6 @@ impl Application {
     pub async fn build(configuration: Settings) -> Result<Self, anyhow::Error> {
         // postgres connection pool
         let connection_pool = get_connection_pool(&configuration.database);
-        let email_client = configuration.email_client.client();
 
         let address = format!(
             "{}:{}",
@@ -34,15 +52,7 @@ impl Application {
 
         let listener = TcpListener::bind(address)?;
         let port = listener.local_addr().unwrap().port();
-        let server = run(
-            listener,
-            connection_pool,
-            email_client,
-            configuration.application.base_url,
-            configuration.application.hmac_secret,
-            configuration.redis_uri,
-        )
-        .await?;
+        let server = run(listener, connection_pool, configuration).await?;
 
         Ok(Self { port, server })
     }
@@ -63,51 +73,326 @@ pub struct ApplicationBaseUrl(pub String);
 async fn run(
     listener: TcpListener,
     db_pool: PgPool,
-    email_client: EmailClient,
-    base_url: String,
-    hmac_secret: SecretString,
-    redis_uri: SecretString,
+    configuration: Settings,
 ) -> Result<Server, anyhow::Error> {
+    let hmac_secret = configuration.application.hmac_secret;
+    let trusted_proxies = Data::new(TrustedProxies(configuration.application.trusted_proxy_hops));
+    let redis_uri = configuration.redis_uri.as_ref();
+    let session_store =
+        AppSessionStore::build(configuration.session_store, redis_uri, &db_pool).await?;
+    let counters = CounterStore::build(configuration.session_store, redis_uri, &db_pool).await?;
+    let signup_protection =
+        SignupProtection::new(configuration.signup_protection, counters.clone());
+    let link_request_protection =
+        LinkRequestProtection::new(configuration.link_request_protection, counters.clone());
+    let login_protection = LoginProtection::new(configuration.login_protection, counters);
     let db_pool = Data::new(db_pool);
-    let email_client = Data::new(email_client);
-    let base_url = Data::new(ApplicationBaseUrl(base_url));
+    let email_client = Data::new(configuration.email_client.client());
+    let base_url = Data::new(ApplicationBaseUrl(configuration.application.base_url));
+    let postmark_webhook_settings = Data::new(configuration.postmark_webhook);
+    let signup_protection = Data::new(signup_protection);
+    let login_protection = Data::new(login_protection);
+    let link_request_protection = Data::new(link_request_protection);
+    let dummy_password_hash = Data::new(DummyPasswordHash::new(&configuration.password_hashing)?);
+    let password_hashing = Data::new(configuration.password_hashing);
+    // stored session state outlives the session by no more than the absolute lifetime
+    let session_lifecycle = BrowserSession::default().state_ttl(Duration::seconds(
+        configuration.session_timeouts.absolute_seconds as i64,
+    ));
+    let session_timeouts = Data::new(configuration.session_timeouts);
+    let cors_settings = configuration.cors;
     let secret_key = Key::from(hmac_secret.expose_secret().as_bytes());
     let message_store = CookieMessageStore::builder(secret_key.clone()).build();
     let message_framework = FlashMessagesFramework::builder(message_store).build();
-    let redis_store = RedisSessionStore::new(redis_uri.expose_secret()).await?;
     let server = HttpServer::new(move || {
         App::new()
             // wrap the entire app in message_framework for sending FlashMessages
             .wrap(message_framework.clone())
             // authentication middleware
-            .wrap(SessionMiddleware::new(
-                redis_store.clone(),
-                secret_key.clone(),
-            ))
+            .wrap(
+                SessionMiddleware::builder(session_store.clone(), secret_key.clone())
+                    .session_lifecycle(session_lifecycle.clone())
+                    .build(),
+            )
             // application-wide logging
             .wrap(TracingLogger::default())
             // routes
             .route("/", web::get().to(home))
             .route("/login", web::get().to(login_form))
-            .route("/login", web::post().to(login))
+            .route(
+                "/login",
+                web::post().to(login).wrap(from_fn(verify_csrf_token)),
+            )
+            .route("/login/two-factor", web::get().to(two_factor_login_form))
+            .route(
+                "/login/two-factor",
+                web::post()
+                    .to(two_factor_login)
+                    .wrap(from_fn(verify_csrf_token)),
+            )
+            .route(
+                "/password-reset",
+                web::get().to(password_reset_request_form),
+            )
+            .route("/password-reset", web::post().to(request_password_reset))
+            .route(
+                "/password-reset/confirm",
+                web::get().to(password_reset_form),
+            )
+            .route("/password-reset/confirm", web::post().to(reset_password))
             .route("/health_check", web::get().to(health_check))
-            .route("/subscriptions", web::post().to(subscribe))
+            .service(
+                web::resource("/subscriptions")
+                    .wrap(subscribe_cors(&cors_settings))
+                    // the size limit `web::Form` used to enforce
+                    .app_data(web::PayloadConfig::new(16 * 1024))
+                    .route(web::post().to(subscribe)),
+            )
+            .route("/subscriptions/widget.js", web::get().to(subscribe_widget))
             .route("/subscriptions/confirm", web::get().to(confirm))
-            // scope the admin paths so only authenticated users can access them
+            .route(
+                "/subscriptions/data",
+                web::get().to(download_subscriber_data),
+            )
+            .route(
+                "/subscriptions/data/request",
+                web::get().to(data_request_form),
+            )
+            .route(
+                "/subscriptions/data/request",
+                web::post().to(request_subscriber_data),
+            )
+            .route("/webhooks/postmark", web::post().to(postmark_webhook))
+            .route("/invitations/accept", web::get().to(accept_invitation_form))
+            .route("/invitations/accept", web::post().to(accept_invitation))
+            // scope the admin paths so only authenticated users can access them,
+            // anything that changes data also needs the right role and the session's
+            // csrf token, checked once the user is known to be logged in.
+            // api tokens need the read scope for everything else
             .service(
                 web::scope("/admin")
+                    .wrap(from_fn(require_read_scope))
+                    .wrap(from_fn(verify_csrf_token))
                     .wrap(from_fn(reject_anonymous_users))
                     .route("/dashboard", web::get().to(admin_dashboard))
                     .route("/password", web::get().to(change_password_form))
-                    .route("/password", web::post().to(change_password))
+                    // account settings stay out of reach of api tokens
+                    .route(
+                        "/password",
+                        reject_api_tokens(web::post().to(change_password)),
+                    )
                     .route("/logout", web::post().to(log_out))
+                    .route("/sessions", web::get().to(sessions_page))
+                    .route(
+                        "/sessions/logout-everywhere",
+                        reject_api_tokens(web::post().to(log_out_everywhere)),
+                    )
+                    .route(
+                        "/sessions/{session_id}/revoke",
+                        reject_api_tokens(web::post().to(revoke_session)),
+                    )
+                    .route("/two-factor", web::get().to(two_factor_form))
+                    .route(
+                        "/two-factor/setup",
+                        reject_api_tokens(web::post().to(start_two_factor_setup)),
+                    )
+                    .route(
+                        "/two-factor/confirm",
+                        reject_api_tokens(web::post().to(confirm_two_factor_setup)),
+                    )
+                    .route(
+                        "/two-factor/recovery-codes",
+                        reject_api_tokens(web::post().to(regenerate_recovery_codes)),
+                    )
+                    .route(
+                        "/two-factor/disable",
+                        reject_api_tokens(web::post().to(disable_two_factor)),
+                    )
+                    .route(
+                        "/api-tokens",
+                        reject_api_tokens(web::get().to(api_tokens_page)),
+                    )
+                    .route(
+                        "/api-tokens",
+                        reject_api_tokens(web::post().to(create_api_token)),
+                    )
+                    .route(
+                        "/api-tokens/{token_id}/revoke",
+                        reject_api_tokens(web::post().to(revoke_api_token)),
+                    )
                     .route("/newsletter", web::get().to(publish_newsletter_form))
-                    .route("/newsletter", web::post().to(publish_newsletter)),
+                    .route(
+                        "/newsletter",
+                        require_permission(Permission::Publish, web::post().to(publish_newsletter)),
+                    )
+                    .route("/segments", web::get().to(segments_form))
+                    .route(
+                        "/segments",
+                        require_permission(Permission::Edit, web::post().to(create_segment)),
+                    )
+                    .route("/tags", web::get().to(tags_form))
+                    .route(
+                        "/tags",
+                        require_permission(Permission::Edit, web::post().to(update_tags)),
+                    )
+                    .route("/fields", web::get().to(custom_fields_form))
+                    .route(
+                        "/fields",
+                        require_permission(Permission::Edit, web::post().to(create_custom_field)),
+                    )
+                    .route("/suppressions", web::get().to(suppressions_form))
+                    .route(
+                        "/suppressions",
+                        require_permission(Permission::Edit, web::post().to(add_suppression)),
+                    )
+                    .route(
+                        "/suppressions/delete",
+                        require_permission(Permission::Edit, web::post().to(delete_suppression)),
+                    )
+                    .route("/lockouts", web::get().to(login_lockouts))
+                    .route(
+                        "/users",
+                        require_permission(Permission::ManageUsers, web::get().to(users_page)),
+                    )
+                    .route(
+                        "/users/invite",
+                        require_permission(Permission::ManageUsers, web::post().to(invite_user)),
+                    )
+                    .route(
+                        "/users/invitations/{invitation_id}/revoke",
+                        require_permission(
+                            Permission::ManageUsers,
+                            web::post().to(revoke_invitation),
+                        ),
+                    )
+                    .route(
+                        "/users/{user_id}/role",
+                        require_permission(
+                            Permission::ManageUsers,
+                            web::post().to(change_user_role),
+                        ),
+                    )
+                    .route(
+                        "/users/{user_id}/deactivate",
+                        require_permission(
+                            Permission::ManageUsers,
+                            web::post().to(deactivate_user),
+                        ),
+                    )
+                    .route(
+                        "/users/{user_id}/activate",
+                        require_permission(Permission::ManageUsers, web::post().to(activate_user)),
+                    )
+                    .route(
+                        "/users/{user_id}/delete",
+                        require_permission(Permission::ManageUsers, web::post().to(delete_user)),
+                    )
+                    .route("/subscribers", web::get().to(list_subscribers))
+                    // registered ahead of `{subscriber_id}` so these aren't taken for an id
+                    .route("/subscribers/export", web::get().to(export_subscribers))
+                    .route(
+                        "/subscribers/import",
+                        web::get().to(import_subscribers_form),
+                    )
+                    .route(
+                        "/subscribers/import",
+                        require_permission(Permission::Edit, web::post().to(import_subscribers)),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}",
+                        web::get().to(subscriber_details),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/confirm",
+                        require_permission(
+                            Permission::Edit,
+                            web::post().to(confirm_subscriber_manually),
+                        ),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/unsubscribe",
+                        require_permission(
+                            Permission::Edit,
+                            web::post().to(unsubscribe_subscriber),
+                        ),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/delete",
+                        require_permission(Permission::Edit, web::post().to(delete_subscriber)),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/data",
+                        web::get().to(download_subscriber_data_as_admin),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/erase",
+                        require_permission(Permission::Edit, web::post().to(erase_subscriber_data)),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/fields",
+                        web::get().to(subscriber_fields_form),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/fields",
+                        require_permission(
+                            Permission::Edit,
+                            web::post().to(update_subscriber_fields),
+                        ),
+                    ),
+            )
+            // the schema of the endpoints below
+            .route("/api/openapi.json", web::get().to(api::openapi_document))
+            // machine-facing endpoints, only reachable with an api token.
+            // bad bodies, queries and paths get the api's json errors
+            .service(
+                web::scope("/api/v1")
+                    .wrap(from_fn(idempotent_requests))
+                    .wrap(from_fn(require_api_token))
+                    .app_data(web::JsonConfig::default().error_handler(|e, _| extractor_error(e)))
+                    .app_data(web::QueryConfig::default().error_handler(|e, _| extractor_error(e)))
+                    .app_data(web::PathConfig::default().error_handler(|e, _| extractor_error(e)))
+                    .route("/issues", web::get().to(api::list_issues))
+                    .route(
+                        "/issues",
+                        require_permission(Permission::Publish, web::post().to(api::create_issue)),
+                    )
+                    .route("/issues/{issue_id}", web::get().to(api::get_issue))
+                    .route(
+                        "/issues/{issue_id}/send",
+                        require_permission(Permission::Publish, web::post().to(api::send_issue)),
+                    )
+                    .route(
+                        "/issues/{issue_id}/stats",
+                        web::get().to(api::get_issue_stats),
+                    )
+                    .route("/subscribers", web::get().to(api::list_subscribers))
+                    .route(
+                        "/subscribers",
+                        require_permission(
+                            Permission::Edit,
+                            web::post().to(api::create_subscriber),
+                        ),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}",
+                        require_permission(
+                            Permission::Edit,
+                            web::delete().to(api::delete_subscriber),
+                        ),
+                    ),
             )
             // attach all the data services
             .app_data(db_pool.clone())
             .app_data(email_client.clone())
             .app_data(base_url.clone())
+            .app_data(trusted_proxies.clone())
+            .app_data(postmark_webhook_settings.clone())
+            .app_data(signup_protection.clone())
+            .app_data(login_protection.clone())
+            .app_data(link_request_protection.clone())
+            .app_data(password_hashing.clone())
+            .app_data(dummy_password_hash.clone())
+            .app_data(session_timeouts.clone())
             .app_data(Data::new(HmacSecret(hmac_secret.clone())))
     })
     .listen(listener)?
@@ -116,6 +401,17 @@ async fn run(
     Ok(server)
 }
 
+// only the signup endpoint is shared across origins, and it needs no cookies
+fn subscribe_cors(settings: &CorsSettings) -> Cors {
+    settings
+        .allowed_origins
+        .iter()
+        .fold(Cors::default(), |cors, origin| cors.allowed_origin(origin))
+        .allowed_methods(["POST"])
+        .allowed_headers([header::CONTENT_TYPE, header::ACCEPT])
+        .max_age(3600)
+}
+
 pub fn get_connection_pool(configuration: &DatabaseSettings) -> PgPool {
     PgPoolOptions::new().connect_lazy_with(configuration.connect_options())
 }
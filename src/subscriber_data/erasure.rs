@@ -0,0 +1,121 @@
+use anyhow::Context;
+use sqlx::{Executor, PgPool};
+use uuid::Uuid;
+
+use crate::domain::SuppressionReason;
+use crate::suppression::suppress;
+
+// erase everything that identifies `email`, returning whether anything was stored about it.
+// the subscription, delivery log and email event rows are pseudonymized rather than
+// deleted, so subscriber and per-issue delivery counts stay the same
+#[tracing::instrument(name = "Erase subscriber data", skip(pool, email))]
+pub async fn erase_subscriber(pool: &PgPool, email: &str) -> Result<bool, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let subscriber_id = sqlx::query!(
+        r#"SELECT id FROM subscriptions WHERE lower(email) = lower($1) FOR UPDATE"#,
+        email
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to retrieve the subscriber")?
+    .map(|r| r.id);
+    // `.invalid` is reserved, so the pseudonym can never reach a real mailbox
+    let pseudonym = format!(
+        "erased-{}@erased.invalid",
+        subscriber_id.unwrap_or_else(Uuid::new_v4)
+    );
+
+    // deliveries and emails that haven't gone out yet never will
+    let dequeued = transaction
+        .execute(sqlx::query!(
+            r#"DELETE FROM issue_delivery_queue WHERE lower(subscriber_email) = lower($1)"#,
+            email
+        ))
+        .await
+        .context("Failed to remove queued deliveries")?
+        .rows_affected();
+    let dequeued_emails = transaction
+        .execute(sqlx::query!(
+            r#"DELETE FROM email_queue WHERE lower(recipient) = lower($1)"#,
+            email
+        ))
+        .await
+        .context("Failed to remove queued emails")?
+        .rows_affected();
+    let pseudonymized = transaction
+        .execute(sqlx::query!(
+            r#"
+            UPDATE issue_delivery_log SET subscriber_email = $2
+            WHERE lower(subscriber_email) = lower($1)
+            "#,
+            email,
+            pseudonym
+        ))
+        .await
+        .context("Failed to pseudonymize the delivery log")?
+        .rows_affected();
+    let pseudonymized_events = transaction
+        .execute(sqlx::query!(
+            r#"
+            UPDATE email_events SET email = $2, description = NULL
+            WHERE lower(email) = lower($1)
+            "#,
+            email,
+            pseudonym
+        ))
+        .await
+        .context("Failed to pseudonymize email events")?
+        .rows_affected();
+
+    if let Some(subscriber_id) = subscriber_id {
+        transaction
+            .execute(sqlx::query!(
+                r#"DELETE FROM subscription_tokens WHERE subscriber_id = $1"#,
+                subscriber_id
+            ))
+            .await
+            .context("Failed to remove subscription tokens")?;
+        transaction
+            .execute(sqlx::query!(
+                r#"DELETE FROM subscriber_tags WHERE subscriber_id = $1"#,
+                subscriber_id
+            ))
+            .await
+            .context("Failed to remove subscriber tags")?;
+        transaction
+            .execute(sqlx::query!(
+                r#"DELETE FROM subscriber_field_values WHERE subscriber_id = $1"#,
+                subscriber_id
+            ))
+            .await
+            .context("Failed to remove custom field values")?;
+        transaction
+            .execute(sqlx::query!(
+                r#"
+                UPDATE subscriptions
+                SET email = $2, name = 'erased', status = 'erased'
+                WHERE id = $1
+                "#,
+                subscriber_id,
+                pseudonym
+            ))
+            .await
+            .context("Failed to pseudonymize the subscriber")?;
+    }
+    // keep them from being emailed again, without keeping their address
+    suppress(&mut *transaction, email, SuppressionReason::Erased)
+        .await
+        .context("Failed to suppress the erased address")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to erase a subscriber.")?;
+    Ok(subscriber_id.is_some()
+        || dequeued > 0
+        || dequeued_emails > 0
+        || pseudonymized > 0
+        || pseudonymized_events > 0)
+}
@@ -0,0 +1,258 @@
+use anyhow::Context;
+use sqlx::PgPool;
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+use crate::suppression::email_hash;
+
+// everything stored about an email address, as handed over on a data access request
+#[derive(serde::Serialize)]
+pub struct SubscriberData {
+    pub email: String,
+    pub subscription: Option<SubscriptionRecord>,
+    pub confirmation_tokens: Vec<TokenRecord>,
+    pub tags: Vec<String>,
+    pub custom_fields: BTreeMap<String, String>,
+    pub pending_deliveries: Vec<PendingDeliveryRecord>,
+    pub queued_emails: Vec<QueuedEmailRecord>,
+    pub delivery_log: Vec<DeliveryRecord>,
+    pub email_events: Vec<EmailEventRecord>,
+    pub suppression: Option<SuppressionRecord>,
+}
+
+#[derive(serde::Serialize)]
+pub struct SubscriptionRecord {
+    pub id: Uuid,
+    pub email: String,
+    pub name: String,
+    pub status: String,
+    pub subscribed_at: String,
+    pub confirmed_at: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+pub struct TokenRecord {
+    pub subscription_token: String,
+    pub created_at: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct PendingDeliveryRecord {
+    pub newsletter_issue_id: Uuid,
+    pub title: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct QueuedEmailRecord {
+    pub subject: String,
+    pub text_body: String,
+    pub enqueued_at: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct DeliveryRecord {
+    pub newsletter_issue_id: Uuid,
+    pub title: String,
+    pub outcome: String,
+    pub attempted_at: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct EmailEventRecord {
+    pub provider: String,
+    pub event_type: String,
+    pub description: Option<String>,
+    pub occurred_at: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct SuppressionRecord {
+    pub reason: String,
+    pub created_at: String,
+}
+
+// `None` when nothing at all is stored about the address
+#[tracing::instrument(name = "Collect subscriber data", skip(pool))]
+pub async fn collect_subscriber_data(
+    pool: &PgPool,
+    email: &str,
+) -> Result<Option<SubscriberData>, anyhow::Error> {
+    let subscription = sqlx::query!(
+        r#"
+        SELECT id, email, name, status, subscribed_at, confirmed_at
+        FROM subscriptions
+        WHERE lower(email) = lower($1)
+        "#,
+        email
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the subscription")?
+    .map(|r| SubscriptionRecord {
+        id: r.id,
+        email: r.email,
+        name: r.name,
+        status: r.status,
+        subscribed_at: r.subscribed_at.to_rfc3339(),
+        confirmed_at: r.confirmed_at.map(|c| c.to_rfc3339()),
+    });
+
+    let mut confirmation_tokens = Vec::new();
+    let mut tags = Vec::new();
+    let mut custom_fields = BTreeMap::new();
+    if let Some(subscription) = &subscription {
+        confirmation_tokens = sqlx::query!(
+            r#"
+            SELECT subscription_token, created_at
+            FROM subscription_tokens
+            WHERE subscriber_id = $1
+            ORDER BY created_at
+            "#,
+            subscription.id
+        )
+        .fetch_all(pool)
+        .await
+        .context("Failed to retrieve the confirmation tokens")?
+        .into_iter()
+        .map(|r| TokenRecord {
+            subscription_token: r.subscription_token,
+            created_at: r.created_at.to_rfc3339(),
+        })
+        .collect();
+        tags = sqlx::query!(
+            r#"SELECT tag FROM subscriber_tags WHERE subscriber_id = $1 ORDER BY tag"#,
+            subscription.id
+        )
+        .fetch_all(pool)
+        .await
+        .context("Failed to retrieve the tags")?
+        .into_iter()
+        .map(|r| r.tag)
+        .collect();
+        custom_fields = sqlx::query!(
+            r#"SELECT field_name, value FROM subscriber_field_values WHERE subscriber_id = $1"#,
+            subscription.id
+        )
+        .fetch_all(pool)
+        .await
+        .context("Failed to retrieve the custom fields")?
+        .into_iter()
+        .map(|r| (r.field_name, r.value))
+        .collect();
+    }
+
+    // the queues and the log only hold a copy of the email, so they are looked up by it
+    let pending_deliveries: Vec<PendingDeliveryRecord> = sqlx::query!(
+        r#"
+        SELECT q.newsletter_issue_id, i.title
+        FROM issue_delivery_queue q
+        JOIN newsletter_issues i ON i.newsletter_issue_id = q.newsletter_issue_id
+        WHERE lower(q.subscriber_email) = lower($1)
+        ORDER BY i.published_at
+        "#,
+        email
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve pending deliveries")?
+    .into_iter()
+    .map(|r| PendingDeliveryRecord {
+        newsletter_issue_id: r.newsletter_issue_id,
+        title: r.title,
+    })
+    .collect();
+    let queued_emails: Vec<QueuedEmailRecord> = sqlx::query!(
+        r#"
+        SELECT subject, text_body, enqueued_at
+        FROM email_queue
+        WHERE lower(recipient) = lower($1)
+        ORDER BY enqueued_at
+        "#,
+        email
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve queued emails")?
+    .into_iter()
+    .map(|r| QueuedEmailRecord {
+        subject: r.subject,
+        text_body: r.text_body,
+        enqueued_at: r.enqueued_at.to_rfc3339(),
+    })
+    .collect();
+    let delivery_log: Vec<DeliveryRecord> = sqlx::query!(
+        r#"
+        SELECT l.newsletter_issue_id, i.title, l.outcome, l.attempted_at
+        FROM issue_delivery_log l
+        JOIN newsletter_issues i ON i.newsletter_issue_id = l.newsletter_issue_id
+        WHERE lower(l.subscriber_email) = lower($1)
+        ORDER BY l.attempted_at
+        "#,
+        email
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the delivery log")?
+    .into_iter()
+    .map(|r| DeliveryRecord {
+        newsletter_issue_id: r.newsletter_issue_id,
+        title: r.title,
+        outcome: r.outcome,
+        attempted_at: r.attempted_at.to_rfc3339(),
+    })
+    .collect();
+    let email_events: Vec<EmailEventRecord> = sqlx::query!(
+        r#"
+        SELECT provider, event_type, description, occurred_at
+        FROM email_events
+        WHERE lower(email) = lower($1)
+        ORDER BY occurred_at
+        "#,
+        email
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve email events")?
+    .into_iter()
+    .map(|r| EmailEventRecord {
+        provider: r.provider,
+        event_type: r.event_type,
+        description: r.description,
+        occurred_at: r.occurred_at.to_rfc3339(),
+    })
+    .collect();
+
+    let suppression = sqlx::query!(
+        r#"SELECT reason, created_at FROM email_suppressions WHERE email_hash = $1"#,
+        email_hash(email)
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the suppression")?
+    .map(|r| SuppressionRecord {
+        reason: r.reason,
+        created_at: r.created_at.to_rfc3339(),
+    });
+
+    if subscription.is_none()
+        && pending_deliveries.is_empty()
+        && queued_emails.is_empty()
+        && delivery_log.is_empty()
+        && email_events.is_empty()
+        && suppression.is_none()
+    {
+        return Ok(None);
+    }
+    Ok(Some(SubscriberData {
+        email: email.to_owned(),
+        subscription,
+        confirmation_tokens,
+        tags,
+        custom_fields,
+        pending_deliveries,
+        queued_emails,
+        delivery_log,
+        email_events,
+        suppression,
+    }))
+}
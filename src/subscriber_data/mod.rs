@@ -0,0 +1,7 @@
+mod erasure;
+mod export;
+mod signed_link;
+
+pub use erasure::erase_subscriber;
+pub use export::{SubscriberData, collect_subscriber_data};
+pub use signed_link::{sign_data_request, verify_data_request};
@@ -0,0 +1,93 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, SecretString};
+use sha2::Sha256;
+
+// the purpose is part of the signed message, so a signature can't be reused elsewhere
+fn mac(secret: &SecretString, email: &str, expires_at: i64) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.expose_secret().as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(format!("subscriber-data:{}:{}", email, expires_at).as_bytes());
+    mac
+}
+
+// lets the holder of a link download the data stored about `email` until `expires_at`
+pub fn sign_data_request(secret: &SecretString, email: &str, expires_at: DateTime<Utc>) -> String {
+    hex::encode(
+        mac(secret, email, expires_at.timestamp())
+            .finalize()
+            .into_bytes(),
+    )
+}
+
+pub fn verify_data_request(
+    secret: &SecretString,
+    email: &str,
+    expires_at: i64,
+    signature: &str,
+) -> Result<(), String> {
+    if Utc::now().timestamp() > expires_at {
+        return Err("The link has expired.".into());
+    }
+    let signature = hex::decode(signature).map_err(|_| "The link is invalid.".to_string())?;
+    mac(secret, email, expires_at)
+        .verify_slice(&signature)
+        .map_err(|_| "The link is invalid.".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sign_data_request, verify_data_request};
+    use chrono::{Duration, Utc};
+    use claims::{assert_err, assert_ok};
+    use secrecy::SecretString;
+
+    fn secret() -> SecretString {
+        SecretString::from("a-secret-key")
+    }
+
+    #[test]
+    fn a_signed_link_is_accepted_until_it_expires() {
+        let expires_at = Utc::now() + Duration::hours(1);
+        let signature = sign_data_request(&secret(), "ursula@example.com", expires_at);
+        assert_ok!(verify_data_request(
+            &secret(),
+            "ursula@example.com",
+            expires_at.timestamp(),
+            &signature
+        ));
+
+        let expired = Utc::now() - Duration::seconds(1);
+        let signature = sign_data_request(&secret(), "ursula@example.com", expired);
+        assert_err!(verify_data_request(
+            &secret(),
+            "ursula@example.com",
+            expired.timestamp(),
+            &signature
+        ));
+    }
+
+    #[test]
+    fn a_signature_only_covers_its_own_email_and_expiry() {
+        let expires_at = Utc::now() + Duration::hours(1);
+        let signature = sign_data_request(&secret(), "ursula@example.com", expires_at);
+        assert_err!(verify_data_request(
+            &secret(),
+            "ged@example.com",
+            expires_at.timestamp(),
+            &signature
+        ));
+        assert_err!(verify_data_request(
+            &secret(),
+            "ursula@example.com",
+            expires_at.timestamp() + 3600,
+            &signature
+        ));
+        assert_err!(verify_data_request(
+            &SecretString::from("another-key"),
+            "ursula@example.com",
+            expires_at.timestamp(),
+            &signature
+        ));
+    }
+}
@@ -0,0 +1,5 @@
+mod persistence;
+
+pub use persistence::{
+    Suppression, email_hash, is_suppressed, list_suppressions, remove_suppression, suppress,
+};
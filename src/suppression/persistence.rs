@@ -0,0 +1,102 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::{Executor, PgPool, Postgres};
+
+use crate::domain::SuppressionReason;
+
+pub struct Suppression {
+    pub email_hash: String,
+    // not kept for erased subscribers, whose entry is only the hash
+    pub email: Option<String>,
+    pub reason: SuppressionReason,
+    pub created_at: DateTime<Utc>,
+}
+
+// entries are keyed by a hash of the address, so erased subscribers can stay
+// suppressed without us keeping their email around
+pub fn email_hash(email: &str) -> String {
+    hex::encode(Sha256::digest(email.trim().to_lowercase().as_bytes()))
+}
+
+// every sending path checks this right before handing an email over
+#[tracing::instrument(name = "Check the suppression list", skip(executor))]
+pub async fn is_suppressed<'c, E>(executor: E, email: &str) -> Result<bool, sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let row = sqlx::query!(
+        r#"SELECT EXISTS (SELECT 1 FROM email_suppressions WHERE email_hash = $1) AS "suppressed!""#,
+        email_hash(email)
+    )
+    .fetch_one(executor)
+    .await?;
+    Ok(row.suppressed)
+}
+
+// an address that is already suppressed keeps its original reason, unless it
+// is being erased: then the stored email is dropped and only the hash remains
+#[tracing::instrument(name = "Suppress an email address", skip(executor))]
+pub async fn suppress<'c, E>(
+    executor: E,
+    email: &str,
+    reason: SuppressionReason,
+) -> Result<(), sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let stored_email = match reason {
+        SuppressionReason::Erased => None,
+        _ => Some(email),
+    };
+    sqlx::query!(
+        r#"
+        INSERT INTO email_suppressions (email_hash, email, reason, created_at)
+        VALUES ($1, $2, $3, now())
+        ON CONFLICT (email_hash) DO UPDATE
+        SET email = NULL, reason = EXCLUDED.reason
+        WHERE EXCLUDED.reason = 'erased'
+        "#,
+        email_hash(email),
+        stored_email,
+        reason.as_str(),
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Remove a suppression", skip(pool))]
+pub async fn remove_suppression(pool: &PgPool, email_hash: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"DELETE FROM email_suppressions WHERE email_hash = $1"#,
+        email_hash
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+#[tracing::instrument(name = "List suppressions", skip(pool))]
+pub async fn list_suppressions(pool: &PgPool) -> Result<Vec<Suppression>, anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT email_hash, email, reason, created_at
+        FROM email_suppressions
+        ORDER BY created_at DESC
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the suppression list.")?;
+    rows.into_iter()
+        .map(|r| {
+            Ok(Suppression {
+                email_hash: r.email_hash,
+                email: r.email,
+                reason: SuppressionReason::parse(&r.reason).map_err(anyhow::Error::msg)?,
+                created_at: r.created_at,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,74 @@
+use htmlescape::encode_minimal;
+use std::collections::HashMap;
+
+pub enum Escape {
+    None,
+    Html,
+}
+
+// replace `{{ variable }}` placeholders with per-subscriber values
+// placeholders for unknown variables (e.g. an unset custom field) render as empty
+pub fn render_template(
+    template: &str,
+    variables: &HashMap<String, String>,
+    escape: Escape,
+) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        rendered.push_str(&rest[..start]);
+        let name = rest[start + 2..start + end].trim();
+        let value = variables.get(name).map(String::as_str).unwrap_or_default();
+        match escape {
+            Escape::None => rendered.push_str(value),
+            Escape::Html => rendered.push_str(&encode_minimal(value)),
+        }
+        rest = &rest[start + end + 2..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Escape, render_template};
+    use std::collections::HashMap;
+
+    fn variables() -> HashMap<String, String> {
+        HashMap::from([
+            ("name".to_string(), "Ursula".to_string()),
+            ("company".to_string(), "<Earthsea & Co>".to_string()),
+        ])
+    }
+
+    #[test]
+    fn placeholders_are_replaced() {
+        let rendered = render_template(
+            "Hi {{name}}, from {{ company }}!",
+            &variables(),
+            Escape::None,
+        );
+        assert_eq!(rendered, "Hi Ursula, from <Earthsea & Co>!");
+    }
+
+    #[test]
+    fn values_are_escaped_in_html() {
+        let rendered = render_template("<p>{{company}}</p>", &variables(), Escape::Html);
+        assert_eq!(rendered, "<p>&lt;Earthsea &amp; Co&gt;</p>");
+    }
+
+    #[test]
+    fn unknown_placeholders_render_as_empty() {
+        let rendered = render_template("Role: {{role}}.", &variables(), Escape::None);
+        assert_eq!(rendered, "Role: .");
+    }
+
+    #[test]
+    fn unterminated_placeholders_are_left_alone() {
+        let rendered = render_template("Hi {{name", &variables(), Escape::None);
+        assert_eq!(rendered, "Hi {{name");
+    }
+}
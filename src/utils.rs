@@ -1,4 +1,7 @@
-use actix_web::{HttpResponse, http::header::LOCATION};
+use actix_web::{
+    HttpResponse,
+    http::{StatusCode, header::LOCATION},
+};
 
 // http 400 error (client-side error)
 pub fn e400<T>(e: T) -> actix_web::Error
@@ -23,6 +26,27 @@ pub fn see_other(location: &str) -> HttpResponse {
         .finish()
 }
 
+// the body of every error sent to JSON clients, which match on the code rather than the message
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct ErrorBody {
+    error: ErrorDetails,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct ErrorDetails {
+    code: String,
+    message: String,
+}
+
+pub fn json_error(status: StatusCode, code: &str, message: &str) -> HttpResponse {
+    HttpResponse::build(status).json(ErrorBody {
+        error: ErrorDetails {
+            code: code.into(),
+            message: message.into(),
+        },
+    })
+}
+
 pub fn error_chain_fmt(
     e: &impl std::error::Error,
     f: &mut std::fmt::Formatter<'_>,
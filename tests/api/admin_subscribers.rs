@@ -0,0 +1,208 @@
+use crate::helpers::{TestApp, assert_is_redirect_to, spawn_app};
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+
+// helpers
+// insert directly rather than going through the signup flow, so we can control
+// how many subscribers there are and when they signed up
+async fn insert_subscriber(app: &TestApp, email: &str, status: &str, days_ago: i64) -> Uuid {
+    let id = Uuid::new_v4();
+    sqlx::query!(
+        "INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES ($1, $2, $3, $4, $5)",
+        id,
+        email,
+        "le guin",
+        Utc::now() - Duration::days(days_ago),
+        status,
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to insert subscriber.");
+    id
+}
+
+async fn status_of(app: &TestApp, subscriber_id: Uuid) -> Option<String> {
+    sqlx::query!(
+        "SELECT status FROM subscriptions WHERE id = $1",
+        subscriber_id
+    )
+    .fetch_optional(&app.db_pool)
+    .await
+    .unwrap()
+    .map(|r| r.status)
+}
+
+// tests
+#[tokio::test]
+async fn you_must_be_logged_in_to_list_subscribers() {
+    // arrange
+    let app = spawn_app().await;
+
+    // act
+    let response = app.get_subscribers("").await;
+
+    // assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn subscribers_can_be_filtered_and_searched() {
+    // arrange
+    let app = spawn_app().await;
+    insert_subscriber(&app, "old_confirmed@example.com", "confirmed", 30).await;
+    insert_subscriber(&app, "new_confirmed@example.com", "confirmed", 1).await;
+    insert_subscriber(&app, "new_pending@example.com", "pending_confirmation", 1).await;
+    app.test_user.login(&app).await;
+    let last_week = (Utc::now() - Duration::days(7)).format("%Y-%m-%d");
+
+    // act 1: filter by status
+    let html_page = app.get_subscribers_html("status=confirmed").await;
+    assert!(html_page.contains("old_confirmed@example.com"));
+    assert!(html_page.contains("new_confirmed@example.com"));
+    assert!(!html_page.contains("new_pending@example.com"));
+
+    // act 2: filter by signup date
+    let html_page = app
+        .get_subscribers_html(&format!("subscribed_after={}&status=", last_week))
+        .await;
+    assert!(!html_page.contains("old_confirmed@example.com"));
+    assert!(html_page.contains("new_pending@example.com"));
+
+    // act 3: search, treating LIKE wildcards literally
+    let html_page = app.get_subscribers_html("q=NEW_PENDING").await;
+    assert!(html_page.contains("new_pending@example.com"));
+    assert!(!html_page.contains("new_confirmed@example.com"));
+    let html_page = app.get_subscribers_html("q=%25").await;
+    assert!(html_page.contains("(0 subscribers)"));
+}
+
+#[tokio::test]
+async fn subscribers_are_paginated() {
+    // arrange
+    let app = spawn_app().await;
+    for i in 0..30 {
+        insert_subscriber(&app, &format!("subscriber{i}@example.com"), "confirmed", i).await;
+    }
+    app.test_user.login(&app).await;
+
+    // act 1: the first page holds the newest subscribers
+    let html_page = app.get_subscribers_html("").await;
+    assert!(html_page.contains("Page 1 of 2 (30 subscribers)"));
+    assert!(html_page.contains("subscriber0@example.com"));
+    assert!(!html_page.contains("subscriber29@example.com"));
+
+    // act 2: the second page holds the rest
+    let html_page = app.get_subscribers_html("page=2").await;
+    assert!(html_page.contains("subscriber29@example.com"));
+    assert!(!html_page.contains("subscriber0@example.com"));
+}
+
+#[tokio::test]
+async fn the_detail_page_shows_confirmation_and_delivery_history() {
+    // arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html).await.unwrap();
+    let subscription = sqlx::query!("SELECT s.id, t.subscription_token FROM subscriptions s JOIN subscription_tokens t ON t.subscriber_id = s.id")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+
+    app.test_user.login(&app).await;
+    app.post_publish_newsletter(&serde_json::json!({
+        "title": "Issue number one",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    // act
+    let html_page = app.get_subscriber_details_html(subscription.id).await;
+
+    // assert
+    assert!(html_page.contains("<td>confirmed</td>"));
+    // the token itself would confirm the subscription, only a prefix is shown
+    let token = &subscription.subscription_token;
+    assert!(!html_page.contains(token.as_str()));
+    assert!(html_page.contains(&format!("<code>{}&hellip;</code>", &token[..4])));
+    assert!(html_page.contains("<td>Issue number one</td><td>sent</td>"));
+}
+
+#[tokio::test]
+async fn admins_can_confirm_unsubscribe_and_delete_subscribers() {
+    // arrange
+    let app = spawn_app().await;
+    let subscriber_id =
+        insert_subscriber(&app, "ursula@example.com", "pending_confirmation", 0).await;
+    app.test_user.login(&app).await;
+
+    // act 1: confirm
+    let response = app.post_subscriber_action(subscriber_id, "confirm").await;
+    assert_is_redirect_to(&response, &format!("/admin/subscribers/{}", subscriber_id));
+    assert_eq!(status_of(&app, subscriber_id).await.unwrap(), "confirmed");
+
+    // act 2: unsubscribe
+    app.post_subscriber_action(subscriber_id, "unsubscribe")
+        .await;
+    assert_eq!(
+        status_of(&app, subscriber_id).await.unwrap(),
+        "unsubscribed"
+    );
+
+    // act 3: delete
+    let response = app.post_subscriber_action(subscriber_id, "delete").await;
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    assert!(status_of(&app, subscriber_id).await.is_none());
+    let html_page = app.get_subscribers_html("").await;
+    assert!(html_page.contains("<p><i>The subscriber has been deleted.</i></p>"));
+}
+
+#[tokio::test]
+async fn actions_on_an_unknown_subscriber_return_404() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    for action in ["confirm", "unsubscribe", "delete"] {
+        // act
+        let response = app.post_subscriber_action(Uuid::new_v4(), action).await;
+
+        // assert
+        assert_eq!(response.status().as_u16(), 404, "{}", action);
+    }
+}
+
+#[tokio::test]
+async fn subscriber_actions_require_login() {
+    // arrange
+    let app = spawn_app().await;
+    let subscriber_id =
+        insert_subscriber(&app, "ursula@example.com", "pending_confirmation", 0).await;
+
+    for action in ["confirm", "unsubscribe", "delete"] {
+        // act
+        let response = app.post_subscriber_action(subscriber_id, action).await;
+
+        // assert
+        assert_is_redirect_to(&response, "/login");
+    }
+    assert_eq!(
+        status_of(&app, subscriber_id).await.unwrap(),
+        "pending_confirmation"
+    );
+}
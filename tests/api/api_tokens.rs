@@ -0,0 +1,219 @@
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{TestApp, TestUser, assert_is_redirect_to, spawn_app};
+
+// a script: no cookies, so no session and no csrf token
+fn script_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+}
+
+async fn publish_with_token(app: &TestApp, token: &str) -> reqwest::Response {
+    script_client()
+        .post(format!("{}/admin/newsletter", &app.address))
+        .bearer_auth(token)
+        .form(&serde_json::json!({
+            "title": "Release notes",
+            "text_content": "What's new, as plain text",
+            "html_content": "<p>What's new, as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+        }))
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn token_id(app: &TestApp, name: &str) -> Uuid {
+    sqlx::query!("SELECT id FROM api_tokens WHERE name = $1", name)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id
+}
+
+#[tokio::test]
+async fn a_new_token_is_shown_once_and_only_its_name_is_listed() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // act
+    let token = app.create_api_token("ci", &["publish"]).await;
+
+    // assert
+    let html_page = app.get_api_tokens_html().await;
+    assert!(html_page.contains("<td>ci</td><td>publish</td>"));
+    assert!(!html_page.contains(&token));
+    let stored = sqlx::query!("SELECT token_hash FROM api_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_ne!(stored.token_hash, token);
+}
+
+#[tokio::test]
+async fn a_scoped_token_can_publish_a_newsletter() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token("ci", &["publish"]).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // act
+    let response = publish_with_token(&app, &token).await;
+
+    // assert
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    let issues = sqlx::query!("SELECT title FROM newsletter_issues")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].title, "Release notes");
+    let used = sqlx::query!("SELECT last_used_at FROM api_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(used.last_used_at.is_some());
+}
+
+#[tokio::test]
+async fn a_token_can_read_but_not_change_what_it_is_not_scoped_for() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token("reporting", &["read"]).await;
+
+    // act
+    let read = script_client()
+        .get(format!("{}/admin/subscribers", &app.address))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    let publish = publish_with_token(&app, &token).await;
+
+    // assert
+    assert_eq!(read.status().as_u16(), 200);
+    assert_eq!(publish.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn a_token_without_the_read_scope_cannot_download_subscriber_data() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.post_subscriber_import("confirmed", "email,name\nursula@example.com,Ursula\n")
+        .await;
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+    let token = app.create_api_token("ci", &["publish"]).await;
+
+    // act
+    let response = script_client()
+        .get(format!(
+            "{}/admin/subscribers/{}/data",
+            &app.address, subscriber_id
+        ))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn unknown_and_revoked_tokens_are_unauthorized() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token("ci", &["publish"]).await;
+    app.post_revoke_api_token(token_id(&app, "ci").await).await;
+
+    // act
+    let revoked = publish_with_token(&app, &token).await;
+    let unknown = publish_with_token(&app, "not-a-token").await;
+
+    // assert
+    for response in [revoked, unknown] {
+        assert_eq!(response.status().as_u16(), 401);
+        assert_eq!(response.headers()["WWW-Authenticate"], "Bearer");
+    }
+}
+
+#[tokio::test]
+async fn tokens_of_deactivated_users_stop_working() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token("ci", &["publish"]).await;
+    sqlx::query!(
+        "UPDATE users SET is_active = false WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    // act
+    let response = publish_with_token(&app, &token).await;
+
+    // assert
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn a_token_cannot_be_used_to_create_more_tokens() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token("ci", &["publish"]).await;
+
+    // act
+    let response = script_client()
+        .post(format!("{}/admin/api-tokens", &app.address))
+        .bearer_auth(&token)
+        .form(&[("name", "more"), ("scope", "manage_users")])
+        .send()
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(response.status().as_u16(), 403);
+    let count = sqlx::query!(r#"SELECT count(*) AS "count!" FROM api_tokens"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(count, 1);
+}
+
+#[tokio::test]
+async fn a_token_cannot_be_scoped_beyond_its_owners_role() {
+    // arrange
+    let app = spawn_app().await;
+    let viewer = TestUser::generate_with_role("viewer");
+    viewer.store(&app.db_pool).await;
+    viewer.login(&app).await;
+
+    // act
+    let response = app.post_create_api_token("ci", &["publish"]).await;
+
+    // assert
+    assert_is_redirect_to(&response, "/admin/api-tokens");
+    let html_page = app.get_api_tokens_html().await;
+    assert!(html_page.contains("<p><i>A token can't be allowed to do more than you can.</i></p>"));
+}
@@ -0,0 +1,457 @@
+use serde_json::{Value, json};
+use uuid::Uuid;
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{TestApp, spawn_app};
+
+// logs the test user in to mint a token, the api itself never sees the session
+async fn api_token(app: &TestApp, scopes: &[&str]) -> String {
+    app.test_user.login(app).await;
+    app.create_api_token("tests", scopes).await
+}
+
+async fn store_confirmed_subscriber(app: &TestApp, email: &str) {
+    sqlx::query!(
+        "INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES ($1, $2, 'Ursula', now(), 'confirmed')",
+        Uuid::new_v4(),
+        email
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+fn new_issue() -> Value {
+    json!({
+        "title": "Release notes",
+        "text_content": "What's new, as plain text",
+        "html_content": "<p>What's new, as HTML</p>",
+    })
+}
+
+async fn assert_json_error(response: reqwest::Response, status: u16, code: &str) {
+    assert_eq!(response.status().as_u16(), status);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], code);
+    assert!(body["error"]["message"].is_string());
+}
+
+#[tokio::test]
+async fn requests_without_a_token_are_unauthorized() {
+    // arrange
+    let app = spawn_app().await;
+
+    // act
+    let response = reqwest::get(format!("{}/api/v1/issues", &app.address))
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(response.headers()["WWW-Authenticate"], "Bearer");
+    assert_json_error(response, 401, "missing_token").await;
+}
+
+#[tokio::test]
+async fn a_logged_in_browser_is_not_enough() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // act
+    let response = app
+        .api_client
+        .get(format!("{}/api/v1/issues", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    // assert
+    assert_json_error(response, 401, "missing_token").await;
+}
+
+#[tokio::test]
+async fn an_issue_is_created_as_a_draft_and_sent_on_request() {
+    // arrange
+    let app = spawn_app().await;
+    let token = api_token(&app, &["publish"]).await;
+    store_confirmed_subscriber(&app, "ursula@example.com").await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // act 1: create
+    let response = app.api_post("/issues", &token, &new_issue()).await;
+    assert_eq!(response.status().as_u16(), 201);
+    let issue: Value = response.json().await.unwrap();
+    let issue_id = issue["id"].as_str().unwrap().to_string();
+    assert_eq!(issue["title"], "Release notes");
+    assert!(issue["published_at"].is_null());
+
+    // act 2: nothing goes out for a draft
+    app.dispatch_all_pending_emails().await;
+
+    // act 3: send
+    let response = app
+        .api_post(&format!("/issues/{issue_id}/send"), &token, &json!({}))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    let sent: Value = response.json().await.unwrap();
+    assert!(sent["published_at"].is_string());
+    app.dispatch_all_pending_emails().await;
+
+    // assert
+    let stats: Value = app
+        .api_get(&format!("/issues/{issue_id}/stats"), &token)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(stats["queued"], 0);
+    assert_eq!(stats["sent"], 1);
+    let fetched: Value = app
+        .api_get(&format!("/issues/{issue_id}"), &token)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(fetched["html_content"], "<p>What's new, as HTML</p>");
+    let listed: Value = app.api_get("/issues", &token).await.json().await.unwrap();
+    assert_eq!(listed["issues"][0]["id"], issue_id.as_str());
+}
+
+#[tokio::test]
+async fn an_issue_is_only_sent_once() {
+    // arrange
+    let app = spawn_app().await;
+    let token = api_token(&app, &["publish"]).await;
+    let issue: Value = app
+        .api_post("/issues", &token, &new_issue())
+        .await
+        .json()
+        .await
+        .unwrap();
+    let send_path = format!("/issues/{}/send", issue["id"].as_str().unwrap());
+    app.api_post(&send_path, &token, &json!({})).await;
+
+    // act
+    let response = app.api_post(&send_path, &token, &json!({})).await;
+
+    // assert
+    assert_json_error(response, 409, "conflict").await;
+}
+
+#[tokio::test]
+async fn creating_an_issue_with_an_idempotency_key_is_idempotent() {
+    // arrange
+    let app = spawn_app().await;
+    let token = api_token(&app, &["publish"]).await;
+    let idempotency_key = Uuid::new_v4().to_string();
+
+    // act
+    let first = app
+        .api_post_with_idempotency_key("/issues", &token, &idempotency_key, &new_issue())
+        .await;
+    let first_location = first.headers()["Location"].clone();
+    let first: Value = first.json().await.unwrap();
+    let second = app
+        .api_post_with_idempotency_key("/issues", &token, &idempotency_key, &new_issue())
+        .await;
+
+    // assert
+    assert_eq!(second.status().as_u16(), 201);
+    assert_eq!(second.headers()["Location"], first_location);
+    let second: Value = second.json().await.unwrap();
+    assert_eq!(first["id"], second["id"]);
+    let listed: Value = app.api_get("/issues", &token).await.json().await.unwrap();
+    assert_eq!(listed["issues"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn a_retried_send_gets_the_first_response_back() {
+    // arrange
+    let app = spawn_app().await;
+    let token = api_token(&app, &["publish"]).await;
+    store_confirmed_subscriber(&app, "ursula@example.com").await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let issue: Value = app
+        .api_post("/issues", &token, &new_issue())
+        .await
+        .json()
+        .await
+        .unwrap();
+    let send_path = format!("/issues/{}/send", issue["id"].as_str().unwrap());
+    let idempotency_key = Uuid::new_v4().to_string();
+
+    // act
+    let first = app
+        .api_post_with_idempotency_key(&send_path, &token, &idempotency_key, &json!({}))
+        .await;
+    let second = app
+        .api_post_with_idempotency_key(&send_path, &token, &idempotency_key, &json!({}))
+        .await;
+    app.dispatch_all_pending_emails().await;
+
+    // assert
+    assert_eq!(first.status().as_u16(), 202);
+    assert_eq!(second.status().as_u16(), 202);
+    assert_eq!(
+        first.json::<Value>().await.unwrap(),
+        second.json::<Value>().await.unwrap()
+    );
+    // without the key it's a second send
+    let third = app.api_post(&send_path, &token, &json!({})).await;
+    assert_json_error(third, 409, "conflict").await;
+}
+
+#[tokio::test]
+async fn failed_requests_can_be_retried_with_the_same_idempotency_key() {
+    // arrange
+    let app = spawn_app().await;
+    let token = api_token(&app, &["publish"]).await;
+    let idempotency_key = Uuid::new_v4().to_string();
+
+    // act
+    let failed = app
+        .api_post_with_idempotency_key(
+            "/issues",
+            &token,
+            &idempotency_key,
+            &json!({ "title": "No content" }),
+        )
+        .await;
+    let retried = app
+        .api_post_with_idempotency_key("/issues", &token, &idempotency_key, &new_issue())
+        .await;
+
+    // assert
+    assert_json_error(failed, 400, "validation_error").await;
+    assert_eq!(retried.status().as_u16(), 201);
+}
+
+#[tokio::test]
+async fn an_idempotency_key_cannot_be_reused_for_a_different_request() {
+    // arrange
+    let app = spawn_app().await;
+    let token = api_token(&app, &["publish"]).await;
+    let idempotency_key = Uuid::new_v4().to_string();
+    let issue: Value = app
+        .api_post_with_idempotency_key("/issues", &token, &idempotency_key, &new_issue())
+        .await
+        .json()
+        .await
+        .unwrap();
+    let send_path = format!("/issues/{}/send", issue["id"].as_str().unwrap());
+
+    // act
+    let other_body = app
+        .api_post_with_idempotency_key(
+            "/issues",
+            &token,
+            &idempotency_key,
+            &json!({ "title": "Another", "text_content": "text", "html_content": "<p>html</p>" }),
+        )
+        .await;
+    let other_path = app
+        .api_post_with_idempotency_key(&send_path, &token, &idempotency_key, &json!({}))
+        .await;
+
+    // assert
+    assert_json_error(other_body, 422, "idempotency_key_reused").await;
+    assert_json_error(other_path, 422, "idempotency_key_reused").await;
+    let listed: Value = app.api_get("/issues", &token).await.json().await.unwrap();
+    assert_eq!(listed["issues"].as_array().unwrap().len(), 1);
+    assert!(listed["issues"][0]["published_at"].is_null());
+}
+
+#[tokio::test]
+async fn a_retried_signup_through_the_api_stores_and_emails_the_subscriber_once() {
+    // arrange
+    let app = spawn_app().await;
+    let token = api_token(&app, &["edit"]).await;
+    let idempotency_key = Uuid::new_v4().to_string();
+    let body = json!({ "email": "ursula@example.com", "name": "Ursula" });
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // act
+    let first = app
+        .api_post_with_idempotency_key("/subscribers", &token, &idempotency_key, &body)
+        .await;
+    let second = app
+        .api_post_with_idempotency_key("/subscribers", &token, &idempotency_key, &body)
+        .await;
+    app.dispatch_all_pending_emails().await;
+
+    // assert
+    assert_eq!(first.status().as_u16(), 201);
+    assert_eq!(second.status().as_u16(), 201);
+    assert_eq!(
+        first.json::<Value>().await.unwrap(),
+        second.json::<Value>().await.unwrap()
+    );
+    let n_tokens = sqlx::query!(r#"SELECT count(*) AS "count!" FROM subscription_tokens"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_tokens, 1);
+}
+
+#[tokio::test]
+async fn a_malformed_idempotency_key_is_rejected() {
+    // arrange
+    let app = spawn_app().await;
+    let token = api_token(&app, &["publish"]).await;
+
+    // act
+    let response = app
+        .api_post_with_idempotency_key("/issues", &token, &"a".repeat(50), &new_issue())
+        .await;
+
+    // assert
+    assert_json_error(response, 400, "validation_error").await;
+    let listed: Value = app.api_get("/issues", &token).await.json().await.unwrap();
+    assert!(listed["issues"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn bad_requests_get_json_errors() {
+    // arrange
+    let app = spawn_app().await;
+    let token = api_token(&app, &["publish"]).await;
+
+    // act
+    let missing_field = app
+        .api_post("/issues", &token, &json!({ "title": "No content" }))
+        .await;
+    let empty_title = app
+        .api_post(
+            "/issues",
+            &token,
+            &json!({ "title": " ", "text_content": "text", "html_content": "html" }),
+        )
+        .await;
+    let bad_id = app.api_get("/issues/not-an-id", &token).await;
+    let unknown = app
+        .api_get(&format!("/issues/{}", Uuid::new_v4()), &token)
+        .await;
+
+    // assert
+    assert_json_error(missing_field, 400, "validation_error").await;
+    assert_json_error(empty_title, 400, "validation_error").await;
+    assert_json_error(bad_id, 400, "validation_error").await;
+    assert_json_error(unknown, 404, "not_found").await;
+}
+
+#[tokio::test]
+async fn a_token_without_the_scope_gets_a_json_error() {
+    // arrange
+    let app = spawn_app().await;
+    let token = api_token(&app, &["publish"]).await;
+
+    // act
+    let response = app
+        .api_post(
+            "/subscribers",
+            &token,
+            &json!({ "email": "ursula@example.com", "name": "Ursula" }),
+        )
+        .await;
+
+    // assert
+    assert_json_error(response, 403, "forbidden").await;
+}
+
+#[tokio::test]
+async fn subscribers_can_be_added_searched_and_deleted() {
+    // arrange
+    let app = spawn_app().await;
+    let token = api_token(&app, &["edit"]).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // act 1: add, which queues the confirmation email
+    let response = app
+        .api_post(
+            "/subscribers",
+            &token,
+            &json!({ "email": "ursula@example.com", "name": "Ursula", "tags": "api" }),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+    app.dispatch_all_pending_emails().await;
+    let subscriber: Value = response.json().await.unwrap();
+    assert_eq!(subscriber["status"], "pending_confirmation");
+
+    // act 2: search
+    let page: Value = app
+        .api_get("/subscribers?q=ursula", &token)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(page["total"], 1);
+    assert_eq!(page["subscribers"][0]["id"], subscriber["id"]);
+
+    // act 3: delete
+    let path = format!("/subscribers/{}", subscriber["id"].as_str().unwrap());
+    let deleted = app.api_delete(&path, &token).await;
+    let deleted_again = app.api_delete(&path, &token).await;
+
+    // assert
+    assert_eq!(deleted.status().as_u16(), 204);
+    assert_json_error(deleted_again, 404, "not_found").await;
+}
+
+#[tokio::test]
+async fn adding_an_existing_or_invalid_subscriber_fails() {
+    // arrange
+    let app = spawn_app().await;
+    let token = api_token(&app, &["edit"]).await;
+    store_confirmed_subscriber(&app, "ursula@example.com").await;
+
+    // act
+    let existing = app
+        .api_post(
+            "/subscribers",
+            &token,
+            &json!({ "email": "ursula@example.com", "name": "Ursula" }),
+        )
+        .await;
+    let differently_cased = app
+        .api_post(
+            "/subscribers",
+            &token,
+            &json!({ "email": "Ursula@Example.com", "name": "Ursula" }),
+        )
+        .await;
+    let invalid = app
+        .api_post(
+            "/subscribers",
+            &token,
+            &json!({ "email": "not-an-email", "name": "Ursula" }),
+        )
+        .await;
+
+    // assert
+    assert_json_error(existing, 409, "conflict").await;
+    assert_json_error(differently_cased, 409, "conflict").await;
+    assert_json_error(invalid, 400, "validation_error").await;
+}
@@ -0,0 +1,127 @@
+use crate::helpers::{assert_is_redirect_to, get_csrf_token, spawn_app};
+
+#[tokio::test]
+async fn logging_in_without_a_csrf_token_is_forbidden() {
+    // arrange
+    let app = spawn_app().await;
+    app.get_login_html().await;
+
+    // act
+    let response = app
+        .api_client
+        .post(format!("{}/login", &app.address))
+        .form(&[
+            ("username", &app.test_user.username),
+            ("password", &app.test_user.password),
+        ])
+        .send()
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(response.status().as_u16(), 403);
+    assert_is_redirect_to(&app.get_admin_dashboard().await, "/login");
+}
+
+#[tokio::test]
+async fn a_csrf_token_from_another_session_is_rejected() {
+    // arrange
+    let app = spawn_app().await;
+    app.get_login_html().await;
+    let other_client = reqwest::Client::builder()
+        .cookie_store(true)
+        .build()
+        .unwrap();
+    let other_token = get_csrf_token(&other_client, &app.address).await;
+
+    // act
+    let response = app
+        .api_client
+        .post(format!("{}/login", &app.address))
+        .form(&[
+            ("csrf_token", &other_token),
+            ("username", &app.test_user.username),
+            ("password", &app.test_user.password),
+        ])
+        .send()
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn the_csrf_token_can_be_sent_in_the_form() {
+    // arrange
+    let app = spawn_app().await;
+    let csrf_token = app.csrf_token().await;
+
+    // act
+    let response = app
+        .api_client
+        .post(format!("{}/login", &app.address))
+        .form(&[
+            ("csrf_token", &csrf_token),
+            ("username", &app.test_user.username),
+            ("password", &app.test_user.password),
+        ])
+        .send()
+        .await
+        .unwrap();
+
+    // assert
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn admin_forms_carry_the_sessions_csrf_token() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let csrf_token = app.csrf_token().await;
+
+    // act
+    let html_page = app.get_admin_dashboard_html().await;
+
+    // assert
+    assert!(html_page.contains(&format!(
+        r#"<input type="hidden" name="csrf_token" value="{csrf_token}">"#
+    )));
+}
+
+#[tokio::test]
+async fn admin_posts_without_a_csrf_token_are_forbidden() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // act
+    let response = app
+        .api_client
+        .post(format!("{}/admin/logout", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn anonymous_admin_posts_are_sent_to_the_login_page_first() {
+    // arrange
+    let app = spawn_app().await;
+
+    // act
+    let response = app
+        .api_client
+        .post(format!("{}/admin/logout", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    // assert
+    assert_is_redirect_to(&response, "/login");
+}
@@ -0,0 +1,294 @@
+use crate::helpers::{TestApp, assert_is_redirect_to, spawn_app};
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+
+// helpers
+async fn define_fields(app: &TestApp) {
+    for (name, label, field_type, options) in [
+        ("company", "Company", "text", ""),
+        ("seats", "Seats", "number", ""),
+        ("role", "Role", "enum", "Engineer, Designer"),
+        ("opt_in", "Opt in", "boolean", ""),
+    ] {
+        let response = app
+            .post_custom_fields(&serde_json::json!({
+                "name": name,
+                "label": label,
+                "field_type": field_type,
+                "options": options,
+            }))
+            .await;
+        assert_is_redirect_to(&response, "/admin/fields");
+    }
+}
+
+async fn subscribe(app: &TestApp, body: &str) -> reqwest::Response {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.post_subscriptions(body.into()).await
+}
+
+async fn field_values(app: &TestApp) -> Vec<(String, String)> {
+    sqlx::query!("SELECT field_name, value FROM subscriber_field_values ORDER BY field_name")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| (r.field_name, r.value))
+        .collect()
+}
+
+// tests
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_custom_fields() {
+    // arrange
+    let app = spawn_app().await;
+
+    // act
+    let response = app.get_custom_fields().await;
+
+    // assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn custom_fields_submitted_on_signup_are_validated_and_stored() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    define_fields(&app).await;
+
+    // act
+    let response = subscribe(
+        &app,
+        "name=le%20guin&email=ursula_le_guin%40gmail.com\
+        &company=Earthsea&seats=05&role=engineer&unrelated=ignored",
+    )
+    .await;
+
+    // assert
+    assert_eq!(200, response.status().as_u16());
+    assert_eq!(
+        field_values(&app).await,
+        vec![
+            ("company".to_string(), "Earthsea".to_string()),
+            ("role".to_string(), "Engineer".to_string()),
+            ("seats".to_string(), "5".to_string()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn subscribe_returns_400_when_a_custom_field_is_invalid() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    define_fields(&app).await;
+    let test_cases = vec![
+        ("seats=many", "non-numeric number"),
+        ("role=Manager", "unknown enum option"),
+    ];
+
+    for (fields, description) in test_cases {
+        // act
+        let body = format!("name=le%20guin&email=ursula_le_guin%40gmail.com&{}", fields);
+        let response = app.post_subscriptions(body).await;
+
+        // assert
+        assert_eq!(
+            400,
+            response.status().as_u16(),
+            "The API did not return a 400 Bad Request when the payload had a {}.",
+            description
+        );
+    }
+}
+
+#[tokio::test]
+async fn an_admin_can_edit_a_subscribers_custom_fields() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    define_fields(&app).await;
+    subscribe(
+        &app,
+        "name=le%20guin&email=ursula_le_guin%40gmail.com&company=Earthsea",
+    )
+    .await;
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+
+    // act 1: the form shows the current values
+    let html_page = app.get_subscriber_fields_html(subscriber_id).await;
+    assert!(html_page.contains(r#"name="company" value="Earthsea""#));
+
+    // act 2: clear one field and set another
+    let response = app
+        .post_subscriber_fields(
+            subscriber_id,
+            &serde_json::json!({
+                "company": "",
+                "seats": "12",
+                "role": "Designer",
+            }),
+        )
+        .await;
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/subscribers/{}/fields", subscriber_id),
+    );
+
+    // assert
+    assert_eq!(
+        field_values(&app).await,
+        vec![
+            ("role".to_string(), "Designer".to_string()),
+            ("seats".to_string(), "12".to_string()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn custom_fields_drive_segments_and_issue_placeholders() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    define_fields(&app).await;
+    subscribe(
+        &app,
+        "name=Ursula&email=ursula%40example.com&company=Earthsea&seats=20",
+    )
+    .await;
+    subscribe(&app, "name=Ged&email=ged%40example.com&seats=2").await;
+    sqlx::query!("UPDATE subscriptions SET status = 'confirmed'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    app.post_segments(&serde_json::json!({
+        "name": "Big accounts",
+        "definition": "field:seats>10",
+    }))
+    .await;
+    let segment_id = sqlx::query!("SELECT segment_id FROM segments")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .segment_id;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // act
+    app.post_publish_newsletter(&serde_json::json!({
+        "title": "News for {{company}}",
+        "text_content": "Hi {{name}}, you have {{seats}} seats.",
+        "html_content": "<p>Hi {{name}} ({{role}})</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        "segment_id": segment_id.to_string(),
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    // assert
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["To"], "ursula@example.com");
+    assert_eq!(body["Subject"], "News for Earthsea");
+    assert_eq!(body["TextBody"], "Hi Ursula, you have 20 seats.");
+    assert_eq!(body["HtmlBody"], "<p>Hi Ursula ()</p>");
+}
+
+#[tokio::test]
+async fn updating_the_fields_of_an_unknown_subscriber_returns_404() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    define_fields(&app).await;
+
+    // act
+    let response = app
+        .post_subscriber_fields(
+            uuid::Uuid::new_v4(),
+            &serde_json::json!({ "company": "Earthsea" }),
+        )
+        .await;
+
+    // assert
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn segments_match_field_values_however_they_are_spelled() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    define_fields(&app).await;
+    subscribe(
+        &app,
+        "name=Ursula&email=ursula%40example.com&opt_in=on&role=engineer",
+    )
+    .await;
+    subscribe(&app, "name=Ged&email=ged%40example.com&opt_in=no").await;
+    sqlx::query!("UPDATE subscriptions SET status = 'confirmed'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // act
+    for (name, definition) in [
+        ("Opted in", "field:opt_in=yes"),
+        ("Engineers", "field:role=ENGINEER"),
+    ] {
+        let response = app
+            .post_segments(&serde_json::json!({
+                "name": name,
+                "definition": definition,
+            }))
+            .await;
+        assert_is_redirect_to(&response, "/admin/segments");
+    }
+
+    // assert
+    let html_page = app.get_segments_html().await;
+    assert!(html_page.contains("<code>field:opt_in=yes</code></td><td>1</td>"));
+    assert!(html_page.contains("<code>field:role=ENGINEER</code></td><td>1</td>"));
+}
+
+#[tokio::test]
+async fn a_segment_with_an_invalid_field_value_is_rejected() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    define_fields(&app).await;
+
+    // act
+    app.post_segments(&serde_json::json!({
+        "name": "Undecided",
+        "definition": "field:opt_in=maybe",
+    }))
+    .await;
+
+    // assert
+    let html_page = app.get_segments_html().await;
+    assert!(html_page.contains("maybe is not a valid value for Opt in."));
+    assert!(!html_page.contains("Undecided"));
+}
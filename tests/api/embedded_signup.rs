@@ -0,0 +1,211 @@
+use crate::helpers::spawn_app;
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+
+const ALLOWED_ORIGIN: &str = "https://marketing.example.com";
+
+#[tokio::test]
+async fn subscribe_accepts_a_json_body() {
+    // arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let body = serde_json::json!({
+        "name": "le guin",
+        "email": "ursula_le_guin@gmail.com",
+        "tags": "landing-page"
+    });
+
+    // act
+    let response = app.post_subscriptions_json(&body).await;
+
+    // assert
+    assert_eq!(response.status().as_u16(), 200);
+    let json: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(json["status"], "pending_confirmation");
+    let saved = sqlx::query!("SELECT email, name FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.email, "ursula_le_guin@gmail.com");
+    assert_eq!(saved.name, "le guin");
+}
+
+#[tokio::test]
+async fn json_bodies_can_send_custom_fields_as_numbers_and_booleans() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    for (name, label, field_type) in [
+        ("age", "Age", "number"),
+        ("newsletter_opt_in", "Newsletter opt in", "boolean"),
+    ] {
+        app.post_custom_fields(&serde_json::json!({
+            "name": name,
+            "label": label,
+            "field_type": field_type,
+            "options": "",
+        }))
+        .await;
+    }
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let body = serde_json::json!({
+        "name": "le guin",
+        "email": "ursula_le_guin@gmail.com",
+        "age": 30,
+        "newsletter_opt_in": true
+    });
+
+    // act
+    let response = app.post_subscriptions_json(&body).await;
+
+    // assert
+    assert_eq!(response.status().as_u16(), 200);
+    let saved: Vec<(String, String)> =
+        sqlx::query!("SELECT field_name, value FROM subscriber_field_values ORDER BY field_name")
+            .fetch_all(&app.db_pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|r| (r.field_name, r.value))
+            .collect();
+    assert_eq!(
+        saved,
+        vec![
+            ("age".to_string(), "30".to_string()),
+            ("newsletter_opt_in".to_string(), "true".to_string()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn json_clients_get_structured_errors() {
+    // arrange
+    let app = spawn_app().await;
+    let test_cases = vec![
+        (
+            serde_json::json!({ "name": "le guin", "email": "definitely-not-an-email" }),
+            "an invalid email",
+        ),
+        (serde_json::json!({ "name": "le guin" }), "a missing email"),
+    ];
+
+    for (body, description) in test_cases {
+        // act
+        let response = app.post_subscriptions_json(&body).await;
+
+        // assert
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The API did not fail with 400 Bad Request for {}.",
+            description
+        );
+        let json: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(json["error"]["code"], "validation_error", "{}", description);
+        assert!(json["error"]["message"].is_string(), "{}", description);
+    }
+}
+
+#[tokio::test]
+async fn form_clients_keep_getting_plain_errors() {
+    // arrange
+    let app = spawn_app().await;
+
+    // act
+    let response = app
+        .post_subscriptions("name=le%20guin&email=definitely-not-an-email".into())
+        .await;
+
+    // assert
+    assert_eq!(response.status().as_u16(), 400);
+    assert_ne!(
+        response.headers().get("Content-Type").map(|v| v.as_bytes()),
+        Some("application/json".as_bytes())
+    );
+}
+
+#[tokio::test]
+async fn allowed_origins_pass_the_cors_preflight() {
+    // arrange
+    let app = spawn_app().await;
+
+    // act
+    let response = reqwest::Client::new()
+        .request(
+            reqwest::Method::OPTIONS,
+            format!("{}/subscriptions", &app.address),
+        )
+        .header("Origin", ALLOWED_ORIGIN)
+        .header("Access-Control-Request-Method", "POST")
+        .header("Access-Control-Request-Headers", "content-type")
+        .send()
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["Access-Control-Allow-Origin"],
+        ALLOWED_ORIGIN
+    );
+}
+
+#[tokio::test]
+async fn other_origins_fail_the_cors_preflight() {
+    // arrange
+    let app = spawn_app().await;
+
+    // act
+    let response = reqwest::Client::new()
+        .request(
+            reqwest::Method::OPTIONS,
+            format!("{}/subscriptions", &app.address),
+        )
+        .header("Origin", "https://evil.example.com")
+        .header("Access-Control-Request-Method", "POST")
+        .header("Access-Control-Request-Headers", "content-type")
+        .send()
+        .await
+        .unwrap();
+
+    // assert
+    assert!(
+        response
+            .headers()
+            .get("Access-Control-Allow-Origin")
+            .is_none()
+    );
+}
+
+#[tokio::test]
+async fn the_widget_script_is_served() {
+    // arrange
+    let app = spawn_app().await;
+
+    // act
+    let response = reqwest::get(format!("{}/subscriptions/widget.js", &app.address))
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(
+        response.headers()["Content-Type"]
+            .to_str()
+            .unwrap()
+            .starts_with("application/javascript")
+    );
+    let script = response.text().await.unwrap();
+    assert!(script.contains("data-newsletter-subscribe"));
+}
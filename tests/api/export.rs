@@ -0,0 +1,136 @@
+use crate::helpers::{TestApp, assert_is_redirect_to, spawn_app};
+
+// helpers
+async fn import_subscribers(app: &TestApp) {
+    app.post_custom_fields(&serde_json::json!({
+        "name": "company",
+        "label": "Company",
+        "field_type": "text",
+        "options": "",
+    }))
+    .await;
+    let response = app
+        .post_subscriber_import(
+            "confirmed",
+            "email,name,tags,company\n\
+            ursula@example.com,\"Le Guin, Ursula\",vip,Earthsea Inc\n\
+            ged@example.com,Ged,,\n",
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    sqlx::query!(
+        "UPDATE subscriptions SET status = 'unsubscribed' WHERE email = 'ged@example.com'"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+// tests
+#[tokio::test]
+async fn you_must_be_logged_in_to_export_subscribers() {
+    // arrange
+    let app = spawn_app().await;
+
+    // act
+    let response = app.get_subscriber_export("format=csv").await;
+
+    // assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn subscribers_are_exported_as_csv() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    import_subscribers(&app).await;
+
+    // act
+    let response = app.get_subscriber_export("format=csv").await;
+
+    // assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(
+        response.headers()["content-disposition"]
+            .to_str()
+            .unwrap()
+            .starts_with("attachment")
+    );
+    let body = response.text().await.unwrap();
+    let lines: Vec<&str> = body.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(
+        lines[0],
+        "id,email,name,status,subscribed_at,confirmed_at,tags,company"
+    );
+    assert!(body.contains(",ursula@example.com,\"Le Guin, Ursula\",confirmed,"));
+    assert!(lines.iter().any(|l| l.ends_with(",vip,Earthsea Inc")));
+    assert!(body.contains(",ged@example.com,Ged,unsubscribed,"));
+}
+
+#[tokio::test]
+async fn subscribers_are_exported_as_ndjson_filtered_by_status() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    import_subscribers(&app).await;
+
+    // act
+    let response = app
+        .get_subscriber_export("format=ndjson&status=confirmed")
+        .await;
+
+    // assert
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response.text().await.unwrap();
+    let records: Vec<serde_json::Value> = body
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0]["email"], "ursula@example.com");
+    assert_eq!(records[0]["tags"], serde_json::json!(["vip"]));
+    assert_eq!(records[0]["custom_fields"]["company"], "Earthsea Inc");
+}
+
+#[tokio::test]
+async fn subscribers_can_be_exported_by_segment() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    import_subscribers(&app).await;
+    app.post_segments(&serde_json::json!({
+        "name": "Not VIPs",
+        "definition": "NOT tag:vip",
+    }))
+    .await;
+    let segment_id = sqlx::query!("SELECT segment_id FROM segments")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .segment_id;
+
+    // act
+    let response = app
+        .get_subscriber_export(&format!("format=ndjson&segment_id={}", segment_id))
+        .await;
+
+    // assert
+    let body = response.text().await.unwrap();
+    assert_eq!(body.lines().count(), 1);
+    assert!(body.contains("ged@example.com"));
+}
+
+#[tokio::test]
+async fn unknown_export_formats_are_rejected() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // act
+    let response = app.get_subscriber_export("format=xml").await;
+
+    // assert
+    assert_eq!(response.status().as_u16(), 400);
+}
@@ -2,14 +2,15 @@ use argon2::{
     Algorithm, Argon2, Params, PasswordHasher, Version,
     password_hash::{SaltString, rand_core::OsRng},
 };
-use secrecy::SecretString;
+use secrecy::{ExposeSecret, SecretString};
 use sqlx::{Connection, Executor, PgConnection, PgPool};
 use std::sync::LazyLock;
 use uuid::Uuid;
 use wiremock::MockServer;
 use zero2prod::{
-    configuration::{DatabaseSettings, get_configuration},
+    configuration::{DatabaseSettings, PostmarkWebhookSettings, Settings, get_configuration},
     email_client::EmailClient,
+    email_queue::try_send_queued_email,
     issue_delivery_worker::{ExecutionOutcome, try_execute_task},
     startup::{Application, get_connection_pool},
     telemetry::{get_subscriber, init_subscriber},
@@ -39,14 +40,20 @@ pub struct TestUser {
     pub user_id: Uuid,
     pub username: String,
     pub password: String,
+    pub role: &'static str,
 }
 
 impl TestUser {
     pub fn generate() -> Self {
+        Self::generate_with_role("owner")
+    }
+
+    pub fn generate_with_role(role: &'static str) -> Self {
         Self {
             user_id: Uuid::new_v4(),
             username: Uuid::new_v4().to_string(),
             password: Uuid::new_v4().to_string(),
+            role,
         }
     }
 
@@ -58,7 +65,7 @@ impl TestUser {
         .await;
     }
 
-    async fn store(&self, pool: &PgPool) {
+    pub async fn store(&self, pool: &PgPool) {
         let salt = SaltString::generate(&mut OsRng);
         // we dont care about the Argon2 params here since this is for testing
         let password_hash = Argon2::new(
@@ -70,11 +77,12 @@ impl TestUser {
         .unwrap()
         .to_string();
         sqlx::query!(
-            "INSERT INTO users (user_id, username, password_hash)
-            VALUES ($1, $2, $3)",
+            "INSERT INTO users (user_id, username, password_hash, role)
+            VALUES ($1, $2, $3, $4)",
             self.user_id,
             self.username,
             password_hash,
+            self.role,
         )
         .execute(pool)
         .await
@@ -90,6 +98,7 @@ pub struct TestApp {
     pub test_user: TestUser,
     pub api_client: reqwest::Client,
     pub email_client: EmailClient,
+    pub postmark_webhook: PostmarkWebhookSettings,
 }
 
 impl TestApp {
@@ -105,6 +114,10 @@ impl TestApp {
             .unwrap()
     }
 
+    pub async fn csrf_token(&self) -> String {
+        get_csrf_token(&self.api_client, &self.address).await
+    }
+
     pub async fn post_login<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
@@ -112,6 +125,7 @@ impl TestApp {
         self.api_client
             .post(&format!("{}/login", &self.address))
             .form(&body)
+            .header("X-CSRF-Token", self.csrf_token().await)
             .send()
             .await
             .expect("Failed to execute request.")
@@ -148,6 +162,7 @@ impl TestApp {
         self.api_client
             .post(&format!("{}/admin/password", &self.address))
             .form(body)
+            .header("X-CSRF-Token", self.csrf_token().await)
             .send()
             .await
             .expect("Failed to execute request.")
@@ -156,6 +171,7 @@ impl TestApp {
     pub async fn post_logout(&self) -> reqwest::Response {
         self.api_client
             .post(&format!("{}/admin/logout", &self.address))
+            .header("X-CSRF-Token", self.csrf_token().await)
             .send()
             .await
             .expect("Failed to execute request.")
@@ -180,6 +196,491 @@ impl TestApp {
         self.api_client
             .post(&format!("{}/admin/newsletter", &self.address))
             .form(body)
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_segments(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/segments", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_segments_html(&self) -> String {
+        self.get_segments().await.text().await.unwrap()
+    }
+
+    pub async fn post_segments<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/segments", &self.address))
+            .form(body)
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_tags<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/tags", &self.address))
+            .form(body)
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_custom_fields(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/fields", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_custom_fields<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/fields", &self.address))
+            .form(body)
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_subscriber_fields_html(&self, subscriber_id: Uuid) -> String {
+        self.api_client
+            .get(format!(
+                "{}/admin/subscribers/{}/fields",
+                &self.address, subscriber_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_subscriber_fields<Body>(
+        &self,
+        subscriber_id: Uuid,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!(
+                "{}/admin/subscribers/{}/fields",
+                &self.address, subscriber_id
+            ))
+            .form(body)
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_subscribers(&self, query: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/subscribers?{}", &self.address, query))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_subscribers_html(&self, query: &str) -> String {
+        self.get_subscribers(query).await.text().await.unwrap()
+    }
+
+    pub async fn get_subscriber_details_html(&self, subscriber_id: Uuid) -> String {
+        self.api_client
+            .get(format!(
+                "{}/admin/subscribers/{}",
+                &self.address, subscriber_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_subscriber_action(
+        &self,
+        subscriber_id: Uuid,
+        action: &str,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/subscribers/{}/{}",
+                &self.address, subscriber_id, action
+            ))
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_subscriber_export(&self, query: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/subscribers/export?{}",
+                &self.address, query
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // the body is put together by hand so tests control the order of the parts
+    pub async fn post_subscriber_import(&self, mode: &str, csv: &str) -> reqwest::Response {
+        let boundary = "zero2prod-import-boundary";
+        let csrf_token = self.csrf_token().await;
+        let body = format!(
+            "--{boundary}\r\n\
+            Content-Disposition: form-data; name=\"csrf_token\"\r\n\r\n\
+            {csrf_token}\r\n\
+            --{boundary}\r\n\
+            Content-Disposition: form-data; name=\"mode\"\r\n\r\n\
+            {mode}\r\n\
+            --{boundary}\r\n\
+            Content-Disposition: form-data; name=\"file\"; filename=\"subscribers.csv\"\r\n\
+            Content-Type: text/csv\r\n\r\n\
+            {csv}\r\n\
+            --{boundary}--\r\n"
+        );
+        self.api_client
+            .post(format!("{}/admin/subscribers/import", &self.address))
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_suppressions_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/suppressions", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_suppressions(&self, email: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/suppressions", &self.address))
+            .form(&[("email", email)])
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_delete_suppression(&self, email_hash: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/suppressions/delete", &self.address))
+            .form(&[("email_hash", email_hash)])
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_users(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/users", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_users_html(&self) -> String {
+        self.get_users().await.text().await.unwrap()
+    }
+
+    pub async fn post_invite_user(&self, email: &str, role: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/users/invite", &self.address))
+            .form(&[("email", email), ("role", role)])
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_user_action(&self, user_id: Uuid, action: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/users/{}/{}",
+                &self.address, user_id, action
+            ))
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_user_role(&self, user_id: Uuid, role: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/users/{}/role", &self.address, user_id))
+            .form(&[("role", role)])
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_accept_invitation<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/invitations/accept", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // webhooks
+    pub async fn get_two_factor_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/two-factor", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    // `action` is one of setup, confirm, recovery-codes or disable
+    pub async fn post_two_factor(&self, action: &str, code: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/two-factor/{}", &self.address, action))
+            .form(&[("code", code)])
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_two_factor_login(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/login/two-factor", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_two_factor_login(&self, code: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/login/two-factor", &self.address))
+            .form(&[("code", code)])
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_sessions_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/sessions", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_revoke_session(&self, session_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/sessions/{}/revoke",
+                &self.address, session_id
+            ))
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_log_out_everywhere(&self) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/sessions/logout-everywhere",
+                &self.address
+            ))
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_api_tokens_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/api-tokens", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_create_api_token(&self, name: &str, scopes: &[&str]) -> reqwest::Response {
+        let mut form = vec![("name", name)];
+        form.extend(scopes.iter().map(|scope| ("scope", *scope)));
+        self.api_client
+            .post(format!("{}/admin/api-tokens", &self.address))
+            .form(&form)
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // the token is only shown on the page the form leads to
+    pub async fn create_api_token(&self, name: &str, scopes: &[&str]) -> String {
+        let html = self
+            .post_create_api_token(name, scopes)
+            .await
+            .text()
+            .await
+            .unwrap();
+        let (_, rest) = html
+            .split_once("<p><code>")
+            .expect("The page has no API token.");
+        rest.split_once("</code>").unwrap().0.to_string()
+    }
+
+    pub async fn post_revoke_api_token(&self, token_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/api-tokens/{}/revoke",
+                &self.address, token_id
+            ))
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_password_reset_request(&self, username: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/password-reset", &self.address))
+            .form(&[("username", username)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_password_reset<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/password-reset/confirm", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_postmark_webhook(&self, body: &str) -> reqwest::Response {
+        self.post_postmark_webhook_as(
+            &self.postmark_webhook.username,
+            self.postmark_webhook.password.expose_secret(),
+            body,
+        )
+        .await
+    }
+
+    pub async fn post_postmark_webhook_as(
+        &self,
+        username: &str,
+        password: &str,
+        body: &str,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/webhooks/postmark", &self.address))
+            .basic_auth(username, Some(password))
+            .header("Content-Type", "application/json")
+            .body(body.to_string())
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // json api, requests only carry the token
+    pub async fn api_get(&self, path: &str, token: &str) -> reqwest::Response {
+        reqwest::Client::new()
+            .get(format!("{}/api/v1{}", &self.address, path))
+            .bearer_auth(token)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn api_post(
+        &self,
+        path: &str,
+        token: &str,
+        body: &serde_json::Value,
+    ) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!("{}/api/v1{}", &self.address, path))
+            .bearer_auth(token)
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn api_post_with_idempotency_key(
+        &self,
+        path: &str,
+        token: &str,
+        idempotency_key: &str,
+        body: &serde_json::Value,
+    ) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!("{}/api/v1{}", &self.address, path))
+            .bearer_auth(token)
+            .header("Idempotency-Key", idempotency_key)
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn api_delete(&self, path: &str, token: &str) -> reqwest::Response {
+        reqwest::Client::new()
+            .delete(format!("{}/api/v1{}", &self.address, path))
+            .bearer_auth(token)
             .send()
             .await
             .expect("Failed to execute request.")
@@ -196,6 +697,32 @@ impl TestApp {
                 break;
             }
         }
+        loop {
+            if let ExecutionOutcome::EmptyQueue =
+                try_send_queued_email(&self.db_pool, &self.email_client)
+                    .await
+                    .unwrap()
+            {
+                break;
+            }
+        }
+    }
+
+    // emails sent in the background arrive some time after the response
+    pub async fn wait_for_emails(&self, n: usize) -> Vec<wiremock::Request> {
+        for _ in 0..100 {
+            let received = self.email_server.received_requests().await.unwrap();
+            if received.len() >= n {
+                return received;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+        panic!("Expected {} emails to have been sent.", n);
+    }
+
+    // give background sends a chance to happen before checking that none did
+    pub async fn let_background_emails_settle(&self) {
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
     }
 
     // subscriptions
@@ -209,6 +736,25 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_subscriptions_json(&self, body: &serde_json::Value) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/subscriptions", &self.address))
+            .header("Accept", "application/json")
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_data_request(&self, email: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/subscriptions/data/request", &self.address))
+            .form(&[("email", email)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub fn get_confirmation_links(&self, email_request: &wiremock::Request) -> ConfirmationLinks {
         let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
 
@@ -232,6 +778,11 @@ impl TestApp {
 }
 
 pub async fn spawn_app() -> TestApp {
+    spawn_app_with(|_| {}).await
+}
+
+// for tests that need something other than the usual test configuration
+pub async fn spawn_app_with(configure: impl FnOnce(&mut Settings)) -> TestApp {
     LazyLock::force(&TRACING);
 
     let email_server = MockServer::start().await;
@@ -244,6 +795,12 @@ pub async fn spawn_app() -> TestApp {
         // use a random OS port
         c.application.port = 0;
         c.email_client.base_url = email_server.uri();
+        // tests share one redis, keep their rate limit counters apart
+        c.signup_protection.key_prefix = Uuid::new_v4().to_string();
+        c.login_protection.key_prefix = Uuid::new_v4().to_string();
+        c.link_request_protection.key_prefix = Uuid::new_v4().to_string();
+        c.cors.allowed_origins = vec!["https://marketing.example.com".into()];
+        configure(&mut c);
         c
     };
 
@@ -273,6 +830,7 @@ pub async fn spawn_app() -> TestApp {
         test_user: TestUser::generate(),
         api_client: client,
         email_client: configuration.email_client.client(),
+        postmark_webhook: configuration.postmark_webhook,
     };
     test_app.test_user.store(&test_app.db_pool).await;
     test_app
@@ -284,6 +842,22 @@ pub fn assert_is_redirect_to(response: &reqwest::Response, location: &str) {
     assert_eq!(response.headers().get("Location").unwrap(), location);
 }
 
+// the token the login form is rendered with, valid for every form of the client's session
+pub async fn get_csrf_token(client: &reqwest::Client, address: &str) -> String {
+    let html = client
+        .get(format!("{}/login", address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap();
+    let (_, rest) = html
+        .split_once(r#"name="csrf_token" value=""#)
+        .expect("The login form has no CSRF token.");
+    rest.split_once('"').unwrap().0.to_string()
+}
+
 async fn configure_database(config: &DatabaseSettings) -> PgPool {
     let maintenance_settings = DatabaseSettings {
         database_name: "postgres".to_string(),
@@ -0,0 +1,224 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+use zero2prod::domain::SuppressionReason;
+use zero2prod::suppression::suppress;
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_import_subscribers() {
+    // arrange
+    let app = spawn_app().await;
+
+    // act
+    let response = app
+        .post_subscriber_import("confirmed", "email,name\nursula@example.com,Ursula")
+        .await;
+
+    // assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn imported_rows_can_be_marked_as_confirmed() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+    let csv = "Email,Name,Tags\n\
+        ursula@example.com,\"Le Guin, Ursula\",\"vip,beta\"\n\
+        ged@example.com,Ged,\n";
+
+    // act
+    let response = app.post_subscriber_import("confirmed", csv).await;
+
+    // assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html = response.text().await.unwrap();
+    assert!(html.contains("Imported 2 subscribers."));
+    let saved =
+        sqlx::query!("SELECT email, name, status, confirmed_at FROM subscriptions ORDER BY email")
+            .fetch_all(&app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(saved.len(), 2);
+    assert_eq!(saved[1].email, "ursula@example.com");
+    assert_eq!(saved[1].name, "Le Guin, Ursula");
+    assert!(
+        saved
+            .iter()
+            .all(|s| s.status == "confirmed" && s.confirmed_at.is_some())
+    );
+    let tags = sqlx::query!("SELECT tag FROM subscriber_tags ORDER BY tag")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    let tags: Vec<String> = tags.into_iter().map(|r| r.tag).collect();
+    assert_eq!(tags, vec!["beta".to_string(), "vip".to_string()]);
+}
+
+#[tokio::test]
+async fn imported_rows_can_be_sent_a_confirmation_email() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+    let csv = "email,name\nursula@example.com,Ursula\nged@example.com,Ged";
+
+    // act
+    let response = app.post_subscriber_import("send_confirmation", csv).await;
+    // the emails go out in the background
+    assert!(
+        app.email_server
+            .received_requests()
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    app.dispatch_all_pending_emails().await;
+
+    // assert
+    assert_eq!(response.status().as_u16(), 200);
+    let statuses = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(statuses.len(), 2);
+    assert!(statuses.iter().all(|s| s.status == "pending_confirmation"));
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn invalid_rows_are_reported_and_duplicates_skipped() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.post_subscriber_import("confirmed", "email,name\nursula@example.com,Ursula")
+        .await;
+    let csv = "email,name\n\
+        ursula@example.com,Ursula\n\
+        not-an-email,Someone\n\
+        ged@example.com,\n\
+        ged@example.com,Ged\n\
+        GED@Example.com,Ged again\n";
+
+    // act
+    let response = app.post_subscriber_import("confirmed", csv).await;
+
+    // assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html = response.text().await.unwrap();
+    assert!(html.contains("Imported 1 subscribers."));
+    assert!(html.contains("Skipped 2 rows"));
+    assert!(html.contains("2 rows had errors."));
+    assert!(
+        html.contains("<tr><td>3</td><td>not-an-email is not a valid subscriber email.</td></tr>")
+    );
+    assert!(html.contains("<tr><td>4</td>"));
+    let count = sqlx::query!("SELECT count(*) AS \"count!\" FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(count, 2);
+}
+
+#[tokio::test]
+async fn a_file_without_the_required_columns_is_rejected() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // act
+    let response = app
+        .post_subscriber_import("confirmed", "address,full_name\nursula@example.com,Ursula")
+        .await;
+
+    // assert
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn a_database_error_is_reported_instead_of_aborting_the_import() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    // make storing tags fail
+    sqlx::query!("ALTER TABLE subscriber_tags RENAME TO subscriber_tags_gone")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let csv = "email,name,tags\n\
+        ursula@example.com,Ursula,vip\n\
+        not-an-email,Someone,\n";
+
+    // act
+    let response = app.post_subscriber_import("confirmed", csv).await;
+
+    // assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html = response.text().await.unwrap();
+    assert!(html.contains("Imported 0 subscribers."));
+    assert!(html.contains("2 rows had errors."));
+    assert!(html.contains("<tr><td>2</td><td>Not imported because of a database error"));
+    let count = sqlx::query!("SELECT count(*) AS \"count!\" FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(count, 0);
+}
+
+#[tokio::test]
+async fn suppressed_addresses_are_not_imported() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    suppress(
+        &app.db_pool,
+        "ursula@example.com",
+        SuppressionReason::HardBounce,
+    )
+    .await
+    .unwrap();
+    let csv = "email,name\nUrsula@Example.com,Ursula\nged@example.com,Ged\n";
+
+    // act
+    let response = app.post_subscriber_import("send_confirmation", csv).await;
+
+    // assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html = response.text().await.unwrap();
+    assert!(html.contains("Imported 1 subscribers."));
+    assert!(html.contains("Skipped 0 rows with an email that is already subscribed."));
+    assert!(html.contains("Skipped 1 rows with an email on the suppression list."));
+    let saved = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.len(), 1);
+    assert_eq!(saved[0].email, "ged@example.com");
+    let queued = sqlx::query!("SELECT recipient FROM email_queue")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(queued.len(), 1);
+    assert_eq!(queued[0].recipient, "ged@example.com");
+}
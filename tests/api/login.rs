@@ -1,4 +1,21 @@
-use crate::helpers::{assert_is_redirect_to, spawn_app};
+use argon2::{
+    Algorithm, Argon2, Params, PasswordHasher, Version,
+    password_hash::{SaltString, rand_core::OsRng},
+};
+use uuid::Uuid;
+
+use crate::helpers::{TestApp, assert_is_redirect_to, spawn_app};
+
+async fn stored_password_hash(app: &TestApp, user_id: Uuid) -> String {
+    sqlx::query!(
+        "SELECT password_hash FROM users WHERE user_id = $1",
+        user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .password_hash
+}
 
 #[tokio::test]
 async fn an_error_flash_message_is_set_on_failure() {
@@ -42,3 +59,96 @@ async fn redirect_to_admin_dashboard_after_login_success() {
     let html_page = app.get_admin_dashboard_html().await;
     assert!(html_page.contains(&format!("Welcome {}", app.test_user.username)));
 }
+
+#[tokio::test]
+async fn logging_in_upgrades_a_hash_with_outdated_parameters() {
+    // arrange
+    let app = spawn_app().await;
+    let salt = SaltString::generate(&mut OsRng);
+    let outdated_hash = Argon2::new(
+        Algorithm::Argon2i,
+        Version::V0x10,
+        Params::new(4096, 1, 1, None).unwrap(),
+    )
+    .hash_password(app.test_user.password.as_bytes(), &salt)
+    .unwrap()
+    .to_string();
+    sqlx::query!(
+        "UPDATE users SET password_hash = $1 WHERE user_id = $2",
+        outdated_hash,
+        app.test_user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    // act
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password
+        }))
+        .await;
+
+    // assert
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let upgraded_hash = stored_password_hash(&app, app.test_user.user_id).await;
+    assert!(upgraded_hash.starts_with("$argon2id$v=19$m=15000,t=2,p=1$"));
+    // and the password still works against the new hash
+    app.post_logout().await;
+    app.test_user.login(&app).await;
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains(&format!("Welcome {}", app.test_user.username)));
+}
+
+#[tokio::test]
+async fn logging_in_keeps_a_hash_with_current_parameters() {
+    // arrange
+    let app = spawn_app().await;
+    let hash_before = stored_password_hash(&app, app.test_user.user_id).await;
+
+    // act
+    app.test_user.login(&app).await;
+
+    // assert
+    assert_eq!(
+        stored_password_hash(&app, app.test_user.user_id).await,
+        hash_before
+    );
+}
+
+#[tokio::test]
+async fn a_failed_login_leaves_an_outdated_hash_alone() {
+    // arrange
+    let app = spawn_app().await;
+    let salt = SaltString::generate(&mut OsRng);
+    let outdated_hash = Argon2::new(
+        Algorithm::Argon2id,
+        Version::V0x13,
+        Params::new(4096, 1, 1, None).unwrap(),
+    )
+    .hash_password(app.test_user.password.as_bytes(), &salt)
+    .unwrap()
+    .to_string();
+    sqlx::query!(
+        "UPDATE users SET password_hash = $1 WHERE user_id = $2",
+        outdated_hash,
+        app.test_user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    // act
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": "wrong-password"
+    }))
+    .await;
+
+    // assert
+    assert_eq!(
+        stored_password_hash(&app, app.test_user.user_id).await,
+        outdated_hash
+    );
+}
@@ -0,0 +1,126 @@
+use crate::helpers::{TestApp, assert_is_redirect_to, get_csrf_token, spawn_app};
+use std::time::Duration;
+use uuid::Uuid;
+
+const TOO_MANY_ATTEMPTS: &str = "Too many failed attempts, please try again later.";
+
+async fn login_with(app: &TestApp, password: &str) -> reqwest::Response {
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": password
+    }))
+    .await
+}
+
+async fn login_with_the_right_password(app: &TestApp) -> reqwest::Response {
+    login_with(app, &app.test_user.password.clone()).await
+}
+
+#[tokio::test]
+async fn repeated_failures_make_the_username_wait() {
+    // arrange
+    let app = spawn_app().await;
+    for _ in 0..4 {
+        let response = login_with(&app, "wrong-password").await;
+        assert_is_redirect_to(&response, "/login");
+    }
+
+    // act 1: even the right password is turned away while the username waits
+    let response = login_with_the_right_password(&app).await;
+
+    // assert 1
+    assert_is_redirect_to(&response, "/login");
+    assert!(app.get_login_html().await.contains(TOO_MANY_ATTEMPTS));
+
+    // act 2: the first delay is a second long
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    let response = login_with_the_right_password(&app).await;
+
+    // assert 2
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn a_successful_login_forgives_earlier_failures() {
+    // arrange
+    let app = spawn_app().await;
+    for _ in 0..3 {
+        login_with(&app, "wrong-password").await;
+    }
+    let response = login_with_the_right_password(&app).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    app.post_logout().await;
+
+    // act
+    // a fourth failure in a row would have made the username wait
+    login_with(&app, "wrong-password").await;
+    let response = login_with_the_right_password(&app).await;
+
+    // assert
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn an_ip_guessing_at_many_usernames_is_locked_out_and_reported() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let attacker = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .build()
+        .unwrap();
+    let csrf_token = get_csrf_token(&attacker, &app.address).await;
+    let login = |username: String, password: String| {
+        attacker
+            .post(format!("{}/login", &app.address))
+            .header("X-CSRF-Token", &csrf_token)
+            // no proxy in front of the test app, so this is the client's own claim
+            .header("X-Forwarded-For", "203.0.113.7")
+            .form(&[("username", username), ("password", password)])
+            .send()
+    };
+    for _ in 0..50 {
+        login(Uuid::new_v4().to_string(), "guess".into())
+            .await
+            .unwrap();
+    }
+
+    // act
+    let response = login(
+        app.test_user.username.clone(),
+        app.test_user.password.clone(),
+    )
+    .await
+    .unwrap();
+
+    // assert
+    assert_is_redirect_to(&response, "/login");
+    let html = app
+        .api_client
+        .get(format!("{}/admin/lockouts", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html.contains("ip 127.0.0.1"));
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_lockouts() {
+    // arrange
+    let app = spawn_app().await;
+
+    // act
+    let response = app
+        .api_client
+        .get(format!("{}/admin/lockouts", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    // assert
+    assert_is_redirect_to(&response, "/login");
+}
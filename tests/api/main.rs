@@ -1,8 +1,28 @@
 mod admin_dashboard;
+mod admin_subscribers;
+mod api_tokens;
+mod api_v1;
 mod change_password;
+mod csrf;
+mod custom_fields;
+mod embedded_signup;
+mod export;
 mod health_check;
 mod helpers;
+mod import;
 mod login;
+mod login_protection;
 mod newsletter;
+mod openapi;
+mod password_reset;
+mod roles;
+mod segments;
+mod session_store;
+mod sessions;
+mod subscriber_data;
 mod subscriptions;
 mod subscriptions_confirm;
+mod suppressions;
+mod two_factor;
+mod users;
+mod webhooks;
@@ -0,0 +1,304 @@
+use serde_json::{Value, json};
+use uuid::Uuid;
+use wiremock::matchers::any;
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{TestApp, spawn_app};
+
+async fn get_openapi_document(app: &TestApp) -> Value {
+    let response = reqwest::get(format!("{}/api/openapi.json", &app.address))
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+    response.json().await.unwrap()
+}
+
+fn resolve<'a>(document: &'a Value, schema: &'a Value) -> &'a Value {
+    match schema["$ref"].as_str() {
+        Some(reference) => {
+            let name = reference
+                .strip_prefix("#/components/schemas/")
+                .expect("Only component references are supported");
+            &document["components"]["schemas"][name]
+        }
+        None => schema,
+    }
+}
+
+fn type_of(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+// a strict subset of json schema validation: types, required and undocumented fields
+fn assert_matches_schema(document: &Value, schema: &Value, value: &Value, at: &str) {
+    let schema = resolve(document, schema);
+    let allowed: Vec<&str> = match &schema["type"] {
+        Value::String(t) => vec![t.as_str()],
+        Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+        _ => panic!("{at}: the schema has no type: {schema}"),
+    };
+    let actual = type_of(value);
+    assert!(
+        allowed.contains(&actual) || (actual == "integer" && allowed.contains(&"number")),
+        "{at}: expected {allowed:?}, got {value}"
+    );
+    match value {
+        Value::Object(fields) => {
+            for required in schema["required"].as_array().into_iter().flatten() {
+                let required = required.as_str().unwrap();
+                assert!(
+                    fields.contains_key(required),
+                    "{at}: the required field {required} is missing"
+                );
+            }
+            for (name, field) in fields {
+                let at = format!("{at}.{name}");
+                match &schema["properties"][name] {
+                    Value::Null => match &schema["additionalProperties"] {
+                        Value::Object(_) => assert_matches_schema(
+                            document,
+                            &schema["additionalProperties"],
+                            field,
+                            &at,
+                        ),
+                        _ => panic!("{at}: the field is not in the schema"),
+                    },
+                    property => assert_matches_schema(document, property, field, &at),
+                }
+            }
+        }
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                assert_matches_schema(document, &schema["items"], item, &format!("{at}[{i}]"));
+            }
+        }
+        _ => {}
+    }
+}
+
+// fails when the status isn't documented for the operation, or the body doesn't match its schema
+async fn assert_documented(
+    document: &Value,
+    method: &str,
+    path: &str,
+    response: reqwest::Response,
+) -> Value {
+    let status = response.status().as_u16().to_string();
+    let operation = format!("{} {}", method.to_uppercase(), path);
+    let documented = &document["paths"][path][method]["responses"][&status];
+    assert!(
+        documented.is_object(),
+        "{operation} answered {status}, which is not documented"
+    );
+    let body = response.text().await.unwrap();
+    match &documented["content"]["application/json"]["schema"] {
+        Value::Null => {
+            assert!(
+                body.is_empty(),
+                "{operation} {status} has an undocumented body"
+            );
+            Value::Null
+        }
+        schema => {
+            let value: Value = serde_json::from_str(&body).unwrap_or_else(|e| {
+                panic!("{operation} {status} did not answer with json: {e}: {body}")
+            });
+            assert_matches_schema(document, schema, &value, &format!("{operation} {status}"));
+            value
+        }
+    }
+}
+
+#[tokio::test]
+async fn the_openapi_document_is_public() {
+    // arrange
+    let app = spawn_app().await;
+
+    // act
+    let document = get_openapi_document(&app).await;
+
+    // assert
+    assert!(document["openapi"].as_str().unwrap().starts_with("3."));
+    let mut operations: Vec<String> = document["paths"]
+        .as_object()
+        .unwrap()
+        .iter()
+        .flat_map(|(path, item)| {
+            item.as_object()
+                .unwrap()
+                .keys()
+                .map(move |method| format!("{} {}", method.to_uppercase(), path))
+        })
+        .collect();
+    operations.sort();
+    assert_eq!(
+        operations,
+        [
+            "DELETE /api/v1/subscribers/{subscriber_id}",
+            "GET /api/v1/issues",
+            "GET /api/v1/issues/{issue_id}",
+            "GET /api/v1/issues/{issue_id}/stats",
+            "GET /api/v1/subscribers",
+            "POST /api/v1/issues",
+            "POST /api/v1/issues/{issue_id}/send",
+            "POST /api/v1/subscribers",
+        ]
+    );
+    assert_eq!(
+        document["components"]["securitySchemes"]["api_token"]["scheme"],
+        "bearer"
+    );
+}
+
+#[tokio::test]
+async fn every_documented_operation_answers_as_documented() {
+    // arrange
+    let app = spawn_app().await;
+    let document = get_openapi_document(&app).await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token("tests", &["publish", "edit"]).await;
+    let unscoped_token = app.create_api_token("read only", &[]).await;
+    let client = reqwest::Client::new();
+
+    for (path, item) in document["paths"].as_object().unwrap() {
+        // unknown ids and empty bodies, so nothing gets created along the way
+        let url = format!(
+            "{}{}",
+            &app.address,
+            path.replace("{issue_id}", &Uuid::new_v4().to_string())
+                .replace("{subscriber_id}", &Uuid::new_v4().to_string())
+        );
+        for (method, operation) in item.as_object().unwrap() {
+            let request = |token: &str| {
+                let request = match method.as_str() {
+                    "get" => client.get(&url),
+                    "post" => client.post(&url).json(&json!({})),
+                    "delete" => client.delete(&url),
+                    method => panic!("{method} is not exercised by this test"),
+                };
+                request.bearer_auth(token)
+            };
+
+            // act
+            let response = request(&token).send().await.unwrap();
+            let anonymous = request("").send().await.unwrap();
+
+            // assert
+            assert_documented(&document, method, path, response).await;
+            assert_documented(&document, method, path, anonymous).await;
+            if operation["security"].is_array() {
+                let forbidden = request(&unscoped_token).send().await.unwrap();
+                assert_eq!(forbidden.status().as_u16(), 403);
+                assert_documented(&document, method, path, forbidden).await;
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn successful_responses_match_the_published_schema() {
+    // arrange
+    let app = spawn_app().await;
+    let document = get_openapi_document(&app).await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token("tests", &["publish", "edit"]).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let issue_body = json!({
+        "title": "Release notes",
+        "text_content": "What's new, as plain text",
+        "html_content": "<p>What's new, as HTML</p>",
+    });
+    let subscriber_body = json!({ "email": "ursula@example.com", "name": "Ursula" });
+
+    // act and assert
+    let issue = assert_documented(
+        &document,
+        "post",
+        "/api/v1/issues",
+        app.api_post("/issues", &token, &issue_body).await,
+    )
+    .await;
+    let issue_id = issue["id"].as_str().unwrap();
+    assert_documented(
+        &document,
+        "get",
+        "/api/v1/issues",
+        app.api_get("/issues", &token).await,
+    )
+    .await;
+    assert_documented(
+        &document,
+        "get",
+        "/api/v1/issues/{issue_id}",
+        app.api_get(&format!("/issues/{issue_id}"), &token).await,
+    )
+    .await;
+    let subscriber = assert_documented(
+        &document,
+        "post",
+        "/api/v1/subscribers",
+        app.api_post("/subscribers", &token, &subscriber_body).await,
+    )
+    .await;
+    assert_documented(
+        &document,
+        "post",
+        "/api/v1/subscribers",
+        app.api_post("/subscribers", &token, &subscriber_body).await,
+    )
+    .await;
+    assert_documented(
+        &document,
+        "get",
+        "/api/v1/subscribers",
+        app.api_get("/subscribers?status=pending_confirmation", &token)
+            .await,
+    )
+    .await;
+    assert_documented(
+        &document,
+        "post",
+        "/api/v1/issues/{issue_id}/send",
+        app.api_post(&format!("/issues/{issue_id}/send"), &token, &json!({}))
+            .await,
+    )
+    .await;
+    assert_documented(
+        &document,
+        "post",
+        "/api/v1/issues/{issue_id}/send",
+        app.api_post(&format!("/issues/{issue_id}/send"), &token, &json!({}))
+            .await,
+    )
+    .await;
+    assert_documented(
+        &document,
+        "get",
+        "/api/v1/issues/{issue_id}/stats",
+        app.api_get(&format!("/issues/{issue_id}/stats"), &token)
+            .await,
+    )
+    .await;
+    assert_documented(
+        &document,
+        "delete",
+        "/api/v1/subscribers/{subscriber_id}",
+        app.api_delete(
+            &format!("/subscribers/{}", subscriber["id"].as_str().unwrap()),
+            &token,
+        )
+        .await,
+    )
+    .await;
+}
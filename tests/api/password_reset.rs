@@ -0,0 +1,290 @@
+use crate::helpers::{TestApp, assert_is_redirect_to, spawn_app};
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+
+const NEW_PASSWORD: &str = "a-brand-new-password";
+const CONFIRMATION: &str =
+    "If that account has an email address, we've sent it a link to reset the password.";
+
+// gives the test user an address and requests a reset, returning the token from the link
+async fn request_reset(app: &TestApp) -> String {
+    sqlx::query!(
+        "UPDATE users SET email = 'admin@example.com' WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_password_reset_request(&app.test_user.username)
+        .await;
+    assert_is_redirect_to(&response, "/login");
+    let email_request = app.wait_for_emails(1).await;
+    let links = app.get_confirmation_links(email_request.last().unwrap());
+    links
+        .html
+        .query_pairs()
+        .find(|(k, _)| k == "token")
+        .unwrap()
+        .1
+        .into_owned()
+}
+
+fn reset_body(token: &str, password: &str) -> serde_json::Value {
+    serde_json::json!({
+        "token": token,
+        "new_password": password,
+        "new_password_check": password,
+    })
+}
+
+async fn get_reset_form(app: &TestApp, token: &str) -> reqwest::Response {
+    app.api_client
+        .get(format!(
+            "{}/password-reset/confirm?token={}",
+            &app.address, token
+        ))
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn the_login_page_links_to_the_password_reset() {
+    // arrange
+    let app = spawn_app().await;
+
+    // act
+    let html = app.get_login_html().await;
+
+    // assert
+    assert!(html.contains(r#"href="/password-reset""#));
+}
+
+#[tokio::test]
+async fn an_unknown_username_gets_the_same_response_and_no_email() {
+    // arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // act
+    let response = app.post_password_reset_request("nobody").await;
+
+    // assert
+    assert_is_redirect_to(&response, "/login");
+    assert!(app.get_login_html().await.contains(CONFIRMATION));
+    app.let_background_emails_settle().await;
+}
+
+#[tokio::test]
+async fn a_failing_email_does_not_change_the_response() {
+    // arrange
+    let app = spawn_app().await;
+    sqlx::query!(
+        "UPDATE users SET email = 'admin@example.com' WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&app.email_server)
+        .await;
+
+    // act
+    let response = app
+        .post_password_reset_request(&app.test_user.username)
+        .await;
+
+    // assert
+    assert_is_redirect_to(&response, "/login");
+    assert!(app.get_login_html().await.contains(CONFIRMATION));
+    // mock servers are reused, don't leave the send to land in another test
+    app.wait_for_emails(1).await;
+}
+
+#[tokio::test]
+async fn repeated_resets_for_a_username_send_no_more_emails() {
+    // arrange
+    let app = spawn_app().await;
+    sqlx::query!(
+        "UPDATE users SET email = 'admin@example.com' WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(3)
+        .mount(&app.email_server)
+        .await;
+
+    // act
+    for _ in 0..4 {
+        let response = app
+            .post_password_reset_request(&app.test_user.username)
+            .await;
+
+        // assert
+        assert_is_redirect_to(&response, "/login");
+    }
+    app.wait_for_emails(3).await;
+    app.let_background_emails_settle().await;
+}
+
+#[tokio::test]
+async fn repeated_resets_from_the_same_ip_are_rate_limited() {
+    // arrange
+    let app = spawn_app().await;
+    for i in 0..10 {
+        let response = app
+            .post_password_reset_request(&format!("reader{}", i))
+            .await;
+        assert_is_redirect_to(&response, "/login");
+    }
+
+    // act
+    let response = app.post_password_reset_request("one_more").await;
+
+    // assert
+    assert_is_redirect_to(&response, "/password-reset");
+}
+
+#[tokio::test]
+async fn a_known_username_is_emailed_a_reset_link() {
+    // arrange
+    let app = spawn_app().await;
+
+    // act
+    let token = request_reset(&app).await;
+
+    // assert
+    assert!(app.get_login_html().await.contains(CONFIRMATION));
+    let response = get_reset_form(&app, &token).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn resetting_the_password_lets_the_user_log_in_with_the_new_one() {
+    // arrange
+    let app = spawn_app().await;
+    let token = request_reset(&app).await;
+
+    // act
+    let response = app
+        .post_password_reset(&reset_body(&token, NEW_PASSWORD))
+        .await;
+
+    // assert
+    assert_is_redirect_to(&response, "/login");
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": NEW_PASSWORD
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn a_reset_link_can_only_be_used_once() {
+    // arrange
+    let app = spawn_app().await;
+    let token = request_reset(&app).await;
+    app.post_password_reset(&reset_body(&token, NEW_PASSWORD))
+        .await;
+
+    // act
+    let response = app
+        .post_password_reset(&reset_body(&token, "yet-another-password"))
+        .await;
+
+    // assert
+    assert_is_redirect_to(
+        &response,
+        &format!("/password-reset/confirm?token={}", token),
+    );
+    assert_eq!(get_reset_form(&app, &token).await.status().as_u16(), 404);
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": NEW_PASSWORD
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn an_expired_reset_link_does_not_work() {
+    // arrange
+    let app = spawn_app().await;
+    let token = request_reset(&app).await;
+    sqlx::query!("UPDATE password_reset_tokens SET expires_at = now() - interval '1 minute'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // act
+    let response = get_reset_form(&app, &token).await;
+
+    // assert
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn the_new_password_must_follow_the_length_rules() {
+    // arrange
+    let app = spawn_app().await;
+    let token = request_reset(&app).await;
+
+    // act
+    let response = app.post_password_reset(&reset_body(&token, "short")).await;
+
+    // assert
+    assert_is_redirect_to(
+        &response,
+        &format!("/password-reset/confirm?token={}", token),
+    );
+    let html = get_reset_form(&app, &token).await.text().await.unwrap();
+    assert!(html.contains("Your new password is too short"));
+}
+
+#[tokio::test]
+async fn a_reset_logs_out_every_existing_session() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+    let token = request_reset(&app).await;
+
+    // act
+    app.post_password_reset(&reset_body(&token, NEW_PASSWORD))
+        .await;
+
+    // assert
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
@@ -0,0 +1,213 @@
+use crate::helpers::{TestApp, TestUser, assert_is_redirect_to, spawn_app};
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{any, method, path},
+};
+
+// logs in as a freshly stored user with the given role
+async fn login_as(app: &TestApp, role: &'static str) -> TestUser {
+    let user = TestUser::generate_with_role(role);
+    user.store(&app.db_pool).await;
+    user.login(app).await;
+    user
+}
+
+fn newsletter_request_body() -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    })
+}
+
+#[tokio::test]
+async fn viewers_can_look_around_the_admin_pages() {
+    // arrange
+    let app = spawn_app().await;
+    login_as(&app, "viewer").await;
+
+    // act
+    let dashboard = app.get_admin_dashboard().await;
+    let subscribers = app.get_subscribers("").await;
+    let newsletter_form = app.get_publish_newsletter().await;
+
+    // assert
+    assert_eq!(dashboard.status().as_u16(), 200);
+    assert_eq!(subscribers.status().as_u16(), 200);
+    assert_eq!(newsletter_form.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn viewers_cannot_publish_a_newsletter() {
+    // arrange
+    let app = spawn_app().await;
+    login_as(&app, "viewer").await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // act
+    let response = app
+        .post_publish_newsletter(&newsletter_request_body())
+        .await;
+
+    // assert
+    assert_eq!(response.status().as_u16(), 403);
+    let issues = sqlx::query!(r#"SELECT count(*) AS "count!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(issues, 0);
+}
+
+#[tokio::test]
+async fn viewers_cannot_change_subscriber_data() {
+    // arrange
+    let app = spawn_app().await;
+    login_as(&app, "viewer").await;
+
+    // act
+    let response = app.post_suppressions("someone@example.com").await;
+
+    // assert
+    assert_eq!(response.status().as_u16(), 403);
+    let suppressed = sqlx::query!(r#"SELECT count(*) AS "count!" FROM email_suppressions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(suppressed, 0);
+}
+
+#[tokio::test]
+async fn editors_can_publish_a_newsletter() {
+    // arrange
+    let app = spawn_app().await;
+    login_as(&app, "editor").await;
+
+    // act
+    let response = app
+        .post_publish_newsletter(&newsletter_request_body())
+        .await;
+
+    // assert
+    assert_is_redirect_to(&response, "/admin/newsletter");
+}
+
+#[tokio::test]
+async fn editors_cannot_manage_users() {
+    // arrange
+    let app = spawn_app().await;
+    login_as(&app, "editor").await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // act
+    let users_page = app.get_users().await;
+    let invite = app.post_invite_user("ursula@example.com", "owner").await;
+    let promote = app.post_user_role(app.test_user.user_id, "viewer").await;
+
+    // assert
+    assert_eq!(users_page.status().as_u16(), 403);
+    assert_eq!(invite.status().as_u16(), 403);
+    assert_eq!(promote.status().as_u16(), 403);
+    assert!(
+        !app.get_admin_dashboard_html()
+            .await
+            .contains("/admin/users")
+    );
+}
+
+#[tokio::test]
+async fn owners_can_change_the_role_of_other_users() {
+    // arrange
+    let app = spawn_app().await;
+    let editor = TestUser::generate_with_role("editor");
+    editor.store(&app.db_pool).await;
+    app.test_user.login(&app).await;
+
+    // act
+    let response = app.post_user_role(editor.user_id, "viewer").await;
+
+    // assert
+    assert_is_redirect_to(&response, "/admin/users");
+    assert!(
+        app.get_users_html()
+            .await
+            .contains("The user's role is now viewer.")
+    );
+    let saved = sqlx::query!("SELECT role FROM users WHERE user_id = $1", editor.user_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.role, "viewer");
+}
+
+#[tokio::test]
+async fn a_role_change_applies_to_existing_sessions() {
+    // arrange
+    let app = spawn_app().await;
+    login_as(&app, "editor").await;
+    let response = app
+        .post_publish_newsletter(&newsletter_request_body())
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletter");
+
+    // act
+    sqlx::query!("UPDATE users SET role = 'viewer'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let response = app
+        .post_publish_newsletter(&newsletter_request_body())
+        .await;
+
+    // assert
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn owners_cannot_change_their_own_role() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // act
+    let response = app.post_user_role(app.test_user.user_id, "viewer").await;
+
+    // assert
+    assert_is_redirect_to(&response, "/admin/users");
+    assert!(
+        app.get_users_html()
+            .await
+            .contains("You can't do that to your own account.")
+    );
+}
+
+#[tokio::test]
+async fn an_unknown_role_is_rejected() {
+    // arrange
+    let app = spawn_app().await;
+    let editor = TestUser::generate_with_role("editor");
+    editor.store(&app.db_pool).await;
+    app.test_user.login(&app).await;
+
+    // act
+    let response = app.post_user_role(editor.user_id, "admin").await;
+
+    // assert
+    assert_is_redirect_to(&response, "/admin/users");
+    assert!(
+        app.get_users_html()
+            .await
+            .contains("admin is not a valid role.")
+    );
+}
@@ -0,0 +1,175 @@
+use crate::helpers::{TestApp, assert_is_redirect_to, spawn_app};
+use fake::{Fake, faker::internet::en::SafeEmail};
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+
+// helpers
+// subscribe through the public form (with the given hidden tags) and confirm
+async fn create_confirmed_subscriber_with_tags(app: &TestApp, tags: &str) -> String {
+    let email: String = SafeEmail().fake();
+    let body = serde_urlencoded::to_string(serde_json::json!({
+        "name": "le guin",
+        "email": email,
+        "tags": tags,
+    }))
+    .unwrap();
+
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.post_subscriptions(body)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let email_request = &app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    email
+}
+
+async fn segment_id(app: &TestApp, name: &str) -> uuid::Uuid {
+    sqlx::query!("SELECT segment_id FROM segments WHERE name = $1", name)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .segment_id
+}
+
+// tests
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_segments() {
+    // arrange
+    let app = spawn_app().await;
+
+    // act
+    let response = app.get_segments().await;
+
+    // assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn invalid_segment_definitions_are_rejected() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // act 1: submit a malformed definition
+    let response = app
+        .post_segments(&serde_json::json!({
+            "name": "Broken",
+            "definition": "tag:vip AND",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/segments");
+
+    // act 2: follow the redirect
+    let html_page = app.get_segments_html().await;
+    assert!(html_page.contains("<p><i>The segment definition ended unexpectedly.</i></p>"));
+    let saved = sqlx::query!("SELECT COUNT(*) as \"count!\" FROM segments")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.count, 0);
+}
+
+#[tokio::test]
+async fn tags_can_be_added_and_removed_by_an_admin() {
+    // arrange
+    let app = spawn_app().await;
+    let email = create_confirmed_subscriber_with_tags(&app, "").await;
+    app.test_user.login(&app).await;
+
+    // act 1: add two tags
+    let response = app
+        .post_tags(&serde_json::json!({
+            "email": &email,
+            "tags": "VIP, beta",
+            "action": "add",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/tags");
+
+    // act 2: remove one of them
+    app.post_tags(&serde_json::json!({
+        "email": &email,
+        "tags": "beta",
+        "action": "remove",
+    }))
+    .await;
+
+    // assert
+    let tags = sqlx::query!("SELECT tag FROM subscriber_tags")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    let tags: Vec<String> = tags.into_iter().map(|r| r.tag).collect();
+    assert_eq!(tags, vec!["vip".to_string()]);
+}
+
+#[tokio::test]
+async fn newsletters_sent_to_a_segment_only_reach_its_members() {
+    // arrange
+    let app = spawn_app().await;
+    let vip_email = create_confirmed_subscriber_with_tags(&app, "vip,beta").await;
+    create_confirmed_subscriber_with_tags(&app, "beta").await;
+    app.test_user.login(&app).await;
+
+    // act 1: define the segment
+    let response = app
+        .post_segments(&serde_json::json!({
+            "name": "VIP testers",
+            "definition": "tag:vip AND status:confirmed",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/segments");
+    let segment_id = segment_id(&app, "VIP testers").await;
+
+    // act 2: the publish form shows the recipient count up front
+    let html_page = app.get_publish_newsletter_html().await;
+    assert!(html_page.contains("All confirmed subscribers (2 recipients)"));
+    assert!(html_page.contains("VIP testers (1 recipients)"));
+
+    // act 3: publish to the segment
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string(),
+            "segment_id": segment_id.to_string(),
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletter");
+
+    // assert
+    let queued = sqlx::query!("SELECT subscriber_email FROM issue_delivery_queue")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(queued.len(), 1);
+    assert_eq!(queued[0].subscriber_email, vip_email);
+    app.dispatch_all_pending_emails().await;
+}
@@ -0,0 +1,126 @@
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::configuration::SessionStoreKind;
+use zero2prod::session_store::delete_expired_sessions;
+
+use crate::helpers::{TestApp, assert_is_redirect_to, spawn_app_with};
+
+// no redis at all, so everything below has to come out of postgres
+async fn spawn_app_without_redis() -> TestApp {
+    spawn_app_with(|c| {
+        c.session_store = SessionStoreKind::Postgres;
+        c.redis_uri = None;
+    })
+    .await
+}
+
+async fn stored_session_count(app: &TestApp) -> i64 {
+    sqlx::query!(r#"SELECT count(*) AS "count!" FROM session_states"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count
+}
+
+#[tokio::test]
+async fn logging_in_works_with_sessions_in_postgres() {
+    // arrange
+    let app = spawn_app_without_redis().await;
+
+    // act
+    app.test_user.login(&app).await;
+
+    // assert
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains(&format!("Welcome {}", app.test_user.username)));
+    assert_eq!(stored_session_count(&app).await, 1);
+}
+
+#[tokio::test]
+async fn logging_out_deletes_the_stored_session() {
+    // arrange
+    let app = spawn_app_without_redis().await;
+    app.test_user.login(&app).await;
+
+    // act
+    let response = app.post_logout().await;
+
+    // assert
+    assert_is_redirect_to(&response, "/login");
+    assert_eq!(stored_session_count(&app).await, 0);
+    assert_is_redirect_to(&app.get_admin_dashboard().await, "/login");
+}
+
+#[tokio::test]
+async fn an_expired_session_is_not_loaded_and_gets_cleaned_up() {
+    // arrange
+    let app = spawn_app_without_redis().await;
+    app.test_user.login(&app).await;
+    sqlx::query!("UPDATE session_states SET expires_at = now() - interval '1 second'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // act 1: the cookie still points at the expired session
+    let response = app.get_admin_dashboard().await;
+
+    // assert 1
+    assert_is_redirect_to(&response, "/login");
+
+    // act 2
+    let deleted = delete_expired_sessions(&app.db_pool).await.unwrap();
+
+    // assert 2
+    assert!(deleted >= 1);
+    assert_eq!(stored_session_count(&app).await, 0);
+}
+
+#[tokio::test]
+async fn failed_logins_are_counted_in_postgres() {
+    // arrange
+    let app = spawn_app_without_redis().await;
+    for _ in 0..4 {
+        app.post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": "wrong-password"
+        }))
+        .await;
+    }
+
+    // act
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password
+        }))
+        .await;
+
+    // assert
+    assert_is_redirect_to(&response, "/login");
+    assert!(
+        app.get_login_html()
+            .await
+            .contains("Too many failed attempts, please try again later.")
+    );
+}
+
+#[tokio::test]
+async fn signups_are_rate_limited_in_postgres() {
+    // arrange
+    let app = spawn_app_without_redis().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // act
+    for _ in 0..3 {
+        app.post_subscriptions(body.into()).await;
+    }
+    let response = app.post_subscriptions(body.into()).await;
+
+    // assert
+    assert_eq!(429, response.status().as_u16());
+}
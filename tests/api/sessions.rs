@@ -0,0 +1,327 @@
+use uuid::Uuid;
+
+use zero2prod::configuration::SessionStoreKind;
+
+use crate::helpers::{
+    TestApp, TestUser, assert_is_redirect_to, get_csrf_token, spawn_app, spawn_app_with,
+};
+
+// the test user logged in from a second browser
+async fn log_in_elsewhere(app: &TestApp, user: &TestUser) -> reqwest::Client {
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .user_agent("second-device")
+        .build()
+        .unwrap();
+    let csrf_token = get_csrf_token(&client, &app.address).await;
+    let response = client
+        .post(format!("{}/login", &app.address))
+        .header("X-CSRF-Token", csrf_token)
+        .form(&[("username", &user.username), ("password", &user.password)])
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    client
+}
+
+async fn get_dashboard(app: &TestApp, client: &reqwest::Client) -> reqwest::Response {
+    client
+        .get(format!("{}/admin/dashboard", &app.address))
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn session_id_for(app: &TestApp, user_agent: &str) -> Uuid {
+    sqlx::query!(
+        "SELECT session_id FROM user_sessions WHERE user_agent = $1",
+        user_agent
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .session_id
+}
+
+// moves the user's sessions into the past, the default timeouts are 30 minutes idle
+// and 12 hours absolute
+async fn age_sessions(app: &TestApp, logged_in_ago: &str, last_seen_ago: &str) {
+    sqlx::query!(
+        "UPDATE user_sessions
+        SET created_at = now() - $1::text::interval, last_seen_at = now() - $2::text::interval",
+        logged_in_ago,
+        last_seen_ago
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+async fn session_count(app: &TestApp, user_id: Uuid) -> i64 {
+    sqlx::query!(
+        r#"SELECT count(*) as "count!" FROM user_sessions WHERE user_id = $1"#,
+        user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .count
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_your_sessions() {
+    // arrange
+    let app = spawn_app().await;
+
+    // act
+    let response = app
+        .api_client
+        .get(format!("{}/admin/sessions", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    // assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn the_sessions_page_lists_every_session_of_the_user() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    log_in_elsewhere(&app, &app.test_user).await;
+
+    // act
+    let html_page = app.get_sessions_html().await;
+
+    // assert
+    assert!(html_page.contains("<i>this session</i>"));
+    assert!(html_page.contains("second-device"));
+    assert!(html_page.contains("127.0.0.1"));
+    let other_session_id = session_id_for(&app, "second-device").await;
+    assert!(html_page.contains(&format!("/admin/sessions/{other_session_id}/revoke")));
+}
+
+#[tokio::test]
+async fn sessions_of_other_users_are_not_listed() {
+    // arrange
+    let app = spawn_app().await;
+    let other_user = TestUser::generate();
+    other_user.store(&app.db_pool).await;
+    log_in_elsewhere(&app, &other_user).await;
+    app.test_user.login(&app).await;
+
+    // act
+    let html_page = app.get_sessions_html().await;
+
+    // assert
+    assert!(!html_page.contains("second-device"));
+}
+
+#[tokio::test]
+async fn a_revoked_session_is_logged_out() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let other_device = log_in_elsewhere(&app, &app.test_user).await;
+    let other_session_id = session_id_for(&app, "second-device").await;
+
+    // act
+    let response = app.post_revoke_session(other_session_id).await;
+
+    // assert
+    assert_is_redirect_to(&response, "/admin/sessions");
+    assert!(
+        app.get_sessions_html()
+            .await
+            .contains("The session has been revoked.")
+    );
+    let response = get_dashboard(&app, &other_device).await;
+    assert_is_redirect_to(&response, "/login");
+    // the session making the request is unaffected
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn you_cannot_revoke_the_session_of_another_user() {
+    // arrange
+    let app = spawn_app().await;
+    let other_user = TestUser::generate();
+    other_user.store(&app.db_pool).await;
+    let other_device = log_in_elsewhere(&app, &other_user).await;
+    let other_session_id = session_id_for(&app, "second-device").await;
+    app.test_user.login(&app).await;
+
+    // act
+    app.post_revoke_session(other_session_id).await;
+
+    // assert
+    assert_eq!(
+        get_dashboard(&app, &other_device).await.status().as_u16(),
+        200
+    );
+}
+
+#[tokio::test]
+async fn logging_out_everywhere_ends_every_session() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let other_device = log_in_elsewhere(&app, &app.test_user).await;
+
+    // act
+    let response = app.post_log_out_everywhere().await;
+
+    // assert
+    assert_is_redirect_to(&response, "/login");
+    assert!(
+        app.get_login_html()
+            .await
+            .contains("You have been logged out everywhere.")
+    );
+    assert_is_redirect_to(&app.get_admin_dashboard().await, "/login");
+    assert_is_redirect_to(&get_dashboard(&app, &other_device).await, "/login");
+    assert_eq!(session_count(&app, app.test_user.user_id).await, 0);
+}
+
+#[tokio::test]
+async fn changing_the_password_logs_out_the_other_sessions() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let other_device = log_in_elsewhere(&app, &app.test_user).await;
+    let new_password = Uuid::new_v4().to_string();
+
+    // act
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+
+    // assert
+    assert_is_redirect_to(&response, "/admin/password");
+    assert_is_redirect_to(&get_dashboard(&app, &other_device).await, "/login");
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn logging_out_removes_the_session() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    assert_eq!(session_count(&app, app.test_user.user_id).await, 1);
+
+    // act
+    app.post_logout().await;
+
+    // assert
+    assert_eq!(session_count(&app, app.test_user.user_id).await, 0);
+}
+
+#[tokio::test]
+async fn an_idle_session_expires() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    age_sessions(&app, "31 minutes", "31 minutes").await;
+
+    // act
+    let response = app.get_admin_dashboard().await;
+
+    // assert
+    assert_is_redirect_to(&response, "/login");
+    assert!(
+        app.get_login_html()
+            .await
+            .contains("Your session has expired, please log in again.")
+    );
+    assert_eq!(session_count(&app, app.test_user.user_id).await, 0);
+}
+
+#[tokio::test]
+async fn a_busy_session_expires_after_its_absolute_lifetime() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    age_sessions(&app, "13 hours", "1 minute").await;
+
+    // act
+    let response = app.get_admin_dashboard().await;
+
+    // assert
+    assert_is_redirect_to(&response, "/login");
+    assert!(
+        app.get_login_html()
+            .await
+            .contains("Your session has expired, please log in again.")
+    );
+}
+
+#[tokio::test]
+async fn activity_keeps_a_session_alive() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    age_sessions(&app, "11 hours", "29 minutes").await;
+
+    // act
+    let response = app.get_admin_dashboard().await;
+
+    // assert
+    assert_eq!(response.status().as_u16(), 200);
+    let idle = sqlx::query!(
+        r#"SELECT now() - last_seen_at < interval '1 minute' AS "recent!" FROM user_sessions"#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert!(idle.recent);
+}
+
+#[tokio::test]
+async fn expired_sessions_are_not_listed() {
+    // arrange
+    let app = spawn_app().await;
+    log_in_elsewhere(&app, &app.test_user).await;
+    age_sessions(&app, "31 minutes", "31 minutes").await;
+    app.test_user.login(&app).await;
+
+    // act
+    let html_page = app.get_sessions_html().await;
+
+    // assert
+    assert!(html_page.contains("<i>this session</i>"));
+    assert!(!html_page.contains("second-device"));
+}
+
+#[tokio::test]
+async fn the_stored_session_state_lives_as_long_as_the_absolute_lifetime() {
+    // arrange
+    let app = spawn_app_with(|c| {
+        c.session_store = SessionStoreKind::Postgres;
+        c.session_timeouts.absolute_seconds = 600;
+    })
+    .await;
+
+    // act
+    app.test_user.login(&app).await;
+
+    // assert
+    let row = sqlx::query!(
+        r#"
+        SELECT expires_at - now() BETWEEN interval '590 seconds' AND interval '600 seconds'
+            AS "aligned!"
+        FROM session_states
+        "#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert!(row.aligned);
+}
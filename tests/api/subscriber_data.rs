@@ -0,0 +1,303 @@
+use crate::helpers::{TestApp, assert_is_redirect_to, spawn_app};
+use uuid::Uuid;
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+use zero2prod::domain::SuppressionReason;
+use zero2prod::suppression::suppress;
+
+// helpers
+// a confirmed subscriber with a tag, one delivered issue and one still queued
+async fn create_subscriber_with_history(app: &TestApp) -> Uuid {
+    app.test_user.login(app).await;
+    app.post_subscriber_import(
+        "confirmed",
+        "email,name,tags\nursula@example.com,Ursula,vip\n",
+    )
+    .await;
+    for (title, delivered) in [("Delivered issue", true), ("Queued issue", false)] {
+        let issue_id = Uuid::new_v4();
+        sqlx::query!(
+            "INSERT INTO newsletter_issues
+            (newsletter_issue_id, title, text_content, html_content, published_at)
+            VALUES ($1, $2, 'text', 'html', now()::text)",
+            issue_id,
+            title
+        )
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+        if delivered {
+            sqlx::query!(
+                "INSERT INTO issue_delivery_log
+                (newsletter_issue_id, subscriber_email, outcome, attempted_at)
+                VALUES ($1, 'ursula@example.com', 'sent', now())",
+                issue_id
+            )
+            .execute(&app.db_pool)
+            .await
+            .unwrap();
+        } else {
+            sqlx::query!(
+                "INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_email)
+                VALUES ($1, 'ursula@example.com')",
+                issue_id
+            )
+            .execute(&app.db_pool)
+            .await
+            .unwrap();
+        }
+    }
+    sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id
+}
+
+// tests
+#[tokio::test]
+async fn a_subscriber_can_download_their_data_through_an_emailed_link() {
+    // arrange
+    let app = spawn_app().await;
+    create_subscriber_with_history(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // act
+    let response = app.post_data_request("ursula@example.com").await;
+    assert_eq!(response.status().as_u16(), 200);
+    let email_request = &app.wait_for_emails(1).await[0];
+    let links = app.get_confirmation_links(email_request);
+    let response = reqwest::get(links.html).await.unwrap();
+
+    // assert
+    assert_eq!(response.status().as_u16(), 200);
+    let data: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(data["subscription"]["email"], "ursula@example.com");
+    assert_eq!(data["subscription"]["status"], "confirmed");
+    assert_eq!(data["tags"], serde_json::json!(["vip"]));
+    assert_eq!(data["delivery_log"][0]["title"], "Delivered issue");
+    assert_eq!(data["pending_deliveries"][0]["title"], "Queued issue");
+}
+
+#[tokio::test]
+async fn requesting_data_for_an_unknown_address_sends_no_email() {
+    // arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // act
+    let response = app.post_data_request("nobody@example.com").await;
+
+    // assert
+    assert_eq!(response.status().as_u16(), 200);
+    app.let_background_emails_settle().await;
+}
+
+#[tokio::test]
+async fn known_and_unknown_addresses_get_the_same_response() {
+    // arrange
+    let app = spawn_app().await;
+    create_subscriber_with_history(&app).await;
+    // the send fails, which must not show in the response either
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&app.email_server)
+        .await;
+
+    // act
+    let known = app.post_data_request("ursula@example.com").await;
+    let unknown = app.post_data_request("nobody@example.com").await;
+
+    // assert
+    assert_eq!(known.status().as_u16(), 200);
+    assert_eq!(unknown.status().as_u16(), 200);
+    assert_eq!(known.text().await.unwrap(), unknown.text().await.unwrap());
+    // mock servers are reused, don't leave the send to land in another test
+    app.wait_for_emails(1).await;
+}
+
+#[tokio::test]
+async fn repeated_data_requests_for_an_address_send_no_more_emails() {
+    // arrange
+    let app = spawn_app().await;
+    create_subscriber_with_history(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(3)
+        .mount(&app.email_server)
+        .await;
+
+    // act
+    for _ in 0..4 {
+        let response = app.post_data_request("ursula@example.com").await;
+
+        // assert
+        assert_eq!(response.status().as_u16(), 200);
+    }
+    app.wait_for_emails(3).await;
+    app.let_background_emails_settle().await;
+}
+
+#[tokio::test]
+async fn repeated_data_requests_from_the_same_ip_are_rate_limited() {
+    // arrange
+    let app = spawn_app().await;
+
+    // act
+    for i in 0..10 {
+        let response = app
+            .post_data_request(&format!("reader{}@example.com", i))
+            .await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+    let response = app.post_data_request("one_more@example.com").await;
+
+    // assert
+    assert_eq!(response.status().as_u16(), 429);
+}
+
+#[tokio::test]
+async fn a_tampered_data_link_is_rejected() {
+    // arrange
+    let app = spawn_app().await;
+    create_subscriber_with_history(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_data_request("ursula@example.com").await;
+    let email_request = &app.wait_for_emails(1).await[0];
+    let link = app.get_confirmation_links(email_request).html;
+
+    // act
+    let mut tampered = link.clone();
+    let query: Vec<(String, String)> = link
+        .query_pairs()
+        .map(|(k, v)| {
+            let v = if k == "email" {
+                "ged@example.com".to_string()
+            } else {
+                v.into_owned()
+            };
+            (k.into_owned(), v)
+        })
+        .collect();
+    tampered.query_pairs_mut().clear().extend_pairs(query);
+    let response = reqwest::get(tampered).await.unwrap();
+
+    // assert
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn admins_can_download_a_subscribers_data() {
+    // arrange
+    let app = spawn_app().await;
+    let subscriber_id = create_subscriber_with_history(&app).await;
+
+    // act
+    let response = app
+        .api_client
+        .get(format!(
+            "{}/admin/subscribers/{}/data",
+            app.address, subscriber_id
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(response.status().as_u16(), 200);
+    let data: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(data["email"], "ursula@example.com");
+}
+
+#[tokio::test]
+async fn erasure_pseudonymizes_the_subscriber_everywhere() {
+    // arrange
+    let app = spawn_app().await;
+    let subscriber_id = create_subscriber_with_history(&app).await;
+    sqlx::query!(
+        "INSERT INTO email_queue (email_id, recipient, subject, html_body, text_body)
+        VALUES ($1, 'ursula@example.com', 'Queued email', 'html', 'text')",
+        Uuid::new_v4()
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    suppress(
+        &app.db_pool,
+        "ursula@example.com",
+        SuppressionReason::SpamComplaint,
+    )
+    .await
+    .unwrap();
+
+    // act 1: the queued email and the suppression are part of their data
+    let response = app
+        .api_client
+        .get(format!(
+            "{}/admin/subscribers/{}/data",
+            app.address, subscriber_id
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    // assert 1
+    let data: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(data["queued_emails"][0]["subject"], "Queued email");
+    assert_eq!(data["suppression"]["reason"], "spam_complaint");
+    assert!(data["suppression"]["created_at"].is_string());
+
+    // act 2
+    let response = app.post_subscriber_action(subscriber_id, "erase").await;
+
+    // assert 2
+    assert_is_redirect_to(&response, &format!("/admin/subscribers/{}", subscriber_id));
+    let subscriber = sqlx::query!("SELECT email, name, status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(
+        subscriber.email,
+        format!("erased-{}@erased.invalid", subscriber_id)
+    );
+    assert_eq!(subscriber.name, "erased");
+    assert_eq!(subscriber.status, "erased");
+    let log = sqlx::query!("SELECT subscriber_email FROM issue_delivery_log")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(log.len(), 1);
+    assert_eq!(log[0].subscriber_email, subscriber.email);
+    let remaining = sqlx::query!(
+        r#"SELECT
+            (SELECT count(*) FROM issue_delivery_queue) AS "queued!",
+            (SELECT count(*) FROM email_queue) AS "queued_emails!",
+            (SELECT count(*) FROM subscriber_tags) AS "tags!""#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(remaining.queued, 0);
+    assert_eq!(remaining.queued_emails, 0);
+    assert_eq!(remaining.tags, 0);
+    let html = app.get_subscriber_details_html(subscriber_id).await;
+    assert!(!html.contains("ursula@example.com"));
+}
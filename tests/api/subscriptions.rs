@@ -44,6 +44,30 @@ async fn subscribe_persists_the_new_subscriber() {
     assert_eq!(saved.name, "le guin");
     assert_eq!(saved.status, "pending_confirmation");
 }
+#[tokio::test]
+async fn subscribe_persists_tags_from_hidden_form_fields() {
+    // arrange
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com&tags=Early-Adopter%2Cbeta";
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // act
+    let response = app.post_subscriptions(body.into()).await;
+
+    // assert
+    assert_eq!(200, response.status().as_u16());
+    let saved = sqlx::query!("SELECT tag FROM subscriber_tags ORDER BY tag")
+        .fetch_all(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved tags.");
+    let tags: Vec<String> = saved.into_iter().map(|r| r.tag).collect();
+    assert_eq!(tags, vec!["beta".to_string(), "early-adopter".to_string()]);
+}
+
 #[tokio::test]
 async fn subscribe_returns_a_400_when_data_is_missing() {
     let app = spawn_app().await;
@@ -73,6 +97,10 @@ async fn subscribe_returns_400_when_fields_are_present_but_invalid() {
         ("name=&email=ursula_le_guin%40gmail.com", "empty name"),
         ("name=Ursula&email=", "empty email"),
         ("name=Ursula&email=definitely-not-an-email", "invalid email"),
+        (
+            "name=Ursula&email=ursula_le_guin%40gmail.com&tags=not%20a%20tag",
+            "invalid tag",
+        ),
     ];
 
     for (body, description) in test_cases {
@@ -146,3 +174,196 @@ async fn subscribe_fails_if_there_is_a_fatal_database_error() {
     // assert
     assert_eq!(response.status().as_u16(), 500);
 }
+
+#[tokio::test]
+async fn subscribing_again_with_a_differently_cased_email_does_not_create_a_duplicate() {
+    // arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // act
+    let first = app
+        .post_subscriptions("name=le%20guin&email=Ursula_Le_Guin%40Gmail.COM".into())
+        .await;
+    let second = app
+        .post_subscriptions("name=le%20guin&email=%20ursula_le_guin%40gmail.com".into())
+        .await;
+
+    // assert
+    assert_eq!(first.status().as_u16(), 200);
+    assert_eq!(second.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscriptions.");
+    assert_eq!(saved.len(), 1);
+    assert_eq!(saved[0].email, "Ursula_Le_Guin@gmail.com");
+    let tokens = sqlx::query!("SELECT subscription_token FROM subscription_tokens")
+        .fetch_all(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved tokens.");
+    assert_eq!(tokens.len(), 1);
+    // the pending address gets the same link again
+    let email_requests = app.email_server.received_requests().await.unwrap();
+    assert_eq!(email_requests.len(), 2);
+    assert_eq!(
+        app.get_confirmation_links(&email_requests[0]).html,
+        app.get_confirmation_links(&email_requests[1]).html
+    );
+}
+
+#[tokio::test]
+async fn subscribing_again_once_confirmed_sends_nothing() {
+    // arrange
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions(body.into()).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html).await.unwrap();
+
+    // act
+    let response = app.post_subscriptions(body.into()).await;
+
+    // assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(app.email_server.received_requests().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn a_signup_that_fills_in_the_honeypot_is_silently_dropped() {
+    // arrange
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com&website=http%3A%2F%2Fspam.example";
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // act
+    let response = app.post_subscriptions(body.into()).await;
+
+    // assert
+    assert_eq!(200, response.status().as_u16());
+    let saved = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscriptions.");
+    assert!(saved.is_empty());
+}
+
+#[tokio::test]
+async fn signups_from_blocked_domains_are_rejected_with_a_400() {
+    // arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    for email in ["bot%40mailinator.com", "bot%40eu.Mailinator.com"] {
+        // act
+        let response = app
+            .post_subscriptions(format!("name=bot&email={}", email))
+            .await;
+
+        // assert
+        assert_eq!(
+            400,
+            response.status().as_u16(),
+            "The API did not reject {}.",
+            email
+        );
+    }
+}
+
+#[tokio::test]
+async fn repeated_signups_for_the_same_email_are_rate_limited() {
+    // arrange
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // act
+    for _ in 0..3 {
+        app.post_subscriptions(body.into()).await;
+    }
+    let response = app
+        .post_subscriptions("name=le%20guin&email=URSULA_LE_GUIN%40gmail.com".into())
+        .await;
+
+    // assert
+    assert_eq!(429, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn repeated_signups_from_the_same_ip_are_rate_limited() {
+    // arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(10)
+        .mount(&app.email_server)
+        .await;
+
+    // act
+    for i in 0..10 {
+        let response = app
+            .post_subscriptions(format!("name=reader&email=reader{}%40example.com", i))
+            .await;
+        assert_eq!(200, response.status().as_u16());
+    }
+    let response = app
+        .post_subscriptions("name=reader&email=one_more%40example.com".into())
+        .await;
+
+    // assert
+    assert_eq!(429, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn a_spoofed_forwarded_for_header_does_not_reset_the_ip_limit() {
+    // arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    for i in 0..10 {
+        app.post_subscriptions(format!("name=reader&email=reader{}%40example.com", i))
+            .await;
+    }
+
+    // act
+    let response = app
+        .api_client
+        .post(format!("{}/subscriptions", &app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .header("X-Forwarded-For", "203.0.113.7")
+        .body("name=reader&email=one_more%40example.com")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // assert
+    assert_eq!(429, response.status().as_u16());
+}
@@ -128,7 +128,9 @@ async fn subscribing_twice_without_confirming_returns_identical_confirmation_lin
     let first_email_request = &app.email_server.received_requests().await.unwrap()[0];
 
     app.post_subscriptions(body.into()).await;
-    let second_email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let email_requests = app.email_server.received_requests().await.unwrap();
+    assert_eq!(email_requests.len(), 2);
+    let second_email_request = &email_requests[1];
 
     let first_confirmation_links = app.get_confirmation_links(&first_email_request);
     let second_confirmation_links = app.get_confirmation_links(&second_email_request);
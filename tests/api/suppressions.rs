@@ -0,0 +1,172 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+use uuid::Uuid;
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_suppressions() {
+    // arrange
+    let app = spawn_app().await;
+
+    // act
+    let response = app.post_suppressions("ursula@example.com").await;
+
+    // assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn a_suppressed_address_gets_no_confirmation_email_when_it_subscribes() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let response = app.post_suppressions("Ursula_Le_Guin@gmail.com").await;
+    assert_is_redirect_to(&response, "/admin/suppressions");
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // act
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+
+    // assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html = app.get_suppressions_html().await;
+    assert!(html.contains("Ursula_Le_Guin@gmail.com"));
+    assert!(html.contains("manual"));
+}
+
+#[tokio::test]
+async fn queued_deliveries_to_a_suppressed_address_are_skipped() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let issue_id = Uuid::new_v4();
+    sqlx::query!(
+        "INSERT INTO newsletter_issues
+        (newsletter_issue_id, title, text_content, html_content, published_at)
+        VALUES ($1, 'Title', 'text', 'html', now()::text)",
+        issue_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    sqlx::query!(
+        "INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_email)
+        VALUES ($1, 'ursula@example.com')",
+        issue_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.post_suppressions("ursula@example.com").await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // act
+    app.dispatch_all_pending_emails().await;
+
+    // assert
+    let outcome = sqlx::query!("SELECT outcome FROM issue_delivery_log")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .outcome;
+    assert_eq!(outcome, "suppressed");
+}
+
+#[tokio::test]
+async fn erased_subscribers_are_suppressed_without_keeping_their_address() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.post_subscriber_import("confirmed", "email,name\nursula@example.com,Ursula")
+        .await;
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+
+    // act
+    app.post_subscriber_action(subscriber_id, "erase").await;
+
+    // assert
+    let suppression = sqlx::query!("SELECT email, reason FROM email_suppressions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(suppression.email, None);
+    assert_eq!(suppression.reason, "erased");
+    let html = app.get_suppressions_html().await;
+    assert!(html.contains("erased subscriber"));
+    assert!(!html.contains("ursula@example.com"));
+}
+
+#[tokio::test]
+async fn erasing_a_bounced_subscriber_drops_the_address_from_its_suppression() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.post_subscriber_import("confirmed", "email,name\nursula@example.com,Ursula")
+        .await;
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+    sqlx::query!(
+        "INSERT INTO email_suppressions (email_hash, email, reason, created_at)
+        VALUES ($1, 'ursula@example.com', 'hard_bounce', now())",
+        zero2prod::suppression::email_hash("ursula@example.com")
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    // act
+    app.post_subscriber_action(subscriber_id, "erase").await;
+
+    // assert
+    let suppression = sqlx::query!("SELECT email, reason FROM email_suppressions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(suppression.email, None);
+    assert_eq!(suppression.reason, "erased");
+    let html = app.get_suppressions_html().await;
+    assert!(!html.contains("ursula@example.com"));
+}
+
+#[tokio::test]
+async fn a_suppression_can_be_removed() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.post_suppressions("ursula@example.com").await;
+    let email_hash = sqlx::query!("SELECT email_hash FROM email_suppressions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email_hash;
+
+    // act
+    let response = app.post_delete_suppression(&email_hash).await;
+
+    // assert
+    assert_is_redirect_to(&response, "/admin/suppressions");
+    let html = app.get_suppressions_html().await;
+    assert!(html.contains("The suppression has been removed."));
+    assert!(!html.contains("ursula@example.com"));
+}
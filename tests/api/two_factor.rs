@@ -0,0 +1,269 @@
+use crate::helpers::{TestApp, assert_is_redirect_to, spawn_app};
+use std::time::{SystemTime, UNIX_EPOCH};
+use totp_rs::{Algorithm, Secret, TOTP};
+
+// the code an authenticator app would show at `time`
+async fn totp_code_at(app: &TestApp, time: u64) -> String {
+    let secret = sqlx::query!(
+        "SELECT secret FROM user_totp WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .secret;
+    let totp = TOTP::new(
+        Algorithm::SHA1,
+        6,
+        1,
+        30,
+        Secret::Encoded(secret).to_bytes().unwrap(),
+        None,
+        String::new(),
+    )
+    .unwrap();
+    totp.generate(time)
+}
+
+// the code an authenticator app would show `offset` steps from now
+async fn totp_code(app: &TestApp, offset: u64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    totp_code_at(app, now + offset * 30).await
+}
+
+// turns two-factor on for the test user, returning the recovery codes
+async fn enable_two_factor(app: &TestApp) -> Vec<String> {
+    app.test_user.login(app).await;
+    app.post_two_factor("setup", "").await;
+    let code = totp_code(app, 0).await;
+    let response = app.post_two_factor("confirm", &code).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let html = response.text().await.unwrap();
+    let codes: Vec<String> = html
+        .split("<li><code>")
+        .skip(1)
+        .map(|s| s.split("</code>").next().unwrap().to_string())
+        .collect();
+    assert_eq!(codes.len(), 10);
+    app.post_logout().await;
+    codes
+}
+
+#[tokio::test]
+async fn setting_up_two_factor_shows_a_qr_code() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // act
+    let response = app.post_two_factor("setup", "").await;
+
+    // assert
+    assert_is_redirect_to(&response, "/admin/two-factor");
+    let html = app.get_two_factor_html().await;
+    assert!(html.contains("<svg"));
+    assert!(html.contains("/admin/two-factor/confirm"));
+}
+
+#[tokio::test]
+async fn a_wrong_code_does_not_turn_two_factor_on() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.post_two_factor("setup", "").await;
+
+    // act
+    let response = app.post_two_factor("confirm", "000000x").await;
+
+    // assert
+    assert_is_redirect_to(&response, "/admin/two-factor");
+    let html = app.get_two_factor_html().await;
+    assert!(html.contains("That code is not valid."));
+    let confirmed = sqlx::query!(
+        "SELECT confirmed_at FROM user_totp WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .confirmed_at;
+    assert!(confirmed.is_none());
+}
+
+#[tokio::test]
+async fn the_password_alone_is_not_enough_once_two_factor_is_on() {
+    // arrange
+    let app = spawn_app().await;
+    enable_two_factor(&app).await;
+
+    // act
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password
+        }))
+        .await;
+
+    // assert
+    assert_is_redirect_to(&response, "/login/two-factor");
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+}
+
+#[tokio::test]
+async fn a_valid_code_completes_the_login() {
+    // arrange
+    let app = spawn_app().await;
+    enable_two_factor(&app).await;
+    app.test_user.login(&app).await;
+
+    // act
+    // the code used for setup can't be used again, the next one can
+    let code = totp_code(&app, 1).await;
+    let response = app.post_two_factor_login(&code).await;
+
+    // assert
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let html = app.get_admin_dashboard_html().await;
+    assert!(html.contains(&format!("Welcome {}", app.test_user.username)));
+}
+
+#[tokio::test]
+async fn an_invalid_code_is_rejected() {
+    // arrange
+    let app = spawn_app().await;
+    enable_two_factor(&app).await;
+    app.test_user.login(&app).await;
+
+    // act
+    let response = app.post_two_factor_login("123456x").await;
+
+    // assert
+    assert_is_redirect_to(&response, "/login/two-factor");
+    let html = app.get_two_factor_login().await.text().await.unwrap();
+    assert!(html.contains("That code is not valid."));
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+}
+
+#[tokio::test]
+async fn entering_the_password_again_does_not_forgive_wrong_codes() {
+    // arrange
+    let app = spawn_app().await;
+    enable_two_factor(&app).await;
+    for _ in 0..2 {
+        app.test_user.login(&app).await;
+        app.post_two_factor_login("123456x").await;
+        app.post_two_factor_login("123456x").await;
+    }
+    app.test_user.login(&app).await;
+
+    // act
+    let code = totp_code(&app, 1).await;
+    let response = app.post_two_factor_login(&code).await;
+
+    // assert
+    assert_is_redirect_to(&response, "/login/two-factor");
+    let html = app.get_two_factor_login().await.text().await.unwrap();
+    assert!(html.contains("Too many failed attempts, please try again later."));
+}
+
+#[tokio::test]
+async fn a_code_cannot_be_replayed() {
+    // arrange
+    let app = spawn_app().await;
+    enable_two_factor(&app).await;
+    app.test_user.login(&app).await;
+
+    // act
+    let last_used_step = sqlx::query!(
+        "SELECT last_used_step FROM user_totp WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .last_used_step
+    .unwrap();
+    let code = totp_code_at(&app, last_used_step as u64 * 30).await;
+    let response = app.post_two_factor_login(&code).await;
+
+    // assert
+    assert_is_redirect_to(&response, "/login/two-factor");
+}
+
+#[tokio::test]
+async fn a_recovery_code_works_exactly_once() {
+    // arrange
+    let app = spawn_app().await;
+    let recovery_codes = enable_two_factor(&app).await;
+
+    // act 1
+    app.test_user.login(&app).await;
+    let response = app
+        .post_two_factor_login(&recovery_codes[0].to_uppercase())
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    app.post_logout().await;
+
+    // act 2
+    app.test_user.login(&app).await;
+    let response = app.post_two_factor_login(&recovery_codes[0]).await;
+
+    // assert
+    assert_is_redirect_to(&response, "/login/two-factor");
+}
+
+#[tokio::test]
+async fn the_second_step_needs_the_password_first() {
+    // arrange
+    let app = spawn_app().await;
+
+    // act
+    let form = app.get_two_factor_login().await;
+    let submit = app.post_two_factor_login("123456").await;
+
+    // assert
+    assert_is_redirect_to(&form, "/login");
+    assert_is_redirect_to(&submit, "/login");
+}
+
+#[tokio::test]
+async fn turning_two_factor_off_needs_a_valid_code() {
+    // arrange
+    let app = spawn_app().await;
+    let recovery_codes = enable_two_factor(&app).await;
+    app.test_user.login(&app).await;
+    app.post_two_factor_login(&recovery_codes[0]).await;
+
+    // act 1
+    let response = app.post_two_factor("disable", "not-a-code").await;
+    assert_is_redirect_to(&response, "/admin/two-factor");
+    assert!(
+        app.get_two_factor_html()
+            .await
+            .contains("That code is not valid.")
+    );
+
+    // act 2
+    let response = app.post_two_factor("disable", &recovery_codes[1]).await;
+
+    // assert
+    assert_is_redirect_to(&response, "/admin/two-factor");
+    assert!(
+        app.get_two_factor_html()
+            .await
+            .contains("Two-factor authentication is off.")
+    );
+    app.post_logout().await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
@@ -0,0 +1,275 @@
+use crate::helpers::{TestApp, TestUser, assert_is_redirect_to, spawn_app};
+use uuid::Uuid;
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+
+const PASSWORD: &str = "a-long-enough-password";
+
+// invites `email` as an editor, returning the token from the emailed link
+async fn invite(app: &TestApp, email: &str) -> String {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let response = app.post_invite_user(email, "editor").await;
+    assert_is_redirect_to(&response, "/admin/users");
+    let email_request = app.email_server.received_requests().await.unwrap();
+    let links = app.get_confirmation_links(email_request.last().unwrap());
+    links
+        .html
+        .query_pairs()
+        .find(|(k, _)| k == "token")
+        .unwrap()
+        .1
+        .into_owned()
+}
+
+fn accept_body(token: &str, username: &str) -> serde_json::Value {
+    serde_json::json!({
+        "token": token,
+        "username": username,
+        "new_password": PASSWORD,
+        "new_password_check": PASSWORD,
+    })
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_users() {
+    // arrange
+    let app = spawn_app().await;
+
+    // act
+    let response = app.get_users().await;
+
+    // assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn an_invited_user_can_set_a_password_and_log_in() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = invite(&app, "ursula@example.com").await;
+    assert!(app.get_users_html().await.contains("ursula@example.com"));
+
+    // act 1: open the link from the email
+    let form = app
+        .api_client
+        .get(format!(
+            "{}/invitations/accept?token={}",
+            &app.address, token
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(form.status().as_u16(), 200);
+
+    // act 2: accept the invitation
+    app.post_logout().await;
+    let response = app
+        .post_accept_invitation(&accept_body(&token, "ursula"))
+        .await;
+
+    // assert
+    assert_is_redirect_to(&response, "/login");
+    let saved = sqlx::query!("SELECT email, role, is_active FROM users WHERE username = 'ursula'")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.email.as_deref(), Some("ursula@example.com"));
+    assert_eq!(saved.role, "editor");
+    assert!(saved.is_active);
+    let response = app
+        .post_login(&serde_json::json!({ "username": "ursula", "password": PASSWORD }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn an_invitation_can_only_be_used_once() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = invite(&app, "ursula@example.com").await;
+    app.post_accept_invitation(&accept_body(&token, "ursula"))
+        .await;
+
+    // act
+    app.post_accept_invitation(&accept_body(&token, "someone_else"))
+        .await;
+    let form = app
+        .api_client
+        .get(format!(
+            "{}/invitations/accept?token={}",
+            &app.address, token
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(form.status().as_u16(), 404);
+    let users = sqlx::query!(r#"SELECT count(*) AS "count!" FROM users"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    // the seeded admin, the test user and ursula
+    assert_eq!(users, 3);
+}
+
+#[tokio::test]
+async fn accepting_an_invitation_with_an_invalid_password_is_rejected() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = invite(&app, "ursula@example.com").await;
+
+    // act
+    let response = app
+        .post_accept_invitation(&serde_json::json!({
+            "token": token,
+            "username": "ursula",
+            "new_password": "short",
+            "new_password_check": "short",
+        }))
+        .await;
+
+    // assert
+    assert_is_redirect_to(&response, &format!("/invitations/accept?token={}", token));
+    let saved = sqlx::query!("SELECT username FROM users WHERE username = 'ursula'")
+        .fetch_optional(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(saved.is_none());
+}
+
+#[tokio::test]
+async fn inviting_the_address_of_an_existing_user_is_rejected() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = invite(&app, "ursula@example.com").await;
+    app.post_accept_invitation(&accept_body(&token, "ursula"))
+        .await;
+
+    // act
+    let response = app.post_invite_user("Ursula@Example.com", "editor").await;
+
+    // assert
+    assert_is_redirect_to(&response, "/admin/users");
+    let html = app.get_users_html().await;
+    assert!(html.contains("A user with this email address already exists."));
+}
+
+#[tokio::test]
+async fn a_deactivated_user_can_no_longer_log_in() {
+    // arrange
+    let app = spawn_app().await;
+    let other_user = TestUser::generate();
+    other_user.store(&app.db_pool).await;
+    app.test_user.login(&app).await;
+
+    // act
+    let response = app.post_user_action(other_user.user_id, "deactivate").await;
+
+    // assert
+    assert_is_redirect_to(&response, "/admin/users");
+    app.post_logout().await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &other_user.username,
+            "password": &other_user.password
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn deactivating_a_user_ends_their_existing_session() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    sqlx::query!(
+        "UPDATE users SET is_active = false WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    // act
+    let response = app.get_admin_dashboard().await;
+
+    // assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn a_removed_user_is_deleted() {
+    // arrange
+    let app = spawn_app().await;
+    let other_user = TestUser::generate();
+    other_user.store(&app.db_pool).await;
+    app.test_user.login(&app).await;
+
+    // act
+    let response = app.post_user_action(other_user.user_id, "delete").await;
+
+    // assert
+    assert_is_redirect_to(&response, "/admin/users");
+    let saved = sqlx::query!(
+        "SELECT username FROM users WHERE user_id = $1",
+        other_user.user_id
+    )
+    .fetch_optional(&app.db_pool)
+    .await
+    .unwrap();
+    assert!(saved.is_none());
+    assert!(
+        app.get_users_html()
+            .await
+            .contains("The user has been removed.")
+    );
+}
+
+#[tokio::test]
+async fn you_cannot_deactivate_or_remove_yourself() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    for action in ["deactivate", "delete"] {
+        // act
+        let response = app.post_user_action(app.test_user.user_id, action).await;
+
+        // assert
+        assert_is_redirect_to(&response, "/admin/users");
+        let html = app.get_users_html().await;
+        assert!(html.contains("You can't do that to your own account."));
+    }
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn acting_on_an_unknown_user_is_reported() {
+    // arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // act
+    let response = app.post_user_action(Uuid::new_v4(), "deactivate").await;
+
+    // assert
+    assert_is_redirect_to(&response, "/admin/users");
+    assert!(
+        app.get_users_html()
+            .await
+            .contains("That user does not exist.")
+    );
+}
@@ -0,0 +1,181 @@
+use crate::helpers::{TestApp, spawn_app};
+
+const HARD_BOUNCE: &str = include_str!("fixtures/postmark/hard_bounce.json");
+const SPAM_COMPLAINT: &str = include_str!("fixtures/postmark/spam_complaint.json");
+
+async fn store_confirmed_subscriber(app: &TestApp) {
+    app.test_user.login(app).await;
+    app.post_subscriber_import("confirmed", "email,name\njohn@example.com,John")
+        .await;
+}
+
+async fn subscriber_status(app: &TestApp) -> String {
+    sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .status
+}
+
+async fn suppression_reasons(app: &TestApp) -> Vec<String> {
+    sqlx::query!("SELECT reason FROM email_suppressions")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| r.reason)
+        .collect()
+}
+
+#[tokio::test]
+async fn webhooks_without_valid_credentials_are_rejected() {
+    // arrange
+    let app = spawn_app().await;
+    store_confirmed_subscriber(&app).await;
+
+    // act
+    let response = app
+        .post_postmark_webhook_as("postmark", "not-the-secret", HARD_BOUNCE)
+        .await;
+    let anonymous = reqwest::Client::new()
+        .post(format!("{}/webhooks/postmark", &app.address))
+        .body(HARD_BOUNCE)
+        .send()
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        r#"Basic realm="webhooks""#,
+        response.headers()["WWW-Authenticate"]
+    );
+    assert_eq!(anonymous.status().as_u16(), 401);
+    assert_eq!(subscriber_status(&app).await, "confirmed");
+    assert!(suppression_reasons(&app).await.is_empty());
+}
+
+#[tokio::test]
+async fn a_hard_bounce_marks_the_subscriber_as_bounced_and_suppresses_them() {
+    // arrange
+    let app = spawn_app().await;
+    store_confirmed_subscriber(&app).await;
+
+    // act
+    let response = app.post_postmark_webhook(HARD_BOUNCE).await;
+
+    // assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(subscriber_status(&app).await, "bounced");
+    assert_eq!(suppression_reasons(&app).await, vec!["hard_bounce"]);
+    let event = sqlx::query!("SELECT provider, event_type, email FROM email_events")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(event.provider, "postmark");
+    assert_eq!(event.event_type, "hard_bounce");
+    assert_eq!(event.email, "john@example.com");
+}
+
+#[tokio::test]
+async fn a_spam_complaint_marks_the_subscriber_as_complained_and_suppresses_them() {
+    // arrange
+    let app = spawn_app().await;
+    store_confirmed_subscriber(&app).await;
+
+    // act
+    let response = app.post_postmark_webhook(SPAM_COMPLAINT).await;
+
+    // assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(subscriber_status(&app).await, "complained");
+    assert_eq!(suppression_reasons(&app).await, vec!["spam_complaint"]);
+}
+
+#[tokio::test]
+async fn a_soft_bounce_is_recorded_without_suppressing_the_subscriber() {
+    // arrange
+    let app = spawn_app().await;
+    store_confirmed_subscriber(&app).await;
+    let soft_bounce = HARD_BOUNCE.replace(r#""HardBounce""#, r#""SoftBounce""#);
+
+    // act
+    let response = app.post_postmark_webhook(&soft_bounce).await;
+
+    // assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(subscriber_status(&app).await, "confirmed");
+    assert!(suppression_reasons(&app).await.is_empty());
+    let event_type = sqlx::query!("SELECT event_type FROM email_events")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .event_type;
+    assert_eq!(event_type, "soft_bounce");
+}
+
+#[tokio::test]
+async fn a_redelivered_event_is_only_recorded_once() {
+    // arrange
+    let app = spawn_app().await;
+    store_confirmed_subscriber(&app).await;
+
+    // act
+    app.post_postmark_webhook(HARD_BOUNCE).await;
+    let response = app.post_postmark_webhook(HARD_BOUNCE).await;
+
+    // assert
+    assert_eq!(response.status().as_u16(), 200);
+    let events = sqlx::query!(r#"SELECT count(*) AS "count!" FROM email_events"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(events, 1);
+}
+
+#[tokio::test]
+async fn events_we_do_not_handle_are_acknowledged_and_ignored() {
+    // arrange
+    let app = spawn_app().await;
+    store_confirmed_subscriber(&app).await;
+    let delivery = r#"{"RecordType": "Delivery", "Recipient": "john@example.com"}"#;
+
+    // act
+    let response = app.post_postmark_webhook(delivery).await;
+
+    // assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(subscriber_status(&app).await, "confirmed");
+}
+
+#[tokio::test]
+async fn malformed_payloads_are_rejected_with_a_400() {
+    // arrange
+    let app = spawn_app().await;
+    let test_cases = vec![
+        ("not json", "invalid json"),
+        (r#"{"Type": "HardBounce"}"#, "missing record type"),
+        (
+            r#"{"RecordType": "Bounce", "ID": 1, "Email": "not-an-email"}"#,
+            "invalid email",
+        ),
+        (
+            r#"{"RecordType": "SpamComplaint", "Email": "john@example.com"}"#,
+            "missing id",
+        ),
+    ];
+
+    for (body, description) in test_cases {
+        // act
+        let response = app.post_postmark_webhook(body).await;
+
+        // assert
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The webhook did not fail with 400 Bad Request when the payload had {}.",
+            description
+        );
+    }
+}